@@ -11,6 +11,11 @@ fn main() -> Result<(), Box<dyn Error>> {
     // create a HashMap by iterating over a vector or string pairs
     let hash_map: HashMap<String, String> = pairs.into_iter().collect();
 
+    // render from the HashMap without converting it
+    let expected = "value2";
+    let actual = varj::render("{{ key2 }}", &hash_map)?;
+    assert_eq!(expected, actual);
+
     // convert the HashMap into a VarjMap
     let map = VarjMap::from(hash_map);
 
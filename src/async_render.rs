@@ -0,0 +1,341 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::future::{poll_fn, Future};
+use std::pin::{pin, Pin};
+use std::task::Poll;
+
+use futures_core::Stream;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use crate::{
+    check_block, collect_unknown, escape_block, preprocess, push_block, render_stream_with_options,
+    resolve_block, section, substitute_block, BlockKind, Error, RenderOptions, VarjMap,
+};
+
+/// A source of variables that is looked up asynchronously.
+///
+/// Implement this to fetch values from databases, HTTP services or secret
+/// stores while rendering with [`render_async`].
+///
+/// # Example
+///
+/// ```rust
+/// use varj::AsyncVariableSource;
+///
+/// struct Secrets;
+///
+/// impl AsyncVariableSource for Secrets {
+///     async fn lookup(&self, key: &str) -> Option<String> {
+///         // e.g. fetch from a remote secret store
+///         match key {
+///             "token" => Some("abc123".to_owned()),
+///             _ => None,
+///         }
+///     }
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), varj::Error> {
+/// let actual = varj::render_async("token: {{ token }}", &Secrets).await?;
+/// assert_eq!("token: abc123", actual);
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+pub trait AsyncVariableSource {
+    /// Look up the value of `key`, returning `None` if it is not set.
+    fn lookup(&self, key: &str) -> impl Future<Output = Option<String>> + Send;
+}
+
+impl AsyncVariableSource for VarjMap {
+    async fn lookup(&self, key: &str) -> Option<String> {
+        self.lookup_value(key).map(str::to_owned)
+    }
+}
+
+/// Render a template, looking up each placeholder's value from an
+/// [`AsyncVariableSource`].
+///
+/// Values are looked up one block at a time, in template order.
+///
+/// # Errors
+///
+/// Will return an [`Error`] if the template contains a key that the source
+/// does not provide.
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+pub async fn render_async<S>(template: &str, source: &S) -> Result<String, Error>
+where
+    S: AsyncVariableSource + Sync,
+{
+    render_async_with_options(template, source, &RenderOptions::default()).await
+}
+
+/// Render a template from an [`AsyncVariableSource`] using the given
+/// [`RenderOptions`].
+///
+/// [`RenderOptions::deny_unused`] is ignored, as a source cannot list its
+/// variables.
+///
+/// # Errors
+///
+/// See [`VarjMap::render_with_options`].
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+pub async fn render_async_with_options<S>(
+    template: &str,
+    source: &S,
+    options: &RenderOptions,
+) -> Result<String, Error>
+where
+    S: AsyncVariableSource + Sync,
+{
+    let processed = preprocess::apply(template, options);
+    let template = processed.source();
+    let blocks = processed.blocks();
+    section::check(&blocks)?;
+
+    let mut output = String::with_capacity(template.len() + 32);
+    let mut idx = 0;
+    let mut unknown = Vec::new();
+    // resolved values with RenderOptions::memoize
+    let mut memo: HashMap<_, String> = HashMap::new();
+
+    let mut blocks = blocks.iter();
+    while let Some(block) = blocks.next() {
+        if let Some(literal) = block.escaped(template) {
+            output.push_str(&template[idx..block.start]);
+            output.push_str(&literal);
+            idx = block.start + block.len;
+            continue;
+        }
+        check_block(block, options)?;
+        match block.kind {
+            BlockKind::Section { .. } => {
+                let value = cancellable(source.lookup(&block.variable_key), options).await?;
+                let value = value.map(Cow::Owned);
+                idx = section::enter(
+                    &mut output,
+                    template,
+                    idx,
+                    block,
+                    value,
+                    &mut blocks,
+                    options,
+                )?;
+                continue;
+            }
+            BlockKind::SectionEnd => {
+                idx = push_block(&mut output, template, idx, block, "", options);
+                continue;
+            }
+            _ => {}
+        }
+
+        let key = (&*block.variable_key, block.align, block.filters);
+        let value = match memo.get(&key) {
+            Some(value) => Ok(escape_block(
+                template,
+                block,
+                Cow::Borrowed(value.as_str()),
+                options,
+            )),
+            None => {
+                let value = cancellable(source.lookup(&block.variable_key), options).await?;
+                match value {
+                    Some(value) if options.memoize => {
+                        let value = resolve_block(block, Cow::Owned(value), options)?;
+                        let value = memo.entry(key).or_insert(value.into_owned());
+                        Ok(escape_block(template, block, Cow::Borrowed(value), options))
+                    }
+                    value => substitute_block(template, block, value.as_deref(), options)
+                        .map(|value| Cow::Owned(value.into_owned())),
+                }
+            }
+        };
+        let value = collect_unknown(value, &mut unknown, options)?;
+        idx = push_block(&mut output, template, idx, block, &value, options);
+    }
+
+    output.push_str(&template[idx..]);
+    if !unknown.is_empty() {
+        return Err(Error::collected(unknown));
+    }
+
+    Ok(options.finish(output))
+}
+
+/// Await `future`, stopping early if the render is cancelled.
+async fn cancellable<F: Future>(future: F, options: &RenderOptions) -> Result<F::Output, Error> {
+    let mut future = pin!(future);
+    // deregistered once the lookup finishes
+    let mut registration = None;
+    poll_fn(|cx| match future.as_mut().poll(cx) {
+        Poll::Ready(output) => Poll::Ready(Ok(output)),
+        Poll::Pending if options.poll_cancelled(cx, &mut registration) => {
+            Poll::Ready(Err(Error::cancelled()))
+        }
+        Poll::Pending => Poll::Pending,
+    })
+    .await
+}
+
+/// Render a template straight into a tokio [`AsyncWrite`], looking up each
+/// placeholder's value from an [`AsyncVariableSource`].
+///
+/// Output is written chunk by chunk as it is rendered, without building the
+/// whole output in memory. The writer is flushed once rendering completes.
+///
+/// # Errors
+///
+/// Will return an [`Error`] if rendering fails, or an error of kind
+/// [`ErrorKind::Io`](crate::ErrorKind::Io) if writing fails. Output rendered
+/// before an error has already been written.
+///
+/// # Example
+///
+/// ```rust
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), varj::Error> {
+/// let mut map = varj::VarjMap::new();
+/// map.insert("name", "Christopher");
+///
+/// let mut output = Vec::new();
+/// varj::render_to_async("name: {{ name }}", &map, &mut output).await?;
+///
+/// assert_eq!(b"name: Christopher", output.as_slice());
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+pub async fn render_to_async<S, W>(template: &str, source: &S, writer: W) -> Result<(), Error>
+where
+    S: AsyncVariableSource + Sync,
+    W: AsyncWrite + Unpin,
+{
+    render_to_async_with_options(template, source, writer, &RenderOptions::default()).await
+}
+
+/// Render a template straight into a tokio [`AsyncWrite`] using the given
+/// [`RenderOptions`].
+///
+/// See [`render_to_async`] for details. Output is rendered as by
+/// [`render_stream_with_options`], so [`RenderOptions::pre_process`],
+/// [`RenderOptions::post_process`], [`RenderOptions::line_ending`] and
+/// [`RenderOptions::trim_standalone`] are ignored, and
+/// [`MissingKey::Collect`] stops at the first unknown key, as
+/// [`MissingKey::Error`] does. Use [`render_async_with_options`] to apply
+/// them.
+///
+/// [`MissingKey::Collect`]: crate::MissingKey::Collect
+/// [`MissingKey::Error`]: crate::MissingKey::Error
+///
+/// # Errors
+///
+/// See [`render_to_async`].
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+pub async fn render_to_async_with_options<S, W>(
+    template: &str,
+    source: &S,
+    mut writer: W,
+    options: &RenderOptions,
+) -> Result<(), Error>
+where
+    S: AsyncVariableSource + Sync,
+    W: AsyncWrite + Unpin,
+{
+    let mut stream = render_stream_with_options(template, source, options);
+    while let Some(chunk) = poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await {
+        let chunk = chunk?;
+        let text = std::str::from_utf8(&chunk).expect("rendered chunks are UTF-8");
+        let bytes = options.output_encoding.encode(text, options.unmappable)?;
+        writer.write_all(&bytes).await?;
+    }
+    writer.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorKind;
+
+    #[tokio::test]
+    async fn render_async_from_map() {
+        let mut map = VarjMap::new();
+        map.insert("testKey", "testValue");
+        map.insert("testKey2", "testValue2");
+
+        let actual = render_async("{{ testKey }}; {{testKey2}};", &map)
+            .await
+            .expect("rendering should succeed");
+        assert_eq!("testValue; testValue2;", actual);
+
+        let actual = render_async("{{#testKey}}{{ testKey2 }}{{/testKey}}{{^a}};{{/a}}", &map)
+            .await
+            .expect("rendering should succeed");
+        assert_eq!("testValue2;", actual);
+    }
+
+    #[tokio::test]
+    async fn render_async_memoized() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct Counting(AtomicUsize);
+
+        impl AsyncVariableSource for Counting {
+            async fn lookup(&self, key: &str) -> Option<String> {
+                self.0.fetch_add(1, Ordering::Relaxed);
+                Some(key.to_owned())
+            }
+        }
+
+        let template = "{{ a }}{{ a }}, {{ a | hex }}{{#a}} {{ a }}{{/a}}";
+        for (memoize, expected) in [(false, 5), (true, 3)] {
+            let source = Counting(AtomicUsize::new(0));
+            let options = RenderOptions::new().memoize(memoize);
+            let actual = render_async_with_options(template, &source, &options)
+                .await
+                .expect("rendering should succeed");
+
+            assert_eq!("aa, 61 a", actual);
+            assert_eq!(expected, source.0.load(Ordering::Relaxed));
+        }
+    }
+
+    #[tokio::test]
+    async fn render_to_async_writer() {
+        let mut map = VarjMap::new();
+        map.insert("testKey", "testValue");
+
+        let mut output = Vec::new();
+        render_to_async("testKey: {{ testKey }};", &map, &mut output)
+            .await
+            .expect("rendering should succeed");
+        assert_eq!(b"testKey: testValue;", output.as_slice());
+    }
+
+    #[tokio::test]
+    async fn render_to_async_write_error() {
+        let map = VarjMap::new();
+        let mut output = [0u8; 2];
+        let writer = std::io::Cursor::new(&mut output[..]);
+
+        let actual = render_to_async("testKey: testValue;", &map, writer)
+            .await
+            .expect_err("writing should error");
+        assert_eq!(ErrorKind::Io, actual.kind());
+        assert!(std::error::Error::source(&actual).is_some());
+    }
+
+    #[tokio::test]
+    async fn render_async_unknown_key() {
+        let map = VarjMap::new();
+
+        let actual = render_async("testKey:\n {{ testKey }}", &map)
+            .await
+            .expect_err("rendering should error");
+        assert_eq!(ErrorKind::UnknownVariable, actual.kind());
+        assert_eq!("2:2 unknown variable 'testKey'", actual.to_string());
+    }
+}
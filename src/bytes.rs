@@ -0,0 +1,374 @@
+use std::borrow::Cow;
+
+use crate::{
+    block_kind, check_block, check_unused, collect_unknown, escape, section, split_filters,
+    substitute_block, Block, BlockKind, Error, ErrorKind, MissingKey, RenderOptions, VarjMap,
+};
+
+impl VarjMap {
+    /// Render a template of bytes, which need not be UTF-8, with its
+    /// placeholder blocks replaced by set values.
+    ///
+    /// Placeholders are found at the byte level, so templates embedded in
+    /// binary files or text in other ASCII-compatible encodings render as
+    /// long as their placeholder blocks are UTF-8. Values are inserted as
+    /// UTF-8, and all other bytes are copied as is. Error columns count
+    /// bytes rather than characters.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::error::Error;
+    /// #
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let mut map = varj::VarjMap::new();
+    /// map.insert("name", "Chris");
+    ///
+    /// // "café {{ name }}" in Latin-1, followed by binary data
+    /// let template = b"caf\xE9 {{ name }}\x00\xFF";
+    /// assert_eq!(b"caf\xE9 Chris\x00\xFF", map.render_bytes(template)?.as_slice());
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Will return an [`Error`] of kind [`ErrorKind::InvalidKey`] if a
+    /// placeholder block is not UTF-8, otherwise see [`render`](Self::render).
+    pub fn render_bytes(&self, template: &[u8]) -> Result<Vec<u8>, Error> {
+        self.render_bytes_with_options(template, &RenderOptions::default())
+    }
+
+    /// Render a template of bytes using the given [`RenderOptions`].
+    ///
+    /// Options transforming the template source or the output as text, such
+    /// as [`RenderOptions::pre_process`], [`RenderOptions::post_process`]
+    /// and [`RenderOptions::line_ending`], are ignored, and so is
+    /// [`RenderOptions::threads`].
+    ///
+    /// # Errors
+    ///
+    /// See [`render_bytes`](Self::render_bytes).
+    pub fn render_bytes_with_options(
+        &self,
+        template: &[u8],
+        options: &RenderOptions,
+    ) -> Result<Vec<u8>, Error> {
+        let blocks = scan(template);
+        let tags = blocks
+            .iter()
+            .filter(|block| {
+                matches!(
+                    block.kind,
+                    BlockKind::Section { .. } | BlockKind::SectionEnd
+                )
+            })
+            .map(|block| parse(template, block))
+            .collect::<Result<Vec<_>, _>>()?;
+        section::check(&tags)?;
+
+        let mut output = Vec::with_capacity(template.len() + 32);
+        let mut keys = Vec::with_capacity(blocks.len());
+        let mut idx = 0;
+        let mut unknown = Vec::new();
+
+        let mut remaining = blocks.iter();
+        while let Some(block) = remaining.next() {
+            output.extend_from_slice(&template[idx..block.start]);
+            idx = block.start + block.len;
+            let text = &template[block.start..idx];
+
+            if block.kind == BlockKind::Escape {
+                let text = std::str::from_utf8(text).expect("escapes are ASCII");
+                output.extend_from_slice(escape::unescape(text).as_bytes());
+                continue;
+            }
+
+            let parsed = parse(template, block)?;
+            keys.push(parsed.variable_key.clone());
+            check_block(&parsed, options)?;
+            match parsed.kind {
+                BlockKind::Section { .. } => {
+                    let value = self.lookup_value(&parsed.variable_key).map(Cow::Borrowed);
+                    if !section::shown_for(&parsed, value, options)? {
+                        let kinds = remaining.as_slice().iter().map(|block| block.kind);
+                        let end = remaining.nth(section::end_index(kinds));
+                        let end = end.expect("sections are checked before rendering");
+                        idx = end.start + end.len;
+                    }
+                    continue;
+                }
+                BlockKind::SectionEnd => continue,
+                _ => {}
+            }
+
+            // the block's line up to it, for escapes depending on it
+            let line_start = template[..block.start]
+                .iter()
+                .rposition(|&byte| byte == b'\n')
+                .map_or(0, |idx| idx + 1);
+            let preceding = String::from_utf8_lossy(&template[line_start..block.start]);
+            let parsed = Block {
+                start: preceding.len(),
+                ..parsed
+            };
+
+            let value = self.lookup_value(&parsed.variable_key).map(Cow::Borrowed);
+            if value.is_none() && options.missing_key == MissingKey::KeepPlaceholder {
+                output.extend_from_slice(text);
+                continue;
+            }
+            let value = substitute_block(&preceding, &parsed, value.as_deref(), options);
+            let value = collect_unknown(value, &mut unknown, options)?;
+            output.extend_from_slice(value.as_bytes());
+        }
+        output.extend_from_slice(&template[idx..]);
+        if !unknown.is_empty() {
+            return Err(Error::collected(unknown));
+        }
+
+        if options.deny_unused {
+            let used = self.used_keys(keys.iter().map(|key| &**key));
+            check_unused(&used, self.map.keys())?;
+        }
+
+        Ok(output)
+    }
+}
+
+/// Parse the key, alignment and filters of a placeholder block or section
+/// tag of a template of bytes, which must be UTF-8.
+fn parse<'t>(template: &'t [u8], block: &ByteBlock) -> Result<Block<'t>, Error> {
+    let braces = 2 + usize::from(block.raw);
+    let inner = &template[block.start + braces..block.start + block.len - braces];
+    let inner = std::str::from_utf8(inner).map_err(|_| Error {
+        kind: ErrorKind::InvalidKey,
+        key: String::from_utf8_lossy(inner).trim().to_owned(),
+        line: block.line,
+        col: block.col,
+        source: None,
+        unknown: Vec::new(),
+    })?;
+    let inner = match block.kind {
+        BlockKind::Variable => inner,
+        _ => block_kind(inner).1,
+    };
+    let (variable_key, align, filters) = split_filters(inner);
+    Ok(Block {
+        start: block.start,
+        len: block.len,
+        line: block.line,
+        col: block.col,
+        variable_key,
+        align,
+        filters,
+        raw: block.raw,
+        kind: block.kind,
+    })
+}
+
+/// A block found in a template of bytes.
+struct ByteBlock {
+    start: usize,
+    len: usize,
+    line: usize,
+    col: usize,
+    raw: bool,
+    kind: BlockKind,
+}
+
+/// Find the blocks of a template of bytes, as `parse_blocks` does for text,
+/// with columns counted in bytes.
+fn scan(template: &[u8]) -> Vec<ByteBlock> {
+    let mut blocks = Vec::new();
+
+    let mut in_block = false;
+    let mut idx_start = 0;
+    let mut line = 1;
+    let mut line_start = 1;
+    let mut col = 0;
+    let mut col_start = 0;
+
+    let mut idx = 0;
+    while idx < template.len() {
+        let byte = template[idx];
+        let next = template.get(idx + 1).copied();
+        col += 1;
+
+        if byte == b'\n' {
+            line += 1;
+            col = 0;
+        }
+
+        if in_block && byte == b'"' && opens_key(&template[idx_start + 2..idx]) {
+            // skip a quoted key, which may contain braces
+            idx += 1;
+            while let Some(&byte) = template.get(idx) {
+                col += 1;
+                idx += 1;
+                let byte = match byte {
+                    b'\\' => match template.get(idx) {
+                        Some(&escaped) => {
+                            col += 1;
+                            idx += 1;
+                            escaped
+                        }
+                        None => break,
+                    },
+                    b'"' => break,
+                    byte => byte,
+                };
+                if byte == b'\n' {
+                    line += 1;
+                    col = 0;
+                }
+            }
+            continue;
+        } else if in_block && byte == b'}' && next == Some(b'}') {
+            let raw = template[idx_start + 2] == b'{' && template.get(idx + 2) == Some(&b'}');
+            let len = idx + 2 + usize::from(raw) - idx_start;
+            let kind = match std::str::from_utf8(&template[idx_start + 2..idx]) {
+                Ok(inner) if !raw => block_kind(inner).0,
+                _ => BlockKind::Variable,
+            };
+            blocks.push(ByteBlock {
+                start: idx_start,
+                len,
+                line: line_start,
+                col: col_start,
+                raw,
+                kind,
+            });
+
+            // end of block
+            in_block = false;
+            col += len - (idx - idx_start) - 1;
+            idx = idx_start + len;
+            continue;
+        } else if byte == b'{' && next == Some(b'{') {
+            let slashes = if in_block {
+                0
+            } else {
+                template[..idx]
+                    .iter()
+                    .rev()
+                    .take_while(|&&byte| byte == b'\\')
+                    .count()
+            };
+            if slashes > 0 {
+                let literal = slashes % 2 == 1;
+                blocks.push(ByteBlock {
+                    start: idx - slashes,
+                    len: if literal { slashes + 2 } else { slashes },
+                    line,
+                    col: col - slashes,
+                    raw: false,
+                    kind: BlockKind::Escape,
+                });
+                if literal {
+                    col += 1;
+                    idx += 2;
+                    continue;
+                }
+            }
+
+            // start of block
+            idx_start = idx;
+            line_start = line;
+            col_start = col;
+            in_block = true;
+            col += 1;
+            idx += 2;
+            continue;
+        }
+
+        idx += 1;
+    }
+
+    blocks
+}
+
+/// Whether a quote after `inside`, a block's bytes so far, starts a quoted
+/// key, as `opens_key` does for text.
+fn opens_key(inside: &[u8]) -> bool {
+    let inside = inside.strip_prefix(b"{").unwrap_or(inside);
+    let inside = match inside.iter().position(|byte| !byte.is_ascii_whitespace()) {
+        Some(idx) if b"#^/".contains(&inside[idx]) => &inside[idx + 1..],
+        _ => inside,
+    };
+    inside.iter().all(u8::is_ascii_whitespace)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Escape;
+
+    #[test]
+    fn scan_like_text() {
+        let template = "a {{ b }}\n\\{{ c }} \\\\{{ d | trim }} {{ {{ e }} }} {{ \"}}\\\"\n\" }} \
+                        {{{ f }}} {{{ \"}}}\" }}} {{{ g }} {{# \"}}\" }}{{^i}}{{/i}} {{ h";
+        let expected: Vec<_> = crate::parse_blocks(template)
+            .iter()
+            .map(|block| {
+                (
+                    block.start,
+                    block.len,
+                    block.line,
+                    block.col,
+                    block.kind,
+                    block.raw,
+                )
+            })
+            .collect();
+        let actual: Vec<_> = scan(template.as_bytes())
+            .iter()
+            .map(|block| {
+                (
+                    block.start,
+                    block.len,
+                    block.line,
+                    block.col,
+                    block.kind,
+                    block.raw,
+                )
+            })
+            .collect();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn render_non_utf8_bytes() {
+        let mut map = VarjMap::new();
+        map.insert("key", "value\n");
+        let options = RenderOptions::new().escape(Escape::Yaml).deny_unused(true);
+
+        let actual = map
+            .render_bytes_with_options(b"\xFF\xFE\n  key: {{ key }}\n\\{{ x", &options)
+            .expect("rendering should succeed");
+        assert_eq!(
+            b"\xFF\xFE\n  key: |\n    value\n{{ x".as_slice(),
+            actual.as_slice()
+        );
+
+        let actual = map
+            .render_bytes(b"\xE9\n\xE9 {{ k\xE9y }}")
+            .expect_err("key is not UTF-8");
+        assert_eq!("2:3 invalid key 'k\u{FFFD}y'", actual.to_string());
+
+        let actual = map
+            .render_bytes(b"\xE9 {{ other }}")
+            .expect_err("other is missing");
+        assert_eq!("1:3 unknown variable 'other'", actual.to_string());
+
+        let actual = map
+            .render_bytes(b"\xE9{{#key}}a{{/key}}{{^key}}{{ other }}{{/key}}\xFF")
+            .expect("rendering should succeed");
+        assert_eq!(b"\xE9a\xFF".as_slice(), actual.as_slice());
+        let actual = map
+            .render_bytes(b"\xE9 {{#key}}{{/other}}")
+            .expect_err("sections are unmatched");
+        assert_eq!("1:3 unmatched section 'key'", actual.to_string());
+    }
+}
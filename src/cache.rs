@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::AsyncVariableSource;
+
+/// An [`AsyncVariableSource`] caching the values of another source for a
+/// fixed time to live.
+///
+/// Repeated renders reuse a fetched value until it expires, instead of
+/// fetching the same remote secret or config value every time. Keys the
+/// source does not provide are not cached. A time to live too long to
+/// reach, such as `Duration::MAX`, caches values until invalidated, and
+/// expired values are dropped whenever another value is stored.
+///
+/// # Example
+///
+/// ```rust
+/// use std::time::Duration;
+/// use varj::{AsyncVariableSource, CachedSource};
+///
+/// struct Secrets;
+///
+/// impl AsyncVariableSource for Secrets {
+///     async fn lookup(&self, key: &str) -> Option<String> {
+///         // e.g. an expensive request to a secret store
+///         Some(format!("secret for {}", key))
+///     }
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), varj::Error> {
+/// let source = CachedSource::new(Secrets, Duration::from_secs(60));
+///
+/// // only the first render fetches `token`
+/// for _ in 0..3 {
+///     let actual = varj::render_async("{{ token }}", &source).await?;
+///     assert_eq!("secret for token", actual);
+/// }
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+#[derive(Debug)]
+pub struct CachedSource<S> {
+    source: S,
+    ttl: Duration,
+    cache: Mutex<HashMap<String, CachedValue>>,
+}
+
+#[derive(Debug)]
+struct CachedValue {
+    value: String,
+    /// `None` if the value never expires.
+    expires: Option<Instant>,
+}
+
+impl CachedValue {
+    fn is_live(&self, now: Instant) -> bool {
+        self.expires.map_or(true, |expires| expires > now)
+    }
+}
+
+impl<S> CachedSource<S> {
+    /// Wrap `source`, caching each fetched value for `ttl`.
+    pub fn new(source: S, ttl: Duration) -> Self {
+        Self {
+            source,
+            ttl,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Remove a cached value, so the next lookup fetches it again.
+    pub fn invalidate(&self, key: &str) {
+        self.cache().remove(key);
+    }
+
+    /// Remove all cached values.
+    pub fn clear(&self) {
+        self.cache().clear();
+    }
+
+    /// Get a reference to the wrapped source.
+    pub fn get_ref(&self) -> &S {
+        &self.source
+    }
+
+    /// Unwrap the source, dropping the cache.
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+
+    fn cached(&self, key: &str) -> Option<String> {
+        let mut cache = self.cache();
+        match cache.get(key) {
+            Some(cached) if cached.is_live(Instant::now()) => Some(cached.value.clone()),
+            Some(_) => {
+                cache.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn store(&self, key: &str, value: &str) {
+        let now = Instant::now();
+        let cached = CachedValue {
+            value: value.to_owned(),
+            expires: now.checked_add(self.ttl),
+        };
+        let mut cache = self.cache();
+        // drop values no lookup has come back for since they expired
+        cache.retain(|_, cached| cached.is_live(now));
+        cache.insert(key.to_owned(), cached);
+    }
+
+    fn cache(&self) -> MutexGuard<'_, HashMap<String, CachedValue>> {
+        self.cache
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<S> AsyncVariableSource for CachedSource<S>
+where
+    S: AsyncVariableSource + Send + Sync,
+{
+    async fn lookup(&self, key: &str) -> Option<String> {
+        if let Some(value) = self.cached(key) {
+            return Some(value);
+        }
+
+        let value = self.source.lookup(key).await?;
+        self.store(key, &value);
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct CountingSource(AtomicUsize);
+
+    impl AsyncVariableSource for CountingSource {
+        async fn lookup(&self, key: &str) -> Option<String> {
+            let count = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+            match key {
+                "testKey" => Some(format!("testValue{}", count)),
+                "otherKey" => Some("otherValue".to_owned()),
+                _ => None,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn cached_until_expired() {
+        let source = CachedSource::new(CountingSource::default(), Duration::from_secs(60));
+
+        assert_eq!(
+            Some("testValue1".to_owned()),
+            source.lookup("testKey").await
+        );
+        assert_eq!(
+            Some("testValue1".to_owned()),
+            source.lookup("testKey").await
+        );
+        assert_eq!(1, source.get_ref().0.load(Ordering::SeqCst));
+
+        source.invalidate("testKey");
+        assert_eq!(
+            Some("testValue2".to_owned()),
+            source.lookup("testKey").await
+        );
+    }
+
+    #[tokio::test]
+    async fn expired_values_fetched_again() {
+        let source = CachedSource::new(CountingSource::default(), Duration::ZERO);
+
+        assert_eq!(
+            Some("testValue1".to_owned()),
+            source.lookup("testKey").await
+        );
+        assert_eq!(
+            Some("testValue2".to_owned()),
+            source.lookup("testKey").await
+        );
+
+        // storing another value drops the expired one
+        source.lookup("otherKey").await;
+        assert_eq!(vec!["otherKey"], source.cache().keys().collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn cached_forever() {
+        let source = CachedSource::new(CountingSource::default(), Duration::MAX);
+
+        for _ in 0..2 {
+            assert_eq!(
+                Some("testValue1".to_owned()),
+                source.lookup("testKey").await
+            );
+        }
+        assert_eq!(1, source.get_ref().0.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn missing_values_not_cached() {
+        let source = CachedSource::new(CountingSource::default(), Duration::from_secs(60));
+
+        assert_eq!(None, source.lookup("wrongKey").await);
+        assert_eq!(None, source.lookup("wrongKey").await);
+        assert_eq!(2, source.get_ref().0.load(Ordering::SeqCst));
+    }
+}
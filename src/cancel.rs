@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use std::fmt;
+#[cfg(feature = "async")]
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::Waker;
+
+/// A handle for cancelling renders, such as on shutdown.
+///
+/// Pass a clone to [`RenderOptions::cancel_token`] and call
+/// [`cancel`](Self::cancel) from anywhere to stop every render using it with
+/// an [`ErrorKind::Cancelled`] error. Async and streaming renders waiting on a
+/// variable lookup are woken and stop immediately.
+///
+/// [`RenderOptions::cancel_token`]: crate::RenderOptions::cancel_token
+/// [`ErrorKind::Cancelled`]: crate::ErrorKind::Cancelled
+///
+/// # Example
+///
+/// ```rust
+/// use varj::{CancelToken, ErrorKind, RenderOptions, VarjMap};
+///
+/// let mut map = VarjMap::new();
+/// map.insert("key", "value");
+///
+/// let token = CancelToken::new();
+/// let options = RenderOptions::new().cancel_token(token.clone());
+///
+/// token.cancel();
+///
+/// let err = map
+///     .render_with_options("{{ key }}", &options)
+///     .expect_err("render was cancelled");
+/// assert_eq!(ErrorKind::Cancelled, err.kind());
+/// ```
+#[derive(Clone, Default)]
+pub struct CancelToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    /// Wakers of pending lookups by registration id.
+    wakers: Mutex<HashMap<u64, Waker>>,
+    #[cfg(feature = "async")]
+    next_id: AtomicU64,
+}
+
+/// A waker registered with a [`CancelToken`], deregistered when dropped so
+/// finished lookups do not keep theirs.
+#[cfg(feature = "async")]
+pub(crate) struct Registration {
+    inner: Arc<Inner>,
+    id: u64,
+}
+
+#[cfg(feature = "async")]
+impl Drop for Registration {
+    fn drop(&mut self) {
+        lock(&self.inner.wakers).remove(&self.id);
+    }
+}
+
+impl CancelToken {
+    /// Create a `CancelToken` that is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel every render using this token or one of its clones.
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        let wakers = std::mem::take(&mut *lock(&self.inner.wakers));
+        wakers.into_values().for_each(Waker::wake);
+    }
+
+    /// Whether [`cancel`](Self::cancel) has been called.
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Wake `waker` when the token is cancelled, until `registration` is
+    /// dropped. An existing registration is updated to `waker`.
+    #[cfg(feature = "async")]
+    pub(crate) fn register(&self, waker: &Waker, registration: &mut Option<Registration>) {
+        let registration = match registration {
+            Some(registration) if Arc::ptr_eq(&registration.inner, &self.inner) => registration,
+            _ => registration.insert(Registration {
+                inner: Arc::clone(&self.inner),
+                id: self.inner.next_id.fetch_add(1, Ordering::Relaxed),
+            }),
+        };
+        let mut wakers = lock(&self.inner.wakers);
+        match wakers.get_mut(&registration.id) {
+            Some(registered) if registered.will_wake(waker) => {}
+            Some(registered) => *registered = waker.clone(),
+            None => {
+                wakers.insert(registration.id, waker.clone());
+            }
+        }
+    }
+
+    /// The number of wakers waiting on the token.
+    #[cfg(all(test, feature = "async"))]
+    pub(crate) fn waiting(&self) -> usize {
+        lock(&self.inner.wakers).len()
+    }
+}
+
+fn lock(wakers: &Mutex<HashMap<u64, Waker>>) -> std::sync::MutexGuard<'_, HashMap<u64, Waker>> {
+    wakers
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl fmt::Debug for CancelToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CancelToken")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
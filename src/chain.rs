@@ -0,0 +1,176 @@
+use std::borrow::Cow;
+
+use crate::{
+    parallel, preprocess, render_blocks, Error, Lookup, RenderOptions, VariableSource, VarjMap,
+};
+
+/// [`VarjMap`]s stacked on top of each other, looked up in order.
+///
+/// Each key is taken from the first map that sets it, so a map of overrides
+/// can sit on a base map of defaults without cloning and merging them for
+/// every render. Keys are matched by each map's own rules, and a key no map
+/// sets falls back to the first [default value](VarjMap::set_default) of
+/// any map.
+///
+/// # Example
+///
+/// ```rust
+/// # use std::error::Error;
+/// #
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// let mut defaults = varj::VarjMap::new();
+/// defaults.insert("host", "localhost");
+/// defaults.insert("port", "8080");
+///
+/// let mut overrides = varj::VarjMap::new();
+/// overrides.insert("host", "example.com");
+///
+/// let chain = overrides.with_fallback(&defaults);
+/// assert_eq!("example.com:8080", chain.render("{{ host }}:{{ port }}")?);
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct VarjChain<'a> {
+    maps: Vec<&'a VarjMap>,
+}
+
+impl<'a> VarjChain<'a> {
+    /// Create a `VarjChain` looking keys up in `map` only.
+    pub fn new(map: &'a VarjMap) -> Self {
+        Self { maps: vec![map] }
+    }
+
+    /// Look keys missing from every map so far up in `map`.
+    pub fn with_fallback(mut self, map: &'a VarjMap) -> Self {
+        self.maps.push(map);
+        self
+    }
+
+    /// Get a value by key from the first map that sets it.
+    pub fn get<K: AsRef<str>>(&self, key: K) -> Option<&'a str> {
+        let key = key.as_ref();
+        self.maps.iter().find_map(|map| map.get(key))
+    }
+
+    fn lookup_value(&self, key: &str) -> Option<&'a str> {
+        self.get(key)
+            .or_else(|| self.maps.iter().find_map(|map| map.default.as_deref()))
+    }
+
+    /// Render a template with its placeholder blocks replaced by values from
+    /// the chain.
+    ///
+    /// # Errors
+    ///
+    /// See [`VarjMap::render`].
+    pub fn render(&self, template: &str) -> Result<String, Error> {
+        self.render_with_options(template, &RenderOptions::default())
+    }
+
+    /// Render a template using the given [`RenderOptions`].
+    ///
+    /// With [`RenderOptions::deny_unused`], every entry of every map must be
+    /// used, including entries another map overrides.
+    ///
+    /// # Errors
+    ///
+    /// See [`VarjMap::render_with_options`].
+    pub fn render_with_options(
+        &self,
+        template: &str,
+        options: &RenderOptions,
+    ) -> Result<String, Error> {
+        let processed = preprocess::apply(template, options);
+        let template = processed.source();
+        let blocks = processed.blocks();
+        let output = match options.threads {
+            threads if threads > 1 => {
+                parallel::render_blocks(template, &blocks, options, threads, self)?
+            }
+            _ => render_blocks(template, &blocks, options, self)?,
+        };
+
+        if options.deny_unused {
+            for map in &self.maps {
+                map.check_used(&blocks)?;
+            }
+        }
+
+        Ok(output)
+    }
+}
+
+impl<'a> Lookup<'a> for &VarjChain<'a> {
+    fn value(&self, key: &str) -> Option<Cow<'a, str>> {
+        self.lookup_value(key).map(Cow::Borrowed)
+    }
+
+    fn list(&self, key: &str) -> Vec<Cow<'a, str>> {
+        self.maps
+            .iter()
+            .map(|map| Lookup::list(map, key))
+            .find(|list| !list.is_empty())
+            .unwrap_or_default()
+    }
+}
+
+impl VariableSource for VarjChain<'_> {
+    fn lookup(&self, key: &str) -> Option<Cow<'_, str>> {
+        self.lookup_value(key).map(Cow::Borrowed)
+    }
+
+    fn list(&self, key: &str) -> Vec<Cow<'_, str>> {
+        Lookup::list(&self, key)
+    }
+}
+
+impl VarjMap {
+    /// Stack this map on `base`, looking keys it does not set up in `base`.
+    ///
+    /// See [`VarjChain`].
+    pub fn with_fallback<'a>(&'a self, base: &'a VarjMap) -> VarjChain<'a> {
+        VarjChain::new(self).with_fallback(base)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorKind;
+
+    #[test]
+    fn chain_maps() {
+        let mut base = VarjMap::new();
+        base.insert("a", "base a");
+        base.insert("b", "base b");
+        base.set_default("base default");
+
+        let mut middle = VarjMap::new();
+        middle.set_case_insensitive(true);
+        middle.insert("B", "middle b");
+
+        let mut top = VarjMap::new();
+        top.insert("a", "top a");
+        top.alias("c", "a");
+
+        let chain = top.with_fallback(&middle).with_fallback(&base);
+        assert_eq!(Some("middle b"), chain.get("b"));
+        assert_eq!(None, chain.get("d"));
+        let actual = chain
+            .render("{{ a }}, {{ b }}, {{ c }}, {{ d }}")
+            .expect("rendering should succeed");
+        assert_eq!("top a, middle b, top a, base default", actual);
+        let actual = crate::render("{{ b }}", &chain).expect("rendering should succeed");
+        assert_eq!("middle b", actual);
+
+        let options = RenderOptions::new().deny_unused(true);
+        let actual = VarjChain::new(&top)
+            .with_fallback(&base)
+            .render_with_options("{{ a }}", &options)
+            .expect_err("b is unused");
+        assert_eq!(ErrorKind::UnusedVariable, actual.kind());
+        assert_eq!("b", actual.key());
+    }
+}
@@ -0,0 +1,162 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+use dashmap::DashMap;
+
+use crate::{check_unused, preprocess, render_blocks, Error, RenderOptions, VarjMap};
+
+/// A map of variables that can be updated and rendered from many threads at
+/// once.
+///
+/// Backed by a sharded [`DashMap`], so inserts from one thread only lock the
+/// shard being written while other threads keep rendering.
+///
+/// # Example
+///
+/// ```rust
+/// # use std::error::Error;
+/// #
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// let map = Arc::new(varj::ConcurrentVarjMap::new());
+/// map.insert("status", "starting");
+///
+/// let updater = Arc::clone(&map);
+/// thread::spawn(move || updater.insert("status", "running")).join().unwrap();
+///
+/// assert_eq!("status: running", map.render("status: {{ status }}")?);
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "concurrent")))]
+#[derive(Debug, Default, Clone)]
+pub struct ConcurrentVarjMap {
+    map: DashMap<String, String>,
+}
+
+impl ConcurrentVarjMap {
+    /// Create an empty `ConcurrentVarjMap`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty `ConcurrentVarjMap` with the specified capacity.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            map: DashMap::with_capacity(capacity),
+        }
+    }
+
+    /// Insert a key value pair into the `ConcurrentVarjMap`, returning the
+    /// value it replaced, if any.
+    ///
+    /// Use any type so long as it can be converted into a string.
+    pub fn insert<K, V>(&self, key: K, value: V) -> Option<String>
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.map.insert(key.into(), value.into())
+    }
+
+    /// Get a copy of a value from the `ConcurrentVarjMap` by key.
+    pub fn get<K: AsRef<str>>(&self, key: K) -> Option<String> {
+        self.map.get(key.as_ref()).map(|value| value.clone())
+    }
+
+    /// Render a template with its placeholder blocks replaced by set values.
+    ///
+    /// Each value is read as the template reaches it, so inserts made during
+    /// a render may or may not be seen by it.
+    ///
+    /// # Errors
+    ///
+    /// See [`VarjMap::render`].
+    pub fn render(&self, template: &str) -> Result<String, Error> {
+        self.render_with_options(template, &RenderOptions::default())
+    }
+
+    /// Render a template using the given [`RenderOptions`].
+    ///
+    /// # Errors
+    ///
+    /// See [`VarjMap::render_with_options`].
+    pub fn render_with_options(
+        &self,
+        template: &str,
+        options: &RenderOptions,
+    ) -> Result<String, Error> {
+        let processed = preprocess::apply(template, options);
+        let template = processed.source();
+        let blocks = processed.blocks();
+        let output = render_blocks(template, &blocks, options, |key: &str| {
+            self.get(key).map(Cow::Owned)
+        })?;
+
+        if options.deny_unused {
+            let used: HashSet<Cow<str>> = blocks
+                .iter()
+                .filter(|block| block.kind.has_key())
+                .map(|block| Cow::Borrowed(&*block.variable_key))
+                .collect();
+            let keys: Vec<String> = self.map.iter().map(|entry| entry.key().clone()).collect();
+            check_unused(&used, keys)?;
+        }
+
+        Ok(output)
+    }
+}
+
+impl From<VarjMap> for ConcurrentVarjMap {
+    fn from(map: VarjMap) -> Self {
+        Self {
+            map: HashMap::from(map).into_iter().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn render_while_inserting() {
+        let map = Arc::new(ConcurrentVarjMap::new());
+        map.insert("testKey", "testValue");
+
+        let handles: Vec<_> = (0..4)
+            .map(|idx| {
+                let map = Arc::clone(&map);
+                thread::spawn(move || {
+                    map.insert(format!("testKey{}", idx), "testValue");
+                    map.render("testKey: {{ testKey }};")
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            let actual = handle.join().unwrap().expect("rendering should succeed");
+            assert_eq!("testKey: testValue;", actual);
+        }
+        assert_eq!(Some("testValue".to_owned()), map.get("testKey3"));
+    }
+
+    #[test]
+    fn from_varj_map() {
+        let mut varj_map = VarjMap::new();
+        varj_map.insert("testKey", "testValue");
+
+        let map = ConcurrentVarjMap::from(varj_map);
+
+        let actual = map
+            .render("testKey: {{ testKey }};")
+            .expect("rendering should succeed");
+        assert_eq!("testKey: testValue;", actual);
+    }
+}
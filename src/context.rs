@@ -0,0 +1,183 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::{Error, RenderOptions, VariableSource, VarjMap};
+
+/// A global [`VarjMap`] with overlays of per-render variables on top.
+///
+/// Keys are looked up in the overlays, last pushed first, then in the
+/// global map, without copying any of them. Keep site-wide variables in
+/// one map and layer request-specific ones over it for each render.
+///
+/// An overlay that answers every key, such as a [`VarjMap`] with a
+/// [default value](VarjMap::set_default), hides the layers below it.
+///
+/// # Example
+///
+/// ```rust
+/// # use std::error::Error;
+/// #
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// let mut site = varj::VarjMap::new();
+/// site.insert("site", "varj.rs");
+/// site.insert("user", "guest");
+///
+/// let request = [("user", "Christopher")];
+/// let context = varj::Context::new(&site).with(&request);
+///
+/// assert_eq!(
+///     "Christopher on varj.rs",
+///     context.render("{{ user }} on {{ site }}")?
+/// );
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Context<'a> {
+    global: &'a VarjMap,
+    overlays: Vec<&'a dyn VariableSource>,
+}
+
+impl<'a> Context<'a> {
+    /// Create a `Context` of a global map without overlays.
+    pub fn new(global: &'a VarjMap) -> Self {
+        Self {
+            global,
+            overlays: Vec::new(),
+        }
+    }
+
+    /// Add an overlay on top of the others.
+    pub fn with<S: VariableSource>(mut self, overlay: &'a S) -> Self {
+        self.push(overlay);
+        self
+    }
+
+    /// Add an overlay on top of the others.
+    pub fn push<S: VariableSource>(&mut self, overlay: &'a S) {
+        self.overlays.push(overlay);
+    }
+
+    /// Remove the top overlay, returning whether there was one.
+    pub fn pop(&mut self) -> bool {
+        self.overlays.pop().is_some()
+    }
+
+    /// The global map below the overlays.
+    pub fn global(&self) -> &'a VarjMap {
+        self.global
+    }
+
+    /// The number of overlays.
+    pub fn depth(&self) -> usize {
+        self.overlays.len()
+    }
+
+    /// Render a template with values from the overlays and global map.
+    ///
+    /// # Errors
+    ///
+    /// Will return an [`Error`] if the template contains a key that no layer
+    /// provides.
+    pub fn render(&self, template: &str) -> Result<String, Error> {
+        self.render_with_options(template, &RenderOptions::default())
+    }
+
+    /// Render a template using the given [`RenderOptions`].
+    ///
+    /// [`RenderOptions::threads`] is ignored, as overlays need not be shared
+    /// between threads.
+    ///
+    /// # Errors
+    ///
+    /// See [`render_with_options`](crate::render_with_options).
+    pub fn render_with_options(
+        &self,
+        template: &str,
+        options: &RenderOptions,
+    ) -> Result<String, Error> {
+        crate::render_local_with_options(template, self, options)
+    }
+}
+
+impl VariableSource for Context<'_> {
+    fn lookup(&self, key: &str) -> Option<Cow<'_, str>> {
+        self.overlays
+            .iter()
+            .rev()
+            .find_map(|overlay| overlay.lookup(key))
+            .or_else(|| self.global.lookup(key))
+    }
+
+    fn list(&self, key: &str) -> Vec<Cow<'_, str>> {
+        self.overlays
+            .iter()
+            .rev()
+            .map(|overlay| overlay.list(key))
+            .chain(std::iter::once(self.global.list(key)))
+            .find(|list| !list.is_empty())
+            .unwrap_or_default()
+    }
+}
+
+impl fmt::Debug for Context<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Context")
+            .field("global", self.global)
+            .field("overlays", &self.overlays.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::ErrorKind;
+
+    #[test]
+    fn resolve_innermost_first() {
+        let mut global = VarjMap::new();
+        global.insert("a", "global");
+        global.insert("b", "global");
+        global.insert("c", "global");
+        let mut outer = HashMap::new();
+        outer.insert("b", "outer");
+        outer.insert("c", "outer");
+        let inner = [("c", "inner")];
+
+        let mut context = Context::new(&global).with(&outer);
+        context.push(&inner);
+        assert_eq!(2, context.depth());
+        let actual = context
+            .render("{{ a }} {{ b }} {{ c }}")
+            .expect("rendering should succeed");
+        assert_eq!("global outer inner", actual);
+
+        assert!(context.pop());
+        let actual = context
+            .render("{{ a }} {{ b }} {{ c }}")
+            .expect("rendering should succeed");
+        assert_eq!("global outer outer", actual);
+
+        let actual = context
+            .render("{{ d }}")
+            .expect_err("rendering should error");
+        assert_eq!(ErrorKind::UnknownVariable, actual.kind());
+    }
+
+    #[test]
+    fn global_default_is_last() {
+        let mut global = VarjMap::new();
+        global.set_default("?");
+        let overlay = [("a", "overlay")];
+
+        let context = Context::new(&global).with(&overlay);
+        let actual = context
+            .render("{{ a }} {{ b }}")
+            .expect("rendering should succeed");
+        assert_eq!("overlay ?", actual);
+    }
+}
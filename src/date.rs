@@ -0,0 +1,271 @@
+use crate::{DateStyle, RenderOptions};
+
+/// Month and weekday names of a language, weeks starting on Monday.
+pub(crate) struct DateNames {
+    pub(crate) months: [&'static str; 12],
+    pub(crate) months_short: [&'static str; 12],
+    pub(crate) days: [&'static str; 7],
+    pub(crate) days_short: [&'static str; 7],
+}
+
+pub(crate) const ENGLISH: DateNames = DateNames {
+    months: [
+        "January",
+        "February",
+        "March",
+        "April",
+        "May",
+        "June",
+        "July",
+        "August",
+        "September",
+        "October",
+        "November",
+        "December",
+    ],
+    months_short: [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ],
+    days: [
+        "Monday",
+        "Tuesday",
+        "Wednesday",
+        "Thursday",
+        "Friday",
+        "Saturday",
+        "Sunday",
+    ],
+    days_short: ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"],
+};
+
+/// US English pattern of a [`DateStyle`].
+pub(crate) fn english_pattern(style: DateStyle) -> &'static str {
+    match style {
+        DateStyle::Short => "%-m/%-d/%y",
+        DateStyle::Medium => "%b %-d, %Y",
+        DateStyle::Long => "%B %-d, %Y",
+        DateStyle::Full => "%A, %B %-d, %Y",
+    }
+}
+
+/// Format an ISO 8601 date with a style name, a pattern, or the style of
+/// the render options if `format` is `None`.
+pub(crate) fn format(
+    value: &str,
+    format: Option<&str>,
+    options: &RenderOptions,
+) -> Result<String, String> {
+    let date = DateTime::parse(value)
+        .ok_or_else(|| format!("filter 'date' expects an ISO 8601 date, got '{}'", value))?;
+    let style = match format {
+        None => Some(options.date_style),
+        Some(name) => DateStyle::from_name(name),
+    };
+
+    #[cfg(feature = "i18n")]
+    let (names, pattern) = (
+        options.locale.date_names(),
+        style.map(|style| options.locale.date_pattern(style)),
+    );
+    #[cfg(not(feature = "i18n"))]
+    let (names, pattern) = (&ENGLISH, style.map(english_pattern));
+
+    date.format(pattern.or(format).unwrap_or_default(), names)
+}
+
+/// A calendar date with an optional time of day. Time zone offsets are
+/// ignored.
+struct DateTime {
+    year: i32,
+    month: u32,
+    day: u32,
+    time: Option<(u32, u32, u32)>,
+}
+
+impl DateTime {
+    /// Parse `YYYY-MM-DD`, optionally followed by `T` or a space and
+    /// `HH:MM[:SS[.fraction]]` with an optional offset.
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let (date, time) = match value.find(['T', 't', ' ']) {
+            Some(idx) => (&value[..idx], Some(&value[idx + 1..])),
+            None => (value, None),
+        };
+
+        let mut parts = date.splitn(3, '-');
+        let year = fixed_digits(parts.next()?, 4)?;
+        let month = fixed_digits(parts.next()?, 2)?;
+        let day = fixed_digits(parts.next()?, 2)?;
+        if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year as i32, month) {
+            return None;
+        }
+
+        let time = match time {
+            Some(time) => {
+                let end = time.find(['Z', 'z', '+', '-']).unwrap_or(time.len());
+                let time = &time[..end];
+                let time = time.split_once('.').map_or(time, |(time, _)| time);
+                let mut parts = time.splitn(3, ':');
+                let hour = fixed_digits(parts.next()?, 2)?;
+                let minute = fixed_digits(parts.next()?, 2)?;
+                let second = parts.next().map_or(Some(0), |part| fixed_digits(part, 2))?;
+                if hour > 23 || minute > 59 || second > 60 {
+                    return None;
+                }
+                Some((hour, minute, second))
+            }
+            None => None,
+        };
+
+        Some(Self {
+            year: year as i32,
+            month,
+            day,
+            time,
+        })
+    }
+
+    /// Format with a strftime-like pattern.
+    fn format(&self, pattern: &str, names: &DateNames) -> Result<String, String> {
+        let mut output = String::with_capacity(pattern.len() + 16);
+        let mut chars = pattern.chars();
+        while let Some(ch) = chars.next() {
+            if ch != '%' {
+                output.push(ch);
+                continue;
+            }
+            let (pad, spec) = match chars.next() {
+                Some('-') => (false, chars.next()),
+                spec => (true, spec),
+            };
+            let number = |number: u32| {
+                if pad {
+                    format!("{:02}", number)
+                } else {
+                    number.to_string()
+                }
+            };
+            let time = |idx: usize| {
+                self.time
+                    .map(|time| [time.0, time.1, time.2][idx])
+                    .ok_or_else(|| format!("date has no time for '%{}'", spec.unwrap_or('%')))
+            };
+            match spec {
+                Some('Y') => output.push_str(&self.year.to_string()),
+                Some('y') => output.push_str(&format!("{:02}", self.year.rem_euclid(100))),
+                Some('m') => output.push_str(&number(self.month)),
+                Some('d') => output.push_str(&number(self.day)),
+                Some('B') => output.push_str(names.months[self.month as usize - 1]),
+                Some('b') => output.push_str(names.months_short[self.month as usize - 1]),
+                Some('A') => output.push_str(names.days[self.weekday()]),
+                Some('a') => output.push_str(names.days_short[self.weekday()]),
+                Some('H') => output.push_str(&number(time(0)?)),
+                Some('M') => output.push_str(&number(time(1)?)),
+                Some('S') => output.push_str(&number(time(2)?)),
+                Some('%') => output.push('%'),
+                Some(spec) => return Err(format!("unknown date format specifier '%{}'", spec)),
+                None => return Err("date format ends with '%'".to_owned()),
+            }
+        }
+        Ok(output)
+    }
+
+    /// Day of the week, from 0 for Monday to 6 for Sunday.
+    fn weekday(&self) -> usize {
+        // days since 1970-01-01, a Thursday
+        let year = i64::from(self.year) - i64::from(self.month <= 2);
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let month = i64::from(self.month);
+        let day_of_year =
+            (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(self.day) - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146_097 + day_of_era - 719_468;
+        (days + 3).rem_euclid(7) as usize
+    }
+}
+
+/// Parse exactly `len` ASCII digits.
+fn fixed_digits(part: &str, len: usize) -> Option<u32> {
+    if part.len() != len || !part.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    part.parse().ok()
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn formatted(value: &str, pattern: &str) -> Result<String, String> {
+        format(value, Some(pattern), &RenderOptions::default())
+    }
+
+    #[test]
+    fn parse_iso_dates() {
+        assert!(DateTime::parse("2024-02-29").is_some());
+        assert!(DateTime::parse("2024-01-05T13:04:05.123+01:00").is_some());
+        assert!(DateTime::parse("2024-01-05 13:04Z").is_some());
+        for value in [
+            "2023-02-29",
+            "2024-1-5",
+            "2024-13-01",
+            "05/01/2024",
+            "2024-01-05T25:00",
+        ] {
+            assert!(DateTime::parse(value).is_none(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn english_styles() {
+        let options = RenderOptions::default();
+        assert_eq!(
+            Ok("Jan 5, 2024".to_owned()),
+            format("2024-01-05", None, &options)
+        );
+        assert_eq!(Ok("1/5/24".to_owned()), formatted("2024-01-05", "short"));
+        assert_eq!(
+            Ok("January 5, 2024".to_owned()),
+            formatted("2024-01-05", "long")
+        );
+        assert_eq!(
+            Ok("Monday, March 1, 2100".to_owned()),
+            formatted("2100-03-01", "full")
+        );
+        let options = RenderOptions::new().date_style(DateStyle::Full);
+        assert_eq!(
+            Ok("Thursday, January 1, 1970".to_owned()),
+            format("1970-01-01", None, &options)
+        );
+    }
+
+    #[test]
+    fn date_patterns() {
+        assert_eq!(
+            Ok("Fri 05/01/2024 13:04:05 100%".to_owned()),
+            formatted("2024-01-05T13:04:05Z", "%a %d/%m/%Y %H:%M:%S 100%%")
+        );
+        assert_eq!(
+            Err("date has no time for '%H'".to_owned()),
+            formatted("2024-01-05", "%H")
+        );
+        assert_eq!(
+            Err("unknown date format specifier '%Q'".to_owned()),
+            formatted("2024-01-05", "%Q")
+        );
+        assert_eq!(
+            Err("filter 'date' expects an ISO 8601 date, got 'today'".to_owned()),
+            formatted("today", "%Y")
+        );
+    }
+}
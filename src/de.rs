@@ -0,0 +1,488 @@
+//! Placeholder interpolation while deserializing with [serde](https://serde.rs).
+//!
+//! Wrap any serde [`Deserializer`] in an [`Interpolate`] to render `{{ key }}`
+//! placeholders in every string value it produces, using a [`VarjMap`].
+//!
+//! # Example
+//!
+//! ```rust
+//! # use std::error::Error;
+//! #
+//! # fn main() -> Result<(), Box<dyn Error>> {
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize)]
+//! struct Config {
+//!     data_dir: String,
+//!     cache_dir: String,
+//! }
+//!
+//! let mut map = varj::VarjMap::new();
+//! map.insert("home", "/home/chris");
+//!
+//! let json = r#"{
+//!     "data_dir": "{{ home }}/.local/share",
+//!     "cache_dir": "{{ home }}/.cache"
+//! }"#;
+//!
+//! let mut deserializer = serde_json::Deserializer::from_str(json);
+//! let config: Config = varj::de::interpolate(&mut deserializer, &map)?;
+//!
+//! assert_eq!("/home/chris/.local/share", config.data_dir);
+//! assert_eq!("/home/chris/.cache", config.cache_dir);
+//! #
+//! #     Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+
+use serde::de::{self, DeserializeSeed, Deserializer, EnumAccess, MapAccess, SeqAccess};
+use serde::de::{VariantAccess, Visitor};
+use serde::Deserialize;
+
+use crate::{parse_blocks, RenderOptions, VarjMap};
+
+/// Deserialize a `T` from `deserializer`, rendering placeholders in its string
+/// values with `map`.
+///
+/// # Errors
+///
+/// Will return the deserializer's error if deserialization fails, or if a
+/// string value fails to render.
+pub fn interpolate<'de, T, D>(deserializer: D, map: &VarjMap) -> Result<T, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    T::deserialize(Interpolate::new(deserializer, map))
+}
+
+/// A [`Deserializer`] rendering placeholders in the string values of the
+/// wrapped deserializer.
+///
+/// Struct field names and enum variant names are not rendered. Rendering
+/// errors are reported as custom errors of the wrapped deserializer.
+#[derive(Debug)]
+pub struct Interpolate<'m, D> {
+    inner: D,
+    map: &'m VarjMap,
+}
+
+impl<'m, D> Interpolate<'m, D> {
+    /// Wrap `deserializer`, rendering its string values with `map`.
+    pub fn new(deserializer: D, map: &'m VarjMap) -> Self {
+        Self {
+            inner: deserializer,
+            map,
+        }
+    }
+
+    /// Unwrap the inner deserializer.
+    pub fn into_inner(self) -> D {
+        self.inner
+    }
+}
+
+/// Render `value`, or `None` if it contains no placeholder blocks.
+fn render<E: de::Error>(map: &VarjMap, value: &str) -> Result<Option<String>, E> {
+    let blocks = parse_blocks(value);
+    if blocks.is_empty() {
+        return Ok(None);
+    }
+    map.render_blocks(value, &blocks, &RenderOptions::default())
+        .map(Some)
+        .map_err(E::custom)
+}
+
+macro_rules! forward_deserialize {
+    ($($method:ident),*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                self.inner.$method(Wrap::new(visitor, self.map))
+            }
+        )*
+    };
+}
+
+impl<'de, D> Deserializer<'de> for Interpolate<'_, D>
+where
+    D: Deserializer<'de>,
+{
+    type Error = D::Error;
+
+    forward_deserialize!(
+        deserialize_any,
+        deserialize_bool,
+        deserialize_i8,
+        deserialize_i16,
+        deserialize_i32,
+        deserialize_i64,
+        deserialize_i128,
+        deserialize_u8,
+        deserialize_u16,
+        deserialize_u32,
+        deserialize_u64,
+        deserialize_u128,
+        deserialize_f32,
+        deserialize_f64,
+        deserialize_char,
+        deserialize_str,
+        deserialize_string,
+        deserialize_bytes,
+        deserialize_byte_buf,
+        deserialize_option,
+        deserialize_unit,
+        deserialize_seq,
+        deserialize_map,
+        deserialize_ignored_any
+    );
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.inner
+            .deserialize_unit_struct(name, Wrap::new(visitor, self.map))
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.inner
+            .deserialize_newtype_struct(name, Wrap::new(visitor, self.map))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.inner
+            .deserialize_tuple(len, Wrap::new(visitor, self.map))
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.inner
+            .deserialize_tuple_struct(name, len, Wrap::new(visitor, self.map))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.inner
+            .deserialize_struct(name, fields, Wrap::new(visitor, self.map))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.inner
+            .deserialize_enum(name, variants, Wrap::new(visitor, self.map))
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        // field and variant names are left as written
+        self.inner.deserialize_identifier(visitor)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+/// Wraps a visitor, seed or access type so nested values are interpolated.
+struct Wrap<'m, T> {
+    inner: T,
+    map: &'m VarjMap,
+}
+
+impl<'m, T> Wrap<'m, T> {
+    fn new(inner: T, map: &'m VarjMap) -> Self {
+        Self { inner, map }
+    }
+}
+
+macro_rules! forward_visit {
+    ($($method:ident($ty:ty)),*) => {
+        $(
+            fn $method<E: de::Error>(self, value: $ty) -> Result<Self::Value, E> {
+                self.inner.$method(value)
+            }
+        )*
+    };
+}
+
+impl<'de, V> Visitor<'de> for Wrap<'_, V>
+where
+    V: Visitor<'de>,
+{
+    type Value = V::Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.inner.expecting(f)
+    }
+
+    forward_visit!(
+        visit_bool(bool),
+        visit_i8(i8),
+        visit_i16(i16),
+        visit_i32(i32),
+        visit_i64(i64),
+        visit_i128(i128),
+        visit_u8(u8),
+        visit_u16(u16),
+        visit_u32(u32),
+        visit_u64(u64),
+        visit_u128(u128),
+        visit_f32(f32),
+        visit_f64(f64),
+        visit_char(char),
+        visit_bytes(&[u8]),
+        visit_borrowed_bytes(&'de [u8]),
+        visit_byte_buf(Vec<u8>)
+    );
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        match render(self.map, value)? {
+            Some(rendered) => self.inner.visit_string(rendered),
+            None => self.inner.visit_str(value),
+        }
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, value: &'de str) -> Result<Self::Value, E> {
+        match render(self.map, value)? {
+            Some(rendered) => self.inner.visit_string(rendered),
+            None => self.inner.visit_borrowed_str(value),
+        }
+    }
+
+    fn visit_string<E: de::Error>(self, value: String) -> Result<Self::Value, E> {
+        match render(self.map, &value)? {
+            Some(rendered) => self.inner.visit_string(rendered),
+            None => self.inner.visit_string(value),
+        }
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        self.inner.visit_none()
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        self.inner.visit_unit()
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        self.inner
+            .visit_some(Interpolate::new(deserializer, self.map))
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        self.inner
+            .visit_newtype_struct(Interpolate::new(deserializer, self.map))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+        self.inner.visit_seq(Wrap::new(seq, self.map))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        self.inner.visit_map(Wrap::new(map, self.map))
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+        self.inner.visit_enum(Wrap::new(data, self.map))
+    }
+}
+
+impl<'de, S> DeserializeSeed<'de> for Wrap<'_, S>
+where
+    S: DeserializeSeed<'de>,
+{
+    type Value = S::Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        self.inner
+            .deserialize(Interpolate::new(deserializer, self.map))
+    }
+}
+
+impl<'de, A> SeqAccess<'de> for Wrap<'_, A>
+where
+    A: SeqAccess<'de>,
+{
+    type Error = A::Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        self.inner.next_element_seed(Wrap::new(seed, self.map))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.inner.size_hint()
+    }
+}
+
+impl<'de, A> MapAccess<'de> for Wrap<'_, A>
+where
+    A: MapAccess<'de>,
+{
+    type Error = A::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        self.inner.next_key_seed(Wrap::new(seed, self.map))
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.inner.next_value_seed(Wrap::new(seed, self.map))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.inner.size_hint()
+    }
+}
+
+impl<'de, 'm, A> EnumAccess<'de> for Wrap<'m, A>
+where
+    A: EnumAccess<'de>,
+{
+    type Error = A::Error;
+    type Variant = Wrap<'m, A::Variant>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), Self::Error> {
+        let (value, variant) = self.inner.variant_seed(seed)?;
+        Ok((value, Wrap::new(variant, self.map)))
+    }
+}
+
+impl<'de, A> VariantAccess<'de> for Wrap<'_, A>
+where
+    A: VariantAccess<'de>,
+{
+    type Error = A::Error;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        self.inner.unit_variant()
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, Self::Error> {
+        self.inner.newtype_variant_seed(Wrap::new(seed, self.map))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.inner.tuple_variant(len, Wrap::new(visitor, self.map))
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.inner
+            .struct_variant(fields, Wrap::new(visitor, self.map))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Config {
+        name: String,
+        port: u16,
+        paths: Vec<String>,
+        env: HashMap<String, String>,
+        mode: Option<Mode>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    enum Mode {
+        Serve { root: String },
+    }
+
+    #[test]
+    fn interpolate_nested_strings() {
+        let mut map = VarjMap::new();
+        map.insert("name", "testName");
+        map.insert("home", "/home/test");
+
+        let json = r#"{
+            "name": "{{ name }}",
+            "port": 8080,
+            "paths": ["{{ home }}/bin", "/usr/bin"],
+            "env": { "{{ name }}_HOME": "{{ home }}" },
+            "mode": { "Serve": { "root": "{{ home }}/www" } }
+        }"#;
+
+        let mut deserializer = serde_json::Deserializer::from_str(json);
+        let actual: Config =
+            interpolate(&mut deserializer, &map).expect("deserializing should succeed");
+
+        let expected = Config {
+            name: "testName".to_owned(),
+            port: 8080,
+            paths: vec!["/home/test/bin".to_owned(), "/usr/bin".to_owned()],
+            env: [("testName_HOME".to_owned(), "/home/test".to_owned())]
+                .into_iter()
+                .collect(),
+            mode: Some(Mode::Serve {
+                root: "/home/test/www".to_owned(),
+            }),
+        };
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn interpolate_unknown_key() {
+        let map = VarjMap::new();
+        let json = r#"{ "name": "{{ name }}" }"#;
+
+        let mut deserializer = serde_json::Deserializer::from_str(json);
+        let actual = interpolate::<HashMap<String, String>, _>(&mut deserializer, &map)
+            .expect_err("deserializing should error");
+
+        assert!(actual
+            .to_string()
+            .starts_with("1:1 unknown variable 'name'"));
+    }
+}
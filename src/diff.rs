@@ -0,0 +1,397 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::template::Part;
+use crate::Template;
+
+impl Template {
+    /// Compare the template with a `new` version of it, reporting the
+    /// changes to its placeholders and literal text.
+    ///
+    /// The templates are aligned by their literal text and placeholders.
+    /// Where placeholders were replaced, they are paired in order: a
+    /// placeholder with another key is reported as renamed, one with other
+    /// filters as changed.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use varj::Template;
+    ///
+    /// let old = Template::compile("host = {{ host }}\nport = {{ port }}\n");
+    /// let new = Template::compile("host = {{ hostname }}\nport = {{ port }}\ntls = {{ tls }}\n");
+    ///
+    /// let diff = old.diff(&new);
+    /// assert_eq!(vec!["hostname", "tls"], diff.added_keys());
+    /// assert_eq!(vec!["host"], diff.removed_keys());
+    /// assert_eq!(
+    ///     "1:8 renamed {{ host }} to {{ hostname }}\n\
+    ///      changed text \"\" to \"\\ntls = \"\n\
+    ///      3:7 added {{ tls }}",
+    ///     diff.to_string()
+    /// );
+    /// ```
+    pub fn diff(&self, new: &Template) -> TemplateDiff {
+        let old_parts = self.parts();
+        let new_parts = new.parts();
+
+        let mut changes = Vec::new();
+        let mut hunk = Hunk::default();
+        for op in align(&old_parts, &new_parts) {
+            match op {
+                Op::Same => hunk.finish(&mut changes),
+                Op::Removed(part) => hunk.removed.push(part),
+                Op::Added(part) => hunk.added.push(part),
+            }
+        }
+        hunk.finish(&mut changes);
+
+        let old_keys = keys(&old_parts);
+        let new_keys = keys(&new_parts);
+        TemplateDiff {
+            changes,
+            added_keys: new_keys
+                .difference(&old_keys)
+                .map(|&key| key.to_owned())
+                .collect(),
+            removed_keys: old_keys
+                .difference(&new_keys)
+                .map(|&key| key.to_owned())
+                .collect(),
+        }
+    }
+}
+
+fn keys<'a>(parts: &[Part<'a>]) -> BTreeSet<&'a str> {
+    parts
+        .iter()
+        .filter_map(|part| match part {
+            Part::Variable { key, .. } => Some(*key),
+            Part::Literal(_) => None,
+        })
+        .collect()
+}
+
+enum Op<'p, 'a> {
+    Same,
+    Removed(&'p Part<'a>),
+    Added(&'p Part<'a>),
+}
+
+/// Align two part lists by their longest common subsequence.
+fn align<'p, 'a>(old: &'p [Part<'a>], new: &'p [Part<'a>]) -> Vec<Op<'p, 'a>> {
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i].same(&new[j]) {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut ops = Vec::with_capacity(old.len().max(new.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i].same(&new[j]) {
+            ops.push(Op::Same);
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push(Op::Removed(&old[i]));
+            i += 1;
+        } else {
+            ops.push(Op::Added(&new[j]));
+            j += 1;
+        }
+    }
+    ops
+}
+
+/// Parts replaced between two aligned parts.
+#[derive(Default)]
+struct Hunk<'p, 'a> {
+    removed: Vec<&'p Part<'a>>,
+    added: Vec<&'p Part<'a>>,
+}
+
+impl Hunk<'_, '_> {
+    fn finish(&mut self, changes: &mut Vec<TemplateChange>) {
+        let literal = |parts: &[&Part]| -> String {
+            parts
+                .iter()
+                .filter_map(|part| match part {
+                    Part::Literal(text) => Some(text.as_str()),
+                    Part::Variable { .. } => None,
+                })
+                .collect()
+        };
+        let (from, to) = (literal(&self.removed), literal(&self.added));
+        if from != to {
+            changes.push(TemplateChange::LiteralChanged { from, to });
+        }
+
+        let variables = |parts: &[&Part]| -> Vec<(String, String, usize, usize)> {
+            parts
+                .iter()
+                .filter_map(|part| match part {
+                    Part::Variable {
+                        key,
+                        filters,
+                        line,
+                        col,
+                        ..
+                    } => Some((key.to_string(), filters.to_string(), *line, *col)),
+                    Part::Literal(_) => None,
+                })
+                .collect()
+        };
+        let mut removed = variables(&self.removed).into_iter();
+        let mut added = variables(&self.added).into_iter();
+        loop {
+            let change = match (removed.next(), added.next()) {
+                (Some(old), Some(new)) if old.0 == new.0 => TemplateChange::FiltersChanged {
+                    key: new.0,
+                    from: old.1,
+                    to: new.1,
+                    line: new.2,
+                    col: new.3,
+                },
+                (Some(old), Some(new)) => TemplateChange::Renamed {
+                    from: old.0,
+                    to: new.0,
+                    line: new.2,
+                    col: new.3,
+                },
+                (Some(old), None) => TemplateChange::Removed {
+                    key: old.0,
+                    line: old.2,
+                    col: old.3,
+                },
+                (None, Some(new)) => TemplateChange::Added {
+                    key: new.0,
+                    line: new.2,
+                    col: new.3,
+                },
+                (None, None) => break,
+            };
+            changes.push(change);
+        }
+
+        self.removed.clear();
+        self.added.clear();
+    }
+}
+
+/// The changes between two versions of a template, see [`Template::diff`].
+///
+/// Displays one change per line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateDiff {
+    changes: Vec<TemplateChange>,
+    added_keys: Vec<String>,
+    removed_keys: Vec<String>,
+}
+
+impl TemplateDiff {
+    /// Whether the templates have the same structure.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// The changes, in template order.
+    pub fn changes(&self) -> &[TemplateChange] {
+        &self.changes
+    }
+
+    /// Keys used by the new template but not the old one, sorted.
+    pub fn added_keys(&self) -> Vec<&str> {
+        self.added_keys.iter().map(String::as_str).collect()
+    }
+
+    /// Keys used by the old template but not the new one, sorted.
+    pub fn removed_keys(&self) -> Vec<&str> {
+        self.removed_keys.iter().map(String::as_str).collect()
+    }
+}
+
+impl fmt::Display for TemplateDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, change) in self.changes.iter().enumerate() {
+            if idx > 0 {
+                f.write_str("\n")?;
+            }
+            write!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// A change between two versions of a template, see [`Template::diff`].
+///
+/// Lines and columns are those of the placeholder in the new template, or
+/// in the old one if it was removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TemplateChange {
+    /// A placeholder was added.
+    Added {
+        /// The key of the placeholder.
+        key: String,
+        /// The line of the placeholder.
+        line: usize,
+        /// The column of the placeholder.
+        col: usize,
+    },
+    /// A placeholder was removed.
+    Removed {
+        /// The key of the placeholder.
+        key: String,
+        /// The line of the placeholder.
+        line: usize,
+        /// The column of the placeholder.
+        col: usize,
+    },
+    /// A placeholder's key was replaced.
+    Renamed {
+        /// The old key.
+        from: String,
+        /// The new key.
+        to: String,
+        /// The line of the placeholder.
+        line: usize,
+        /// The column of the placeholder.
+        col: usize,
+    },
+    /// A placeholder's filter chain was replaced.
+    FiltersChanged {
+        /// The key of the placeholder.
+        key: String,
+        /// The old filter chain, empty if there was none.
+        from: String,
+        /// The new filter chain, empty if there is none.
+        to: String,
+        /// The line of the placeholder.
+        line: usize,
+        /// The column of the placeholder.
+        col: usize,
+    },
+    /// Literal text between placeholders was replaced.
+    LiteralChanged {
+        /// The old text.
+        from: String,
+        /// The new text.
+        to: String,
+    },
+}
+
+impl fmt::Display for TemplateChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let block = |key: &str, filters: &str| match filters {
+            "" => format!("{{{{ {} }}}}", key),
+            filters => format!("{{{{ {} | {} }}}}", key, filters),
+        };
+        match self {
+            TemplateChange::Added { key, line, col } => {
+                write!(f, "{}:{} added {}", line, col, block(key, ""))
+            }
+            TemplateChange::Removed { key, line, col } => {
+                write!(f, "{}:{} removed {}", line, col, block(key, ""))
+            }
+            TemplateChange::Renamed {
+                from,
+                to,
+                line,
+                col,
+            } => write!(
+                f,
+                "{}:{} renamed {} to {}",
+                line,
+                col,
+                block(from, ""),
+                block(to, "")
+            ),
+            TemplateChange::FiltersChanged {
+                key,
+                from,
+                to,
+                line,
+                col,
+            } => write!(
+                f,
+                "{}:{} changed {} to {}",
+                line,
+                col,
+                block(key, from),
+                block(key, to)
+            ),
+            TemplateChange::LiteralChanged { from, to } => {
+                write!(f, "changed text {:?} to {:?}", from, to)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(old: &str, new: &str) -> TemplateDiff {
+        Template::compile(old).diff(&Template::compile(new))
+    }
+
+    #[test]
+    fn same_structure() {
+        let actual = diff("a {{ b | trim }} \\{{ c", "a {{b|trim}} \\{{ c");
+        assert!(actual.is_empty());
+        assert!(actual.added_keys().is_empty());
+        assert!(actual.removed_keys().is_empty());
+    }
+
+    #[test]
+    fn placeholder_changes() {
+        let actual = diff(
+            "{{ a }}-{{ b | trim }}-{{ c }}-{{ d }}",
+            "{{ a }}-{{ b }}-{{ e }}-{{ d }}\n{{ f }}",
+        );
+        assert_eq!(
+            vec![
+                TemplateChange::FiltersChanged {
+                    key: "b".to_owned(),
+                    from: "trim".to_owned(),
+                    to: "".to_owned(),
+                    line: 1,
+                    col: 9,
+                },
+                TemplateChange::Renamed {
+                    from: "c".to_owned(),
+                    to: "e".to_owned(),
+                    line: 1,
+                    col: 17,
+                },
+                TemplateChange::LiteralChanged {
+                    from: "".to_owned(),
+                    to: "\n".to_owned(),
+                },
+                TemplateChange::Added {
+                    key: "f".to_owned(),
+                    line: 2,
+                    col: 1,
+                },
+            ],
+            actual.changes()
+        );
+        assert_eq!(vec!["e", "f"], actual.added_keys());
+        assert_eq!(vec!["c"], actual.removed_keys());
+    }
+
+    #[test]
+    fn removed_placeholder() {
+        let actual = diff("x {{ a }} {{ b }} y", "x {{ b }} y");
+        assert_eq!(
+            "changed text \" \" to \"\"\n1:3 removed {{ a }}",
+            actual.to_string()
+        );
+    }
+}
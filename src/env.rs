@@ -0,0 +1,118 @@
+use std::borrow::Cow;
+
+use crate::{render_with, Error, VarjMap};
+
+impl VarjMap {
+    /// Create a `VarjMap` from the environment variables of the process.
+    ///
+    /// Variables whose name or value is not valid Unicode are skipped. Keys
+    /// are the variable names as set, so `{{ HOME }}` renders `$HOME`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::error::Error;
+    /// #
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// std::env::set_var("VARJ_DOC_USER", "Christopher");
+    ///
+    /// let map = varj::VarjMap::from_env();
+    /// assert_eq!("user: Christopher", map.render("user: {{ VARJ_DOC_USER }}")?);
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+    pub fn from_env() -> Self {
+        Self::from_prefixed_env("")
+    }
+
+    /// Create a `VarjMap` from the environment variables whose name starts
+    /// with `prefix`, keyed by the rest of their name.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::error::Error;
+    /// #
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// std::env::set_var("VARJ_DOC_APP_PORT", "8080");
+    ///
+    /// let map = varj::VarjMap::from_prefixed_env("VARJ_DOC_APP_");
+    /// assert_eq!("listen 8080", map.render("listen {{ PORT }}")?);
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+    pub fn from_prefixed_env(prefix: &str) -> Self {
+        let mut map = Self::new();
+        for (name, value) in std::env::vars_os() {
+            let (Some(name), Some(value)) = (name.to_str(), value.to_str()) else {
+                continue;
+            };
+            match name.strip_prefix(prefix) {
+                Some(key) if !key.is_empty() => map.insert(key, value),
+                _ => continue,
+            };
+        }
+        map
+    }
+}
+
+/// Render a template, looking up each placeholder's key as an environment
+/// variable when it is rendered.
+///
+/// Unlike [`VarjMap::from_env`], only the variables the template uses are
+/// read.
+///
+/// # Example
+///
+/// ```rust
+/// # use std::error::Error;
+/// #
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// std::env::set_var("VARJ_DOC_HOST", "example.com");
+///
+/// assert_eq!("host = example.com", varj::render_with_env("host = {{ VARJ_DOC_HOST }}")?);
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Will return an [`Error`] if the template contains a key that is not set
+/// as an environment variable, or whose value is not valid Unicode.
+#[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+pub fn render_with_env(template: &str) -> Result<String, Error> {
+    render_with(template, |key| std::env::var(key).ok().map(Cow::Owned))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorKind;
+
+    #[test]
+    fn render_from_env() {
+        std::env::set_var("VARJ_TEST_ENV_A", "a");
+        std::env::set_var("VARJ_TEST_ENV_B", "b");
+        std::env::set_var("VARJ_TEST_ENV_", "empty key");
+
+        let map = VarjMap::from_prefixed_env("VARJ_TEST_ENV_");
+        assert_eq!(None, map.get(""));
+        let actual = map
+            .render("{{ A }}{{ B }}")
+            .expect("rendering should succeed");
+        assert_eq!("ab", actual);
+
+        let map = VarjMap::from_env();
+        assert_eq!(Some("a"), map.get("VARJ_TEST_ENV_A"));
+
+        let actual = render_with_env("{{ VARJ_TEST_ENV_A }}").expect("rendering should succeed");
+        assert_eq!("a", actual);
+        let err = render_with_env("{{ VARJ_TEST_ENV_MISSING }}").expect_err("variable is unset");
+        assert_eq!(ErrorKind::UnknownVariable, err.kind());
+    }
+}
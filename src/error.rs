@@ -0,0 +1,281 @@
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+
+use crate::Block;
+#[cfg(doc)]
+use crate::{RenderOptions, TemplateSet};
+
+/// Error rendering a template.
+#[derive(Debug, Clone)]
+pub struct Error {
+    pub(crate) kind: ErrorKind,
+    pub(crate) key: String,
+    pub(crate) line: usize,
+    pub(crate) col: usize,
+    pub(crate) source: Option<Arc<dyn std::error::Error + Send + Sync>>,
+    pub(crate) unknown: Vec<Error>,
+}
+
+/// The kind of [`Error`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Unknown key in input string.
+    UnknownVariable,
+    /// Key contains characters rejected by [`RenderOptions::key_chars`].
+    InvalidKey,
+    /// Value vetoed by [`RenderOptions::on_substitute`].
+    Rejected,
+    /// Variable never referenced, see [`RenderOptions::deny_unused`].
+    UnusedVariable,
+    /// Rendering stopped by [`RenderOptions::cancel_when`] or
+    /// [`RenderOptions::cancel_token`].
+    Cancelled,
+    /// Writing the rendered output failed.
+    Io,
+    /// No template registered under the name in a [`TemplateSet`].
+    UnknownTemplate,
+    /// A template loader failed to fetch a template.
+    Load,
+    /// Rendered output is not valid JSON, see `VarjMap::render_json`.
+    Json,
+    /// A filter in the placeholder block is malformed, unknown or failed.
+    Filter,
+    /// A placeholder block is opened but not closed before the next one or
+    /// the end of the template, see [`check_syntax`](crate::check_syntax).
+    UnclosedBlock,
+    /// A section tag, `{{#key}}` or `{{^key}}`, is not closed by a
+    /// `{{/key}}`, or an end tag closes no open section.
+    UnmatchedSection,
+}
+
+impl Error {
+    pub(crate) fn new(kind: ErrorKind, block: &Block) -> Error {
+        Error {
+            kind,
+            key: block.variable_key.to_string(),
+            line: block.line,
+            col: block.col,
+            source: None,
+            unknown: Vec::new(),
+        }
+    }
+
+    pub(crate) fn without_block(kind: ErrorKind, key: &str) -> Error {
+        Error {
+            kind,
+            key: key.to_owned(),
+            line: 0,
+            col: 0,
+            source: None,
+            unknown: Vec::new(),
+        }
+    }
+
+    /// The first of the `unknown` variable errors, holding them all.
+    pub(crate) fn collected(unknown: Vec<Error>) -> Error {
+        let mut err = unknown[0].clone();
+        err.unknown = unknown;
+        err
+    }
+
+    pub(crate) fn cancelled() -> Error {
+        Error::without_block(ErrorKind::Cancelled, "")
+    }
+
+    pub(crate) fn with_source<E>(kind: ErrorKind, key: &str, source: E) -> Error
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        Error::without_block(kind, key).caused_by(source)
+    }
+
+    pub(crate) fn caused_by<E>(mut self, source: E) -> Error
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        self.source = Some(Arc::from(source.into()));
+        self
+    }
+
+    /// The kind of error.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// The key of the placeholder block that caused the error.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// The line of the placeholder block, starting at 1.
+    ///
+    /// Errors not tied to a placeholder block, such as
+    /// [`ErrorKind::UnusedVariable`] or [`ErrorKind::Cancelled`], have a line
+    /// and column of 0.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// The column of the placeholder block, starting at 1.
+    pub fn col(&self) -> usize {
+        self.col
+    }
+
+    /// Every unknown variable found by a render with
+    /// [`MissingKey::Collect`](crate::MissingKey::Collect), in template order
+    /// and starting with this error.
+    ///
+    /// Empty for errors of other renders.
+    pub fn unknown_variables(&self) -> &[Error] {
+        &self.unknown
+    }
+}
+
+impl PartialEq for Error {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+            && self.key == other.key
+            && self.line == other.line
+            && self.col == other.col
+    }
+}
+
+impl Eq for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self.kind {
+            ErrorKind::UnknownVariable => "unknown variable",
+            ErrorKind::InvalidKey => "invalid key",
+            ErrorKind::Rejected => "rejected variable",
+            ErrorKind::UnmatchedSection => "unmatched section",
+            ErrorKind::UnusedVariable => return write!(f, "unused variable '{}'", self.key),
+            ErrorKind::Cancelled => return f.write_str("render cancelled"),
+            ErrorKind::UnknownTemplate => return write!(f, "unknown template '{}'", self.key),
+            ErrorKind::Io => return self.write_source(f, "io error"),
+            ErrorKind::Load => {
+                let msg = format!("failed to load template '{}'", self.key);
+                return self.write_source(f, &msg);
+            }
+            ErrorKind::UnclosedBlock => {
+                return write!(f, "{}:{} unclosed placeholder block", self.line, self.col)
+            }
+            ErrorKind::Filter => {
+                let msg = format!(
+                    "{}:{} filter failed for '{}'",
+                    self.line, self.col, self.key
+                );
+                return self.write_source(f, &msg);
+            }
+            ErrorKind::Json if self.key.is_empty() => {
+                let msg = format!("{}:{} invalid json", self.line, self.col);
+                return self.write_source(f, &msg);
+            }
+            ErrorKind::Json => {
+                let msg = format!(
+                    "{}:{} invalid json near '{}'",
+                    self.line, self.col, self.key
+                );
+                return self.write_source(f, &msg);
+            }
+        };
+        if self.unknown.len() > 1 {
+            f.write_str("unknown variables")?;
+            for (idx, err) in self.unknown.iter().enumerate() {
+                let sep = if idx == 0 { ": " } else { ", " };
+                write!(f, "{}{}:{} '{}'", sep, err.line, err.col, err.key)?;
+            }
+            return Ok(());
+        }
+        write!(f, "{}:{} {} '{}'", self.line, self.col, msg, self.key)
+    }
+}
+
+impl Error {
+    fn write_source(&self, f: &mut fmt::Formatter<'_>, msg: &str) -> fmt::Result {
+        match &self.source {
+            Some(err) => write!(f, "{}: {}", msg, err),
+            None => f.write_str(msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|err| err.as_ref() as &(dyn std::error::Error + 'static))
+    }
+}
+
+/// Error inserting a key that is already set, see
+/// [`VarjMap::try_insert`](crate::VarjMap::try_insert).
+///
+/// Holds the rejected value, so it can be inserted under another key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateKeyError {
+    pub(crate) key: String,
+    pub(crate) value: String,
+}
+
+impl DuplicateKeyError {
+    /// The key that is already set, as stored in the map.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// The rejected value.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Take the rejected value.
+    pub fn into_value(self) -> String {
+        self.value
+    }
+}
+
+impl fmt::Display for DuplicateKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "variable '{}' is already set", self.key)
+    }
+}
+
+impl std::error::Error for DuplicateKeyError {}
+
+/// Error parsing a `-D` define, see
+/// [`VarjMap::from_defines`](crate::VarjMap::from_defines).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefineError {
+    pub(crate) define: String,
+    pub(crate) reason: &'static str,
+}
+
+impl DefineError {
+    /// The define as given, without its `-D` flag.
+    pub fn define(&self) -> &str {
+        &self.define
+    }
+}
+
+impl fmt::Display for DefineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid define '{}': {}", self.define, self.reason)
+    }
+}
+
+impl std::error::Error for DefineError {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::with_source(ErrorKind::Io, "", err)
+    }
+}
+
+impl From<Block<'_>> for Error {
+    fn from(block: Block) -> Error {
+        Error::new(ErrorKind::UnknownVariable, &block)
+    }
+}
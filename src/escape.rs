@@ -0,0 +1,295 @@
+use std::borrow::Cow;
+
+/// Escape text to render back to itself literally, with no placeholder
+/// blocks.
+///
+/// Each `{{` is prefixed with `\`, and backslashes directly before it are
+/// doubled. Use it to embed user content into templates built in code.
+///
+/// # Example
+///
+/// ```rust
+/// # use std::error::Error;
+/// #
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// let comment = r"use {{ name }} or \{{ name }}";
+/// let template = format!("{{{{ user }}}} wrote: {}", varj::escape(comment));
+/// assert_eq!(r"{{ user }} wrote: use \{{ name }} or \\\{{ name }}", template);
+///
+/// let mut map = varj::VarjMap::new();
+/// map.insert("user", "Christopher");
+/// assert_eq!(
+///     r"Christopher wrote: use {{ name }} or \{{ name }}",
+///     map.render(&template)?
+/// );
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+pub fn escape(text: &str) -> Cow<'_, str> {
+    escape_with(text, "{{")
+}
+
+/// Escape text as [`escape`] does, for templates whose blocks open with
+/// `open`.
+pub(crate) fn escape_with<'a>(text: &'a str, open: &str) -> Cow<'a, str> {
+    if !text.contains(open) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut output = String::with_capacity(text.len() + 8);
+    let mut idx = 0;
+    for (start, _) in text.match_indices(open) {
+        let slashes = text[..start]
+            .bytes()
+            .rev()
+            .take_while(|&byte| byte == b'\\');
+        output.push_str(&text[idx..start]);
+        for _ in 0..=slashes.count() {
+            output.push('\\');
+        }
+        output.push_str(open);
+        idx = start + open.len();
+    }
+    output.push_str(&text[idx..]);
+    Cow::Owned(output)
+}
+
+/// The literal text of an escape: a run of backslashes, followed by the
+/// opening delimiter if there is an odd number of them.
+pub(crate) fn unescape(escape: &str) -> Cow<'_, str> {
+    let slashes = escape.bytes().take_while(|&byte| byte == b'\\').count();
+    let mut literal = "\\".repeat(slashes / 2);
+    literal.push_str(&escape[slashes..]);
+    Cow::Owned(literal)
+}
+
+/// Format `value` as a TOML string.
+///
+/// Values that fit a literal string are wrapped in single quotes as is, all
+/// others become a basic string with quotes, backslashes and control
+/// characters escaped.
+pub(crate) fn toml(value: &str) -> String {
+    let literal = !value
+        .chars()
+        .any(|ch| ch == '\'' || (ch.is_control() && ch != '\t'));
+    if literal {
+        return format!("'{}'", value);
+    }
+
+    let mut output = String::with_capacity(value.len() + 8);
+    output.push('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            ch if ch.is_control() => output.push_str(&format!("\\u{:04X}", ch as u32)),
+            ch => output.push(ch),
+        }
+    }
+    output.push('"');
+    output
+}
+
+/// Escape the characters of `value` that are markup in HTML text and
+/// attribute values.
+pub(crate) fn html(value: &str) -> Cow<'_, str> {
+    if !value.contains(['&', '<', '>', '"', '\'']) {
+        return Cow::Borrowed(value);
+    }
+
+    let mut output = String::with_capacity(value.len() + 16);
+    for ch in value.chars() {
+        match ch {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&#39;"),
+            ch => output.push(ch),
+        }
+    }
+    Cow::Owned(output)
+}
+
+/// Format `value` as a YAML string scalar inserted after the `preceding`
+/// template text.
+///
+/// Values that would be read as another type or as structure are double
+/// quoted, multi-line values become literal block scalars indented deeper
+/// than the template line.
+pub(crate) fn yaml<'v>(value: &'v str, preceding: &str) -> Cow<'v, str> {
+    if !value.contains('\n') {
+        if is_plain_yaml(value) {
+            return Cow::Borrowed(value);
+        }
+        return Cow::Owned(double_quoted_yaml(value));
+    }
+
+    let body = value.trim_end_matches('\n');
+    let first_line = body.lines().find(|line| !line.is_empty()).unwrap_or("");
+    let block_safe = !first_line.starts_with([' ', '\t'])
+        && !body
+            .chars()
+            .any(|ch| ch.is_control() && ch != '\n' && ch != '\t');
+    if !block_safe {
+        return Cow::Owned(double_quoted_yaml(value));
+    }
+
+    let trailing = value.len() - body.len();
+    let chomp = match trailing {
+        0 => "-",
+        1 => "",
+        _ => "+",
+    };
+    let indent = " ".repeat(block_indent(preceding));
+
+    let mut output = format!("|{}", chomp);
+    for line in body.split('\n') {
+        output.push('\n');
+        if !line.is_empty() {
+            output.push_str(&indent);
+            output.push_str(line);
+        }
+    }
+    for _ in 1..trailing {
+        output.push('\n');
+    }
+    Cow::Owned(output)
+}
+
+/// Indentation for block scalar content on the last line of `preceding`,
+/// deeper than the line and any sequence entries opened on it.
+fn block_indent(preceding: &str) -> usize {
+    let line = &preceding[preceding.rfind('\n').map_or(0, |idx| idx + 1)..];
+    let mut rest = line.trim_start_matches(' ');
+    while let Some(entry) = rest.strip_prefix("- ") {
+        rest = entry.trim_start_matches(' ');
+    }
+    line[..line.len() - rest.len()].chars().count() + 2
+}
+
+/// Whether `value` reads back as the same string when written as a plain
+/// scalar.
+fn is_plain_yaml(value: &str) -> bool {
+    const INDICATORS: &[char] = &[
+        '-', '?', ':', ',', '[', ']', '{', '}', '#', '&', '*', '!', '|', '>', '\'', '"', '%', '@',
+        '`',
+    ];
+    const RESERVED: &[&str] = &[
+        "~", "null", "true", "false", "yes", "no", "on", "off", "y", "n", ".inf", "-.inf", "+.inf",
+        ".nan",
+    ];
+
+    if value.is_empty() || value.trim() != value || value.starts_with(INDICATORS) {
+        return false;
+    }
+    if value.contains(": ")
+        || value.contains(" #")
+        || value.ends_with(':')
+        || value.contains([',', '[', ']', '{', '}'])
+        || value.chars().any(char::is_control)
+    {
+        return false;
+    }
+
+    // numbers, dates and times
+    let lowercase = value.to_ascii_lowercase();
+    let numeric = value.starts_with(|ch: char| ch.is_ascii_digit() || ch == '+' || ch == '.');
+    !numeric && !RESERVED.contains(&lowercase.as_str()) && value.parse::<f64>().is_err()
+}
+
+fn double_quoted_yaml(value: &str) -> String {
+    let mut output = String::with_capacity(value.len() + 8);
+    output.push('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            ch if ch.is_control() => output.push_str(&format!("\\u{:04X}", ch as u32)),
+            ch => output.push(ch),
+        }
+    }
+    output.push('"');
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_braces() {
+        assert_eq!(Cow::Borrowed("no {braces} }}"), escape("no {braces} }}"));
+        assert_eq!(r"\{{ a }}", escape("{{ a }}"));
+        assert_eq!(r"\{{\{{", escape("{{{{"));
+        assert_eq!(r"\\\{{ a \b", escape(r"\{{ a \b"));
+        assert_eq!(r"\\\\\{{", escape(r"\\{{"));
+    }
+
+    #[test]
+    fn unescape_backslashes() {
+        assert_eq!("{{", unescape(r"\{{"));
+        assert_eq!(r"\", unescape(r"\\"));
+        assert_eq!(r"\{{", unescape(r"\\\{{"));
+    }
+
+    #[test]
+    fn toml_literal_string() {
+        assert_eq!(r"'C:\Users\test'", toml(r"C:\Users\test"));
+        assert_eq!(r#"'say "hi"'"#, toml(r#"say "hi""#));
+        assert_eq!("''", toml(""));
+    }
+
+    #[test]
+    fn toml_basic_string() {
+        assert_eq!(r#""it's \"here\"""#, toml(r#"it's "here""#));
+        assert_eq!(r#""line1\nline2\\""#, toml("line1\nline2\\"));
+        assert_eq!(r#""bell\u0007\ttab""#, toml("bell\u{7}\ttab"));
+    }
+
+    #[test]
+    fn yaml_plain_scalar() {
+        assert_eq!("hello world", yaml("hello world", "key: "));
+        assert_eq!("C:\\dir", yaml("C:\\dir", "key: "));
+        assert_eq!("v1.2", yaml("v1.2", "key: "));
+    }
+
+    #[test]
+    fn yaml_quoted_scalar() {
+        for value in [
+            "",
+            "true",
+            "No",
+            "null",
+            "~",
+            "1.0",
+            "0x1F",
+            "2024-01-01",
+            "-1",
+        ] {
+            assert_eq!(format!("\"{}\"", value), yaml(value, "key: "));
+        }
+        assert_eq!(r#""a: b""#, yaml("a: b", "key: "));
+        assert_eq!(r#""- item""#, yaml("- item", "key: "));
+        assert_eq!(r#"" padded ""#, yaml(" padded ", "key: "));
+        assert_eq!(
+            r#""\"quoted\" \\ tab\t""#,
+            yaml("\"quoted\" \\ tab\t", "key: ")
+        );
+    }
+
+    #[test]
+    fn yaml_block_scalar() {
+        assert_eq!("|-\n  line1\n\n  line2", yaml("line1\n\nline2", "key: "));
+        assert_eq!("|\n      line1", yaml("line1\n", "a:\n  - key: "));
+        assert_eq!("|+\n      line1\n", yaml("line1\n\n", "  - "));
+        assert_eq!(r#""  indented\nline2""#, yaml("  indented\nline2", "key: "));
+    }
+}
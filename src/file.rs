@@ -0,0 +1,478 @@
+use std::borrow::Cow;
+use std::error::Error as StdError;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::{
+    preprocess, section, write_span, Error, ErrorKind, RenderOptions, VariableSource, VarjMap,
+};
+
+/// Text encodings of template files and output, see
+/// [`RenderOptions::encoding`] and [`RenderOptions::output_encoding`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Encoding {
+    /// UTF-8, with or without a byte order mark.
+    #[default]
+    Utf8,
+    /// UTF-16, big-endian if the file starts with a `FE FF` byte order mark,
+    /// otherwise little-endian as written by Windows tools. Output is
+    /// little-endian.
+    Utf16,
+    /// ISO 8859-1, each byte being the character of the same code point.
+    Latin1,
+}
+
+impl Encoding {
+    /// Decode `bytes`, dropping any byte order mark.
+    pub(crate) fn decode(&self, bytes: Vec<u8>) -> Result<String, Box<dyn StdError + Send + Sync>> {
+        match self {
+            Encoding::Utf8 => {
+                if bytes.starts_with(&[0xFF, 0xFE]) || bytes.starts_with(&[0xFE, 0xFF]) {
+                    return Err("found a UTF-16 byte order mark, use Encoding::Utf16".into());
+                }
+                let mut text = String::from_utf8(bytes)?;
+                if text.starts_with('\u{FEFF}') {
+                    text.drain(..'\u{FEFF}'.len_utf8());
+                }
+                Ok(text)
+            }
+            Encoding::Utf16 => {
+                let (bytes, big_endian) = match bytes.as_slice() {
+                    [0xFE, 0xFF, rest @ ..] => (rest, true),
+                    [0xFF, 0xFE, rest @ ..] => (rest, false),
+                    rest => (rest, false),
+                };
+                if bytes.len() % 2 == 1 {
+                    return Err("UTF-16 text has an odd number of bytes".into());
+                }
+                let units = bytes.chunks_exact(2).map(|pair| match big_endian {
+                    true => u16::from_be_bytes([pair[0], pair[1]]),
+                    false => u16::from_le_bytes([pair[0], pair[1]]),
+                });
+                Ok(char::decode_utf16(units).collect::<Result<String, _>>()?)
+            }
+            Encoding::Latin1 => Ok(bytes.iter().map(|&byte| char::from(byte)).collect()),
+        }
+    }
+
+    /// Encode `text`, without a byte order mark.
+    pub(crate) fn encode<'t>(
+        &self,
+        text: &'t str,
+        unmappable: Unmappable,
+    ) -> io::Result<Cow<'t, [u8]>> {
+        match self {
+            Encoding::Utf8 => Ok(Cow::Borrowed(text.as_bytes())),
+            Encoding::Utf16 => Ok(Cow::Owned(
+                text.encode_utf16().flat_map(u16::to_le_bytes).collect(),
+            )),
+            Encoding::Latin1 => text
+                .chars()
+                .map(|ch| match (u8::try_from(ch), unmappable) {
+                    (Ok(byte), _) => Ok(byte),
+                    (Err(_), Unmappable::Replace(byte)) => Ok(byte),
+                    (Err(_), Unmappable::Error) => Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("character '{}' cannot be encoded in Latin-1", ch),
+                    )),
+                })
+                .collect::<io::Result<Vec<u8>>>()
+                .map(Cow::Owned),
+        }
+    }
+}
+
+/// How to write characters the output encoding cannot encode, see
+/// [`RenderOptions::unmappable`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Unmappable {
+    /// Fail with an error of kind [`ErrorKind::Io`].
+    #[default]
+    Error,
+    /// Write this byte instead, such as `b'?'`.
+    Replace(u8),
+}
+
+/// Render a template file, looking up each placeholder's value from a
+/// [`VariableSource`].
+///
+/// The file is read as UTF-8, without a leading byte order mark. Use
+/// [`render_file_with_options`] with [`RenderOptions::encoding`] for other
+/// encodings.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # use std::error::Error;
+/// #
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// let mut map = varj::VarjMap::new();
+/// map.insert("host", "example.com");
+///
+/// let config = varj::render_file("templates/nginx.conf", &map)?;
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Will return an [`Error`] of kind [`ErrorKind::Load`] naming the file,
+/// with the cause as its source, if it cannot be read or decoded, otherwise
+/// see [`render`](crate::render).
+pub fn render_file<P, S>(path: P, source: &S) -> Result<String, Error>
+where
+    P: AsRef<Path>,
+    S: VariableSource + Sync + ?Sized,
+{
+    render_file_with_options(path, source, &RenderOptions::default())
+}
+
+/// Render a template file from a [`VariableSource`] using the given
+/// [`RenderOptions`].
+///
+/// # Errors
+///
+/// See [`render_file`].
+pub fn render_file_with_options<P, S>(
+    path: P,
+    source: &S,
+    options: &RenderOptions,
+) -> Result<String, Error>
+where
+    P: AsRef<Path>,
+    S: VariableSource + Sync + ?Sized,
+{
+    let template = read(path.as_ref(), options.encoding)?;
+    crate::render_with_options(&template, source, options)
+}
+
+/// Render a template straight into a [`Write`]r, looking up each
+/// placeholder's value from a [`VariableSource`].
+///
+/// Output is written as UTF-8. Use [`render_to_with_options`] with
+/// [`RenderOptions::output_encoding`] for other encodings. The writer is
+/// flushed once the output is written.
+///
+/// # Example
+///
+/// ```rust
+/// # use std::error::Error;
+/// #
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// let mut output = Vec::new();
+/// varj::render_to("name: {{ name }}", &[("name", "Christopher")], &mut output)?;
+///
+/// assert_eq!(b"name: Christopher", output.as_slice());
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Will return an [`Error`] if rendering fails, or an error of kind
+/// [`ErrorKind::Io`] if the output cannot be encoded or written. Nothing is
+/// written if rendering fails.
+pub fn render_to<S, W>(template: &str, source: &S, writer: W) -> Result<(), Error>
+where
+    S: VariableSource + Sync + ?Sized,
+    W: Write,
+{
+    render_to_with_options(template, source, writer, &RenderOptions::default())
+}
+
+/// Render a template straight into a [`Write`]r using the given
+/// [`RenderOptions`].
+///
+/// # Errors
+///
+/// See [`render_to`].
+pub fn render_to_with_options<S, W>(
+    template: &str,
+    source: &S,
+    mut writer: W,
+    options: &RenderOptions,
+) -> Result<(), Error>
+where
+    S: VariableSource + Sync + ?Sized,
+    W: Write,
+{
+    let output = crate::render_with_options(template, source, options)?;
+    writer.write_all(
+        &options
+            .output_encoding
+            .encode(&output, options.unmappable)?,
+    )?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Rendered output buffered before it is written by [`VarjMap::render_to`].
+const WRITE_BUFFER: usize = 8 * 1024;
+
+impl VarjMap {
+    /// Render a template into a [`Write`]r as it is rendered, without
+    /// building the whole output in memory.
+    ///
+    /// Output is written as UTF-8 in chunks of a few kilobytes, and the
+    /// writer is flushed at the end. Wrap unbuffered writers such as files
+    /// and sockets in a [`BufWriter`](std::io::BufWriter) if values are
+    /// small.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::error::Error;
+    /// #
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let mut map = varj::VarjMap::new();
+    /// map.insert("name", "Christopher");
+    ///
+    /// let mut output = Vec::new();
+    /// map.render_to("name: {{ name }}", &mut output)?;
+    ///
+    /// assert_eq!(b"name: Christopher", output.as_slice());
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// See [`render`](Self::render), or an error of kind [`ErrorKind::Io`]
+    /// if the output cannot be written. Output before a failing placeholder
+    /// may already be written.
+    pub fn render_to<W: Write>(&self, template: &str, writer: W) -> Result<(), Error> {
+        self.render_to_with_options(template, writer, &RenderOptions::default())
+    }
+
+    /// Render a template into a [`Write`]r as it is rendered, using the
+    /// given [`RenderOptions`].
+    ///
+    /// Output is encoded with [`RenderOptions::output_encoding`]. Unused
+    /// variables are rejected with [`RenderOptions::deny_unused`] before
+    /// anything is written. [`RenderOptions::post_process`],
+    /// [`RenderOptions::line_ending`] and [`RenderOptions::threads`] need the
+    /// whole output and are ignored; use [`render_to_with_options`] for them.
+    ///
+    /// # Errors
+    ///
+    /// See [`render_with_options`](Self::render_with_options) and
+    /// [`render_to`](Self::render_to).
+    pub fn render_to_with_options<W: Write>(
+        &self,
+        template: &str,
+        mut writer: W,
+        options: &RenderOptions,
+    ) -> Result<(), Error> {
+        let processed = preprocess::apply(template, options);
+        let blocks = processed.blocks();
+        section::check(&blocks)?;
+        if options.deny_unused {
+            self.check_used(&blocks)?;
+        }
+
+        let mut write = |output: &mut String| -> Result<(), Error> {
+            let bytes = options.output_encoding.encode(output, options.unmappable)?;
+            writer.write_all(&bytes)?;
+            output.clear();
+            Ok(())
+        };
+        let source = processed.source();
+        let mut output = String::with_capacity(WRITE_BUFFER + 32);
+        write_span(
+            &mut output,
+            source,
+            0..source.len(),
+            &blocks,
+            options,
+            self,
+            |output: &mut String| match output.len() {
+                len if len >= WRITE_BUFFER => write(output),
+                _ => Ok(()),
+            },
+        )?;
+        write(&mut output)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Read a template file in `encoding`.
+pub(crate) fn read(path: &Path, encoding: Encoding) -> Result<String, Error> {
+    std::fs::read(path)
+        .map_err(Box::from)
+        .and_then(|bytes| encoding.decode(bytes))
+        .map_err(|err| Error::with_source(ErrorKind::Load, &path.to_string_lossy(), err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_utf8() {
+        let decode = |bytes: &[u8]| Encoding::Utf8.decode(bytes.to_vec());
+        assert_eq!(
+            "{{ a }}",
+            decode(b"\xEF\xBB\xBF{{ a }}").expect("valid UTF-8")
+        );
+        assert_eq!(
+            "\u{FEFF}",
+            decode(b"\xEF\xBB\xBF\xEF\xBB\xBF").expect("valid UTF-8")
+        );
+        assert!(decode(b"\xE9").is_err());
+        let actual = decode(b"\xFF\xFE{\0").expect_err("UTF-16");
+        assert_eq!(
+            "found a UTF-16 byte order mark, use Encoding::Utf16",
+            actual.to_string()
+        );
+    }
+
+    #[test]
+    fn decode_utf16() {
+        let decode = |bytes: &[u8]| Encoding::Utf16.decode(bytes.to_vec());
+        assert_eq!(
+            "{é}",
+            decode(b"\xFF\xFE{\0\xE9\0}\0").expect("valid UTF-16")
+        );
+        assert_eq!(
+            "{é}",
+            decode(b"\xFE\xFF\0{\0\xE9\0}").expect("valid UTF-16")
+        );
+        assert_eq!("{é}", decode(b"{\0\xE9\0}\0").expect("valid UTF-16"));
+        assert_eq!("😀", decode(b"\x3D\xD8\x00\xDE").expect("valid UTF-16"));
+        assert!(decode(b"{\0}").is_err());
+        assert!(decode(b"\x3D\xD8").is_err());
+    }
+
+    #[test]
+    fn decode_latin1() {
+        let actual = Encoding::Latin1.decode(b"caf\xE9 {{ a }}".to_vec());
+        assert_eq!("café {{ a }}", actual.expect("any bytes are Latin-1"));
+    }
+
+    #[test]
+    fn encode_output() {
+        let encode = |encoding: Encoding, unmappable| {
+            encoding
+                .encode("é€", unmappable)
+                .map(Cow::into_owned)
+                .map_err(|err| err.to_string())
+        };
+        assert_eq!(
+            Ok(b"\xC3\xA9\xE2\x82\xAC".to_vec()),
+            encode(Encoding::Utf8, Unmappable::Error)
+        );
+        assert_eq!(
+            Ok(b"\xE9\0\xAC\x20".to_vec()),
+            encode(Encoding::Utf16, Unmappable::Error)
+        );
+        assert_eq!(
+            Ok(b"\xE9?".to_vec()),
+            encode(Encoding::Latin1, Unmappable::Replace(b'?'))
+        );
+        assert_eq!(
+            Err("character '€' cannot be encoded in Latin-1".to_owned()),
+            encode(Encoding::Latin1, Unmappable::Error)
+        );
+    }
+
+    #[test]
+    fn render_to_writer() {
+        let source = [("name", "Zoë")];
+        let mut output = Vec::new();
+        let options = RenderOptions::new().output_encoding(Encoding::Utf16);
+        render_to_with_options("{{ name }}", &source, &mut output, &options)
+            .expect("rendering should succeed");
+        assert_eq!(b"Z\0o\0\xEB\0".as_slice(), output.as_slice());
+
+        let mut output = Vec::new();
+        let actual =
+            render_to("{{ name }} {{ age }}", &source, &mut output).expect_err("age is missing");
+        assert_eq!(ErrorKind::UnknownVariable, actual.kind());
+        assert!(output.is_empty());
+
+        let options = RenderOptions::new().output_encoding(Encoding::Latin1);
+        let actual = render_to_with_options("{{ name }} €", &source, &mut output, &options)
+            .expect_err("€ is not Latin-1");
+        assert_eq!(ErrorKind::Io, actual.kind());
+    }
+
+    #[test]
+    fn render_map_to_writer() {
+        /// Records the length of each write.
+        #[derive(Default)]
+        struct Chunks(Vec<usize>);
+
+        impl Write for Chunks {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.push(buf.len());
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut map = VarjMap::new();
+        map.insert("line", "x".repeat(1000));
+        map.insert("unused", "");
+        let template = "{{ line }}\n".repeat(20);
+
+        let mut chunks = Chunks::default();
+        map.render_to(&template, &mut chunks)
+            .expect("rendering should succeed");
+        assert_eq!(vec![9008, 9009, 2003], chunks.0);
+
+        let mut output = Vec::new();
+        let options = RenderOptions::new()
+            .output_encoding(Encoding::Latin1)
+            .trim_standalone(true);
+        map.render_to_with_options("{{ unused }}\né\n{{ unused }}", &mut output, &options)
+            .expect("rendering should succeed");
+        assert_eq!(b"\xE9\n".as_slice(), output.as_slice());
+
+        let mut output = Vec::new();
+        let options = RenderOptions::new().deny_unused(true);
+        let actual = map
+            .render_to_with_options("{{ line }}", &mut output, &options)
+            .expect_err("unused is unused");
+        assert_eq!(ErrorKind::UnusedVariable, actual.kind());
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn render_files() {
+        let dir = std::env::temp_dir().join(format!("varj-file-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("creating dirs should succeed");
+        let utf8 = dir.join("utf8.tpl");
+        let utf16 = dir.join("utf16.tpl");
+        std::fs::write(&utf8, b"\xEF\xBB\xBFname: {{ name }}").expect("writing should succeed");
+        std::fs::write(&utf16, b"\xFF\xFE{\0{\0n\0a\0m\0e\0}\0}\0")
+            .expect("writing should succeed");
+
+        let source = [("name", "Christopher")];
+        let actual = render_file(&utf8, &source);
+        let options = RenderOptions::new().encoding(Encoding::Utf16);
+        let actual_utf16 = render_file_with_options(&utf16, &source, &options);
+        let missing = render_file(dir.join("missing.tpl"), &source);
+        std::fs::remove_dir_all(&dir).expect("removing dirs should succeed");
+
+        assert_eq!(
+            "name: Christopher",
+            actual.expect("rendering should succeed")
+        );
+        assert_eq!(
+            "Christopher",
+            actual_utf16.expect("rendering should succeed")
+        );
+        let missing = missing.expect_err("file is missing");
+        assert_eq!(ErrorKind::Load, missing.kind());
+        assert!(missing.key().ends_with("missing.tpl"));
+    }
+}
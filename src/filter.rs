@@ -0,0 +1,1451 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+#[cfg(feature = "i18n")]
+use crate::i18n::{CurrencyPlacement, Locale, Plural};
+use crate::{date, RenderOptions};
+
+/// Apply a block's filter chain, e.g. `trim | sql_quote("mysql")`, to
+/// `value`.
+///
+/// Returns a message describing the problem if the chain is malformed or a
+/// filter fails.
+pub(crate) fn apply<'v>(
+    value: Cow<'v, str>,
+    filters: &str,
+    options: &RenderOptions,
+) -> Result<Cow<'v, str>, String> {
+    if filters.is_empty() {
+        return Ok(value);
+    }
+
+    let mut value = Value::Str(value);
+    let mut parser = Parser {
+        chars: filters.chars().peekable(),
+    };
+    loop {
+        let filter = parser.filter()?;
+        value = value.filter(&filter, options)?;
+        match parser.next_non_whitespace() {
+            None => return Ok(value.into_str()),
+            Some('|') => continue,
+            Some(ch) => {
+                return Err(format!(
+                    "unexpected '{}' after filter '{}'",
+                    ch, filter.name
+                ))
+            }
+        }
+    }
+}
+
+/// The names of the filters in a chain, without running them.
+pub(crate) fn names(filters: &str) -> Result<Vec<String>, String> {
+    let mut names = Vec::new();
+    if filters.is_empty() {
+        return Ok(names);
+    }
+
+    let mut parser = Parser {
+        chars: filters.chars().peekable(),
+    };
+    loop {
+        let filter = parser.filter()?;
+        match parser.next_non_whitespace() {
+            None => {
+                names.push(filter.name);
+                return Ok(names);
+            }
+            Some('|') => names.push(filter.name),
+            Some(ch) => {
+                return Err(format!(
+                    "unexpected '{}' after filter '{}'",
+                    ch, filter.name
+                ))
+            }
+        }
+    }
+}
+
+/// Whether `name` is a built-in filter with the enabled features.
+pub(crate) fn is_known(name: &str) -> bool {
+    match name {
+        "date" | "filesize" | "group" | "hex" | "join" | "ordinal" | "pad_left" | "pad_right"
+        | "pluralize" | "regex_escape" | "replace" | "slug" | "split" | "sql_quote"
+        | "truncate" | "trim" | "unhex" => true,
+        #[cfg(feature = "base64")]
+        "b64decode" | "b64encode" => true,
+        #[cfg(feature = "hash")]
+        "md5" | "sha256" | "sha512" => true,
+        #[cfg(feature = "i18n")]
+        "currency" | "number" | "plural" => true,
+        _ => false,
+    }
+}
+
+/// A filter parsed from a chain, e.g. `plural(one=file, other=files)`.
+struct Filter {
+    name: String,
+    args: Vec<String>,
+    /// `name=value` arguments, only taken by `plural`.
+    named: Vec<(String, String)>,
+}
+
+/// A value passed along a filter chain.
+enum Value<'v> {
+    Str(Cow<'v, str>),
+    /// Made by `split`, turned back into a string by `join`.
+    List(Vec<String>),
+}
+
+impl<'v> Value<'v> {
+    /// Run a filter on the value. Filters other than `split` and `join` are
+    /// run on every item of a list.
+    fn filter(self, filter: &Filter, options: &RenderOptions) -> Result<Value<'v>, String> {
+        let (name, args) = (filter.name.as_str(), filter.args.as_slice());
+        if let Some((arg, _)) = filter.named.first().filter(|_| name != "plural") {
+            return Err(format!(
+                "unexpected named argument '{}' for filter '{}'",
+                arg, name
+            ));
+        }
+
+        match (name, self) {
+            ("split", Value::Str(value)) => {
+                let items = match optional_arg(name, args)? {
+                    None => value.split_whitespace().map(str::to_owned).collect(),
+                    Some("") => value.chars().map(String::from).collect(),
+                    Some(sep) => value.split(sep).map(str::to_owned).collect(),
+                };
+                Ok(Value::List(items))
+            }
+            ("split", Value::List(_)) => Err("filter 'split' expects a string, got a list".into()),
+            ("join", value) => {
+                let sep = optional_arg(name, args)?.unwrap_or("");
+                match value {
+                    Value::List(items) => Ok(Value::Str(Cow::Owned(items.join(sep)))),
+                    value => Ok(value),
+                }
+            }
+            #[cfg(feature = "i18n")]
+            ("plural", value) => {
+                value.map(|item| plural(item, &filter.args, &filter.named, &options.locale))
+            }
+            (_, value) => value.map(|item| call(name, item, args, options)),
+        }
+    }
+
+    /// Replace the value, or every item of a list, with the output of `f`.
+    fn map<F>(self, f: F) -> Result<Value<'v>, String>
+    where
+        F: Fn(&str) -> Result<String, String>,
+    {
+        match self {
+            Value::Str(value) => Ok(Value::Str(Cow::Owned(f(&value)?))),
+            Value::List(items) => items
+                .iter()
+                .map(|item| f(item))
+                .collect::<Result<_, _>>()
+                .map(Value::List),
+        }
+    }
+
+    /// The final string, with a remaining list joined by `, `.
+    fn into_str(self) -> Cow<'v, str> {
+        match self {
+            Value::Str(value) => value,
+            Value::List(items) => Cow::Owned(items.join(", ")),
+        }
+    }
+}
+
+/// Run a single built-in filter.
+fn call(
+    name: &str,
+    value: &str,
+    args: &[String],
+    options: &RenderOptions,
+) -> Result<String, String> {
+    match name {
+        #[cfg(feature = "base64")]
+        "b64decode" => b64decode(value, optional_arg(name, args)?.unwrap_or("standard")),
+        #[cfg(feature = "base64")]
+        "b64encode" => b64encode(value, optional_arg(name, args)?.unwrap_or("standard")),
+        #[cfg(feature = "i18n")]
+        "currency" => {
+            let args = arity(name, args, 1, 1)?;
+            currency(value, &args[0], &options.locale)
+        }
+        "date" => date::format(value, optional_arg(name, args)?, options),
+        "filesize" => filesize(value, optional_arg(name, args)?.unwrap_or("decimal")),
+        "group" => group(value, optional_arg(name, args)?.unwrap_or(",")),
+        "hex" => Ok(hex(
+            value.as_bytes(),
+            optional_arg(name, args)?.unwrap_or(""),
+        )),
+        #[cfg(feature = "hash")]
+        "md5" => no_args(name, args).map(|()| digest::<md5::Md5>(value)),
+        #[cfg(feature = "i18n")]
+        "number" => {
+            let decimals = optional_arg(name, args)?
+                .map(|decimals| number_arg(name, decimals))
+                .transpose()?;
+            if let Some(decimals) = decimals.filter(|&decimals| decimals > MAX_DECIMALS) {
+                return Err(format!(
+                    "filter '{}' decimals too large: expects at most {}, got '{}'",
+                    name, MAX_DECIMALS, decimals
+                ));
+            }
+            number(value, decimals, &options.locale)
+        }
+        "ordinal" => {
+            no_args(name, args)?;
+            let number: i64 = value
+                .trim()
+                .parse()
+                .map_err(|_| format!("filter 'ordinal' expects a whole number, got '{}'", value))?;
+            #[cfg(feature = "i18n")]
+            return Ok(options.locale.ordinal(number));
+            #[cfg(not(feature = "i18n"))]
+            Ok(english_ordinal(number))
+        }
+        "pad_left" | "pad_right" => {
+            let args = arity(name, args, 1, 2)?;
+            let width = number_arg(name, &args[0])?;
+            if width > MAX_WIDTH {
+                return Err(format!(
+                    "filter '{}' width too large: expects at most {}, got '{}'",
+                    name, MAX_WIDTH, width
+                ));
+            }
+            let fill = match args.get(1) {
+                Some(fill) => char_arg(name, fill)?,
+                None => ' ',
+            };
+            Ok(pad(value, width, fill, name == "pad_left"))
+        }
+        "pluralize" => {
+            let args = arity(name, args, 0, 2)?;
+            let (singular, plural) = match args {
+                [] => ("", "s"),
+                [plural] => ("", plural.as_str()),
+                [singular, plural, ..] => (singular.as_str(), plural.as_str()),
+            };
+            let count: f64 = value.trim().parse().map_err(|_| {
+                format!("filter '{}' expects a number value, got '{}'", name, value)
+            })?;
+            Ok(if count == 1.0 { singular } else { plural }.to_owned())
+        }
+        "regex_escape" => no_args(name, args).map(|()| regex_escape(value)),
+        "replace" => {
+            let args = arity(name, args, 2, 2)?;
+            if args[0].is_empty() {
+                return Err("filter 'replace' cannot replace an empty string".to_owned());
+            }
+            Ok(value.replace(args[0].as_str(), &args[1]))
+        }
+        #[cfg(feature = "hash")]
+        "sha256" => no_args(name, args).map(|()| digest::<sha2::Sha256>(value)),
+        #[cfg(feature = "hash")]
+        "sha512" => no_args(name, args).map(|()| digest::<sha2::Sha512>(value)),
+        "slug" => Ok(slug(value, optional_arg(name, args)?.unwrap_or("-"))),
+        "sql_quote" => sql_quote(value, optional_arg(name, args)?.unwrap_or("ansi")),
+        "truncate" => {
+            let args = arity(name, args, 1, 2)?;
+            let width = number_arg(name, &args[0])?;
+            let ellipsis = args.get(1).map_or("…", String::as_str);
+            Ok(truncate(value, width, ellipsis))
+        }
+        "trim" => no_args(name, args).map(|()| value.trim().to_owned()),
+        "unhex" => no_args(name, args).and_then(|()| unhex(value)),
+        _ => Err(format!("unknown filter '{}'", name)),
+    }
+}
+
+/// Check a filter was given between `min` and `max` arguments.
+fn arity<'a>(
+    name: &str,
+    args: &'a [String],
+    min: usize,
+    max: usize,
+) -> Result<&'a [String], String> {
+    if (min..=max).contains(&args.len()) {
+        return Ok(args);
+    }
+    let plural = if max == 1 { "" } else { "s" };
+    Err(match (min, max) {
+        (_, 0) => format!("filter '{}' takes no arguments", name),
+        (0, _) => format!("filter '{}' takes at most {} argument{}", name, max, plural),
+        _ if min == max => format!("filter '{}' takes {} argument{}", name, max, plural),
+        _ => format!("filter '{}' takes {} to {} arguments", name, min, max),
+    })
+}
+
+/// Check a filter was given no arguments.
+fn no_args(name: &str, args: &[String]) -> Result<(), String> {
+    arity(name, args, 0, 0).map(|_| ())
+}
+
+/// The single optional argument of a filter.
+fn optional_arg<'a>(name: &str, args: &'a [String]) -> Result<Option<&'a str>, String> {
+    arity(name, args, 0, 1).map(|args| args.first().map(String::as_str))
+}
+
+/// Parse a numeric filter argument.
+fn number_arg<T: std::str::FromStr>(name: &str, arg: &str) -> Result<T, String> {
+    arg.parse()
+        .map_err(|_| format!("filter '{}' expects a number, got '{}'", name, arg))
+}
+
+/// Parse a single character filter argument.
+fn char_arg(name: &str, arg: &str) -> Result<char, String> {
+    let mut chars = arg.chars();
+    match (chars.next(), chars.next()) {
+        (Some(ch), None) => Ok(ch),
+        _ => Err(format!(
+            "filter '{}' expects a single character, got '{}'",
+            name, arg
+        )),
+    }
+}
+
+/// Quote a value as an SQL string literal for the given dialect.
+fn sql_quote(value: &str, dialect: &str) -> Result<String, String> {
+    let (prefix, backslash_escapes) = match dialect {
+        "ansi" | "postgres" | "sqlite" => ("", false),
+        "mysql" => ("", true),
+        "mssql" => ("N", false),
+        _ => return Err(format!("unknown sql dialect '{}'", dialect)),
+    };
+
+    let mut output = String::with_capacity(value.len() + 3);
+    output.push_str(prefix);
+    output.push('\'');
+    for ch in value.chars() {
+        match ch {
+            '\'' if backslash_escapes => output.push_str("\\'"),
+            '\'' => output.push_str("''"),
+            '\\' if backslash_escapes => output.push_str("\\\\"),
+            '\0' if backslash_escapes => output.push_str("\\0"),
+            '\0' => return Err("value contains a NUL character".to_owned()),
+            '\n' if backslash_escapes => output.push_str("\\n"),
+            '\r' if backslash_escapes => output.push_str("\\r"),
+            '\u{1a}' if backslash_escapes => output.push_str("\\Z"),
+            ch => output.push(ch),
+        }
+    }
+    output.push('\'');
+    Ok(output)
+}
+
+/// English ordinal of a number, e.g. `1st`, `12th` or `22nd`.
+pub(crate) fn english_ordinal(number: i64) -> String {
+    let abs = number.unsigned_abs();
+    let suffix = match (abs % 10, abs % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", number, suffix)
+}
+
+/// Pad `value` with `fill` to at least `width` characters, on the left to
+/// align it right or on the right to align it left.
+fn pad(value: &str, width: usize, fill: char, left: bool) -> String {
+    let padding: String = std::iter::repeat(fill)
+        .take(width.saturating_sub(value.chars().count()))
+        .collect();
+    if left {
+        padding + value
+    } else {
+        value.to_owned() + &padding
+    }
+}
+
+/// The widest padding, in characters, of an alignment or a pad filter, so a
+/// template cannot make a render allocate without bound.
+pub(crate) const MAX_WIDTH: usize = u16::MAX as usize;
+
+/// Padding of a value to a width, from the `>12` of `{{ key:>12 }}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct Align {
+    fill: char,
+    side: Side,
+    width: usize,
+}
+
+/// Where a value goes in its padding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Side {
+    Left,
+    Right,
+    Center,
+}
+
+impl Align {
+    /// Parse an optional fill character, then `<`, `>` or `^`, then a width
+    /// of at most [`MAX_WIDTH`].
+    pub(crate) fn parse(spec: &str) -> Option<Align> {
+        let side = |ch| match ch {
+            '<' => Some(Side::Left),
+            '>' => Some(Side::Right),
+            '^' => Some(Side::Center),
+            _ => None,
+        };
+        let mut chars = spec.chars();
+        let first = chars.next()?;
+        let (fill, side, width) = match chars.next().and_then(side) {
+            Some(side) => (first, side, chars.as_str()),
+            None => (' ', side(first)?, &spec[1..]),
+        };
+        if width.is_empty() || !width.bytes().all(|byte| byte.is_ascii_digit()) {
+            return None;
+        }
+        let width = width.parse().ok().filter(|&width| width <= MAX_WIDTH)?;
+        Some(Align { fill, side, width })
+    }
+
+    /// Pad `value` to the width, counted in characters.
+    pub(crate) fn apply<'v>(&self, value: Cow<'v, str>) -> Cow<'v, str> {
+        let missing = self.width.saturating_sub(value.chars().count());
+        if missing == 0 {
+            return value;
+        }
+        let (before, after) = match self.side {
+            Side::Left => (0, missing),
+            Side::Right => (missing, 0),
+            Side::Center => (missing / 2, missing - missing / 2),
+        };
+        let fill = |count| std::iter::repeat(self.fill).take(count);
+        Cow::Owned(
+            fill(before)
+                .chain(value.chars())
+                .chain(fill(after))
+                .collect(),
+        )
+    }
+}
+
+impl fmt::Display for Align {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let side = match self.side {
+            Side::Left => '<',
+            Side::Right => '>',
+            Side::Center => '^',
+        };
+        write!(f, "{}{}{}", self.fill, side, self.width)
+    }
+}
+
+/// Shorten `value` to at most `width` characters, ending with `ellipsis`
+/// when shortened.
+fn truncate(value: &str, width: usize, ellipsis: &str) -> String {
+    if char_boundary(value, width).is_none() {
+        return value.to_owned();
+    }
+    let ellipsis_width = char_count(ellipsis);
+    if ellipsis_width > width {
+        return value[..char_boundary(value, width).unwrap_or(value.len())].to_owned();
+    }
+    let end = char_boundary(value, width - ellipsis_width).unwrap_or(value.len());
+    value[..end].trim_end().to_owned() + ellipsis
+}
+
+/// Format a number of bytes with the largest fitting decimal or binary
+/// unit, e.g. `1.5 MB` or `1.5 MiB`.
+fn filesize(value: &str, units: &str) -> Result<String, String> {
+    let (base, units) = match units {
+        "decimal" => (1000.0, ["B", "kB", "MB", "GB", "TB", "PB", "EB"]),
+        "binary" => (1024.0, ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"]),
+        _ => return Err(format!("unknown filesize units '{}'", units)),
+    };
+    let mut size: f64 = value
+        .trim()
+        .parse()
+        .ok()
+        .filter(|size: &f64| size.is_finite())
+        .ok_or_else(|| format!("filter 'filesize' expects a number value, got '{}'", value))?;
+
+    if size.abs() < base {
+        return Ok(format!("{} {}", size, units[0]));
+    }
+    let mut unit = 0;
+    // compare after rounding so 999999 bytes becomes 1.0 MB, not 1000.0 kB
+    while (size.abs() * 10.0).round() >= base * 10.0 && unit < units.len() - 1 {
+        size /= base;
+        unit += 1;
+    }
+    Ok(format!("{:.1} {}", size, units[unit]))
+}
+
+/// Separate the integer digits of a number in groups of three with `sep`.
+fn group(value: &str, sep: &str) -> Result<String, String> {
+    let number = Number::parse(value)
+        .ok_or_else(|| format!("filter 'group' expects a number value, got '{}'", value))?;
+    let mut output = number.sign.to_owned() + &grouped(number.integer, sep, 3);
+    if let Some(fraction) = number.fraction {
+        output.push('.');
+        output.push_str(fraction);
+    }
+    Ok(output)
+}
+
+/// The most fraction digits `number` rounds to, so a template cannot make a
+/// render allocate without bound.
+#[cfg(feature = "i18n")]
+const MAX_DECIMALS: usize = 100;
+
+/// Format a number with the decimal separator and digit grouping of
+/// `locale`, rounded to `decimals` fraction digits if given.
+#[cfg(feature = "i18n")]
+fn number(value: &str, decimals: Option<usize>, locale: &Locale) -> Result<String, String> {
+    let invalid = || format!("filter 'number' expects a number value, got '{}'", value);
+    let rounded;
+    let mut number = Number::parse(value).ok_or_else(invalid)?;
+    if let Some(decimals) = decimals {
+        let float: f64 = value.trim().parse().map_err(|_| invalid())?;
+        rounded = format!("{:.*}", decimals, float);
+        number = Number::parse(&rounded).ok_or_else(invalid)?;
+    }
+
+    let symbols = locale.number_symbols();
+    let mut output = number.sign.to_owned();
+    if number.integer.len() < 3 + symbols.min_grouping {
+        output.push_str(number.integer);
+    } else {
+        output.push_str(&grouped(
+            number.integer,
+            symbols.group,
+            symbols.secondary_grouping,
+        ));
+    }
+    if let Some(fraction) = number.fraction.filter(|fraction| !fraction.is_empty()) {
+        output.push(symbols.decimal);
+        output.push_str(fraction);
+    }
+    Ok(output)
+}
+
+/// Format an amount of the currency with ISO 4217 `code` as written in
+/// `locale`.
+#[cfg(feature = "i18n")]
+fn currency(value: &str, code: &str, locale: &Locale) -> Result<String, String> {
+    if code.len() != 3 || !code.chars().all(|ch| ch.is_ascii_alphabetic()) {
+        return Err(format!("invalid currency code '{}'", code));
+    }
+    let code = code.to_ascii_uppercase();
+    let currency = locale.currency(&code);
+    let amount = number(value, Some(currency.digits), locale)
+        .map_err(|_| format!("filter 'currency' expects a number value, got '{}'", value))?;
+    let (sign, amount) = match amount.strip_prefix('-') {
+        Some(amount) => ("-", amount),
+        None => ("", amount.trim_start_matches('+')),
+    };
+
+    let symbol = currency.symbol;
+    Ok(match currency.placement {
+        CurrencyPlacement::Before if !symbol.ends_with(char::is_alphabetic) => {
+            format!("{}{}{}", sign, symbol, amount)
+        }
+        CurrencyPlacement::Before | CurrencyPlacement::BeforeSpaced => {
+            format!("{}{}\u{a0}{}", sign, symbol, amount)
+        }
+        CurrencyPlacement::After => format!("{}{}\u{a0}{}", sign, amount, symbol),
+    })
+}
+
+/// A decimal number split into its sign, integer and fraction digits.
+struct Number<'a> {
+    sign: &'a str,
+    integer: &'a str,
+    fraction: Option<&'a str>,
+}
+
+impl<'a> Number<'a> {
+    fn parse(value: &'a str) -> Option<Self> {
+        let number = value.trim();
+        let unsigned = number.strip_prefix(['-', '+']).unwrap_or(number);
+        let (integer, fraction) = match unsigned.split_once('.') {
+            Some((integer, fraction)) => (integer, Some(fraction)),
+            None => (unsigned, None),
+        };
+        let digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+        if integer.is_empty() || !digits(integer) || !fraction.map_or(true, digits) {
+            return None;
+        }
+        Some(Self {
+            sign: &number[..number.len() - unsigned.len()],
+            integer,
+            fraction,
+        })
+    }
+}
+
+/// ASCII `digits` with `sep` between the last group of three and groups of
+/// `secondary` digits before it.
+fn grouped(digits: &str, sep: &str, secondary: usize) -> String {
+    let mut output = String::with_capacity(digits.len() + digits.len() / 2 * sep.len());
+    for (idx, digit) in digits.chars().enumerate() {
+        let remaining = digits.len() - idx;
+        if idx > 0 && remaining >= 3 && (remaining - 3) % secondary == 0 {
+            output.push_str(sep);
+        }
+        output.push(digit);
+    }
+    output
+}
+
+/// Lowercase `value` and join its runs of letters and digits with `sep`.
+fn slug(value: &str, sep: &str) -> String {
+    let mut output = String::with_capacity(value.len());
+    let mut pending_sep = false;
+    for ch in transliterate(value).chars() {
+        if ch.is_alphanumeric() {
+            if pending_sep && !output.is_empty() {
+                output.push_str(sep);
+            }
+            pending_sep = false;
+            output.extend(ch.to_lowercase());
+        } else {
+            pending_sep = true;
+        }
+    }
+    output
+}
+
+/// `value` with accents removed and common ligatures spelled out in ASCII.
+#[cfg(feature = "unicode")]
+fn transliterate(value: &str) -> Cow<'_, str> {
+    use unicode_normalization::char::is_combining_mark;
+    use unicode_normalization::UnicodeNormalization;
+
+    if value.is_ascii() {
+        return Cow::Borrowed(value);
+    }
+    let mut output = String::with_capacity(value.len());
+    for ch in value.nfkd().filter(|&ch| !is_combining_mark(ch)) {
+        match ch {
+            'ß' => output.push_str("ss"),
+            'æ' => output.push_str("ae"),
+            'Æ' => output.push_str("AE"),
+            'œ' => output.push_str("oe"),
+            'Œ' => output.push_str("OE"),
+            'ø' => output.push('o'),
+            'Ø' => output.push('O'),
+            'đ' | 'ð' => output.push('d'),
+            'Đ' | 'Ð' => output.push('D'),
+            'ł' => output.push('l'),
+            'Ł' => output.push('L'),
+            'þ' => output.push_str("th"),
+            'Þ' => output.push_str("TH"),
+            ch => output.push(ch),
+        }
+    }
+    Cow::Owned(output)
+}
+
+#[cfg(not(feature = "unicode"))]
+fn transliterate(value: &str) -> Cow<'_, str> {
+    Cow::Borrowed(value)
+}
+
+/// Byte index of the `n`th user-perceived character of `value`, or `None`
+/// if it has `n` or fewer.
+#[cfg(feature = "unicode")]
+fn char_boundary(value: &str, n: usize) -> Option<usize> {
+    use unicode_segmentation::UnicodeSegmentation;
+
+    value.grapheme_indices(true).nth(n).map(|(idx, _)| idx)
+}
+
+#[cfg(not(feature = "unicode"))]
+fn char_boundary(value: &str, n: usize) -> Option<usize> {
+    value.char_indices().nth(n).map(|(idx, _)| idx)
+}
+
+/// Number of user-perceived characters in `value`.
+#[cfg(feature = "unicode")]
+fn char_count(value: &str) -> usize {
+    use unicode_segmentation::UnicodeSegmentation;
+
+    value.graphemes(true).count()
+}
+
+#[cfg(not(feature = "unicode"))]
+fn char_count(value: &str) -> usize {
+    value.chars().count()
+}
+
+/// Escape regular expression meta characters so `value` matches literally.
+fn regex_escape(value: &str) -> String {
+    let mut output = String::with_capacity(value.len() + 8);
+    for ch in value.chars() {
+        if r"\.+*?()|[]{}^$#&-~".contains(ch) {
+            output.push('\\');
+        }
+        output.push(ch);
+    }
+    output
+}
+
+/// Choose the form of the CLDR plural category of a number in `locale`,
+/// replacing `#` in it with the number.
+#[cfg(feature = "i18n")]
+fn plural(
+    value: &str,
+    args: &[String],
+    forms: &[(String, String)],
+    locale: &Locale,
+) -> Result<String, String> {
+    if !args.is_empty() || forms.is_empty() {
+        return Err(
+            "filter 'plural' takes named forms, e.g. plural(one=\"# file\", other=\"# files\")"
+                .to_owned(),
+        );
+    }
+    for (category, _) in forms {
+        if Plural::from_name(category).is_none() {
+            return Err(format!("unknown plural category '{}'", category));
+        }
+    }
+    let form = |category: Plural| {
+        forms
+            .iter()
+            .rev()
+            .find(|(name, _)| Plural::from_name(name) == Some(category))
+            .map(|(_, form)| form)
+    };
+
+    let category = locale
+        .plural(value)
+        .ok_or_else(|| format!("filter 'plural' expects a number value, got '{}'", value))?;
+    let form = form(category)
+        .or_else(|| form(Plural::Other))
+        .ok_or("filter 'plural' requires an 'other' form")?;
+    Ok(form.replace('#', value.trim()))
+}
+
+/// Lowercase hex of `bytes`, with `sep` between each byte.
+fn hex(bytes: &[u8], sep: &str) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+    let mut output = String::with_capacity(bytes.len() * (2 + sep.len()));
+    for (idx, byte) in bytes.iter().enumerate() {
+        if idx > 0 {
+            output.push_str(sep);
+        }
+        output.push(DIGITS[usize::from(byte >> 4)].into());
+        output.push(DIGITS[usize::from(byte & 0xf)].into());
+    }
+    output
+}
+
+/// Decode hex digits, ignoring whitespace and `:` or `-` separators.
+fn unhex(value: &str) -> Result<String, String> {
+    let digits = value
+        .chars()
+        .filter(|&ch| !(ch.is_whitespace() || ch == ':' || ch == '-'))
+        .map(|ch| {
+            ch.to_digit(16)
+                .ok_or_else(|| format!("invalid hex digit '{}'", ch))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if digits.len() % 2 != 0 {
+        return Err("invalid hex: odd number of digits".to_owned());
+    }
+    let bytes = digits
+        .chunks(2)
+        .map(|pair| (pair[0] * 16 + pair[1]) as u8)
+        .collect();
+    String::from_utf8(bytes).map_err(|_| "decoded hex is not valid UTF-8".to_owned())
+}
+
+/// Base64 engine for an alphabet name, padded for encoding and padding
+/// optional for decoding.
+#[cfg(feature = "base64")]
+fn b64_engine(alphabet: &str) -> Result<base64::engine::GeneralPurpose, String> {
+    use base64::alphabet::{STANDARD, URL_SAFE};
+    use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+
+    let alphabet = match alphabet {
+        "standard" => &STANDARD,
+        "url" => &URL_SAFE,
+        _ => return Err(format!("unknown base64 alphabet '{}'", alphabet)),
+    };
+    let config =
+        GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent);
+    Ok(GeneralPurpose::new(alphabet, config))
+}
+
+#[cfg(feature = "base64")]
+fn b64encode(value: &str, alphabet: &str) -> Result<String, String> {
+    use base64::Engine;
+
+    Ok(b64_engine(alphabet)?.encode(value))
+}
+
+#[cfg(feature = "base64")]
+fn b64decode(value: &str, alphabet: &str) -> Result<String, String> {
+    use base64::Engine;
+
+    let bytes = b64_engine(alphabet)?
+        .decode(value.trim())
+        .map_err(|err| format!("invalid base64: {}", err))?;
+    String::from_utf8(bytes).map_err(|_| "decoded base64 is not valid UTF-8".to_owned())
+}
+
+/// Lowercase hex digest of `value` with the hash function `D`.
+#[cfg(feature = "hash")]
+fn digest<D: sha2::Digest>(value: &str) -> String {
+    hex(&D::digest(value), "")
+}
+
+/// Parser for a filter chain: `name`, `name(arg, "quoted arg")`, separated
+/// by `|`.
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl Parser<'_> {
+    fn filter(&mut self) -> Result<Filter, String> {
+        self.skip_whitespace();
+        let mut name = String::new();
+        while let Some(&ch) = self.chars.peek() {
+            if !(ch.is_alphanumeric() || ch == '_') {
+                break;
+            }
+            name.push(ch);
+            self.chars.next();
+        }
+        if name.is_empty() {
+            return Err("missing filter name".to_owned());
+        }
+
+        self.skip_whitespace();
+        let mut filter = Filter {
+            name,
+            args: Vec::new(),
+            named: Vec::new(),
+        };
+        if self.chars.peek() == Some(&'(') {
+            self.chars.next();
+            if self.next_is(')') {
+                return Ok(filter);
+            }
+            loop {
+                match self.arg_name() {
+                    Some(arg) => filter.named.push((arg, self.arg()?)),
+                    None => filter.args.push(self.arg()?),
+                }
+                match self.next_non_whitespace() {
+                    Some(',') => continue,
+                    Some(')') => break,
+                    _ => return Err(format!("unclosed arguments of filter '{}'", filter.name)),
+                }
+            }
+        }
+        Ok(filter)
+    }
+
+    /// Consume the `name=` of a named argument, if the next argument has one.
+    fn arg_name(&mut self) -> Option<String> {
+        let mut lookahead = self.chars.clone();
+        while lookahead.next_if(|ch| ch.is_whitespace()).is_some() {}
+        let mut name = String::new();
+        while let Some(ch) = lookahead.next_if(|&ch| ch.is_alphanumeric() || ch == '_') {
+            name.push(ch);
+        }
+        while lookahead.next_if(|ch| ch.is_whitespace()).is_some() {}
+        if name.is_empty() || lookahead.next() != Some('=') {
+            return None;
+        }
+        self.chars = lookahead;
+        Some(name)
+    }
+
+    fn arg(&mut self) -> Result<String, String> {
+        self.skip_whitespace();
+        match self.chars.peek() {
+            Some(&quote) if quote == '"' || quote == '\'' => {
+                self.chars.next();
+                self.quoted(quote)
+            }
+            _ => {
+                let mut arg = String::new();
+                while let Some(&ch) = self.chars.peek() {
+                    if ch == ',' || ch == ')' {
+                        break;
+                    }
+                    arg.push(ch);
+                    self.chars.next();
+                }
+                Ok(arg.trim_end().to_owned())
+            }
+        }
+    }
+
+    fn quoted(&mut self, quote: char) -> Result<String, String> {
+        let mut arg = String::new();
+        while let Some(ch) = self.chars.next() {
+            match ch {
+                '\\' => match self.chars.next() {
+                    Some('n') => arg.push('\n'),
+                    Some('t') => arg.push('\t'),
+                    Some(ch) => arg.push(ch),
+                    None => break,
+                },
+                ch if ch == quote => return Ok(arg),
+                ch => arg.push(ch),
+            }
+        }
+        Err("unterminated string argument".to_owned())
+    }
+
+    /// Consume `expected` if it is the next non-whitespace character.
+    fn next_is(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+        let found = self.chars.peek() == Some(&expected);
+        if found {
+            self.chars.next();
+        }
+        found
+    }
+
+    fn next_non_whitespace(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|ch| ch.is_whitespace()).is_some() {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filtered(value: &str, filters: &str) -> Result<String, String> {
+        apply(Cow::Borrowed(value), filters, &RenderOptions::default()).map(Cow::into_owned)
+    }
+
+    #[cfg(feature = "i18n")]
+    fn filtered_in(tag: &str, value: &str, filters: &str) -> Result<String, String> {
+        let options = RenderOptions::new().locale(tag.parse().expect("valid tag"));
+        apply(Cow::Borrowed(value), filters, &options).map(Cow::into_owned)
+    }
+
+    #[test]
+    fn parse_filter_chain() {
+        assert_eq!(Ok("value".to_owned()), filtered("value", ""));
+        assert_eq!(
+            Ok("'''it''''s'''".to_owned()),
+            filtered("it's", " sql_quote | sql_quote ( ) ")
+        );
+        assert_eq!(
+            Err("unknown filter 'nope'".to_owned()),
+            filtered("value", "nope")
+        );
+        assert_eq!(
+            Err("unexpected 'x' after filter 'sql_quote'".to_owned()),
+            filtered("value", "sql_quote x")
+        );
+        assert_eq!(
+            Err("unclosed arguments of filter 'sql_quote'".to_owned()),
+            filtered("value", "sql_quote('mysql'")
+        );
+        assert_eq!(
+            Err("unterminated string argument".to_owned()),
+            filtered("value", "sql_quote('mysql)")
+        );
+        assert_eq!(
+            Err("missing filter name".to_owned()),
+            filtered("value", "sql_quote |")
+        );
+    }
+
+    #[cfg(feature = "i18n")]
+    #[test]
+    fn plural_forms() {
+        let filters = r##"plural(one="# file", other="# files")"##;
+        assert_eq!(Ok("1 file".to_owned()), filtered("1", filters));
+        assert_eq!(Ok("1.0 files".to_owned()), filtered("1.0", filters));
+        assert_eq!(
+            Ok("0 fichier".to_owned()),
+            filtered_in("fr", "0", "plural(one='# fichier', other='# fichiers')")
+        );
+
+        let filters = "plural(one=plik, few=pliki, many=plików, other=pliku)";
+        assert_eq!(Ok("pliki".to_owned()), filtered_in("pl", "22", filters));
+        assert_eq!(Ok("plików".to_owned()), filtered_in("pl", "12", filters));
+        assert_eq!(Ok("pliku".to_owned()), filtered_in("pl", "1.5", filters));
+        assert_eq!(
+            Ok("x".to_owned()),
+            filtered_in("ar", "3", "plural(one=y, other=x)")
+        );
+
+        assert_eq!(
+            Err("unknown plural category 'lots'".to_owned()),
+            filtered("1", "plural(lots=x, other=y)")
+        );
+        assert_eq!(
+            Err("filter 'plural' requires an 'other' form".to_owned()),
+            filtered("2", "plural(one=x)")
+        );
+        assert_eq!(
+            Err("filter 'plural' expects a number value, got 'two'".to_owned()),
+            filtered("two", "plural(other=x)")
+        );
+        assert!(filtered("1", "plural(x)")
+            .unwrap_err()
+            .starts_with("filter 'plural' takes named forms"));
+    }
+
+    #[cfg(feature = "i18n")]
+    #[test]
+    fn locale_numbers() {
+        assert_eq!(
+            Ok("1,234.56".to_owned()),
+            filtered_in("en-US", "1234.56", "number")
+        );
+        assert_eq!(
+            Ok("1.234,56".to_owned()),
+            filtered_in("de-DE", "1234.56", "number")
+        );
+        assert_eq!(
+            Ok("-1\u{202f}234,5".to_owned()),
+            filtered_in("fr-FR", "-1234.5", "number")
+        );
+        assert_eq!(
+            Ok("1’234.00".to_owned()),
+            filtered_in("de-CH", "1234", "number(2)")
+        );
+        assert_eq!(Ok("1234".to_owned()), filtered_in("es", "1234", "number"));
+        assert_eq!(
+            Ok("12.345".to_owned()),
+            filtered_in("es", "12345", "number")
+        );
+        assert_eq!(
+            Ok("12,34,567".to_owned()),
+            filtered_in("en-IN", "1234567", "number")
+        );
+        assert_eq!(Ok("1,234.6".to_owned()), filtered("1234.56", "number(1)"));
+        assert_eq!(Ok("0.13".to_owned()), filtered("0.125001", "number(2)"));
+        assert_eq!(
+            Err("filter 'number' expects a number value, got '1,5'".to_owned()),
+            filtered("1,5", "number")
+        );
+        assert_eq!(
+            Err("filter 'number' decimals too large: expects at most 100, got '70000'".to_owned()),
+            filtered("1.5", "number(70000)")
+        );
+        assert_eq!(
+            100,
+            filtered("1.5", "number(100)").map_or(0, |n| n.len() - 2)
+        );
+    }
+
+    #[cfg(feature = "i18n")]
+    #[test]
+    fn locale_currencies() {
+        assert_eq!(
+            Ok("$1,234.50".to_owned()),
+            filtered("1234.5", "currency(USD)")
+        );
+        assert_eq!(Ok("-€5.00".to_owned()), filtered("-5", "currency(eur)"));
+        assert_eq!(Ok("¥1,235".to_owned()), filtered("1234.6", "currency(JPY)"));
+        assert_eq!(
+            Ok("CHF\u{a0}12.00".to_owned()),
+            filtered("12", "currency(CHF)")
+        );
+        assert_eq!(
+            Ok("1.234,50\u{a0}€".to_owned()),
+            filtered_in("de-DE", "1234.5", "currency(EUR)")
+        );
+        assert_eq!(
+            Ok("CHF\u{a0}1’234.50".to_owned()),
+            filtered_in("de-CH", "1234.5", "currency(CHF)")
+        );
+        assert_eq!(
+            Ok("R$\u{a0}9,90".to_owned()),
+            filtered_in("pt-BR", "9.9", "currency(BRL)")
+        );
+        assert_eq!(
+            Ok("1234,00\u{a0}zł".to_owned()),
+            filtered_in("pl", "1234", "currency(PLN)")
+        );
+        assert_eq!(
+            Ok("$20.00".to_owned()),
+            filtered_in("en-CA", "20", "currency(CAD)")
+        );
+        assert_eq!(
+            Err("invalid currency code 'dollar'".to_owned()),
+            filtered("1", "currency(dollar)")
+        );
+        assert_eq!(
+            Err("filter 'currency' expects a number value, got 'free'".to_owned()),
+            filtered("free", "currency(USD)")
+        );
+    }
+
+    #[cfg(feature = "i18n")]
+    #[test]
+    fn locale_dates() {
+        let options = RenderOptions::new()
+            .locale("fr-FR".parse().expect("valid tag"))
+            .date_style(crate::DateStyle::Full);
+        let in_french = |filters: &str| {
+            apply(Cow::Borrowed("2024-03-08"), filters, &options).map(Cow::into_owned)
+        };
+        assert_eq!(Ok("vendredi 8 mars 2024".to_owned()), in_french("date"));
+        assert_eq!(Ok("08/03/2024".to_owned()), in_french("date(short)"));
+        assert_eq!(Ok("ven. 8 mars".to_owned()), in_french("date('%a %-d %b')"));
+    }
+
+    #[test]
+    fn named_args_only_for_plural() {
+        assert_eq!(
+            Err("unexpected named argument 'sep' for filter 'join'".to_owned()),
+            filtered("a", "join(sep = '-')")
+        );
+        assert_eq!(Ok("a=b".to_owned()), filtered("a", "replace(a, 'a=b')"));
+    }
+
+    #[test]
+    fn ordinals() {
+        let ordinals: Vec<_> = [
+            "1", "2", "3", "4", "11", "12", "13", "21", "102", "111", "-3",
+        ]
+        .iter()
+        .map(|value| filtered(value, "ordinal").expect("valid number"))
+        .collect();
+        assert_eq!(
+            vec![
+                "1st", "2nd", "3rd", "4th", "11th", "12th", "13th", "21st", "102nd", "111th",
+                "-3rd"
+            ],
+            ordinals
+        );
+        assert_eq!(
+            Err("filter 'ordinal' expects a whole number, got '1.5'".to_owned()),
+            filtered("1.5", "ordinal")
+        );
+    }
+
+    #[cfg(feature = "i18n")]
+    #[test]
+    fn locale_ordinals() {
+        assert_eq!(Ok("3.".to_owned()), filtered_in("de-DE", "3", "ordinal"));
+        assert_eq!(Ok("1er".to_owned()), filtered_in("fr", "1", "ordinal"));
+        assert_eq!(Ok("2e".to_owned()), filtered_in("fr", "2", "ordinal"));
+        assert_eq!(Ok("4º".to_owned()), filtered_in("es", "4", "ordinal"));
+        assert_eq!(Ok("21:a".to_owned()), filtered_in("sv", "21", "ordinal"));
+        assert_eq!(Ok("11:e".to_owned()), filtered_in("sv", "11", "ordinal"));
+        assert_eq!(Ok("5-й".to_owned()), filtered_in("ru", "5", "ordinal"));
+        assert_eq!(Ok("22nd".to_owned()), filtered_in("en-GB", "22", "ordinal"));
+    }
+
+    #[test]
+    fn pluralize_counts() {
+        assert_eq!(Ok("".to_owned()), filtered("1", "pluralize"));
+        assert_eq!(Ok("s".to_owned()), filtered("0", "pluralize"));
+        assert_eq!(Ok("s".to_owned()), filtered(" 2 ", "pluralize()"));
+        assert_eq!(Ok("es".to_owned()), filtered("1.5", "pluralize(es)"));
+        assert_eq!(Ok("y".to_owned()), filtered("1", "pluralize(y, ies)"));
+        assert_eq!(Ok("ies".to_owned()), filtered("-1", "pluralize(y, ies)"));
+        assert_eq!(
+            Err("filter 'pluralize' expects a number value, got 'many'".to_owned()),
+            filtered("many", "pluralize")
+        );
+        assert_eq!(
+            Err("filter 'pluralize' takes at most 2 arguments".to_owned()),
+            filtered("1", "pluralize(a, b, c)")
+        );
+    }
+
+    #[test]
+    fn regex_escape_meta_chars() {
+        assert_eq!(
+            Ok(r"src/\*\*/\*\.rs \(v1\.0\)\^\$".to_owned()),
+            filtered("src/**/*.rs (v1.0)^$", "regex_escape")
+        );
+        assert_eq!(Ok("plain".to_owned()), filtered("plain", "regex_escape()"));
+        assert_eq!(
+            Err("filter 'regex_escape' takes no arguments".to_owned()),
+            filtered("plain", "regex_escape(x)")
+        );
+    }
+
+    #[cfg(feature = "base64")]
+    #[test]
+    fn base64_round_trip() {
+        assert_eq!(Ok("aGk/Pz4=".to_owned()), filtered("hi??>", "b64encode"));
+        assert_eq!(
+            Ok("aGk_Pz4=".to_owned()),
+            filtered("hi??>", "b64encode(url)")
+        );
+        assert_eq!(Ok("hi??>".to_owned()), filtered("aGk/Pz4", "b64decode"));
+        assert_eq!(
+            Ok("hi??>".to_owned()),
+            filtered("hi??>", "b64encode | b64decode")
+        );
+        assert_eq!(
+            Err("decoded base64 is not valid UTF-8".to_owned()),
+            filtered("/w==", "b64decode")
+        );
+        assert!(filtered("not base64!", "b64decode")
+            .unwrap_err()
+            .starts_with("invalid base64: "));
+        assert_eq!(
+            Err("unknown base64 alphabet 'mime'".to_owned()),
+            filtered("hi", "b64encode(mime)")
+        );
+    }
+
+    #[cfg(feature = "hash")]
+    #[test]
+    fn hash_digests() {
+        assert_eq!(
+            Ok("5d41402abc4b2a76b9719d911017c592".to_owned()),
+            filtered("hello", "md5")
+        );
+        assert_eq!(
+            Ok("2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824".to_owned()),
+            filtered("hello", "sha256")
+        );
+        assert_eq!(
+            Ok("e3b0c44298fc1c14".to_owned()),
+            filtered("", "sha256 | truncate(16, '')")
+        );
+        assert_eq!(128, filtered("hello", "sha512").unwrap().len());
+        assert_eq!(
+            Err("filter 'md5' takes no arguments".to_owned()),
+            filtered("hello", "md5(hex)")
+        );
+    }
+
+    #[test]
+    fn filesize_units() {
+        assert_eq!(Ok("1.5 MB".to_owned()), filtered("1536000", "filesize"));
+        assert_eq!(
+            Ok("1.5 MiB".to_owned()),
+            filtered("1572864", "filesize(binary)")
+        );
+        assert_eq!(Ok("512 B".to_owned()), filtered("512", "filesize"));
+        assert_eq!(
+            Ok("1023 B".to_owned()),
+            filtered("1023", "filesize(binary)")
+        );
+        assert_eq!(Ok("1.0 kB".to_owned()), filtered("1000", "filesize"));
+        assert_eq!(Ok("1.0 MB".to_owned()), filtered("999999", "filesize"));
+        assert_eq!(Ok("-2.5 GB".to_owned()), filtered("-2.5e9", "filesize"));
+        assert_eq!(
+            Err("unknown filesize units 'si'".to_owned()),
+            filtered("1", "filesize(si)")
+        );
+        assert_eq!(
+            Err("filter 'filesize' expects a number value, got 'inf'".to_owned()),
+            filtered("inf", "filesize")
+        );
+    }
+
+    #[test]
+    fn group_digits() {
+        assert_eq!(Ok("1,234,567".to_owned()), filtered("1234567", "group"));
+        assert_eq!(Ok("-123".to_owned()), filtered(" -123 ", "group"));
+        assert_eq!(
+            Ok("+12 345.6789".to_owned()),
+            filtered("+12345.6789", "group(' ')")
+        );
+        assert_eq!(Ok("1.000".to_owned()), filtered("1000", "group(.)"));
+        for value in ["", "1e6", "--1", "1.2.3", ".5", "12a"] {
+            assert_eq!(
+                Err(format!(
+                    "filter 'group' expects a number value, got '{}'",
+                    value
+                )),
+                filtered(value, "group")
+            );
+        }
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        assert_eq!(Ok("6869210a".to_owned()), filtered("hi!\n", "hex"));
+        assert_eq!(Ok("c3:a9".to_owned()), filtered("é", "hex(:)"));
+        assert_eq!(Ok("hi!".to_owned()), filtered("68 69-21", "unhex"));
+        assert_eq!(Ok("Hi".to_owned()), filtered("48:69", "unhex"));
+        assert_eq!(Ok("é".to_owned()), filtered("é", "hex | unhex"));
+        assert_eq!(
+            Err("invalid hex: odd number of digits".to_owned()),
+            filtered("486", "unhex")
+        );
+        assert_eq!(
+            Err("invalid hex digit 'g'".to_owned()),
+            filtered("6g", "unhex")
+        );
+        assert_eq!(
+            Err("decoded hex is not valid UTF-8".to_owned()),
+            filtered("ff", "unhex")
+        );
+    }
+
+    #[test]
+    fn trim_and_pad() {
+        assert_eq!(Ok("value".to_owned()), filtered(" \tvalue\n", "trim"));
+        assert_eq!(Ok("   42".to_owned()), filtered("42", "pad_left(5)"));
+        assert_eq!(Ok("00042".to_owned()), filtered("42", "pad_left(5, 0)"));
+        assert_eq!(
+            Ok("né.. ".to_owned()),
+            filtered("né", "pad_right(4, '.') | pad_right(5)")
+        );
+        assert_eq!(Ok("toolong".to_owned()), filtered("toolong", "pad_left(3)"));
+        assert_eq!(
+            Err("filter 'pad_left' takes 1 to 2 arguments".to_owned()),
+            filtered("42", "pad_left")
+        );
+        assert_eq!(
+            Err("filter 'pad_left' expects a number, got 'five'".to_owned()),
+            filtered("42", "pad_left(five)")
+        );
+        assert_eq!(
+            Err("filter 'pad_right' expects a single character, got 'ab'".to_owned()),
+            filtered("42", "pad_right(5, ab)")
+        );
+        assert_eq!(
+            Err("filter 'pad_left' width too large: expects at most 65535, got '65536'".to_owned()),
+            filtered("42", "pad_left(65536)")
+        );
+        assert_eq!(
+            Err("filter 'pad_right' width too large: expects at most 65535, got '18446744073709551615'".to_owned()),
+            filtered("42", "pad_right(18446744073709551615)")
+        );
+    }
+
+    #[test]
+    fn replace_substrings() {
+        assert_eq!(
+            Ok("C:\\Users\\test".to_owned()),
+            filtered("C:/Users/test", r#"replace("/", "\\")"#)
+        );
+        assert_eq!(Ok("a, b".to_owned()), filtered("a,b", "replace(',', ', ')"));
+        assert_eq!(Ok("ab".to_owned()), filtered("a b", "replace(' ', '')"));
+        assert_eq!(
+            Err("filter 'replace' takes 2 arguments".to_owned()),
+            filtered("a", "replace(a)")
+        );
+        assert_eq!(
+            Err("filter 'replace' cannot replace an empty string".to_owned()),
+            filtered("a", "replace('', b)")
+        );
+    }
+
+    #[test]
+    fn split_and_join_lists() {
+        assert_eq!(
+            Ok("a-b-c".to_owned()),
+            filtered(" a  b\nc ", "split | join('-')")
+        );
+        assert_eq!(
+            Ok("'a' OR 'b'".to_owned()),
+            filtered("a, b", "split(',') | trim | sql_quote | join(' OR ')")
+        );
+        assert_eq!(
+            Ok("a.b.c".to_owned()),
+            filtered("abc", "split('') | join(.)")
+        );
+        assert_eq!(Ok("a, b".to_owned()), filtered("a;b", "split(;)"));
+        assert_eq!(Ok("a;b".to_owned()), filtered("a;b", "join(-)"));
+        assert_eq!(
+            Err("filter 'split' expects a string, got a list".to_owned()),
+            filtered("a;b", "split(;) | split")
+        );
+    }
+
+    #[test]
+    fn truncate_long_values() {
+        assert_eq!(Ok("short".to_owned()), filtered("short", "truncate(5)"));
+        assert_eq!(
+            Ok("a lo…".to_owned()),
+            filtered("a longer value", "truncate(5)")
+        );
+        assert_eq!(
+            Ok("a…".to_owned()),
+            filtered("a longer value", "truncate(3)")
+        );
+        assert_eq!(
+            Ok("a lon...".to_owned()),
+            filtered("a longer value", "truncate(8, '...')")
+        );
+        assert_eq!(
+            Ok("a ".to_owned()),
+            filtered("a longer value", "truncate(2, '...')")
+        );
+        assert_eq!(Ok("".to_owned()), filtered("value", "truncate(0)"));
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn truncate_graphemes() {
+        assert_eq!(
+            Ok("cafe\u{301}…".to_owned()),
+            filtered("cafe\u{301} au lait", "truncate(5)")
+        );
+    }
+
+    #[test]
+    fn slug_words() {
+        assert_eq!(
+            Ok("hello-world-2024".to_owned()),
+            filtered("  Hello, World! 2024 ", "slug")
+        );
+        assert_eq!(
+            Ok("release_notes_v1_2".to_owned()),
+            filtered("Release Notes (v1.2)", "slug(_)")
+        );
+        assert_eq!(Ok("".to_owned()), filtered("--", "slug"));
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn slug_transliterated() {
+        assert_eq!(
+            Ok("creme-brulee-strasse".to_owned()),
+            filtered("Crème Brûlée Straße", "slug")
+        );
+        assert_eq!(Ok("aeroskobing".to_owned()), filtered("Ærøskøbing", "slug"));
+    }
+
+    #[test]
+    fn sql_quote_dialects() {
+        let value = "it's a \\ test\n";
+        assert_eq!(
+            Ok("'it''s a \\ test\n'".to_owned()),
+            filtered(value, "sql_quote")
+        );
+        assert_eq!(
+            Ok("'it''s a \\ test\n'".to_owned()),
+            filtered(value, "sql_quote(postgres)")
+        );
+        assert_eq!(
+            Ok("'it\\'s a \\\\ test\\n'".to_owned()),
+            filtered(value, "sql_quote(\"mysql\")")
+        );
+        assert_eq!(
+            Ok("N'it''s a \\ test\n'".to_owned()),
+            filtered(value, "sql_quote('mssql')")
+        );
+        assert_eq!(
+            Err("value contains a NUL character".to_owned()),
+            filtered("\0", "sql_quote")
+        );
+        assert_eq!(
+            Err("unknown sql dialect 'oracle'".to_owned()),
+            filtered(value, "sql_quote(oracle)")
+        );
+    }
+}
@@ -0,0 +1,225 @@
+//! Localized messages from [Fluent](https://projectfluent.org) bundles.
+//!
+//! A [`FluentSource`] is a [`VariableSource`] rendering placeholders that
+//! name a message in a [`FluentBundle`], with the entries of a [`VarjMap`]
+//! passed to the message as Fluent arguments. Other placeholders are looked
+//! up in the map.
+//!
+//! Fluent bundles cannot be shared between threads, so render from a
+//! `FluentSource` with [`render_local`](crate::render_local).
+//!
+//! # Example
+//!
+//! ```rust
+//! # use std::error::Error;
+//! #
+//! # fn main() -> Result<(), Box<dyn Error>> {
+//! use fluent_bundle::{FluentBundle, FluentResource};
+//! use varj::fluent::FluentSource;
+//! use varj::VarjMap;
+//!
+//! let ftl = r#"
+//! greeting = Hello, { $name }!
+//! unread = { $count ->
+//!     [one] one new message
+//!    *[other] { $count } new messages
+//! }
+//! "#;
+//!
+//! let resource = FluentResource::try_new(ftl.to_owned()).expect("valid ftl");
+//! let mut bundle = FluentBundle::new(vec!["en-US".parse()?]);
+//! bundle.set_use_isolating(false);
+//! bundle.add_resource(resource).expect("no duplicate messages");
+//!
+//! let mut vars = VarjMap::new();
+//! vars.insert("name", "Ada");
+//! vars.insert("count", "1");
+//!
+//! let source = FluentSource::new(&bundle, &vars);
+//! assert_eq!(
+//!     "Hello, Ada! You have one new message.",
+//!     varj::render_local("{{ greeting }} You have {{ unread }}.", &source)?
+//! );
+//! #
+//! #     Ok(())
+//! # }
+//! ```
+
+use std::borrow::{Borrow, Cow};
+use std::fmt;
+
+use fluent_bundle::bundle::FluentBundle;
+use fluent_bundle::memoizer::MemoizerKind;
+use fluent_bundle::{FluentArgs, FluentResource, FluentValue};
+
+use crate::{VariableSource, VarjMap};
+
+/// Messages of a [`FluentBundle`] formatted with the entries of a
+/// [`VarjMap`] as arguments.
+///
+/// A key naming a message renders its value, and `message.attribute`
+/// renders one of its attributes. Values that look like numbers are passed
+/// as Fluent numbers, so they can select plural variants. Keys that are not
+/// messages are looked up in the map.
+///
+/// Formatting errors, such as a missing argument, do not fail the render:
+/// Fluent substitutes the name of what could not be resolved, e.g.
+/// `{$name}`.
+pub struct FluentSource<'a, R, M> {
+    bundle: &'a FluentBundle<R, M>,
+    map: &'a VarjMap,
+    args: FluentArgs<'a>,
+    prefix: String,
+}
+
+impl<'a, R, M> FluentSource<'a, R, M>
+where
+    R: Borrow<FluentResource>,
+    M: MemoizerKind,
+{
+    /// Create a source of the messages in `bundle`, passing every entry of
+    /// `map` as an argument.
+    pub fn new(bundle: &'a FluentBundle<R, M>, map: &'a VarjMap) -> Self {
+        let mut args = FluentArgs::with_capacity(map.map.len());
+        for (key, value) in map.map.iter() {
+            args.set(key.as_str(), FluentValue::try_number(value.as_str()));
+        }
+        Self {
+            bundle,
+            map,
+            args,
+            prefix: String::new(),
+        }
+    }
+
+    /// Only look up messages for keys starting with `prefix` and a `.`,
+    /// using the rest of the key as the message id.
+    pub fn with_prefix<P: Into<String>>(mut self, prefix: P) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    fn message(&self, key: &str) -> Option<String> {
+        let id = if self.prefix.is_empty() {
+            key
+        } else {
+            key.strip_prefix(self.prefix.as_str())?.strip_prefix('.')?
+        };
+        let (id, attribute) = match id.split_once('.') {
+            Some((id, attribute)) => (id, Some(attribute)),
+            None => (id, None),
+        };
+
+        let message = self.bundle.get_message(id)?;
+        let pattern = match attribute {
+            Some(attribute) => message.get_attribute(attribute)?.value(),
+            None => message.value()?,
+        };
+        let mut errors = Vec::new();
+        let value = self
+            .bundle
+            .format_pattern(pattern, Some(&self.args), &mut errors);
+        Some(value.into_owned())
+    }
+}
+
+impl<R, M> VariableSource for FluentSource<'_, R, M>
+where
+    R: Borrow<FluentResource>,
+    M: MemoizerKind,
+{
+    fn lookup(&self, key: &str) -> Option<Cow<'_, str>> {
+        match self.message(key) {
+            Some(value) => Some(Cow::Owned(value)),
+            None => self.map.get(key).map(Cow::Borrowed),
+        }
+    }
+
+    fn list(&self, key: &str) -> Vec<Cow<'_, str>> {
+        self.map.list(key)
+    }
+}
+
+impl<R, M> fmt::Debug for FluentSource<'_, R, M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FluentSource")
+            .field("locales", &self.bundle.locales)
+            .field("map", self.map)
+            .field("prefix", &self.prefix)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorKind;
+
+    const FTL: &str = r#"
+hello = Hallo, { $name }!
+files = { $count ->
+    [one] eine Datei
+   *[other] { $count } Dateien
+}
+login = Anmelden
+    .title = Beim Konto anmelden
+"#;
+
+    fn bundle() -> fluent_bundle::FluentBundle<FluentResource> {
+        let resource = FluentResource::try_new(FTL.to_owned()).expect("valid ftl");
+        let mut bundle =
+            fluent_bundle::FluentBundle::new(vec!["de".parse().expect("valid language")]);
+        bundle.set_use_isolating(false);
+        bundle
+            .add_resource(resource)
+            .expect("no duplicate messages");
+        bundle
+    }
+
+    #[test]
+    fn messages_with_map_arguments() {
+        let bundle = bundle();
+        let mut map = VarjMap::new();
+        map.insert("name", "Welt");
+        map.insert("count", "3");
+        let source = FluentSource::new(&bundle, &map);
+
+        assert_eq!(Some(Cow::Borrowed("Hallo, Welt!")), source.lookup("hello"));
+        assert_eq!(Some(Cow::Borrowed("3 Dateien")), source.lookup("files"));
+        assert_eq!(
+            Some(Cow::Borrowed("Beim Konto anmelden")),
+            source.lookup("login.title")
+        );
+        assert_eq!(Some(Cow::Borrowed("Welt")), source.lookup("name"));
+        assert_eq!(None, source.lookup("login.missing"));
+        assert_eq!(None, source.lookup("missing"));
+    }
+
+    #[test]
+    fn missing_arguments_are_named() {
+        let bundle = bundle();
+        let map = VarjMap::new();
+        let source = FluentSource::new(&bundle, &map);
+
+        assert_eq!(
+            Some(Cow::Borrowed("Hallo, {$name}!")),
+            source.lookup("hello")
+        );
+    }
+
+    #[test]
+    fn prefixed_messages() {
+        let bundle = bundle();
+        let mut map = VarjMap::new();
+        map.insert("count", "1");
+        map.insert("login", "ada");
+        let source = FluentSource::new(&bundle, &map).with_prefix("t");
+
+        let actual = crate::render_local("{{ t.files }} {{ t.login }} {{ login }}", &source)
+            .expect("rendering should succeed");
+        assert_eq!("eine Datei Anmelden ada", actual);
+
+        let err = crate::render_local("{{ files }}", &source).expect_err("files is not prefixed");
+        assert_eq!(ErrorKind::UnknownVariable, err.kind());
+    }
+}
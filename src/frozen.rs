@@ -0,0 +1,231 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    check_unused, parallel, preprocess, render_blocks, unwrap_or_clone, Error, KeyMatching, Lookup,
+    RenderOptions, VariableSource, VarjMap,
+};
+
+/// An immutable snapshot of a [`VarjMap`], laid out for fast lookups.
+///
+/// Entries are kept in one sorted slice and found by binary search, without
+/// hashing keys or following pointers between buckets. Build a `VarjMap`
+/// once, [`freeze`](VarjMap::freeze) it, and render from the snapshot
+/// however many times. Keys are matched, and unknown keys resolved, the same
+/// way as in the `VarjMap` it was frozen from.
+///
+/// # Example
+///
+/// ```rust
+/// # use std::error::Error;
+/// #
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// let mut map = varj::VarjMap::new();
+/// map.insert("service", "billing");
+/// map.insert("port", "8080");
+///
+/// let frozen = map.freeze();
+/// assert_eq!(2, frozen.len());
+/// assert_eq!("billing:8080", frozen.render("{{ service }}:{{ port }}")?);
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrozenVarjMap {
+    entries: Box<[(Box<str>, Box<str>)]>,
+    keys: KeyMatching,
+    default: Option<Box<str>>,
+    aliases: Box<[(Box<str>, Box<str>)]>,
+}
+
+impl FrozenVarjMap {
+    /// Get a value by key.
+    pub fn get<K: AsRef<str>>(&self, key: K) -> Option<&str> {
+        let key = self.keys.map_key(key.as_ref());
+        find(&self.entries, &key).or_else(|| find(&self.entries, find(&self.aliases, &key)?))
+    }
+
+    fn lookup_value(&self, key: &str) -> Option<&str> {
+        self.get(key).or(self.default.as_deref())
+    }
+
+    /// The elements of the list inserted under `key`, see
+    /// [`VarjMap::insert_list`].
+    fn list_entries<'m>(&'m self, key: &str) -> impl Iterator<Item = &'m str> + 'm {
+        let key = key.to_owned();
+        (0..).map_while(move |idx| self.get(format!("{}.{}", key, idx)))
+    }
+
+    /// The number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Render a template with its placeholder blocks replaced by set values.
+    ///
+    /// # Errors
+    ///
+    /// See [`VarjMap::render`].
+    pub fn render(&self, template: &str) -> Result<String, Error> {
+        self.render_with_options(template, &RenderOptions::default())
+    }
+
+    /// Render a template using the given [`RenderOptions`].
+    ///
+    /// # Errors
+    ///
+    /// See [`VarjMap::render_with_options`].
+    pub fn render_with_options(
+        &self,
+        template: &str,
+        options: &RenderOptions,
+    ) -> Result<String, Error> {
+        let processed = preprocess::apply(template, options);
+        let template = processed.source();
+        let blocks = processed.blocks();
+        let output = match options.threads {
+            threads if threads > 1 => {
+                parallel::render_blocks(template, &blocks, options, threads, self)?
+            }
+            _ => render_blocks(template, &blocks, options, self)?,
+        };
+
+        if options.deny_unused {
+            let used: HashSet<Cow<str>> = blocks
+                .iter()
+                .filter(|block| block.kind.has_key())
+                .flat_map(|block| {
+                    let key = self.keys.map_key(&block.variable_key);
+                    let aliased = find(&self.aliases, &key).map(Cow::Borrowed);
+                    std::iter::once(key).chain(aliased)
+                })
+                .collect();
+            check_unused(&used, self.entries.iter().map(|(key, _)| key))?;
+        }
+
+        Ok(output)
+    }
+}
+
+/// Find the value of `key` in entries sorted by key.
+fn find<'a>(entries: &'a [(Box<str>, Box<str>)], key: &str) -> Option<&'a str> {
+    entries
+        .binary_search_by(|(k, _)| k.as_ref().cmp(key))
+        .ok()
+        .map(|idx| entries[idx].1.as_ref())
+}
+
+impl<'m> Lookup<'m> for &'m FrozenVarjMap {
+    fn value(&self, key: &str) -> Option<Cow<'m, str>> {
+        self.lookup_value(key).map(Cow::Borrowed)
+    }
+
+    fn list(&self, key: &str) -> Vec<Cow<'m, str>> {
+        self.list_entries(key).map(Cow::Borrowed).collect()
+    }
+}
+
+impl VariableSource for FrozenVarjMap {
+    fn lookup(&self, key: &str) -> Option<Cow<'_, str>> {
+        self.lookup_value(key).map(Cow::Borrowed)
+    }
+
+    fn list(&self, key: &str) -> Vec<Cow<'_, str>> {
+        Lookup::list(&self, key)
+    }
+}
+
+impl VarjMap {
+    /// Freeze the map into an immutable [`FrozenVarjMap`] optimized for
+    /// repeated lookups.
+    pub fn freeze(self) -> FrozenVarjMap {
+        FrozenVarjMap {
+            entries: sorted(unwrap_or_clone(self.map)),
+            keys: self.keys,
+            default: self.default.map(String::into_boxed_str),
+            aliases: sorted(self.aliases),
+        }
+    }
+}
+
+fn sorted(map: HashMap<String, String>) -> Box<[(Box<str>, Box<str>)]> {
+    let mut entries: Vec<(Box<str>, Box<str>)> = map
+        .into_iter()
+        .map(|(key, value)| (key.into_boxed_str(), value.into_boxed_str()))
+        .collect();
+    entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+    entries.into_boxed_slice()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorKind;
+
+    #[test]
+    fn lookup_frozen_entries() {
+        let mut map = VarjMap::new();
+        for idx in 0..100 {
+            map.insert(format!("key{}", idx), idx.to_string());
+        }
+        let frozen = map.freeze();
+
+        assert_eq!(100, frozen.len());
+        for idx in 0..100 {
+            assert_eq!(
+                Some(idx.to_string().as_str()),
+                frozen.get(format!("key{}", idx))
+            );
+        }
+        assert_eq!(None, frozen.get("key100"));
+        assert!(VarjMap::new().freeze().is_empty());
+    }
+
+    #[test]
+    fn render_frozen_lists() {
+        let mut map = VarjMap::new();
+        map.insert_list("items", ["a", "b"]);
+        map.insert_list("empty", Vec::<String>::new());
+        let frozen = map.clone().freeze();
+
+        let template = "{{#items}}{{ . }};{{/items}}{{^empty}}none{{/empty}}";
+        let actual = frozen.render(template).expect("rendering should succeed");
+        assert_eq!("a;b;none", actual);
+
+        let context = crate::Context::new(&map).with(&frozen);
+        let actual = context.render(template).expect("rendering should succeed");
+        assert_eq!("a;b;none", actual);
+    }
+
+    #[test]
+    fn render_frozen_case_insensitive() {
+        let mut map = VarjMap::new();
+        map.set_case_insensitive(true);
+        map.insert("testKey", "testValue");
+        map.insert("unused", "");
+        map.alias("alias", "unused");
+        let frozen = map.freeze();
+
+        let actual = frozen
+            .render("{{ TESTKEY }} {{ testkey }}")
+            .expect("rendering should succeed");
+        assert_eq!("testValue testValue", actual);
+
+        let options = RenderOptions::new().deny_unused(true);
+        let err = frozen
+            .render_with_options("{{ testKey }}", &options)
+            .expect_err("unused is never used");
+        assert_eq!(ErrorKind::UnusedVariable, err.kind());
+
+        let actual = frozen
+            .render_with_options("{{ testKey }}{{ ALIAS }}", &options)
+            .expect("aliased key counts as used");
+        assert_eq!("testValue", actual);
+    }
+}
@@ -0,0 +1,399 @@
+//! Translated strings from gettext catalogs.
+//!
+//! A [`Catalog`] is a [`VariableSource`] of the translations in a `.po` or
+//! `.mo` file, keyed by their `msgid`. Give it a prefix to keep translation
+//! keys apart from other variables.
+//!
+//! # Example
+//!
+//! ```rust
+//! # use std::error::Error;
+//! #
+//! # fn main() -> Result<(), Box<dyn Error>> {
+//! use varj::gettext::Catalog;
+//!
+//! let po = r#"
+//! msgid "welcome_message"
+//! msgstr "Willkommen!"
+//!
+//! #, fuzzy
+//! msgid "goodbye"
+//! msgstr "Tschüss"
+//! "#;
+//!
+//! let catalog = Catalog::from_po(po)?.with_prefix("t");
+//! assert_eq!("<h1>Willkommen!</h1>", varj::render("<h1>{{ t.welcome_message }}</h1>", &catalog)?);
+//!
+//! // fuzzy translations are skipped
+//! assert!(varj::render("{{ t.goodbye }}", &catalog).is_err());
+//! #
+//! #     Ok(())
+//! # }
+//! ```
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::VariableSource;
+
+/// Translations loaded from a gettext catalog.
+///
+/// Untranslated and fuzzy entries are skipped. Entries with plural forms
+/// provide their first form. Entries with a `msgctxt` are keyed by the
+/// context and `msgid` joined with `\u{4}`, as in `.mo` files.
+#[derive(Debug, Default, Clone)]
+pub struct Catalog {
+    messages: HashMap<String, String>,
+    prefix: String,
+}
+
+impl Catalog {
+    /// Parse the source of a `.po` file.
+    ///
+    /// # Errors
+    ///
+    /// Will return a [`ParseCatalogError`] with the line of the first
+    /// malformed statement.
+    pub fn from_po(source: &str) -> Result<Self, ParseCatalogError> {
+        let mut catalog = Catalog::default();
+        let mut entry = PoEntry::default();
+        let mut field: Option<Field> = None;
+
+        for (idx, line) in source.lines().enumerate() {
+            let line_no = idx + 1;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(comment) = line.strip_prefix('#') {
+                if comment.starts_with(',') && comment.contains("fuzzy") {
+                    if field.take().is_some() {
+                        catalog.insert(std::mem::take(&mut entry));
+                    }
+                    entry.fuzzy = true;
+                }
+                continue;
+            }
+
+            if line.starts_with('"') {
+                let text = po_string(line).ok_or_else(|| ParseCatalogError::po(line_no))?;
+                match field {
+                    Some(field) => entry.push(field, &text),
+                    None => return Err(ParseCatalogError::po(line_no)),
+                }
+                continue;
+            }
+
+            let (keyword, rest) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| ParseCatalogError::po(line_no))?;
+            let text = po_string(rest.trim()).ok_or_else(|| ParseCatalogError::po(line_no))?;
+            let next = match keyword {
+                "msgctxt" => Field::Context,
+                "msgid" => Field::Id,
+                "msgstr" | "msgstr[0]" => Field::Str,
+                // other plural forms are not used
+                "msgid_plural" => Field::Ignored,
+                keyword if keyword.starts_with("msgstr[") => Field::Ignored,
+                _ => return Err(ParseCatalogError::po(line_no)),
+            };
+            // a new entry starts at its msgctxt, or at its msgid without one
+            let starts_entry =
+                next == Field::Context || (next == Field::Id && field != Some(Field::Context));
+            if starts_entry && field.is_some() {
+                catalog.insert(std::mem::take(&mut entry));
+            }
+            entry.push(next, &text);
+            field = Some(next);
+        }
+        catalog.insert(entry);
+
+        Ok(catalog)
+    }
+
+    /// Parse the contents of a compiled `.mo` file.
+    ///
+    /// # Errors
+    ///
+    /// Will return a [`ParseCatalogError`] if the file is truncated, not a
+    /// `.mo` file or has strings that are not valid UTF-8.
+    pub fn from_mo(bytes: &[u8]) -> Result<Self, ParseCatalogError> {
+        let invalid = |msg: &'static str| ParseCatalogError { line: None, msg };
+        let word = |offset: usize, big_endian: bool| -> Result<usize, ParseCatalogError> {
+            let word = bytes
+                .get(offset..offset.saturating_add(4))
+                .ok_or(invalid("truncated file"))?;
+            let word = [word[0], word[1], word[2], word[3]];
+            let word = if big_endian {
+                u32::from_be_bytes(word)
+            } else {
+                u32::from_le_bytes(word)
+            };
+            Ok(word as usize)
+        };
+
+        let big_endian = match word(0, false)? {
+            0x9504_12de => false,
+            0xde12_0495 => true,
+            _ => return Err(invalid("not a mo file")),
+        };
+        let count = word(8, big_endian)?;
+        let originals = word(12, big_endian)?;
+        let translations = word(16, big_endian)?;
+        let string = |table: usize, idx: usize| -> Result<&str, ParseCatalogError> {
+            let len = word(table + idx * 8, big_endian)?;
+            let offset = word(table + idx * 8 + 4, big_endian)?;
+            let string = bytes
+                .get(offset..offset.saturating_add(len))
+                .ok_or(invalid("truncated file"))?;
+            let string = std::str::from_utf8(string).map_err(|_| invalid("string is not UTF-8"))?;
+            // plural forms are separated by NUL
+            Ok(string.split('\0').next().unwrap_or_default())
+        };
+
+        let mut catalog = Catalog::default();
+        for idx in 0..count {
+            let (msgid, msgstr) = (string(originals, idx)?, string(translations, idx)?);
+            if !msgid.is_empty() && !msgstr.is_empty() {
+                catalog.messages.insert(msgid.to_owned(), msgstr.to_owned());
+            }
+        }
+        Ok(catalog)
+    }
+
+    /// Only provide translations for keys starting with `prefix` and a `.`,
+    /// looking up the rest of the key.
+    pub fn with_prefix<P: Into<String>>(mut self, prefix: P) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// The translation of `msgid`, if any.
+    pub fn get(&self, msgid: &str) -> Option<&str> {
+        self.messages.get(msgid).map(String::as_str)
+    }
+
+    /// Number of translations in the catalog.
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// Whether the catalog has no translations.
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    fn insert(&mut self, entry: PoEntry) {
+        if entry.fuzzy || entry.id.is_empty() || entry.str.is_empty() {
+            return;
+        }
+        let key = match entry.context {
+            Some(context) => format!("{}\u{4}{}", context, entry.id),
+            None => entry.id,
+        };
+        self.messages.insert(key, entry.str);
+    }
+}
+
+impl VariableSource for Catalog {
+    fn lookup(&self, key: &str) -> Option<Cow<'_, str>> {
+        let msgid = if self.prefix.is_empty() {
+            key
+        } else {
+            key.strip_prefix(self.prefix.as_str())?.strip_prefix('.')?
+        };
+        self.get(msgid).map(Cow::Borrowed)
+    }
+}
+
+/// Error parsing a gettext [`Catalog`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseCatalogError {
+    line: Option<usize>,
+    msg: &'static str,
+}
+
+impl ParseCatalogError {
+    fn po(line: usize) -> Self {
+        Self {
+            line: Some(line),
+            msg: "invalid po statement",
+        }
+    }
+
+    /// The line of a `.po` file the error was found on.
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+}
+
+impl fmt::Display for ParseCatalogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.msg),
+            None => write!(f, "invalid mo file: {}", self.msg),
+        }
+    }
+}
+
+impl std::error::Error for ParseCatalogError {}
+
+/// A `.po` statement keyword.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Context,
+    Id,
+    Str,
+    Ignored,
+}
+
+#[derive(Default)]
+struct PoEntry {
+    context: Option<String>,
+    id: String,
+    str: String,
+    fuzzy: bool,
+}
+
+impl PoEntry {
+    /// Append a string to a field.
+    fn push(&mut self, field: Field, text: &str) {
+        match field {
+            Field::Context => self.context.get_or_insert_with(String::new).push_str(text),
+            Field::Id => self.id.push_str(text),
+            Field::Str => self.str.push_str(text),
+            Field::Ignored => {}
+        }
+    }
+}
+
+/// Unescape a double quoted `.po` string.
+fn po_string(quoted: &str) -> Option<Cow<'_, str>> {
+    let inner = quoted.strip_prefix('"')?.strip_suffix('"')?;
+    if !inner.contains('\\') {
+        return Some(Cow::Borrowed(inner));
+    }
+    let mut output = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            output.push(ch);
+            continue;
+        }
+        match chars.next()? {
+            'n' => output.push('\n'),
+            't' => output.push('\t'),
+            'r' => output.push('\r'),
+            'a' => output.push('\u{7}'),
+            'b' => output.push('\u{8}'),
+            'f' => output.push('\u{c}'),
+            'v' => output.push('\u{b}'),
+            ch => output.push(ch),
+        }
+    }
+    Some(Cow::Owned(output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PO: &str = r#"# German translations
+msgid ""
+msgstr ""
+"Content-Type: text/plain; charset=UTF-8\n"
+
+#: src/main.rs:10
+msgid "greeting"
+msgstr "Hallo "
+"Welt"
+
+msgctxt "menu"
+msgid "open"
+msgstr "Öffnen"
+
+msgid "file"
+msgid_plural "files"
+msgstr[0] "Datei"
+msgstr[1] "Dateien"
+
+#, fuzzy
+msgid "quote"
+msgstr "\"Zitat\"\n"
+
+msgid "untranslated"
+msgstr ""
+
+msgid "escaped"
+msgstr "Tab\there"
+"#;
+
+    #[test]
+    fn parse_po() {
+        let catalog = Catalog::from_po(PO).expect("valid po");
+        assert_eq!(4, catalog.len());
+        assert_eq!(Some("Hallo Welt"), catalog.get("greeting"));
+        assert_eq!(Some("Öffnen"), catalog.get("menu\u{4}open"));
+        assert_eq!(Some("Datei"), catalog.get("file"));
+        assert_eq!(Some("Tab\there"), catalog.get("escaped"));
+        assert_eq!(None, catalog.get("quote"));
+        assert_eq!(None, catalog.get("untranslated"));
+    }
+
+    #[test]
+    fn parse_invalid_po() {
+        let err = Catalog::from_po("msgid \"a\"\nmsgstr b\n").unwrap_err();
+        assert_eq!(Some(2), err.line());
+        assert_eq!("line 2: invalid po statement", err.to_string());
+        assert!(Catalog::from_po("\"orphan\"").is_err());
+    }
+
+    /// Build a little-endian `.mo` file of `(msgid, msgstr)` pairs.
+    fn mo(messages: &[(&str, &str)]) -> Vec<u8> {
+        let count = messages.len() as u32;
+        let originals = 28;
+        let translations = originals + count * 8;
+        let mut strings = translations + count * 8;
+        let mut header = Vec::new();
+        let mut tables = (Vec::new(), Vec::new());
+        let mut data = Vec::new();
+        for word in [0x9504_12de, 0, count, originals, translations, 0, 0] {
+            header.extend_from_slice(&u32::to_le_bytes(word));
+        }
+        for (msgid, msgstr) in messages {
+            for (table, string) in [(&mut tables.0, msgid), (&mut tables.1, msgstr)] {
+                table.extend_from_slice(&(string.len() as u32).to_le_bytes());
+                table.extend_from_slice(&strings.to_le_bytes());
+                data.extend_from_slice(string.as_bytes());
+                data.push(0);
+                strings += string.len() as u32 + 1;
+            }
+        }
+        [header, tables.0, tables.1, data].concat()
+    }
+
+    #[test]
+    fn parse_mo() {
+        let bytes = mo(&[
+            ("", "header"),
+            ("hello", "hallo"),
+            ("file\0files", "Datei\0Dateien"),
+        ]);
+        let catalog = Catalog::from_mo(&bytes).expect("valid mo").with_prefix("t");
+        assert_eq!(2, catalog.len());
+        assert_eq!(Some(Cow::Borrowed("hallo")), catalog.lookup("t.hello"));
+        assert_eq!(Some(Cow::Borrowed("Datei")), catalog.lookup("t.file"));
+        assert_eq!(None, catalog.lookup("hello"));
+        assert_eq!(None, catalog.lookup("tx.hello"));
+
+        assert_eq!(
+            "invalid mo file: not a mo file",
+            Catalog::from_mo(b"not a mo file").unwrap_err().to_string()
+        );
+        assert_eq!(
+            "invalid mo file: truncated file",
+            Catalog::from_mo(&bytes[..40]).unwrap_err().to_string()
+        );
+    }
+}
@@ -0,0 +1,659 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::date::{self, DateNames};
+use crate::filter;
+use crate::DateStyle;
+
+/// A language, optionally with a region, such as `en`, `de-DE` or `pt_BR`.
+///
+/// Set on [`RenderOptions::locale`](crate::RenderOptions::locale) to choose
+/// the rules of locale-aware filters such as `plural`. The default is `en`.
+///
+/// # Example
+///
+/// ```rust
+/// use varj::Locale;
+///
+/// let locale: Locale = "pt_br".parse().expect("valid language tag");
+/// assert_eq!("pt", locale.language());
+/// assert_eq!(Some("BR"), locale.region());
+/// assert_eq!("pt-BR", locale.to_string());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(docsrs, doc(cfg(feature = "i18n")))]
+pub struct Locale {
+    language: String,
+    region: Option<String>,
+}
+
+impl Locale {
+    /// The lowercase language code, e.g. `de`.
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    /// The uppercase region code, e.g. `DE`, if any.
+    pub fn region(&self) -> Option<&str> {
+        self.region.as_deref()
+    }
+
+    /// The language without its region, e.g. `de` for `de-AT`, or `None`
+    /// if there is no region.
+    pub fn parent(&self) -> Option<Locale> {
+        self.region.as_ref().map(|_| Locale {
+            language: self.language.clone(),
+            region: None,
+        })
+    }
+
+    /// CLDR plural category of a number written with ASCII digits, or
+    /// `None` if it is not a number.
+    ///
+    /// Covers the rules of common languages, others use the English rule.
+    pub(crate) fn plural(&self, number: &str) -> Option<Plural> {
+        let Operands { i, v, f } = Operands::parse(number)?;
+        let one_if = |one: bool| if one { Plural::One } else { Plural::Other };
+        let category = match self.language.as_str() {
+            "id" | "ja" | "km" | "ko" | "lo" | "ms" | "my" | "th" | "vi" | "zh" => Plural::Other,
+            "pt" if self.region.as_deref() == Some("PT") => one_if(i == 1 && v == 0),
+            "fr" | "pt" => one_if(i <= 1),
+            "el" | "es" | "hu" | "tr" => one_if(i == 1 && f == 0),
+            "ru" | "uk" if v == 0 => match (i % 10, i % 100) {
+                (1, rem) if rem != 11 => Plural::One,
+                (2..=4, rem) if !(12..=14).contains(&rem) => Plural::Few,
+                _ => Plural::Many,
+            },
+            "pl" if v == 0 => match (i, i % 10, i % 100) {
+                (1, _, _) => Plural::One,
+                (_, 2..=4, rem) if !(12..=14).contains(&rem) => Plural::Few,
+                _ => Plural::Many,
+            },
+            "ru" | "uk" | "pl" => Plural::Other,
+            "cs" | "sk" => match (i, v) {
+                (1, 0) => Plural::One,
+                (2..=4, 0) => Plural::Few,
+                (_, 0) => Plural::Other,
+                _ => Plural::Many,
+            },
+            "ar" if f == 0 => match (i, i % 100) {
+                (0, _) => Plural::Zero,
+                (1, _) => Plural::One,
+                (2, _) => Plural::Two,
+                (_, 3..=10) => Plural::Few,
+                (_, 11..=99) => Plural::Many,
+                _ => Plural::Other,
+            },
+            "ar" => Plural::Other,
+            "he" if i == 2 && v == 0 => Plural::Two,
+            _ => one_if(i == 1 && v == 0),
+        };
+        Some(category)
+    }
+
+    /// Ordinal of a number, English for languages without ordinals.
+    pub(crate) fn ordinal(&self, number: i64) -> String {
+        let abs = number.unsigned_abs();
+        match self.language.as_str() {
+            "cs" | "da" | "de" | "fi" | "hu" | "nb" | "no" | "pl" | "sk" | "tr" => {
+                format!("{}.", number)
+            }
+            "es" | "it" | "pt" => format!("{}º", number),
+            "fr" if abs == 1 => format!("{}er", number),
+            "fr" | "nl" => format!("{}e", number),
+            "ru" | "uk" => format!("{}-й", number),
+            "sv" => match (abs % 10, abs % 100) {
+                (1 | 2, rem) if rem != 11 && rem != 12 => format!("{}:a", number),
+                _ => format!("{}:e", number),
+            },
+            _ => filter::english_ordinal(number),
+        }
+    }
+
+    /// Month and weekday names, English for languages without names.
+    pub(crate) fn date_names(&self) -> &'static DateNames {
+        match self.language.as_str() {
+            "de" => &GERMAN,
+            "es" => &SPANISH,
+            "fr" => &FRENCH,
+            "it" => &ITALIAN,
+            "nl" => &DUTCH,
+            "pt" => &PORTUGUESE,
+            _ => &date::ENGLISH,
+        }
+    }
+
+    /// Date pattern of a style, US English for languages without patterns.
+    pub(crate) fn date_pattern(&self, style: DateStyle) -> &'static str {
+        let region = self.region.as_deref();
+        let [short, medium, long, full] = match self.language.as_str() {
+            "de" => ["%d.%m.%y", "%d.%m.%Y", "%-d. %B %Y", "%A, %-d. %B %Y"],
+            "en" if !matches!(region, None | Some("US")) => {
+                ["%d/%m/%Y", "%-d %b %Y", "%-d %B %Y", "%A, %-d %B %Y"]
+            }
+            "es" => [
+                "%-d/%-m/%y",
+                "%-d %b %Y",
+                "%-d de %B de %Y",
+                "%A, %-d de %B de %Y",
+            ],
+            "fr" => ["%d/%m/%Y", "%-d %b %Y", "%-d %B %Y", "%A %-d %B %Y"],
+            "it" => ["%d/%m/%y", "%-d %b %Y", "%-d %B %Y", "%A %-d %B %Y"],
+            "nl" => ["%d-%m-%Y", "%-d %b %Y", "%-d %B %Y", "%A %-d %B %Y"],
+            "pt" => [
+                "%d/%m/%Y",
+                "%-d de %b de %Y",
+                "%-d de %B de %Y",
+                "%A, %-d de %B de %Y",
+            ],
+            _ => return date::english_pattern(style),
+        };
+        match style {
+            DateStyle::Short => short,
+            DateStyle::Medium => medium,
+            DateStyle::Long => long,
+            DateStyle::Full => full,
+        }
+    }
+
+    /// Decimal separator and digit grouping of numbers.
+    pub(crate) fn number_symbols(&self) -> NumberSymbols {
+        const NBSP: &str = "\u{a0}";
+        const NARROW_NBSP: &str = "\u{202f}";
+
+        let (decimal, group) = match (self.language.as_str(), self.region.as_deref()) {
+            ("de" | "it", Some("CH" | "LI")) => ('.', "’"),
+            ("de", Some("AT")) | ("pt", Some("PT")) => (',', NBSP),
+            ("es", Some("419" | "MX" | "US")) => ('.', ","),
+            ("fr", _) => (',', NARROW_NBSP),
+            ("da" | "de" | "el" | "es" | "id" | "it" | "nl" | "pt" | "ro" | "tr" | "vi", _) => {
+                (',', ".")
+            }
+            ("bg" | "cs" | "fi" | "hu" | "nb" | "no" | "pl" | "ru" | "sk" | "sv" | "uk", _) => {
+                (',', NBSP)
+            }
+            _ => ('.', ","),
+        };
+        let min_grouping = match (self.language.as_str(), self.region.as_deref()) {
+            ("es", Some("419" | "MX" | "US")) => 1,
+            ("es" | "pl", _) | ("pt", Some("PT")) => 2,
+            _ => 1,
+        };
+        let secondary_grouping = match (self.language.as_str(), self.region.as_deref()) {
+            ("hi", _) | ("en", Some("IN")) => 2,
+            _ => 3,
+        };
+        NumberSymbols {
+            decimal,
+            group,
+            min_grouping,
+            secondary_grouping,
+        }
+    }
+
+    /// Symbol, fraction digits and placement of a currency given by its
+    /// uppercase ISO 4217 code. Currencies without a symbol are written as
+    /// their code.
+    pub(crate) fn currency<'c>(&self, code: &'c str) -> Currency<'c> {
+        let language = self.language.as_str();
+        let region = self.region.as_deref();
+        let (symbol, digits) = match code {
+            "AUD" => ("A$", 2),
+            "BRL" => ("R$", 2),
+            "CAD" => ("CA$", 2),
+            "CNY" => ("CN¥", 2),
+            "EUR" => ("€", 2),
+            "GBP" => ("£", 2),
+            "HKD" => ("HK$", 2),
+            "ILS" => ("₪", 2),
+            "INR" => ("₹", 2),
+            "JPY" => ("¥", 0),
+            "KRW" => ("₩", 0),
+            "MXN" => ("MX$", 2),
+            "NZD" => ("NZ$", 2),
+            "TWD" => ("NT$", 2),
+            "USD" => ("$", 2),
+            "VND" => ("₫", 0),
+            "CLP" | "ISK" => (code, 0),
+            _ => (code, 2),
+        };
+        let local_symbol = match (code, language, region) {
+            ("AUD", _, Some("AU"))
+            | ("CAD", _, Some("CA"))
+            | ("MXN", _, Some("MX"))
+            | ("NZD", _, Some("NZ"))
+            | ("SGD", _, Some("SG")) => Some("$"),
+            ("CZK", "cs", _) => Some("Kč"),
+            ("DKK", "da", _) => Some("kr."),
+            ("HUF", "hu", _) => Some("Ft"),
+            ("NOK", "nb" | "no", _) | ("SEK", "sv", _) => Some("kr"),
+            ("PLN", "pl", _) => Some("zł"),
+            ("RUB", "ru", _) => Some("₽"),
+            ("TRY", "tr", _) => Some("₺"),
+            ("ZAR", _, Some("ZA")) => Some("R"),
+            _ => None,
+        };
+        let placement = match (language, region) {
+            ("de" | "it", Some("CH" | "LI")) | ("nl", _) => CurrencyPlacement::BeforeSpaced,
+            ("pt", Some("PT")) => CurrencyPlacement::After,
+            ("pt", _) => CurrencyPlacement::BeforeSpaced,
+            ("es", Some("419" | "MX" | "US")) => CurrencyPlacement::Before,
+            (
+                "bg" | "cs" | "da" | "de" | "el" | "es" | "fi" | "fr" | "hu" | "it" | "nb" | "no"
+                | "pl" | "ro" | "ru" | "sk" | "sv" | "uk" | "vi",
+                _,
+            ) => CurrencyPlacement::After,
+            _ => CurrencyPlacement::Before,
+        };
+        Currency {
+            symbol: local_symbol.unwrap_or(symbol),
+            digits,
+            placement,
+        }
+    }
+}
+
+/// How a locale writes amounts of a currency.
+pub(crate) struct Currency<'c> {
+    pub(crate) symbol: &'c str,
+    pub(crate) digits: usize,
+    pub(crate) placement: CurrencyPlacement,
+}
+
+/// Where the symbol of a currency goes.
+pub(crate) enum CurrencyPlacement {
+    /// Before the amount, separated by a space if the symbol ends with a
+    /// letter: `$1.50`, `CHF 1.50`.
+    Before,
+    /// Before the amount, separated by a space: `€ 1,50`.
+    BeforeSpaced,
+    /// After the amount, separated by a space: `1,50 €`.
+    After,
+}
+
+/// How a locale writes numbers.
+pub(crate) struct NumberSymbols {
+    pub(crate) decimal: char,
+    pub(crate) group: &'static str,
+    /// Digits an integer needs beyond the first group of three before it is
+    /// grouped.
+    pub(crate) min_grouping: usize,
+    /// Size of the digit groups before the last group of three.
+    pub(crate) secondary_grouping: usize,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Self {
+            language: "en".to_owned(),
+            region: None,
+        }
+    }
+}
+
+impl FromStr for Locale {
+    type Err = ParseLocaleError;
+
+    /// Parse a language tag of a 2 or 3 letter language code, optionally
+    /// followed by `-` or `_` and a region code. Other subtags are ignored.
+    fn from_str(tag: &str) -> Result<Self, Self::Err> {
+        let mut subtags = tag.split(['-', '_']);
+        let language = subtags.next().unwrap_or_default();
+        if !(2..=3).contains(&language.len())
+            || !language.chars().all(|ch| ch.is_ascii_alphabetic())
+        {
+            return Err(ParseLocaleError {
+                tag: tag.to_owned(),
+            });
+        }
+        let region = subtags
+            .find(|subtag| {
+                subtag.len() == 2 && subtag.chars().all(|ch| ch.is_ascii_alphabetic())
+                    || subtag.len() == 3 && subtag.chars().all(|ch| ch.is_ascii_digit())
+            })
+            .map(str::to_ascii_uppercase);
+        Ok(Self {
+            language: language.to_ascii_lowercase(),
+            region,
+        })
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.region {
+            Some(region) => write!(f, "{}-{}", self.language, region),
+            None => f.write_str(&self.language),
+        }
+    }
+}
+
+const GERMAN: DateNames = DateNames {
+    months: [
+        "Januar",
+        "Februar",
+        "März",
+        "April",
+        "Mai",
+        "Juni",
+        "Juli",
+        "August",
+        "September",
+        "Oktober",
+        "November",
+        "Dezember",
+    ],
+    months_short: [
+        "Jan.", "Feb.", "März", "Apr.", "Mai", "Juni", "Juli", "Aug.", "Sept.", "Okt.", "Nov.",
+        "Dez.",
+    ],
+    days: [
+        "Montag",
+        "Dienstag",
+        "Mittwoch",
+        "Donnerstag",
+        "Freitag",
+        "Samstag",
+        "Sonntag",
+    ],
+    days_short: ["Mo.", "Di.", "Mi.", "Do.", "Fr.", "Sa.", "So."],
+};
+
+const SPANISH: DateNames = DateNames {
+    months: [
+        "enero",
+        "febrero",
+        "marzo",
+        "abril",
+        "mayo",
+        "junio",
+        "julio",
+        "agosto",
+        "septiembre",
+        "octubre",
+        "noviembre",
+        "diciembre",
+    ],
+    months_short: [
+        "ene", "feb", "mar", "abr", "may", "jun", "jul", "ago", "sept", "oct", "nov", "dic",
+    ],
+    days: [
+        "lunes",
+        "martes",
+        "miércoles",
+        "jueves",
+        "viernes",
+        "sábado",
+        "domingo",
+    ],
+    days_short: ["lun", "mar", "mié", "jue", "vie", "sáb", "dom"],
+};
+
+const FRENCH: DateNames = DateNames {
+    months: [
+        "janvier",
+        "février",
+        "mars",
+        "avril",
+        "mai",
+        "juin",
+        "juillet",
+        "août",
+        "septembre",
+        "octobre",
+        "novembre",
+        "décembre",
+    ],
+    months_short: [
+        "janv.", "févr.", "mars", "avr.", "mai", "juin", "juil.", "août", "sept.", "oct.", "nov.",
+        "déc.",
+    ],
+    days: [
+        "lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche",
+    ],
+    days_short: ["lun.", "mar.", "mer.", "jeu.", "ven.", "sam.", "dim."],
+};
+
+const ITALIAN: DateNames = DateNames {
+    months: [
+        "gennaio",
+        "febbraio",
+        "marzo",
+        "aprile",
+        "maggio",
+        "giugno",
+        "luglio",
+        "agosto",
+        "settembre",
+        "ottobre",
+        "novembre",
+        "dicembre",
+    ],
+    months_short: [
+        "gen", "feb", "mar", "apr", "mag", "giu", "lug", "ago", "set", "ott", "nov", "dic",
+    ],
+    days: [
+        "lunedì",
+        "martedì",
+        "mercoledì",
+        "giovedì",
+        "venerdì",
+        "sabato",
+        "domenica",
+    ],
+    days_short: ["lun", "mar", "mer", "gio", "ven", "sab", "dom"],
+};
+
+const DUTCH: DateNames = DateNames {
+    months: [
+        "januari",
+        "februari",
+        "maart",
+        "april",
+        "mei",
+        "juni",
+        "juli",
+        "augustus",
+        "september",
+        "oktober",
+        "november",
+        "december",
+    ],
+    months_short: [
+        "jan", "feb", "mrt", "apr", "mei", "jun", "jul", "aug", "sep", "okt", "nov", "dec",
+    ],
+    days: [
+        "maandag",
+        "dinsdag",
+        "woensdag",
+        "donderdag",
+        "vrijdag",
+        "zaterdag",
+        "zondag",
+    ],
+    days_short: ["ma", "di", "wo", "do", "vr", "za", "zo"],
+};
+
+const PORTUGUESE: DateNames = DateNames {
+    months: [
+        "janeiro",
+        "fevereiro",
+        "março",
+        "abril",
+        "maio",
+        "junho",
+        "julho",
+        "agosto",
+        "setembro",
+        "outubro",
+        "novembro",
+        "dezembro",
+    ],
+    months_short: [
+        "jan.", "fev.", "mar.", "abr.", "mai.", "jun.", "jul.", "ago.", "set.", "out.", "nov.",
+        "dez.",
+    ],
+    days: [
+        "segunda-feira",
+        "terça-feira",
+        "quarta-feira",
+        "quinta-feira",
+        "sexta-feira",
+        "sábado",
+        "domingo",
+    ],
+    days_short: ["seg.", "ter.", "qua.", "qui.", "sex.", "sáb.", "dom."],
+};
+
+/// Error parsing a [`Locale`] from an invalid language tag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(docsrs, doc(cfg(feature = "i18n")))]
+pub struct ParseLocaleError {
+    tag: String,
+}
+
+impl fmt::Display for ParseLocaleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid language tag '{}'", self.tag)
+    }
+}
+
+impl std::error::Error for ParseLocaleError {}
+
+/// CLDR plural categories.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Plural {
+    Zero,
+    One,
+    Two,
+    Few,
+    Many,
+    Other,
+}
+
+impl Plural {
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "zero" => Some(Plural::Zero),
+            "one" => Some(Plural::One),
+            "two" => Some(Plural::Two),
+            "few" => Some(Plural::Few),
+            "many" => Some(Plural::Many),
+            "other" => Some(Plural::Other),
+            _ => None,
+        }
+    }
+}
+
+/// CLDR plural operands of a decimal number: its integer digits `i`, the
+/// number of fraction digits `v` and the fraction digits `f` as an integer.
+struct Operands {
+    i: u64,
+    v: usize,
+    f: u64,
+}
+
+impl Operands {
+    fn parse(number: &str) -> Option<Self> {
+        let number = number.trim();
+        let number = number.strip_prefix(['-', '+']).unwrap_or(number);
+        let (integer, fraction) = number.split_once('.').unwrap_or((number, ""));
+        let digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+        if integer.is_empty() || !digits(integer) || !digits(fraction) {
+            return None;
+        }
+        // rules only compare small integers and remainders of 100
+        let tail = |part: &str| part[part.len().saturating_sub(18)..].parse().unwrap_or(0);
+        Some(Self {
+            i: tail(integer),
+            v: fraction.len(),
+            f: tail(fraction.trim_start_matches('0')),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn categories(tag: &str, numbers: &[&str]) -> Vec<Plural> {
+        let locale: Locale = tag.parse().expect("valid language tag");
+        numbers
+            .iter()
+            .map(|number| locale.plural(number).expect("valid number"))
+            .collect()
+    }
+
+    #[test]
+    fn parse_locale() {
+        let parse = |tag: &str| tag.parse::<Locale>().expect("valid language tag");
+        assert_eq!(Locale::default(), parse("EN"));
+        assert_eq!("de-DE", parse("de_de").to_string());
+        assert_eq!("es-419", parse("es-419").to_string());
+        assert_eq!("zh-TW", parse("zh-Hant-TW").to_string());
+        assert_eq!(
+            "invalid language tag 'english'",
+            "english".parse::<Locale>().unwrap_err().to_string()
+        );
+        assert!("".parse::<Locale>().is_err());
+    }
+
+    #[test]
+    fn locale_dates() {
+        let locale: Locale = "de-AT".parse().expect("valid language tag");
+        assert_eq!("%A, %-d. %B %Y", locale.date_pattern(DateStyle::Full));
+        assert_eq!("Freitag", locale.date_names().days[4]);
+
+        let locale: Locale = "en-GB".parse().expect("valid language tag");
+        assert_eq!("%-d %b %Y", locale.date_pattern(DateStyle::Medium));
+        assert_eq!(
+            "%b %-d, %Y",
+            Locale::default().date_pattern(DateStyle::Medium)
+        );
+        let locale: Locale = "ja".parse().expect("valid language tag");
+        assert_eq!("January", locale.date_names().months[0]);
+    }
+
+    #[test]
+    fn english_plurals() {
+        use Plural::*;
+        assert_eq!(
+            vec![Other, One, Other, Other, Other],
+            categories("en-GB", &["0", "1", "2", "1.0", "1.5"])
+        );
+        assert_eq!(None, Locale::default().plural("one"));
+    }
+
+    #[test]
+    fn slavic_plurals() {
+        use Plural::*;
+        let numbers = ["1", "2", "5", "11", "21", "22", "112", "1.5"];
+        assert_eq!(
+            vec![One, Few, Many, Many, One, Few, Many, Other],
+            categories("ru", &numbers)
+        );
+        assert_eq!(
+            vec![One, Few, Many, Many, Many, Few, Many, Other],
+            categories("pl", &numbers)
+        );
+        assert_eq!(
+            vec![One, Few, Other, Other, Other, Other, Other, Many],
+            categories("cs", &numbers)
+        );
+    }
+
+    #[test]
+    fn other_plurals() {
+        use Plural::*;
+        let numbers = ["0", "1", "2", "3", "11", "100", "1.5"];
+        assert_eq!(
+            vec![Zero, One, Two, Few, Many, Other, Other],
+            categories("ar", &numbers)
+        );
+        assert_eq!(
+            vec![One, One, Other, Other, Other, Other, One],
+            categories("fr", &numbers)
+        );
+        assert_eq!(vec![Other; 7], categories("ja", &numbers));
+    }
+}
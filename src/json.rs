@@ -0,0 +1,224 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::ops::Range;
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+
+use crate::{
+    check_block, check_unused, preprocess, push_block, section, substitute_block, write_span,
+    Block, BlockKind, Error, ErrorKind, RenderOptions, VarjMap,
+};
+
+impl VarjMap {
+    /// Render a template and parse the output as JSON.
+    ///
+    /// Parse into [`serde_json::Value`] to only check the output is valid
+    /// JSON, or into any type implementing `Deserialize`.
+    ///
+    /// # Errors
+    ///
+    /// Will return an [`Error`] of kind [`ErrorKind::Json`] if the output
+    /// fails to parse. Its line, column and key are those of the nearest
+    /// placeholder block before the failure, which is usually the value
+    /// that broke the document. Failures before any placeholder are
+    /// reported at their own position with an empty key. Otherwise see
+    /// [`render`](Self::render).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let mut map = varj::VarjMap::new();
+    /// map.insert("name", "Christopher");
+    /// map.insert("quote", r#"say "hi""#);
+    ///
+    /// let json: serde_json::Value = map
+    ///     .render_json(r#"{ "name": "{{ name }}" }"#)
+    ///     .expect("output should be valid json");
+    /// assert_eq!("Christopher", json["name"]);
+    ///
+    /// let err = map
+    ///     .render_json::<serde_json::Value>(r#"{ "name": "{{ name }}", "quote": "{{ quote }}" }"#)
+    ///     .unwrap_err();
+    /// assert_eq!(varj::ErrorKind::Json, err.kind());
+    /// assert_eq!("quote", err.key());
+    /// assert_eq!(35, err.col());
+    /// ```
+    #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+    pub fn render_json<T: DeserializeOwned>(&self, template: &str) -> Result<T, Error> {
+        self.render_json_with_options(template, &RenderOptions::default())
+    }
+
+    /// Render a template using the given [`RenderOptions`] and parse the
+    /// output as JSON.
+    ///
+    /// [`RenderOptions::threads`] is ignored, as the position of every
+    /// substitution is tracked, and so are [`RenderOptions::post_process`]
+    /// and [`RenderOptions::line_ending`].
+    ///
+    /// # Errors
+    ///
+    /// See [`render_json`](Self::render_json).
+    #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+    pub fn render_json_with_options<T: DeserializeOwned>(
+        &self,
+        template: &str,
+        options: &RenderOptions,
+    ) -> Result<T, Error> {
+        let processed = preprocess::apply(template, options);
+        let template = processed.source();
+        let blocks = processed.blocks();
+        let mut output = String::with_capacity(template.len() + 32);
+        let mut substitutions: Vec<(Range<usize>, &Block)> = Vec::with_capacity(blocks.len());
+        let mut idx = 0;
+
+        section::check(&blocks)?;
+        let mut remaining = blocks.iter();
+        while let Some(block) = remaining.next() {
+            if let Some(literal) = block.escaped(template) {
+                output.push_str(&template[idx..block.start]);
+                output.push_str(&literal);
+                idx = block.start + block.len;
+                continue;
+            }
+            check_block(block, options)?;
+            if let BlockKind::Section { .. } = block.kind {
+                // substitutions within sections are not tracked
+                let start = push_block(&mut output, template, idx, block, "", options);
+                let content = section::content(template, start, &mut remaining, options);
+                for scope in section::scopes(block, &self, options)? {
+                    let span = content.span.clone();
+                    write_span(
+                        &mut output,
+                        template,
+                        span,
+                        content.blocks,
+                        options,
+                        scope,
+                        |_| Ok(()),
+                    )?;
+                }
+                idx = content.end;
+                continue;
+            }
+
+            output.push_str(&template[idx..block.start]);
+            idx = block.start + block.len;
+            let value = substitute_block(
+                template,
+                block,
+                self.lookup_value(&block.variable_key),
+                options,
+            )?;
+            let start = output.len();
+            output.push_str(&value);
+            substitutions.push((start..output.len(), block));
+        }
+        output.push_str(&template[idx..]);
+
+        if options.deny_unused {
+            let used: HashSet<Cow<str>> = blocks
+                .iter()
+                .filter(|block| block.kind.has_key())
+                .map(|block| self.map_key(&block.variable_key))
+                .chain(self.section_entries(&blocks))
+                .collect();
+            check_unused(&used, self.map.keys())?;
+        }
+
+        serde_json::from_str(&output).map_err(|err| {
+            let offset = offset_of(&output, err.line(), err.column());
+            let nearest = substitutions
+                .iter()
+                .rev()
+                .find(|(range, _)| range.start <= offset);
+            let mut error = match nearest {
+                Some((_, block)) => Error::new(ErrorKind::Json, block),
+                None => {
+                    // output matches the template up to the first block
+                    let (line, col) = position_of(&output, offset);
+                    Error {
+                        line,
+                        col,
+                        ..Error::without_block(ErrorKind::Json, "")
+                    }
+                }
+            };
+            error.source = Some(Arc::new(err));
+            error
+        })
+    }
+}
+
+/// Byte offset of a 1-based line and byte column, as reported by serde_json.
+fn offset_of(text: &str, line: usize, col: usize) -> usize {
+    let line_start: usize = text
+        .split_inclusive('\n')
+        .take(line.saturating_sub(1))
+        .map(str::len)
+        .sum();
+    (line_start + col.saturating_sub(1)).min(text.len())
+}
+
+/// 1-based line and character column of a byte offset.
+fn position_of(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    let line = before.matches('\n').count() + 1;
+    (line, before[line_start..].chars().count() + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+
+    #[test]
+    fn render_valid_json() {
+        let mut map = VarjMap::new();
+        map.insert("testKey", "testValue");
+        map.insert("testNumber", "42");
+
+        let actual: Value = map
+            .render_json(r#"{"testKey": "{{ testKey }}", "n": {{ testNumber }}}"#)
+            .expect("rendering should succeed");
+
+        assert_eq!(serde_json::json!({"testKey": "testValue", "n": 42}), actual);
+    }
+
+    #[test]
+    fn render_invalid_json_at_substitution() {
+        let mut map = VarjMap::new();
+        map.insert("testKey", "testValue");
+        map.insert("testQuote", "\"quoted\"");
+
+        let template =
+            "{\n  \"a\": \"{{ testKey }}\",\n  \"b\": \"{{ testQuote }}\",\n  \"c\": 1\n}";
+        let actual = map
+            .render_json::<Value>(template)
+            .expect_err("rendering should error");
+
+        assert_eq!(ErrorKind::Json, actual.kind());
+        assert_eq!("testQuote", actual.key());
+        assert_eq!(3, actual.line());
+        assert_eq!(9, actual.col());
+        assert!(actual
+            .to_string()
+            .starts_with("3:9 invalid json near 'testQuote': "));
+    }
+
+    #[test]
+    fn render_invalid_json_before_substitution() {
+        let mut map = VarjMap::new();
+        map.insert("testKey", "testValue");
+
+        let actual = map
+            .render_json::<Value>("{\n  \"a\" 1, \"b\": \"{{ testKey }}\"}")
+            .expect_err("rendering should error");
+
+        assert_eq!("", actual.key());
+        assert_eq!(2, actual.line());
+        assert_eq!(7, actual.col());
+    }
+}
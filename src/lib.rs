@@ -5,6 +5,26 @@
 //!  - Does not require template compilation
 //!  - Simply replaces `{{ key }}` with `value`
 //!  - Whitespace surrounding the key is ignored: `{{key}}` and `{{ key }}` are equal.
+//!  - `\{{` is a literal `{{`, and `\\{{` a backslash before a placeholder. See
+//!    [`escape`] to escape text programmatically.
+//!  - Keys with spaces, braces or `|` can be quoted: `{{ "weird key!" }}`.
+//!    Inside the quotes, `\"` is a quote, `\\` a backslash, and `\n` and `\t`
+//!    a newline and a tab.
+//!  - `{{ key:>12 }}`, `{{ key:<8 }}` and `{{ key:^10 }}` pad values to a
+//!    width in characters, aligned right, left or centered. A fill character
+//!    may precede the alignment, as in `{{ key:*^10 }}`. Padding applies
+//!    after filters and [`RenderOptions::on_substitute`]. Widths go up to
+//!    65535, and a wider alignment is part of the key.
+//!  - Other delimiters, such as `<% key %>` where braces are common, can be
+//!    used with [`Template::with_delimiters`].
+//!  - `{{{ key }}}` is a raw block, inserting its value without
+//!    [`RenderOptions::escape`], such as markup with [`Escape::Html`].
+//!  - `{{#key}}…{{/key}}` is a section, rendered only if `key` has a value
+//!    that is not empty, and `{{^key}}…{{/key}}` an inverted section,
+//!    rendered only if it does not. Sections nest, and lines holding only a
+//!    section tag are removed with [`RenderOptions::trim_standalone`].
+//!  - A section over a key holding a [list](VarjMap::insert_list) is
+//!    rendered once per element, with `{{ . }}` bound to the element.
 //!
 //! Interact with this utility via [`VarjMap`]
 //!
@@ -55,14 +75,189 @@
 //! #     Ok(())
 //! # }
 //! ```
+//!
+//! # Filters
+//!
+//! Values can be transformed by a chain of filters, separated from the key
+//! and each other by `|`: `{{ key | filter | other_filter("arg") }}`. Filter
+//! arguments are bare words or quoted strings, named with `name=` where a
+//! filter takes named arguments. Filters run before
+//! [`RenderOptions::on_substitute`] and [`RenderOptions::escape`].
+//!
+//! `split` turns a value into a list, and other filters then apply to each
+//! item until `join` turns the list back into a string. A list left at the
+//! end of the chain is joined with `, `.
+//!
+//! | Filter | Output |
+//! |--------|--------|
+//! | `b64encode`, `b64encode(url)` | Base64 of the value, with the standard or URL-safe alphabet. Requires the `base64` feature |
+//! | `b64decode`, `b64decode(url)` | Value decoded from base64, padding optional. Requires the `base64` feature |
+//! | `currency(code)` | Amount of the currency with ISO 4217 `code` written with its symbol, fraction digits and placement in [`RenderOptions::locale`], e.g. `$1,234.50` or `1.234,50 €`. Requires the `i18n` feature |
+//! | `date`, `date(style)`, `date(pattern)` | ISO 8601 date or date-time (`2024-03-08T14:30`) in a [`DateStyle`] (`short`, `medium`, `long`, `full`), by default [`RenderOptions::date_style`], or a pattern of `%Y` (year), `%y`, `%m` (month), `%d` (day), `%B` (month name), `%b`, `%A` (weekday), `%a`, `%H`, `%M`, `%S` and `%%`; `%-d` and `%-m` are not zero padded. Names and styles follow [`RenderOptions::locale`] with the `i18n` feature, otherwise US English |
+//! | `filesize`, `filesize(binary)` | Number of bytes in decimal (`kB`, `MB`, …) or binary (`KiB`, `MiB`, …) units, e.g. `1.5 MB` |
+//! | `group`, `group(sep)` | Number with its integer digits separated into thousands by `,` or `sep` |
+//! | `hex`, `hex(sep)` | Lowercase hex of the value's UTF-8 bytes, with `sep` between bytes |
+//! | `join`, `join(sep)` | List items joined with nothing or `sep` |
+//! | `md5` | Hex MD5 digest of the value. Requires the `hash` feature |
+//! | `number`, `number(decimals)` | Number written with the decimal separator and digit grouping of [`RenderOptions::locale`], e.g. `1,234.5` or `1.234,5`, rounded to `decimals` fraction digits if given, at most 100. Requires the `i18n` feature |
+//! | `ordinal` | Whole number as an ordinal, e.g. `1st`, `2nd` or `23rd`. In the language of [`RenderOptions::locale`] with the `i18n` feature, e.g. `2.` or `1er` |
+//! | `pad_left(width)`, `pad_left(width, fill)` | Value right-aligned to at least `width` characters, padded with spaces or `fill`, `width` at most 65535 |
+//! | `pad_right(width)`, `pad_right(width, fill)` | Value left-aligned to at least `width` characters, padded with spaces or `fill`, `width` at most 65535 |
+//! | `plural(one=form, other=form, …)` | For a numeric value, the form of its CLDR plural category (`zero`, `one`, `two`, `few`, `many` or `other`) in [`RenderOptions::locale`], falling back to `other`. `#` in the form is replaced by the value. Requires the `i18n` feature |
+//! | `pluralize`, `pluralize(plural)`, `pluralize(singular, plural)` | For a numeric value, `singular` (default empty) if it is 1, otherwise `plural` (default `s`) |
+//! | `regex_escape` | Regular expression matching the value literally |
+//! | `replace(from, to)` | Value with every `from` replaced by `to` |
+//! | `sha256`, `sha512` | Hex SHA-256 or SHA-512 digest of the value. Requires the `hash` feature |
+//! | `slug`, `slug(sep)` | Lowercase words and numbers of the value joined by `-` or `sep`. With the `unicode` feature, accents are removed and ligatures such as `ß` spelled out |
+//! | `split`, `split(sep)` | List of the value's whitespace separated words, or its parts between each `sep`; an empty `sep` splits into characters |
+//! | `sql_quote`, `sql_quote(dialect)` | SQL string literal; dialects `ansi` (default), `postgres`, `sqlite`, `mysql`, `mssql` |
+//! | `trim` | Value without leading and trailing whitespace |
+//! | `truncate(width)`, `truncate(width, ellipsis)` | Value shortened to at most `width` characters, ending with `…` or `ellipsis` when shortened. With the `unicode` feature, characters are grapheme clusters |
+//! | `unhex` | Value decoded from hex digits, ignoring whitespace, `:` and `-` |
+//!
+//! ```rust
+//! # use std::error::Error;
+//! #
+//! # fn main() -> Result<(), Box<dyn Error>> {
+//! let mut map = varj::VarjMap::new();
+//! map.insert("name", "O'Brien");
+//!
+//! assert_eq!(
+//!     "INSERT INTO users (name) VALUES ('O''Brien');",
+//!     map.render("INSERT INTO users (name) VALUES ({{ name | sql_quote }});")?
+//! );
+//! #
+//! #     Ok(())
+//! # }
+//! ```
+
+#![cfg_attr(docsrs, feature(doc_cfg))]
+
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::str::CharIndices;
+use std::sync::Arc;
+
+use filter::Align;
 
-use std::collections::HashMap;
-use std::fmt;
+#[cfg(feature = "async")]
+mod async_render;
+mod bytes;
+#[cfg(feature = "async")]
+mod cache;
+mod cancel;
+mod chain;
+#[cfg(feature = "concurrent")]
+mod concurrent;
+mod context;
+mod date;
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+pub mod de;
+mod diff;
+#[cfg(feature = "env")]
+mod env;
+mod error;
+mod escape;
+mod file;
+mod filter;
+#[cfg(feature = "fluent")]
+#[cfg_attr(docsrs, doc(cfg(feature = "fluent")))]
+pub mod fluent;
+mod frozen;
+#[cfg(feature = "gettext")]
+#[cfg_attr(docsrs, doc(cfg(feature = "gettext")))]
+pub mod gettext;
+#[cfg(feature = "i18n")]
+mod i18n;
+#[cfg(feature = "json")]
+mod json;
+pub mod lint;
+mod loader;
+#[cfg(feature = "i18n")]
+mod localized;
+mod options;
+mod parallel;
+mod preprocess;
+mod schema;
+mod section;
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+pub mod ser;
+mod source;
+#[cfg(feature = "async")]
+mod stream;
+pub(crate) mod template;
+pub mod testing;
+mod usage;
+mod validate;
+#[cfg(feature = "notify")]
+mod watch;
+
+#[cfg(feature = "async")]
+pub use async_render::{
+    render_async, render_async_with_options, render_to_async, render_to_async_with_options,
+    AsyncVariableSource,
+};
+#[cfg(feature = "async")]
+pub use cache::CachedSource;
+pub use cancel::CancelToken;
+pub use chain::VarjChain;
+#[cfg(feature = "concurrent")]
+pub use concurrent::ConcurrentVarjMap;
+pub use context::Context;
+pub use diff::{TemplateChange, TemplateDiff};
+#[cfg(feature = "env")]
+pub use env::render_with_env;
+pub use error::{DefineError, DuplicateKeyError, Error, ErrorKind};
+pub use escape::escape;
+pub use file::{
+    render_file, render_file_with_options, render_to, render_to_with_options, Encoding, Unmappable,
+};
+pub use frozen::FrozenVarjMap;
+#[cfg(feature = "i18n")]
+pub use i18n::{Locale, ParseLocaleError};
+#[cfg(feature = "async")]
+pub use loader::AsyncTemplateLoader;
+#[cfg(feature = "http")]
+pub use loader::HttpLoader;
+pub use loader::{FileLoader, TemplateLoader};
+#[cfg(feature = "i18n")]
+pub use localized::{LocaleSet, Localized, LocalizedSource};
+pub use options::{
+    DateStyle, Escape, KeyChars, LineEnding, MissingKey, PostProcess, RenderOptions,
+};
+pub use preprocess::PreProcess;
+pub use schema::{Schema, ValueKind, Violation};
+pub use source::{
+    render, render_local, render_local_with_options, render_one, render_with, render_with_options,
+    VariableSource,
+};
+#[cfg(feature = "async")]
+pub use stream::{render_stream, render_stream_with_options, RenderStream};
+pub use template::{KeyPosition, Template, TemplateSet};
+pub use usage::{KeyUsage, UsageTracker};
+pub use validate::check_syntax;
+#[cfg(feature = "macros")]
+#[cfg_attr(docsrs, doc(cfg(feature = "macros")))]
+pub use varj_macros::{embed_templates, varj_context};
+#[cfg(feature = "notify")]
+pub use watch::WatchedTemplateSet;
 
 /// A map of variables to replace placeholders in a string.
+///
+/// Cloning a `VarjMap` is cheap: clones share their entries until one of them
+/// is modified, at which point that clone copies the entries for itself.
+/// Forking a base map per request to add overrides only copies the entries
+/// of the forks that are changed.
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct VarjMap {
-    map: HashMap<String, String>,
+    map: Arc<HashMap<String, String>>,
+    keys: KeyMatching,
+    default: Option<String>,
+    aliases: HashMap<String, String>,
 }
 
 impl VarjMap {
@@ -77,38 +272,96 @@ impl VarjMap {
     /// reallocating. If `capacity` is 0, the hash map will not allocate.
     pub fn with_capacity(capacity: usize) -> Self {
         Self {
-            map: HashMap::with_capacity(capacity),
+            map: Arc::new(HashMap::with_capacity(capacity)),
+            ..Self::default()
         }
     }
 
-    /// Insert a key value pair into the `VarjMap`.
+    /// Create a `VarjMap` from `key=value` command line arguments.
     ///
-    /// Use any type so long as it can be converted into a string.
-    pub fn insert<K, V>(&mut self, key: K, value: V)
+    /// Arguments without an `=`, or with an empty key, are skipped, so the
+    /// program name and flags can be passed along.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let map = varj::VarjMap::from_args(["app", "--verbose", "name=Christopher", "x=a=b"]);
+    ///
+    /// assert_eq!(Some("Christopher"), map.get("name"));
+    /// assert_eq!(Some("a=b"), map.get("x"));
+    /// assert_eq!(None, map.get("--verbose"));
+    /// ```
+    ///
+    /// Typically called with [`std::env::args`]:
+    ///
+    /// ```rust
+    /// let map = varj::VarjMap::from_args(std::env::args());
+    /// ```
+    pub fn from_args<I, S>(args: I) -> Self
     where
-        K: Into<String>,
-        V: Into<String>,
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
     {
-        self.map.insert(key.into(), value.into());
+        let mut map = Self::new();
+        for arg in args {
+            map.insert_arg(arg.as_ref());
+        }
+        map
     }
 
-    /// Get a value from the `VarjMap` by key.
-    pub fn get<K: AsRef<str>>(&self, key: K) -> Option<&str> {
-        self.map.get(key.as_ref()).map(|s| s.as_str())
+    /// Create a `VarjMap` from `key=value` command line arguments following
+    /// a flag, such as `--var name=Christopher` or `--var=name=Christopher`.
+    ///
+    /// All other arguments are skipped.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let args = ["app", "out=file", "--var", "name=Christopher", "--var=x=1"];
+    /// let map = varj::VarjMap::from_prefixed_args(args, "--var");
+    ///
+    /// assert_eq!(Some("Christopher"), map.get("name"));
+    /// assert_eq!(Some("1"), map.get("x"));
+    /// assert_eq!(None, map.get("out"));
+    /// ```
+    pub fn from_prefixed_args<I, S>(args: I, flag: &str) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut map = Self::new();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let arg = arg.as_ref();
+            if arg == flag {
+                if let Some(next) = args.next() {
+                    map.insert_arg(next.as_ref());
+                }
+            } else if let Some(var) = arg.strip_prefix(flag).and_then(|a| a.strip_prefix('=')) {
+                map.insert_arg(var);
+            }
+        }
+        map
     }
 
-    /// Render a template with its placeholder blocks replaced by set values.
+    /// Create a `VarjMap` from `-D key=value` or `-Dkey=value` command line
+    /// defines.
     ///
-    /// If no placeholder blocks(`{{ key }}`) are present in the template,
-    /// returns a cloned [`String`].
+    /// A key may end with a type hint, `:int`, `:uint`, `:float`, `:bool` or
+    /// `:str`, as in `-D count:int=3`. Values of a hinted type are checked
+    /// and stored in their canonical form, as by [`insert_i64`],
+    /// [`insert_u64`], [`insert_f64`] and [`insert_bool`], with `true` and
+    /// `false` as booleans. All other arguments are skipped.
     ///
-    /// Whitespace surrounding the key is ignored: `{{key}}` and `{{ key }}` are
-    /// equal.
+    /// [`insert_i64`]: Self::insert_i64
+    /// [`insert_u64`]: Self::insert_u64
+    /// [`insert_f64`]: Self::insert_f64
+    /// [`insert_bool`]: Self::insert_bool
     ///
     /// # Errors
     ///
-    /// Will return an [`Error`] if the template contains a key that is not
-    /// set.
+    /// Will return a [`DefineError`] if a define has no `=`, an empty key or
+    /// a value not of its hinted type, or if `-D` is the last argument.
     ///
     /// # Example
     ///
@@ -116,230 +369,1874 @@ impl VarjMap {
     /// # use std::error::Error;
     /// #
     /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let args = ["app", "-D", "name=Christopher", "-Dratio:float=.50", "-D", "debug:bool=true"];
+    /// let map = varj::VarjMap::from_defines(args)?;
+    ///
+    /// assert_eq!(Some("Christopher"), map.get("name"));
+    /// assert_eq!(Some("0.5"), map.get("ratio"));
+    /// assert_eq!(Some("true"), map.get("debug"));
+    ///
+    /// let err = varj::VarjMap::from_defines(["-D", "count:int=three"]).expect_err("not an int");
+    /// assert_eq!("invalid define 'count:int=three': expected an integer", err.to_string());
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn from_defines<I, S>(args: I) -> Result<Self, DefineError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut map = Self::new();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_ref() {
+                "-D" => match args.next() {
+                    Some(define) => map.insert_define(define.as_ref())?,
+                    None => {
+                        return Err(DefineError {
+                            define: String::new(),
+                            reason: "missing key=value after -D",
+                        })
+                    }
+                },
+                arg => {
+                    if let Some(define) = arg.strip_prefix("-D") {
+                        map.insert_define(define)?;
+                    }
+                }
+            }
+        }
+        Ok(map)
+    }
+
+    fn insert_define(&mut self, define: &str) -> Result<(), DefineError> {
+        let error = |reason| DefineError {
+            define: define.to_owned(),
+            reason,
+        };
+        let (key, value) = define
+            .split_once('=')
+            .ok_or_else(|| error("expected key=value"))?;
+        let (key, hint) = match key.rsplit_once(':') {
+            Some((key, hint @ ("int" | "uint" | "float" | "bool" | "str"))) => (key, hint),
+            _ => (key, "str"),
+        };
+        if key.is_empty() {
+            return Err(error("empty key"));
+        }
+        match hint {
+            "int" => {
+                let value = value.parse().map_err(|_| error("expected an integer"))?;
+                self.insert_i64(key, value);
+            }
+            "uint" => {
+                let value = value
+                    .parse()
+                    .map_err(|_| error("expected an unsigned integer"))?;
+                self.insert_u64(key, value);
+            }
+            "float" => {
+                let value = value.parse().map_err(|_| error("expected a number"))?;
+                self.insert_f64(key, value);
+            }
+            "bool" => {
+                let value = value.parse().map_err(|_| error("expected true or false"))?;
+                self.insert_bool(key, value);
+            }
+            _ => {
+                self.insert(key, value);
+            }
+        }
+        Ok(())
+    }
+
+    fn insert_arg(&mut self, arg: &str) {
+        match arg.split_once('=') {
+            Some((key, value)) if !key.is_empty() => {
+                self.insert(key, value);
+            }
+            _ => {}
+        }
+    }
+
+    /// Insert a key value pair into the `VarjMap`, returning the value it
+    /// replaced, if any.
+    ///
+    /// Use any type so long as it can be converted into a string.
+    ///
+    /// # Example
+    ///
+    /// ```rust
     /// let mut map = varj::VarjMap::new();
     ///
-    /// // add variables to VarjMap
-    /// let key = "name";
-    /// let value = "Christopher";
-    /// map.insert(key, value);
+    /// assert_eq!(None, map.insert("port", "8080"));
+    /// assert_eq!(Some("8080".to_owned()), map.insert("port", 9090.to_string()));
+    /// assert_eq!(Some("9090"), map.get("port"));
+    /// ```
+    pub fn insert<K, V>(&mut self, key: K, value: V) -> Option<String>
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let key = self.owned_map_key(key.into());
+        Arc::make_mut(&mut self.map).insert(key, value.into())
+    }
+
+    /// Insert a signed integer in its canonical form, returning the value it
+    /// replaced, if any.
     ///
-    /// // template to render
-    /// let template = "name: {{name}}";
+    /// # Example
     ///
-    /// // test result
-    /// let expected = "name: Christopher";
-    /// let actual = map.render(template)?;
+    /// ```rust
+    /// let mut map = varj::VarjMap::new();
+    /// map.insert_i64("offset", -1200);
     ///
-    /// assert_eq!(expected, actual);
+    /// assert_eq!(Some("-1200"), map.get("offset"));
+    /// ```
+    pub fn insert_i64<K: Into<String>>(&mut self, key: K, value: i64) -> Option<String> {
+        self.insert(key, value.to_string())
+    }
+
+    /// Insert an unsigned integer in its canonical form, returning the value
+    /// it replaced, if any.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let mut map = varj::VarjMap::new();
+    /// map.insert_u64("size", 1_500_000);
+    ///
+    /// assert_eq!(Some("1500000"), map.get("size"));
+    /// ```
+    pub fn insert_u64<K: Into<String>>(&mut self, key: K, value: u64) -> Option<String> {
+        self.insert(key, value.to_string())
+    }
+
+    /// Insert a float in its canonical form, returning the value it replaced,
+    /// if any.
+    ///
+    /// The canonical form is the shortest decimal that parses back to the
+    /// same float, never in exponent notation: `0.1` is `0.1` and `1e21` is
+    /// `1000000000000000000000`. Whole numbers have no fraction, negative
+    /// zero is `0`, and non-finite values are `NaN`, `inf` and `-inf`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::error::Error;
+    /// #
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let mut map = varj::VarjMap::new();
+    /// map.insert_f64("ratio", 0.1 + 0.2);
+    /// map.insert_f64("total", 12.0);
+    ///
+    /// assert_eq!(Some("0.30000000000000004"), map.get("ratio"));
+    /// assert_eq!("total: 12", map.render("total: {{ total }}")?);
     /// #
     /// #     Ok(())
     /// # }
     /// ```
-    pub fn render(&self, template: &str) -> Result<String, Error> {
-        let blocks = parse_blocks(template);
+    pub fn insert_f64<K: Into<String>>(&mut self, key: K, value: f64) -> Option<String> {
+        let value = if value == 0.0 { 0.0 } else { value };
+        self.insert(key, value.to_string())
+    }
 
-        let mut output = String::with_capacity(template.len() + 32);
-        let mut idx = 0;
+    /// Insert a boolean as `true` or `false`, returning the value it
+    /// replaced, if any.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let mut map = varj::VarjMap::new();
+    /// map.insert_bool("debug", false);
+    ///
+    /// assert_eq!(Some("false"), map.get("debug"));
+    /// ```
+    pub fn insert_bool<K: Into<String>>(&mut self, key: K, value: bool) -> Option<String> {
+        self.insert(key, value.to_string())
+    }
 
-        for block in blocks {
-            // copy input until block
-            output.push_str(&template[idx..block.start]);
-            idx = block.start;
+    /// Insert every entry of `nested` under `key`, as `key.entry`, so
+    /// structured data renders with dotted paths such as `{{ user.name }}`.
+    ///
+    /// Aliases of `nested` are kept under `key` too, while its default value
+    /// is not used. Nest maps in maps to any depth. Serializable data, such
+    /// as a `serde_json::Value`, can be flattened into dotted keys in one go
+    /// with `VarjMap::from_serialize` behind the `serde` feature.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::error::Error;
+    /// #
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let mut address = varj::VarjMap::new();
+    /// address.insert("city", "Leeds");
+    ///
+    /// let mut user = varj::VarjMap::new();
+    /// user.insert("name", "Christopher");
+    /// user.insert_nested("address", &address);
+    ///
+    /// let mut map = varj::VarjMap::new();
+    /// map.insert_nested("user", &user);
+    ///
+    /// assert_eq!(
+    ///     "Christopher from Leeds",
+    ///     map.render("{{ user.name }} from {{ user.address.city }}")?
+    /// );
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn insert_nested<K: AsRef<str>>(&mut self, key: K, nested: &VarjMap) {
+        let key = key.as_ref();
+        let path = |child: &str| format!("{}.{}", key, child);
+        for (child, value) in nested.map.iter() {
+            self.insert(path(child), value.as_str());
+        }
+        for (alias, child) in &nested.aliases {
+            self.alias(path(alias), path(child));
+        }
+    }
 
-            // copy variable_value
-            if let Some(value) = block.value_from_map(self) {
-                output.push_str(value);
-            } else {
-                return Err(Error::from(block));
+    /// Insert a list of values under `key`, which a section such as
+    /// `{{#items}}...{{/items}}` renders once per element, with `{{ . }}`
+    /// bound to the element.
+    ///
+    /// Elements are inserted as the entries `key.0`, `key.1` and so on, the
+    /// way `VarjMap::from_serialize` flattens sequences, replacing the
+    /// elements of a list inserted under `key` before. Sections over an
+    /// empty list are not rendered, and inverted sections are.
+    ///
+    /// Lists are not repeated when rendering bytes, streams or
+    /// asynchronously, or with [`render_with`], which treat a section's key
+    /// as a single value. Renders from a [`VariableSource`] repeat the lists
+    /// it returns from [`VariableSource::list`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::error::Error;
+    /// #
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let mut map = varj::VarjMap::new();
+    /// map.insert_list("items", ["apples", "pears"]);
+    ///
+    /// assert_eq!(
+    ///     "- apples\n- pears\n",
+    ///     map.render("{{#items}}- {{ . }}\n{{/items}}")?
+    /// );
+    /// assert_eq!(Some("pears"), map.get("items.1"));
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn insert_list<K, I>(&mut self, key: K, values: I)
+    where
+        K: AsRef<str>,
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        let key = key.as_ref();
+        let mut len = 0;
+        for value in values {
+            self.insert(format!("{}.{}", key, len), value);
+            len += 1;
+        }
+        // drop the remaining elements of a longer list
+        for idx in len.. {
+            let stale = self.owned_map_key(format!("{}.{}", key, idx));
+            if !self.map.contains_key(&stale) {
+                break;
             }
-
-            // update idx to end of block
-            idx += block.len;
+            Arc::make_mut(&mut self.map).remove(&stale);
         }
-
-        // copy remaining input
-        output.push_str(&template[idx..template.len()]);
-
-        Ok(output)
     }
 
-    #[deprecated(since = "1.1.0", note = "please use `render` instead")]
-    pub fn parse(&self, template: &str) -> Result<String, Error> {
-        self.render(template)
+    /// Insert a key value pair, unless the key is already set.
+    ///
+    /// # Errors
+    ///
+    /// Will return a [`DuplicateKeyError`] holding the rejected value if the
+    /// key is already set. The existing value is kept.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let mut map = varj::VarjMap::new();
+    /// map.try_insert("port", "8080").expect("port is not set");
+    ///
+    /// let err = map.try_insert("port", "9090").expect_err("port is set");
+    /// assert_eq!("variable 'port' is already set", err.to_string());
+    /// assert_eq!("9090", err.value());
+    /// assert_eq!(Some("8080"), map.get("port"));
+    /// ```
+    pub fn try_insert<K, V>(&mut self, key: K, value: V) -> Result<(), DuplicateKeyError>
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let key = self.owned_map_key(key.into());
+        if self.map.contains_key(&key) {
+            return Err(DuplicateKeyError {
+                key,
+                value: value.into(),
+            });
+        }
+        Arc::make_mut(&mut self.map).insert(key, value.into());
+        Ok(())
     }
-}
 
-impl From<HashMap<String, String>> for VarjMap {
-    fn from(map: HashMap<String, String>) -> Self {
-        VarjMap { map }
+    /// Get the value of `key`, first inserting the value returned by `f` if
+    /// the key is not set.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let mut map = varj::VarjMap::new();
+    /// map.insert("host", "example.com");
+    ///
+    /// assert_eq!("example.com", map.get_or_insert_with("host", || "localhost"));
+    /// assert_eq!("8080", map.get_or_insert_with("port", || 8080.to_string()));
+    /// assert_eq!(Some("8080"), map.get("port"));
+    /// ```
+    pub fn get_or_insert_with<K, V, F>(&mut self, key: K, f: F) -> &str
+    where
+        K: Into<String>,
+        V: Into<String>,
+        F: FnOnce() -> V,
+    {
+        let key = self.owned_map_key(key.into());
+        Arc::make_mut(&mut self.map)
+            .entry(key)
+            .or_insert_with(|| f().into())
     }
-}
 
-impl From<VarjMap> for HashMap<String, String> {
-    fn from(map: VarjMap) -> Self {
-        map.map
+    /// Get a value from the `VarjMap` by key.
+    pub fn get<K: AsRef<str>>(&self, key: K) -> Option<&str> {
+        let key = self.map_key(key.as_ref());
+        self.map
+            .get(key.as_ref())
+            .or_else(|| self.map.get(self.aliases.get(key.as_ref())?))
+            .map(|s| s.as_str())
     }
-}
 
-/// Unknown key in input string
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Error {
-    key: String,
-    line: usize,
-    col: usize,
-}
+    /// Resolve `alias` to the value of `key`, so templates can use either
+    /// name.
+    ///
+    /// A key set under the alias name itself takes precedence. Aliases are
+    /// resolved once: an alias of an alias does not resolve. Using an alias
+    /// counts as using its key for [`RenderOptions::deny_unused`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::error::Error;
+    /// #
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let mut map = varj::VarjMap::new();
+    /// map.insert("host", "example.com");
+    /// map.alias("hostname", "host");
+    ///
+    /// assert_eq!("example.com example.com", map.render("{{ host }} {{ hostname }}")?);
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn alias<A, K>(&mut self, alias: A, key: K)
+    where
+        A: Into<String>,
+        K: Into<String>,
+    {
+        let alias = self.owned_map_key(alias.into());
+        let key = self.owned_map_key(key.into());
+        self.aliases.insert(alias, key);
+    }
 
-impl fmt::Display for Error {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}:{} unknown variable '{}'",
-            self.line, self.col, self.key
-        )
+    /// Remove an alias set with [`alias`](Self::alias), returning its key.
+    pub fn remove_alias<A: AsRef<str>>(&mut self, alias: A) -> Option<String> {
+        let alias = self.map_key(alias.as_ref());
+        self.aliases.remove(alias.as_ref())
     }
-}
 
-impl std::error::Error for Error {}
+    /// Resolve every key that is not set to `value` when rendering, instead
+    /// of failing with an [`ErrorKind::UnknownVariable`] error.
+    ///
+    /// [`get`](Self::get) is not affected.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::error::Error;
+    /// #
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let mut map = varj::VarjMap::new();
+    /// map.insert("title", "Draft");
+    /// map.set_default("TODO");
+    ///
+    /// assert_eq!("Draft: TODO", map.render("{{ title }}: {{ summary }}")?);
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn set_default<V: Into<String>>(&mut self, value: V) {
+        self.default = Some(value.into());
+    }
+
+    /// Remove the value set with [`set_default`](Self::set_default), so
+    /// unknown keys fail to render again.
+    pub fn clear_default(&mut self) {
+        self.default = None;
+    }
+
+    /// The value of `key` when rendering, falling back to the default.
+    pub(crate) fn lookup_value(&self, key: &str) -> Option<&str> {
+        self.get(key).or(self.default.as_deref())
+    }
+
+    /// The elements of the list under `key`, the entries `key.0`, `key.1`
+    /// and so on up to the first index not set, without the default.
+    pub(crate) fn list_entries<'m>(
+        &'m self,
+        key: &str,
+    ) -> impl Iterator<Item = (&'m String, &'m String)> + 'm {
+        let key = key.to_owned();
+        (0..).map_while(move |idx| {
+            let key = self.owned_map_key(format!("{}.{}", key, idx));
+            self.map.get_key_value(&key)
+        })
+    }
+
+    /// Apply Unicode NFC normalization to keys on insert and lookup.
+    ///
+    /// Keys typed as a precomposed character (`é`) and keys typed as a base
+    /// character with a combining mark (`e\u{301}`) then match each other.
+    /// Existing keys are normalized when enabled.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::error::Error;
+    /// #
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let mut map = varj::VarjMap::new();
+    /// map.set_normalize_keys(true);
+    /// map.insert("caf\u{e9}", "open");
+    ///
+    /// assert_eq!("open", map.render("{{ cafe\u{301} }}")?);
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "unicode")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "unicode")))]
+    pub fn set_normalize_keys(&mut self, normalize: bool) {
+        self.keys.normalize = normalize;
+        self.rekey();
+    }
+
+    /// Match keys case-insensitively on insert and lookup.
+    ///
+    /// `{{ NAME }}`, `{{ name }}` and `{{ Name }}` then all resolve to the
+    /// same entry. Existing keys are folded to lowercase when enabled, so
+    /// entries differing only by case are merged, keeping one of their values.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::error::Error;
+    /// #
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let mut map = varj::VarjMap::new();
+    /// map.set_case_insensitive(true);
+    /// map.insert("Name", "Christopher");
+    ///
+    /// assert_eq!("Christopher Christopher", map.render("{{ NAME }} {{ name }}")?);
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn set_case_insensitive(&mut self, ignore_case: bool) {
+        self.keys.ignore_case = ignore_case;
+        self.rekey();
+    }
+
+    /// Match camelCase and PascalCase keys with their snake_case form on
+    /// insert and lookup.
+    ///
+    /// `{{ userName }}` then finds `user_name`, and `{{ user_name }}` finds
+    /// `userName`. Keys are stored in snake_case: a `_` is added before each
+    /// uppercase letter starting a word, and uppercase letters are lowercased.
+    /// Existing keys are converted when enabled.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::error::Error;
+    /// #
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let mut map = varj::VarjMap::new();
+    /// map.set_case_bridging(true);
+    /// map.insert("user_name", "ada");
+    /// map.insert("HTTPPort", "8080");
+    ///
+    /// assert_eq!("ada:8080", map.render("{{ userName }}:{{ http_port }}")?);
+    /// assert_eq!(Some("ada"), map.get("UserName"));
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn set_case_bridging(&mut self, bridge: bool) {
+        self.keys.snake_case = bridge;
+        self.rekey();
+    }
+
+    fn rekey(&mut self) {
+        let old = unwrap_or_clone(std::mem::take(&mut self.map));
+        let mut map = HashMap::with_capacity(old.len());
+        for (key, value) in old {
+            map.insert(self.owned_map_key(key), value);
+        }
+        self.map = Arc::new(map);
+
+        let aliases = std::mem::take(&mut self.aliases);
+        for (alias, key) in aliases {
+            let alias = self.owned_map_key(alias);
+            let key = self.owned_map_key(key);
+            self.aliases.insert(alias, key);
+        }
+    }
+
+    fn map_key<'k>(&self, key: &'k str) -> Cow<'k, str> {
+        self.keys.map_key(key)
+    }
+
+    fn owned_map_key(&self, key: String) -> String {
+        match self.map_key(&key) {
+            Cow::Owned(key) => key,
+            Cow::Borrowed(_) => key,
+        }
+    }
+
+    /// Render a template with its placeholder blocks replaced by set values.
+    ///
+    /// If no placeholder blocks(`{{ key }}`) are present in the template,
+    /// returns a cloned [`String`].
+    ///
+    /// Whitespace surrounding the key is ignored: `{{key}}` and `{{ key }}` are
+    /// equal.
+    ///
+    /// # Errors
+    ///
+    /// Will return an [`Error`] if the template contains a key that is not
+    /// set.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::error::Error;
+    /// #
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let mut map = varj::VarjMap::new();
+    ///
+    /// // add variables to VarjMap
+    /// let key = "name";
+    /// let value = "Christopher";
+    /// map.insert(key, value);
+    ///
+    /// // template to render
+    /// let template = "name: {{name}}";
+    ///
+    /// // test result
+    /// let expected = "name: Christopher";
+    /// let actual = map.render(template)?;
+    ///
+    /// assert_eq!(expected, actual);
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn render(&self, template: &str) -> Result<String, Error> {
+        self.render_with_options(template, &RenderOptions::default())
+    }
+
+    /// Render a template using the given [`RenderOptions`].
+    ///
+    /// See [`render`](Self::render) for details.
+    ///
+    /// # Errors
+    ///
+    /// Will return an [`Error`] if the template contains a key that is not
+    /// set, unless allowed by [`RenderOptions::missing_key`], a key rejected
+    /// by [`RenderOptions::key_chars`], a value vetoed by
+    /// [`RenderOptions::on_substitute`], an unused variable when
+    /// [`RenderOptions::deny_unused`] is enabled, or if cancelled by
+    /// [`RenderOptions::cancel_when`] or [`RenderOptions::cancel_token`].
+    pub fn render_with_options(
+        &self,
+        template: &str,
+        options: &RenderOptions,
+    ) -> Result<String, Error> {
+        let processed = preprocess::apply(template, options);
+        let blocks = processed.blocks();
+        self.render_blocks(processed.source(), &blocks, options)
+    }
+
+    pub(crate) fn render_blocks(
+        &self,
+        template: &str,
+        blocks: &[Block],
+        options: &RenderOptions,
+    ) -> Result<String, Error> {
+        let output = match options.threads {
+            threads if threads > 1 => {
+                parallel::render_blocks(template, blocks, options, threads, self)?
+            }
+            _ => render_blocks(template, blocks, options, self)?,
+        };
+
+        if options.deny_unused {
+            self.check_used(blocks)?;
+        }
+
+        Ok(output)
+    }
+
+    /// Error with the first entry, in sorted order, no variable block uses.
+    pub(crate) fn check_used(&self, blocks: &[Block]) -> Result<(), Error> {
+        let mut used = self.used_keys(
+            blocks
+                .iter()
+                .filter(|block| block.kind.has_key())
+                .map(|block| &*block.variable_key),
+        );
+        used.extend(self.section_entries(blocks));
+        check_unused(&used, self.map.keys())
+    }
+
+    /// The elements of the lists sections of `blocks` repeat over.
+    pub(crate) fn section_entries<'k>(
+        &'k self,
+        blocks: &'k [Block],
+    ) -> impl Iterator<Item = Cow<'k, str>> + 'k {
+        blocks
+            .iter()
+            .filter(|block| matches!(block.kind, BlockKind::Section { .. }))
+            .flat_map(|block| self.list_entries(&block.variable_key))
+            .map(|(key, _)| Cow::Borrowed(key.as_str()))
+    }
+
+    /// The entries used by looking up `keys`, including alias targets.
+    fn used_keys<'k>(&'k self, keys: impl Iterator<Item = &'k str>) -> HashSet<Cow<'k, str>> {
+        keys.flat_map(|key| {
+            let key = self.map_key(key);
+            let aliased = self
+                .aliases
+                .get(key.as_ref())
+                .map(|key| Cow::Borrowed(key.as_str()));
+            std::iter::once(key).chain(aliased)
+        })
+        .collect()
+    }
+
+    #[deprecated(since = "1.1.0", note = "please use `render` instead")]
+    pub fn parse(&self, template: &str) -> Result<String, Error> {
+        self.render(template)
+    }
+}
+
+impl From<HashMap<String, String>> for VarjMap {
+    fn from(map: HashMap<String, String>) -> Self {
+        VarjMap {
+            map: Arc::new(map),
+            ..VarjMap::default()
+        }
+    }
+}
+
+/// How keys are matched on insert and lookup.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct KeyMatching {
+    ignore_case: bool,
+    snake_case: bool,
+    #[cfg(feature = "unicode")]
+    normalize: bool,
+}
+
+impl KeyMatching {
+    fn map_key<'k>(&self, key: &'k str) -> Cow<'k, str> {
+        #[cfg(feature = "unicode")]
+        let key = if self.normalize {
+            normalize_nfc(key)
+        } else {
+            Cow::Borrowed(key)
+        };
+        #[cfg(not(feature = "unicode"))]
+        let key = Cow::Borrowed(key);
+
+        let key = match key {
+            Cow::Borrowed(key) if self.snake_case => snake_case(key),
+            Cow::Owned(key) if self.snake_case => Cow::Owned(snake_case(&key).into_owned()),
+            key => key,
+        };
+
+        if self.ignore_case && key.chars().any(char::is_uppercase) {
+            Cow::Owned(key.to_lowercase())
+        } else {
+            key
+        }
+    }
+}
+
+/// Convert a camelCase or PascalCase key to snake_case.
+fn snake_case(key: &str) -> Cow<'_, str> {
+    if !key.chars().any(char::is_uppercase) {
+        return Cow::Borrowed(key);
+    }
+
+    let chars: Vec<char> = key.chars().collect();
+    let mut snake = String::with_capacity(key.len() + 4);
+    for (idx, &ch) in chars.iter().enumerate() {
+        if ch.is_uppercase() && idx > 0 {
+            let prev = chars[idx - 1];
+            let next_lower = chars.get(idx + 1).is_some_and(|next| next.is_lowercase());
+            let word_start =
+                prev.is_lowercase() || prev.is_ascii_digit() || (prev.is_uppercase() && next_lower);
+            if word_start {
+                snake.push('_');
+            }
+        }
+        snake.extend(ch.to_lowercase());
+    }
+    Cow::Owned(snake)
+}
+
+/// Where a render looks the values of keys up.
+pub(crate) trait Lookup<'v> {
+    /// The value of `key`.
+    fn value(&self, key: &str) -> Option<Cow<'v, str>>;
+
+    /// The elements of the list under `key`, empty if it holds none.
+    fn list(&self, _key: &str) -> Vec<Cow<'v, str>> {
+        Vec::new()
+    }
+}
+
+impl<'v, F> Lookup<'v> for F
+where
+    F: Fn(&str) -> Option<Cow<'v, str>>,
+{
+    fn value(&self, key: &str) -> Option<Cow<'v, str>> {
+        self(key)
+    }
+}
+
+impl<'m> Lookup<'m> for &'m VarjMap {
+    fn value(&self, key: &str) -> Option<Cow<'m, str>> {
+        self.lookup_value(key).map(Cow::Borrowed)
+    }
+
+    fn list(&self, key: &str) -> Vec<Cow<'m, str>> {
+        self.list_entries(key)
+            .map(|(_, value)| Cow::Borrowed(value.as_str()))
+            .collect()
+    }
+}
+
+/// Render parsed blocks, resolving each block's value with `lookup`.
+fn render_blocks<'v, L>(
+    template: &str,
+    blocks: &[Block],
+    options: &RenderOptions,
+    lookup: L,
+) -> Result<String, Error>
+where
+    L: Lookup<'v>,
+{
+    section::check(blocks)?;
+    render_span(template, 0..template.len(), blocks, options, lookup)
+        .map(|output| options.finish(output))
+}
+
+/// Render the `span` of a template containing `blocks`.
+fn render_span<'v, L>(
+    template: &str,
+    span: Range<usize>,
+    blocks: &[Block],
+    options: &RenderOptions,
+    lookup: L,
+) -> Result<String, Error>
+where
+    L: Lookup<'v>,
+{
+    let mut output = String::with_capacity(span.len() + 32);
+    write_span(&mut output, template, span, blocks, options, lookup, |_| {
+        Ok(())
+    })?;
+    Ok(output)
+}
+
+/// Render the `span` of a template containing `blocks` into `output`,
+/// calling `flush` with the output rendered so far after each block and
+/// once at the end.
+fn write_span<'v, L, F>(
+    output: &mut String,
+    template: &str,
+    span: Range<usize>,
+    blocks: &[Block],
+    options: &RenderOptions,
+    lookup: L,
+    mut flush: F,
+) -> Result<(), Error>
+where
+    L: Lookup<'v>,
+    F: FnMut(&mut String) -> Result<(), Error>,
+{
+    let mut idx = span.start;
+    let mut unknown = Vec::new();
+
+    // values by key, alignment, filter chain and rawness, so with
+    // RenderOptions::memoize repeated placeholders are looked up, filtered and
+    // escaped once
+    let mut memo: HashMap<(&str, Option<Align>, &str, bool), Cow<'v, str>> = HashMap::new();
+    let positional = options.escape.is_positional();
+
+    let mut blocks = blocks.iter();
+    while let Some(block) = blocks.next() {
+        if let Some(literal) = block.escaped(template) {
+            output.push_str(&template[idx..block.start]);
+            output.push_str(&literal);
+            idx = block.start + block.len;
+            continue;
+        }
+        check_block(block, options)?;
+        if let BlockKind::Section { .. } = block.kind {
+            let start = push_block(output, template, idx, block, "", options);
+            let content = section::content(template, start, &mut blocks, options);
+            for scope in section::scopes(block, &lookup, options)? {
+                let flush: &mut dyn FnMut(&mut String) -> Result<(), Error> = &mut flush;
+                let span = content.span.clone();
+                match write_span(
+                    output,
+                    template,
+                    span,
+                    content.blocks,
+                    options,
+                    scope,
+                    flush,
+                ) {
+                    Err(err) if !err.unknown.is_empty() => unknown.extend(err.unknown),
+                    result => result?,
+                }
+            }
+            idx = content.end;
+            continue;
+        }
+
+        // copy input until block, then variable_value
+        let key = (&*block.variable_key, block.align, block.filters, block.raw);
+        if !options.memoize {
+            // resolve every placeholder, calling on_substitute for each
+            memo.remove(&key);
+        }
+        let value = match memo.entry(key) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let Some(value) = lookup.value(&block.variable_key) else {
+                    let text = missing_value(template, block, options).map(Cow::Borrowed);
+                    let text = collect_unknown(text, &mut unknown, options)?;
+                    idx = push_block(output, template, idx, block, &text, options);
+                    flush(output)?;
+                    continue;
+                };
+                let value = resolve_block(block, value, options)?;
+                let value = if positional || block.raw {
+                    value
+                } else {
+                    options.escape.apply(value, "")
+                };
+                entry.insert(value)
+            }
+        };
+        let value = if positional && !block.raw {
+            options
+                .escape
+                .apply(Cow::Borrowed(value), &template[..block.start])
+        } else {
+            Cow::Borrowed(&**value)
+        };
+        idx = push_block(output, template, idx, block, &value, options);
+        flush(output)?;
+    }
+
+    // copy remaining input
+    output.push_str(&template[idx..span.end]);
+
+    if !unknown.is_empty() {
+        return Err(Error::collected(unknown));
+    }
+    flush(output)
+}
+
+/// Append the template text from `idx` to `block` and the block's `value`,
+/// returning the index after the block.
+///
+/// With [`RenderOptions::trim_standalone`], an empty value alone on its line
+/// drops the whole line instead.
+fn push_block(
+    output: &mut String,
+    template: &str,
+    idx: usize,
+    block: &Block,
+    value: &str,
+    options: &RenderOptions,
+) -> usize {
+    if options.trim_standalone && value.is_empty() {
+        if let Some(line) = standalone_line(template, block) {
+            output.push_str(&template[idx..line.start.max(idx)]);
+            return line.end;
+        }
+    }
+    output.push_str(&template[idx..block.start]);
+    output.push_str(value);
+    block.start + block.len
+}
+
+/// The line of `block`, with its line ending, if only whitespace surrounds
+/// the block on it.
+fn standalone_line(template: &str, block: &Block) -> Option<Range<usize>> {
+    let start = template[..block.start].rfind('\n').map_or(0, |idx| idx + 1);
+    let after = block.start + block.len;
+    let end = template[after..]
+        .find('\n')
+        .map_or(template.len(), |idx| after + idx + 1);
+    let blank = |text: &str| text.trim().is_empty();
+    (blank(&template[start..block.start]) && blank(&template[after..end])).then_some(start..end)
+}
+
+/// Error with the first of `keys`, in sorted order, missing from `used`.
+fn check_unused<K>(used: &HashSet<Cow<str>>, keys: impl IntoIterator<Item = K>) -> Result<(), Error>
+where
+    K: AsRef<str>,
+{
+    let unused = keys
+        .into_iter()
+        .filter(|key| !used.contains(key.as_ref()))
+        .min_by(|a, b| a.as_ref().cmp(b.as_ref()));
+
+    match unused {
+        Some(key) => Err(Error::without_block(
+            ErrorKind::UnusedVariable,
+            key.as_ref(),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Checks run on a block before its value is looked up.
+fn check_block(block: &Block, options: &RenderOptions) -> Result<(), Error> {
+    if options.is_cancelled() {
+        return Err(Error::cancelled());
+    }
+    if !options.key_chars.is_valid(&block.variable_key) {
+        return Err(Error::new(ErrorKind::InvalidKey, block));
+    }
+    Ok(())
+}
+
+/// Resolve the final value to insert for a block of `template`.
+fn substitute_block<'v>(
+    template: &str,
+    block: &Block,
+    value: Option<&'v str>,
+    options: &RenderOptions,
+) -> Result<Cow<'v, str>, Error> {
+    let Some(value) = value else {
+        let text = missing_value(template, block, options)?;
+        return Ok(Cow::Owned(text.to_owned()));
+    };
+    let value = resolve_block(block, Cow::Borrowed(value), options)?;
+    Ok(escape_block(template, block, value, options))
+}
+
+/// Escape the resolved `value` of a block of `template`, unless it is raw.
+fn escape_block<'v>(
+    template: &str,
+    block: &Block,
+    value: Cow<'v, str>,
+    options: &RenderOptions,
+) -> Cow<'v, str> {
+    if block.raw {
+        return value;
+    }
+    options.escape.apply(value, &template[..block.start])
+}
+
+/// The output of a block whose key has no value, following
+/// [`RenderOptions::missing_key`].
+fn missing_value<'t>(
+    template: &'t str,
+    block: &Block,
+    options: &RenderOptions,
+) -> Result<&'t str, Error> {
+    match options.missing_key {
+        MissingKey::Error | MissingKey::Collect => {
+            Err(Error::new(ErrorKind::UnknownVariable, block))
+        }
+        MissingKey::Empty => Ok(""),
+        MissingKey::KeepPlaceholder => Ok(&template[block.start..block.start + block.len]),
+    }
+}
+
+/// Record an unknown variable error of `result` in `unknown` with
+/// [`MissingKey::Collect`], rendering nothing in its place.
+fn collect_unknown<'v>(
+    result: Result<Cow<'v, str>, Error>,
+    unknown: &mut Vec<Error>,
+    options: &RenderOptions,
+) -> Result<Cow<'v, str>, Error> {
+    match result {
+        Err(err)
+            if err.kind == ErrorKind::UnknownVariable
+                && options.missing_key == MissingKey::Collect =>
+        {
+            unknown.push(err);
+            Ok(Cow::Borrowed(""))
+        }
+        result => result,
+    }
+}
+
+/// The value of a block before escaping: `value` filtered and passed to
+/// [`RenderOptions::on_substitute`].
+fn resolve_block<'v>(
+    block: &Block,
+    value: Cow<'v, str>,
+    options: &RenderOptions,
+) -> Result<Cow<'v, str>, Error> {
+    let value = filter::apply(value, block.filters, options)
+        .map_err(|msg| Error::new(ErrorKind::Filter, block).caused_by(msg))?;
+    let value = match value {
+        Cow::Borrowed(value) => options.substitute(&block.variable_key, value),
+        Cow::Owned(value) => options
+            .substitute(&block.variable_key, &value)
+            .map(|value| Cow::Owned(value.into_owned())),
+    }
+    .ok_or_else(|| Error::new(ErrorKind::Rejected, block))?;
+    let value = match block.align {
+        Some(align) => align.apply(value),
+        None => value,
+    };
+    Ok(options.isolate(value))
+}
+
+#[cfg(feature = "unicode")]
+fn normalize_nfc(key: &str) -> Cow<'_, str> {
+    use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
+
+    match is_nfc_quick(key.chars()) {
+        IsNormalized::Yes => Cow::Borrowed(key),
+        _ => Cow::Owned(key.nfc().collect()),
+    }
+}
+
+impl From<VarjMap> for HashMap<String, String> {
+    fn from(map: VarjMap) -> Self {
+        unwrap_or_clone(map.map)
+    }
+}
+
+fn unwrap_or_clone<T: Clone>(arc: Arc<T>) -> T {
+    Arc::try_unwrap(arc).unwrap_or_else(|arc| (*arc).clone())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Block<'a> {
+    start: usize,
+    len: usize,
+    line: usize,
+    col: usize,
+    variable_key: Cow<'a, str>,
+    align: Option<Align>,
+    filters: &'a str,
+    /// Whether the block is written `{{{ key }}}`, bypassing
+    /// [`RenderOptions::escape`].
+    raw: bool,
+    kind: BlockKind,
+}
+
+/// What a parsed block stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BlockKind {
+    /// A placeholder to substitute with a value.
+    Variable,
+    /// Backslashes before `{{`, rendered by [`escape::unescape`].
+    Escape,
+    /// A tag opening a section, `{{#key}}`, or an inverted section,
+    /// `{{^key}}`, see [`section`].
+    Section { inverted: bool },
+    /// A tag ending a section, `{{/key}}`.
+    SectionEnd,
+}
+
+impl BlockKind {
+    /// Whether the block names a key: a placeholder or a section tag.
+    fn has_key(self) -> bool {
+        self != BlockKind::Escape
+    }
+}
+
+impl Block<'_> {
+    /// The literal text an escape block renders as.
+    fn escaped<'t>(&self, template: &'t str) -> Option<Cow<'t, str>> {
+        match self.kind {
+            BlockKind::Escape => Some(escape::unescape(
+                &template[self.start..self.start + self.len],
+            )),
+            _ => None,
+        }
+    }
+}
+
+fn parse_blocks(template: &str) -> Vec<Block<'_>> {
+    parse_blocks_with(template, &Delimiters::default())
+}
+
+/// The delimiters opening and closing placeholder blocks, `{{` and `}}`
+/// unless a template is compiled with
+/// [`Template::with_delimiters`](crate::Template::with_delimiters).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Delimiters {
+    pub(crate) open: Cow<'static, str>,
+    pub(crate) close: Cow<'static, str>,
+}
+
+impl Default for Delimiters {
+    fn default() -> Self {
+        Self {
+            open: Cow::Borrowed("{{"),
+            close: Cow::Borrowed("}}"),
+        }
+    }
+}
+
+fn parse_blocks_with<'t>(template: &'t str, delimiters: &Delimiters) -> Vec<Block<'t>> {
+    scan_blocks(template, delimiters, |_, _| {})
+}
+
+/// Parse the blocks of a template, calling `unclosed` with the line and
+/// column of each opening delimiter that is not closed before the next one
+/// or the end of the template.
+fn scan_blocks<'t, F>(template: &'t str, delimiters: &Delimiters, mut unclosed: F) -> Vec<Block<'t>>
+where
+    F: FnMut(usize, usize),
+{
+    let mut blocks = Vec::new();
+    let open = &*delimiters.open;
+    let close = &*delimiters.close;
+
+    let mut in_block = false;
+    let mut idx_start = 0;
+    let mut line = 1;
+    let mut line_start = 1;
+    let mut col = 0;
+    let mut col_start = 0;
+
+    let mut chars = template.char_indices();
+
+    while let Some((idx, ch)) = chars.next() {
+        col += 1;
+
+        if ch == '\n' {
+            line += 1;
+            col = 0;
+        }
+
+        if in_block && ch == '"' && opens_key(&template[idx_start + open.len()..idx]) {
+            // skip a quoted key, which may contain delimiters
+            while let Some((_, ch)) = chars.next() {
+                col += 1;
+                let ch = match ch {
+                    '\\' => match chars.next() {
+                        Some((_, escaped)) => {
+                            col += 1;
+                            escaped
+                        }
+                        None => break,
+                    },
+                    '"' => break,
+                    ch => ch,
+                };
+                if ch == '\n' {
+                    line += 1;
+                    col = 0;
+                }
+            }
+        } else if in_block
+            && (template[idx..].starts_with(close)
+                || closes_raw(template, idx_start + open.len(), idx, close))
+        {
+            // a raw block, `{{{ key }}}`, has braces inside its delimiters
+            let raw = closes_raw(template, idx_start + open.len(), idx, close);
+            let (inner, end) = if raw {
+                (
+                    &template[idx_start + open.len() + 1..idx],
+                    idx + 1 + close.len(),
+                )
+            } else {
+                (&template[idx_start + open.len()..idx], idx + close.len())
+            };
+            let (kind, inner) = if raw {
+                (BlockKind::Variable, inner)
+            } else {
+                block_kind(inner)
+            };
+            let (variable_key, align, filters) = split_filters(inner);
+            blocks.push(Block {
+                start: idx_start,
+                len: end - idx_start,
+                line: line_start,
+                col: col_start,
+                variable_key,
+                align,
+                filters,
+                raw,
+                kind,
+            });
+
+            // end of block
+            in_block = false;
+            col += skip_delimiter(&mut chars, &template[idx..end]);
+        } else if template[idx..].starts_with(open) {
+            let slashes = if in_block {
+                0
+            } else {
+                backslashes_before(template, idx)
+            };
+            if slashes > 0 {
+                // each pair of backslashes is one literal backslash, an odd
+                // one makes the delimiter literal
+                let literal = slashes % 2 == 1;
+                blocks.push(Block {
+                    start: idx - slashes,
+                    len: if literal {
+                        slashes + open.len()
+                    } else {
+                        slashes
+                    },
+                    line,
+                    col: col - slashes,
+                    variable_key: Cow::Borrowed(&template[idx..idx]),
+                    align: None,
+                    filters: &template[idx..idx],
+                    raw: false,
+                    kind: BlockKind::Escape,
+                });
+                if literal {
+                    col += skip_delimiter(&mut chars, open);
+                    continue;
+                }
+            }
+
+            // start of block
+            if in_block {
+                unclosed(line_start, col_start);
+            }
+            idx_start = idx;
+            line_start = line;
+            col_start = col;
+            in_block = true;
+            col += skip_delimiter(&mut chars, open);
+        }
+    }
+
+    if in_block {
+        unclosed(line_start, col_start);
+    }
+    blocks
+}
+
+/// Whether a quote after `inside`, a block's text so far, starts a quoted
+/// key: only whitespace, after the `{` of a raw block or around the sigil of
+/// a section tag, precedes it.
+fn opens_key(inside: &str) -> bool {
+    let inside = inside.strip_prefix('{').unwrap_or(inside).trim_start();
+    let inside = inside.strip_prefix(['#', '^', '/']).unwrap_or(inside);
+    inside.trim().is_empty()
+}
+
+/// The kind of a block with `inner` text between its delimiters, and the
+/// text holding its key: `{{#key}}` and `{{^key}}` open a section and
+/// `{{/key}}` ends one.
+fn block_kind(inner: &str) -> (BlockKind, &str) {
+    let trimmed = inner.trim_start();
+    match trimmed.as_bytes().first() {
+        Some(b'#') => (BlockKind::Section { inverted: false }, &trimmed[1..]),
+        Some(b'^') => (BlockKind::Section { inverted: true }, &trimmed[1..]),
+        Some(b'/') => (BlockKind::SectionEnd, &trimmed[1..]),
+        _ => (BlockKind::Variable, inner),
+    }
+}
+
+/// Whether a block whose inside starts at byte `inner` closes as a raw block
+/// at byte `idx`: the inside starts with `{` and `}` precedes `close`.
+fn closes_raw(template: &str, inner: usize, idx: usize, close: &str) -> bool {
+    template[inner..].starts_with('{')
+        && template[idx..].starts_with('}')
+        && template[idx + 1..].starts_with(close)
+}
+
+/// Step over the rest of a delimiter starting at the last character taken
+/// from `chars`, returning the number of characters skipped.
+fn skip_delimiter(chars: &mut CharIndices, delimiter: &str) -> usize {
+    let rest = delimiter.chars().count() - 1;
+    for _ in 0..rest {
+        chars.next();
+    }
+    rest
+}
+
+/// The number of backslashes directly before byte `idx` of `template`.
+fn backslashes_before(template: &str, idx: usize) -> usize {
+    template.as_bytes()[..idx]
+        .iter()
+        .rev()
+        .take_while(|&&byte| byte == b'\\')
+        .count()
+}
+
+/// Split the inside of a block into its key, its alignment and its filter
+/// chain.
+fn split_filters(inner: &str) -> (Cow<'_, str>, Option<Align>, &str) {
+    if let Some((key, rest)) = split_quoted(inner.trim_start()) {
+        let (spec, filters) = match rest.find('|') {
+            Some(idx) => (rest[..idx].trim(), rest[idx + 1..].trim()),
+            None => (rest.trim(), &rest[rest.len()..]),
+        };
+        if spec.is_empty() {
+            return (key, None, filters);
+        }
+        if let Some(align) = spec.strip_prefix(':').and_then(Align::parse) {
+            return (key, Some(align), filters);
+        }
+    }
+    let (key, filters) = match inner.find('|') {
+        Some(idx) => (inner[..idx].trim(), inner[idx + 1..].trim()),
+        None => (inner.trim(), &inner[inner.len()..]),
+    };
+    // a key ending in an alignment, any other `:` is part of the key
+    match key
+        .rsplit_once(':')
+        .and_then(|(key, spec)| Some((key, Align::parse(spec)?)))
+    {
+        Some((key, align)) => (Cow::Borrowed(key.trim_end()), Some(align), filters),
+        None => (Cow::Borrowed(key), None, filters),
+    }
+}
+
+/// Split a quoted key at the start of `text` from the text after it.
+///
+/// Inside the quotes, `\n` and `\t` are a newline and a tab, and a backslash
+/// before any other character, such as `"` or `\`, is that character.
+fn split_quoted(text: &str) -> Option<(Cow<'_, str>, &str)> {
+    let body = text.strip_prefix('"')?;
+    let mut unescaped: Option<String> = None;
+    let mut chars = body.char_indices();
+    while let Some((idx, ch)) = chars.next() {
+        match ch {
+            '"' => {
+                let key = match unescaped {
+                    Some(key) => Cow::Owned(key),
+                    None => Cow::Borrowed(&body[..idx]),
+                };
+                return Some((key, &body[idx + 1..]));
+            }
+            '\\' => {
+                let key = unescaped.get_or_insert_with(|| body[..idx].to_owned());
+                match chars.next()?.1 {
+                    'n' => key.push('\n'),
+                    't' => key.push('\t'),
+                    ch => key.push(ch),
+                }
+            }
+            ch => {
+                if let Some(key) = &mut unescaped {
+                    key.push(ch);
+                }
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_single_var() {
+        test_render_vars(
+            "testKey: testValue;",
+            "testKey: {{ testKey }};",
+            &[("testKey", "testValue")],
+        );
+    }
+
+    #[test]
+    fn render_multiple_vars() {
+        test_render_vars(
+            "testKey: testValue; testKey2: testValue2;",
+            "testKey: {{testKey}}; testKey2: {{ testKey2 }};",
+            &[("testKey", "testValue"), ("testKey2", "testValue2")],
+        );
+    }
+
+    #[test]
+    fn render_without_vars() {
+        test_render_vars(
+            "testKey: testValue; testKey2: testValue2;",
+            "testKey: testValue; testKey2: testValue2;",
+            &[],
+        );
+    }
+
+    #[test]
+    fn render_incorrect_vars() {
+        let input = "testKey: {{testKey}}; testValue2: {{ wrongKey }};";
+        let mut map = VarjMap::new();
+        map.insert("testKey", "testValue");
+        map.insert("testKey2", "testValue2");
+
+        let expected = Error {
+            kind: ErrorKind::UnknownVariable,
+            line: 1,
+            col: 35,
+            key: "wrongKey".to_owned(),
+            source: None,
+            unknown: Vec::new(),
+        };
+
+        let actual = map.render(input).expect_err("parsing should error");
+        assert_eq!(expected.line, actual.line);
+        assert_eq!(expected.col, actual.col);
+        assert_eq!(expected.key, actual.key);
+
+        let expected_error_msg = format!(
+            "{}:{} unknown variable '{}'",
+            expected.line, expected.col, expected.key
+        );
+        let actual_error_msg = format!("{}", actual);
+        assert_eq!(expected_error_msg, actual_error_msg);
+    }
+
+    #[test]
+    fn render_incorrect_vars_crlf() {
+        let input = "testKey: {{ testKey }};\r\n\r\n  testKey2: {{ wrongKey }};\r\n";
+        let mut map = VarjMap::new();
+        map.insert("testKey", "testValue");
+
+        let actual = map.render(input).expect_err("rendering should error");
+        assert_eq!("3:13 unknown variable 'wrongKey'", actual.to_string());
+    }
+
+    #[test]
+    fn render_normalized_line_endings() {
+        let mut map = VarjMap::new();
+        map.insert("testKey", "a\r\nb");
+        let input = "{{ testKey }}\n{{ testKey }}\r\n";
+
+        let options = RenderOptions::new().line_ending(LineEnding::Lf);
+        let actual = map
+            .render_with_options(input, &options)
+            .expect("rendering should succeed");
+        assert_eq!("a\nb\na\nb\n", actual);
+
+        let options = RenderOptions::new().line_ending(LineEnding::CrLf);
+        let actual = map
+            .render_with_options(input, &options)
+            .expect("rendering should succeed");
+        assert_eq!("a\r\nb\r\na\r\nb\r\n", actual);
+    }
+
+    #[test]
+    fn render_invalid_key_chars() {
+        let mut map = VarjMap::new();
+        map.insert("x", "testValue");
+        let options = RenderOptions::new().key_chars(KeyChars::Identifier);
+
+        let actual = map
+            .render_with_options("testKey: {{ x }};\n{{ if x }}", &options)
+            .expect_err("rendering should error");
+
+        assert_eq!(ErrorKind::InvalidKey, actual.kind());
+        assert_eq!("if x", actual.key());
+        assert_eq!(2, actual.line());
+        assert_eq!(1, actual.col());
+        assert_eq!("2:1 invalid key 'if x'", actual.to_string());
+    }
+
+    #[test]
+    fn render_valid_key_chars() {
+        let mut map = VarjMap::new();
+        map.insert("test.key_1", "testValue");
+        let options = RenderOptions::new().key_chars(KeyChars::Identifier);
+
+        let actual = map
+            .render_with_options("testKey: {{ test.key_1 }};", &options)
+            .expect("rendering should succeed");
+
+        assert_eq!("testKey: testValue;", actual);
+    }
+
+    #[test]
+    fn render_with_substitute_hook() {
+        let mut map = VarjMap::new();
+        map.insert("testKey", "testValue");
+        map.insert("secret", "testSecret");
+        let options = RenderOptions::new().on_substitute(|key, value| match key {
+            "secret" => None,
+            _ => Some(value.to_uppercase().into()),
+        });
+
+        let actual = map
+            .render_with_options("testKey: {{ testKey }};", &options)
+            .expect("rendering should succeed");
+        assert_eq!("testKey: TESTVALUE;", actual);
+
+        let actual = map
+            .render_with_options("secret: {{ secret }};", &options)
+            .expect_err("rendering should error");
+        assert_eq!(ErrorKind::Rejected, actual.kind());
+        assert_eq!("1:9 rejected variable 'secret'", actual.to_string());
+    }
+
+    #[test]
+    fn render_escaped_yaml() {
+        let mut map = VarjMap::new();
+        map.insert("testKey", "testValue");
+        map.insert("testScript", "line1\nline2\n");
+        let options = RenderOptions::new()
+            .on_substitute(|_, value| Some(value.replace("testValue", "on").into()))
+            .escape(Escape::Yaml);
+
+        let actual = map
+            .render_with_options(
+                "testKey: {{ testKey }}\nsteps:\n  - {{ testScript }}\n",
+                &options,
+            )
+            .expect("rendering should succeed");
+        assert_eq!(
+            "testKey: \"on\"\nsteps:\n  - |\n      line1\n      line2\n",
+            actual
+        );
+    }
+
+    #[test]
+    fn render_escaped_html() {
+        let mut map = VarjMap::new();
+        map.insert("testKey", "<b>\"Tom\" & 'Jerry'</b>");
+        map.insert("test key", "<i>x</i>");
+        let options = RenderOptions::new().escape(Escape::Html);
+
+        let actual = map
+            .render_with_options(
+                "{{ testKey }} {{{ testKey }}} {{{ \"test key\" | replace(i, em) }}} {{ testKey }}",
+                &options,
+            )
+            .expect("rendering should succeed");
+        assert_eq!(
+            "&lt;b&gt;&quot;Tom&quot; &amp; &#39;Jerry&#39;&lt;/b&gt; \
+             <b>\"Tom\" & 'Jerry'</b> <em>x</em> \
+             &lt;b&gt;&quot;Tom&quot; &amp; &#39;Jerry&#39;&lt;/b&gt;",
+            actual
+        );
+
+        let actual = map
+            .render("{{{ testKey }} {{{ testKey }}}}")
+            .expect_err("'{ testKey' is unknown");
+        assert_eq!("{ testKey", actual.key());
+    }
+
+    #[test]
+    fn render_sections() {
+        let mut map = VarjMap::new();
+        map.insert("testKey", "testValue");
+        map.insert("flag", "on");
+        map.insert("empty", "");
+        map.insert("spaces", "  ");
+
+        let template = "{{#flag}}a {{ testKey }}{{/flag}},\
+                        {{^flag}}b {{ missing }}{{/flag}},\
+                        {{#empty}}c {{ missing }}{{/empty}},\
+                        {{^ missing }}d{{#flag}}e{{/flag}}{{/ missing }},\
+                        {{#spaces | trim}}f{{/spaces}}{{#\"flag\"}}g{{/flag}}";
+        let actual = map.render(template).expect("rendering should succeed");
+        assert_eq!("a testValue,,,de,g", actual);
+
+        let options = RenderOptions::new().trim_standalone(true);
+        let actual = map
+            .render_with_options(
+                "{{#flag}}\n  a\n{{/flag}}\n{{#empty}}\nb\n{{/empty}}\nc\n",
+                &options,
+            )
+            .expect("rendering should succeed");
+        assert_eq!("  a\nc\n", actual);
+
+        // only filters decide whether a section shows
+        let options = RenderOptions::new()
+            .bidi_isolate(true)
+            .on_substitute(|key, value| (key != "flag").then_some(Cow::Borrowed(value)));
+        let actual = map
+            .render_with_options(
+                "{{#empty}}a{{/empty}}{{#empty:>3}}b{{/empty}}{{^empty:>3}}c{{/empty}}\
+                 {{#flag}}d{{/flag}}",
+                &options,
+            )
+            .expect("rendering should succeed");
+        assert_eq!("cd", actual);
+
+        let unmatched = |template: &str| {
+            let err = map.render(template).expect_err("sections are unmatched");
+            assert_eq!(ErrorKind::UnmatchedSection, err.kind());
+            err.to_string()
+        };
+        assert_eq!("1:1 unmatched section 'a'", unmatched("{{#a}}{{ flag }}"));
+        assert_eq!(
+            "1:14 unmatched section 'a'",
+            unmatched("{{#a}} {{/a}}{{/a}}")
+        );
+        assert_eq!("1:7 unmatched section 'b'", unmatched("{{#a}}{{^b}}{{/a}}"));
+    }
+
+    #[test]
+    fn render_list_sections() {
+        let mut map = VarjMap::new();
+        map.insert("sep", ";");
+        map.insert_list("items", ["a", "b", "c"]);
+        map.insert_list("items", ["x", "<y>"]);
+        map.insert_list("none", Vec::<String>::new());
+
+        let template = "{{#items}}{{ . }}{{ sep }}{{/items}},\
+                        {{^items}}z{{/items}}{{^none}}empty{{/none}},\
+                        {{#items}}{{#none}}{{ . }}{{/none}}{{/items}}";
+        for threads in [1, 2] {
+            let options = RenderOptions::new().threads(threads);
+            let actual = map
+                .render_with_options(template, &options)
+                .expect("rendering should succeed");
+            assert_eq!("x;<y>;,empty,", actual);
+        }
+        assert_eq!(None, map.get("items.2"));
+
+        let options = RenderOptions::new()
+            .trim_standalone(true)
+            .escape(Escape::Html)
+            .deny_unused(true);
+        let actual = map
+            .render_with_options("{{#items}}\n- {{.}}\n{{/items}}\n{{ sep }}", &options)
+            .expect("every entry should be used");
+        assert_eq!("- x\n- &lt;y&gt;\n;", actual);
+
+        // the default does not make lists endless
+        map.set_default("?");
+        let actual = map
+            .render("{{#items}}{{.}}{{/items}} {{#other}}{{.}}{{/other}}")
+            .expect("rendering should succeed");
+        assert_eq!("x<y> ?", actual);
+
+        let options = RenderOptions::new().missing_key(MissingKey::Collect);
+        map.clear_default();
+        let err = map
+            .render_with_options("{{ a }}{{#items}}{{ b }}{{/items}}{{ . }}", &options)
+            .expect_err("a, b and . are missing");
+        let unknown: Vec<(&str, usize)> = err
+            .unknown_variables()
+            .iter()
+            .map(|err| (err.key(), err.col()))
+            .collect();
+        assert_eq!(vec![("a", 1), ("b", 18), ("b", 18), (".", 35)], unknown);
+    }
+
+    #[test]
+    fn render_bidi_isolated() {
+        let mut map = VarjMap::new();
+        map.insert("testKey", "\u{5E9}\u{5DC}\u{5D5}\u{5DD}\nworld");
+        let options = RenderOptions::new().bidi_isolate(true).escape(Escape::Toml);
+
+        let actual = map
+            .render_with_options("testKey = {{ testKey }}", &options)
+            .expect("rendering should succeed");
+        assert_eq!(
+            "testKey = \"\u{2068}\u{5E9}\u{5DC}\u{5D5}\u{5DD}\\nworld\u{2069}\"",
+            actual
+        );
+    }
+
+    #[test]
+    fn render_post_processed() {
+        let mut map = VarjMap::new();
+        map.insert("testKey", "testValue  ");
+        let template = "{{ testKey }}\n{{ testKey }}";
 
-impl From<Block<'_>> for Error {
-    fn from(block: Block) -> Error {
-        Error {
-            key: block.variable_key.to_owned(),
-            line: block.line,
-            col: block.col,
+        for threads in [1, 2] {
+            let options = RenderOptions::new()
+                .threads(threads)
+                .post_process(PostProcess::TrimTrailingWhitespace)
+                .post_process(PostProcess::FinalNewline);
+            let actual = map
+                .render_with_options(template, &options)
+                .expect("rendering should succeed");
+            assert_eq!("testValue\ntestValue\n", actual);
         }
     }
-}
-
-#[derive(Debug, PartialEq)]
-struct Block<'a> {
-    start: usize,
-    len: usize,
-    line: usize,
-    col: usize,
-    variable_key: &'a str,
-}
 
-impl<'a> Block<'a> {
-    fn value_from_map(&self, vars: &'a VarjMap) -> Option<&'a str> {
-        vars.get(self.variable_key)
-    }
-}
+    #[test]
+    fn render_escaped_braces() {
+        let mut map = VarjMap::new();
+        map.insert("testKey", "testValue");
+        let template = r"\{{ testKey }} \\{{ testKey }} \\\{{ testKey }} \\ \{";
 
-fn parse_blocks(template: &str) -> Vec<Block> {
-    let mut blocks = Vec::new();
+        let actual = map.render(template).expect("rendering should succeed");
+        assert_eq!(r"{{ testKey }} \testValue \{{ testKey }} \\ \{", actual);
 
-    let mut in_block = false;
-    let mut idx_start = 0;
-    let mut line = 1;
-    let mut line_start = 1;
-    let mut col = 0;
-    let mut col_start = 0;
+        let compiled = Template::compile(template);
+        assert_eq!(Ok(actual), compiled.render(&map));
 
-    let mut chars = template.char_indices().peekable();
+        let actual = map
+            .render(&escape(template))
+            .expect("rendering should succeed");
+        assert_eq!(template, actual);
+    }
 
-    while let Some((idx, ch)) = chars.next() {
-        col += 1;
+    #[test]
+    fn render_filtered_value() {
+        let mut map = VarjMap::new();
+        map.insert("testKey", "test'Value");
+        let options = RenderOptions::new().on_substitute(|key, value| {
+            assert_eq!("testKey", key);
+            Some(value.to_uppercase().into())
+        });
 
-        if ch == '\n' {
-            line += 1;
-            col = 0;
-        }
+        let actual = map
+            .render_with_options("testKey: {{ testKey | sql_quote(mysql) }};", &options)
+            .expect("rendering should succeed");
+        assert_eq!("testKey: 'TEST\\'VALUE';", actual);
 
-        if in_block && ch == '}' {
-            match chars.peek() {
-                Some((next_idx, next_ch)) if *next_ch == '}' => {
-                    blocks.push(Block {
-                        start: idx_start,
-                        len: next_idx - idx_start + 1,
-                        line: line_start,
-                        col: col_start,
-                        variable_key: template[idx_start + 2..next_idx - 1].trim(),
-                    });
-
-                    // end of block
-                    in_block = false;
-                    col += 1;
-                    chars.next();
-                }
-                Some(_) => continue,
-                None => break,
-            };
-        } else if ch == '{' {
-            match chars.peek() {
-                Some((_, next_ch)) if *next_ch == '{' => {
-                    // start of block
-                    idx_start = idx;
-                    line_start = line;
-                    col_start = col;
-                    in_block = true;
-                    col += 1;
-                    chars.next();
-                }
-                Some(_) => continue,
-                None => break,
-            };
-        }
+        let actual = map
+            .render("testKey:\n {{ testKey | sql_quote(oracle) }}")
+            .expect_err("rendering should error");
+        assert_eq!(ErrorKind::Filter, actual.kind());
+        assert_eq!("testKey", actual.key());
+        assert_eq!(
+            "2:2 filter failed for 'testKey': unknown sql dialect 'oracle'",
+            actual.to_string()
+        );
     }
 
-    blocks
-}
+    #[test]
+    fn map_from_args() {
+        let args = [
+            "varj",
+            "-v",
+            "testKey=testValue",
+            "=ignored",
+            "testKey2=a=b",
+        ];
+        let actual = VarjMap::from_args(args);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let mut expected = VarjMap::new();
+        expected.insert("testKey", "testValue");
+        expected.insert("testKey2", "a=b");
+        assert_eq!(expected, actual);
 
-    #[test]
-    fn render_single_var() {
-        test_render_vars(
-            "testKey: testValue;",
-            "testKey: {{ testKey }};",
-            &[("testKey", "testValue")],
-        );
+        let args = [
+            "testKey=ignored",
+            "--var",
+            "testKey=testValue",
+            "--var=testKey2=a=b",
+            "--var",
+        ];
+        let actual = VarjMap::from_prefixed_args(args, "--var");
+        assert_eq!(expected, actual);
     }
 
     #[test]
-    fn render_multiple_vars() {
-        test_render_vars(
-            "testKey: testValue; testKey2: testValue2;",
-            "testKey: {{testKey}}; testKey2: {{ testKey2 }};",
-            &[("testKey", "testValue"), ("testKey2", "testValue2")],
-        );
+    fn render_deny_unused() {
+        let mut map = VarjMap::new();
+        map.insert("testKey", "testValue");
+        map.insert("testKey3", "testValue3");
+        map.insert("testKey2", "testValue2");
+        let options = RenderOptions::new().deny_unused(true);
+
+        let actual = map
+            .render_with_options("testKey: {{ testKey }};", &options)
+            .expect_err("rendering should error");
+        assert_eq!(ErrorKind::UnusedVariable, actual.kind());
+        assert_eq!("testKey2", actual.key());
+        assert_eq!("unused variable 'testKey2'", actual.to_string());
+
+        let actual = map
+            .render_with_options("{{testKey}} {{testKey2}} {{testKey3}}", &options)
+            .expect("rendering should succeed");
+        assert_eq!("testValue testValue2 testValue3", actual);
     }
 
     #[test]
-    fn render_without_vars() {
-        test_render_vars(
-            "testKey: testValue; testKey2: testValue2;",
-            "testKey: testValue; testKey2: testValue2;",
-            &[],
-        );
+    fn render_cancelled() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let mut map = VarjMap::new();
+        map.insert("testKey", "testValue");
+        let polls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&polls);
+        let options =
+            RenderOptions::new().cancel_when(move || counter.fetch_add(1, Ordering::SeqCst) == 1);
+
+        let actual = map
+            .render_with_options("{{ testKey }} {{ testKey }} {{ testKey }}", &options)
+            .expect_err("rendering should be cancelled");
+        assert_eq!(ErrorKind::Cancelled, actual.kind());
+        assert_eq!("render cancelled", actual.to_string());
+        assert_eq!(2, polls.load(Ordering::SeqCst));
     }
 
     #[test]
-    fn render_incorrect_vars() {
-        let input = "testKey: {{testKey}}; testValue2: {{ wrongKey }};";
+    fn render_case_insensitive_keys() {
         let mut map = VarjMap::new();
         map.insert("testKey", "testValue");
-        map.insert("testKey2", "testValue2");
+        assert_eq!(None, map.get("TESTKEY"));
 
-        let expected = Error {
-            line: 1,
-            col: 35,
-            key: "wrongKey".to_owned(),
-        };
+        map.set_case_insensitive(true);
+        map.insert("TestKey2", "testValue2");
 
-        let actual = map.render(input).expect_err("parsing should error");
-        assert_eq!(expected.line, actual.line);
-        assert_eq!(expected.col, actual.col);
-        assert_eq!(expected.key, actual.key);
+        assert_eq!(Some("testValue"), map.get("TESTKEY"));
+        assert_eq!(Some("testValue2"), map.get("testkey2"));
+        let actual = map
+            .render("{{ TestKey }} {{ testkey }} {{ TESTKEY2 }}")
+            .expect("rendering should succeed");
+        assert_eq!("testValue testValue testValue2", actual);
+    }
 
-        let expected_error_msg = format!(
-            "{}:{} unknown variable '{}'",
-            expected.line, expected.col, expected.key
-        );
-        let actual_error_msg = format!("{}", actual);
-        assert_eq!(expected_error_msg, actual_error_msg);
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn render_normalized_keys() {
+        let mut map = VarjMap::new();
+        map.insert("caf\u{e9}", "testValue");
+        map.insert("nai\u{308}ve", "testValue2");
+        assert_eq!(None, map.get("cafe\u{301}"));
+
+        map.set_normalize_keys(true);
+        map.insert("cafe\u{301}2", "testValue3");
+
+        assert_eq!(Some("testValue"), map.get("cafe\u{301}"));
+        assert_eq!(Some("testValue2"), map.get("na\u{ef}ve"));
+        assert_eq!(Some("testValue3"), map.get("caf\u{e9}2"));
+        let actual = map
+            .render("{{ cafe\u{301} }} {{ na\u{ef}ve }}")
+            .expect("rendering should succeed");
+        assert_eq!("testValue testValue2", actual);
     }
 
     #[test]
@@ -351,7 +2248,11 @@ mod tests {
                 len: 13,
                 line: 1,
                 col: 10,
-                variable_key: "testKey",
+                variable_key: "testKey".into(),
+                align: None,
+                filters: "",
+                raw: false,
+                kind: BlockKind::Variable,
             }],
         );
     }
@@ -365,7 +2266,11 @@ mod tests {
                 len: 11,
                 line: 1,
                 col: 10,
-                variable_key: "testKey",
+                variable_key: "testKey".into(),
+                align: None,
+                filters: "",
+                raw: false,
+                kind: BlockKind::Variable,
             }],
         );
     }
@@ -379,7 +2284,11 @@ mod tests {
                 len: 11,
                 line: 1,
                 col: 1,
-                variable_key: "testKey",
+                variable_key: "testKey".into(),
+                align: None,
+                filters: "",
+                raw: false,
+                kind: BlockKind::Variable,
             }],
         );
     }
@@ -393,7 +2302,11 @@ mod tests {
                 len: 11,
                 line: 1,
                 col: 10,
-                variable_key: "testKey",
+                variable_key: "testKey".into(),
+                align: None,
+                filters: "",
+                raw: false,
+                kind: BlockKind::Variable,
             }],
         );
     }
@@ -407,7 +2320,11 @@ mod tests {
                 len: 14,
                 line: 1,
                 col: 11,
-                variable_key: "test}Key",
+                variable_key: "test}Key".into(),
+                align: None,
+                filters: "",
+                raw: false,
+                kind: BlockKind::Variable,
             }],
         );
     }
@@ -422,14 +2339,22 @@ mod tests {
                     len: 11,
                     line: 1,
                     col: 10,
-                    variable_key: "testKey",
+                    variable_key: "testKey".into(),
+                    align: None,
+                    filters: "",
+                    raw: false,
+                    kind: BlockKind::Variable,
                 },
                 Block {
                     start: 32,
                     len: 14,
                     line: 1,
                     col: 33,
-                    variable_key: "testKey2",
+                    variable_key: "testKey2".into(),
+                    align: None,
+                    filters: "",
+                    raw: false,
+                    kind: BlockKind::Variable,
                 },
             ],
         );
@@ -445,19 +2370,175 @@ mod tests {
                     len: 11,
                     line: 1,
                     col: 10,
-                    variable_key: "testKey",
+                    variable_key: "testKey".into(),
+                    align: None,
+                    filters: "",
+                    raw: false,
+                    kind: BlockKind::Variable,
                 },
                 Block {
                     start: 32,
                     len: 14,
                     line: 2,
                     col: 11,
-                    variable_key: "testKey2",
+                    variable_key: "testKey2".into(),
+                    align: None,
+                    filters: "",
+                    raw: false,
+                    kind: BlockKind::Variable,
                 },
             ],
         );
     }
 
+    #[test]
+    fn parse_multiple_blocks_on_crlf_lines() {
+        test_parsed_blocks(
+            "testKey: {{testKey}};\r\ntestKey2: {{ testKey2 }};",
+            vec![
+                Block {
+                    start: 9,
+                    len: 11,
+                    line: 1,
+                    col: 10,
+                    variable_key: "testKey".into(),
+                    align: None,
+                    filters: "",
+                    raw: false,
+                    kind: BlockKind::Variable,
+                },
+                Block {
+                    start: 33,
+                    len: 14,
+                    line: 2,
+                    col: 11,
+                    variable_key: "testKey2".into(),
+                    align: None,
+                    filters: "",
+                    raw: false,
+                    kind: BlockKind::Variable,
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn clone_on_write() {
+        let mut base = VarjMap::new();
+        base.insert("testKey", "testValue");
+        base.insert("testKey2", "testValue2");
+
+        let mut fork = base.clone();
+        assert!(Arc::ptr_eq(&base.map, &fork.map));
+
+        fork.insert("testKey2", "overridden");
+        assert!(!Arc::ptr_eq(&base.map, &fork.map));
+        assert_eq!(Some("testValue2"), base.get("testKey2"));
+        assert_eq!(Some("overridden"), fork.get("testKey2"));
+        assert_eq!(Some("testValue"), fork.get("testKey"));
+    }
+
+    #[test]
+    fn insert_replaced_value() {
+        let mut map = VarjMap::new();
+        assert_eq!(None, map.insert("testKey", "testValue"));
+        assert_eq!(
+            Some("testValue".to_owned()),
+            map.insert("testKey", "testValue2")
+        );
+        assert_eq!(Some("testValue2"), map.get("testKey"));
+    }
+
+    #[test]
+    fn try_insert_duplicate() {
+        let mut base = VarjMap::new();
+        base.set_case_insensitive(true);
+        base.insert("testKey", "testValue");
+        let mut fork = base.clone();
+
+        let err = fork
+            .try_insert("TESTKEY", "other")
+            .expect_err("key is already set");
+        assert_eq!("testkey", err.key());
+        assert_eq!("other", err.into_value());
+        assert!(Arc::ptr_eq(&base.map, &fork.map));
+
+        fork.try_insert("testKey2", "testValue2")
+            .expect("key is not set");
+        assert_eq!(Some("testValue2"), fork.get("testKey2"));
+    }
+
+    #[test]
+    fn get_or_insert_with() {
+        let mut map = VarjMap::new();
+        map.set_case_insensitive(true);
+        map.insert("testKey", "testValue");
+
+        let value = map.get_or_insert_with("TESTKEY", || -> String { unreachable!() });
+        assert_eq!("testValue", value);
+        assert_eq!("new", map.get_or_insert_with("newKey", || "new"));
+        assert_eq!(Some("new"), map.get("NEWKEY"));
+    }
+
+    #[test]
+    fn render_default_value() {
+        let mut map = VarjMap::new();
+        map.insert("testKey", "testValue");
+        map.set_default("");
+
+        let actual = map
+            .render("{{ testKey }}:{{ missing }};")
+            .expect("rendering should succeed");
+        assert_eq!("testValue:;", actual);
+        assert_eq!(None, map.get("missing"));
+
+        map.clear_default();
+        let err = map.render("{{ missing }}").expect_err("missing is not set");
+        assert_eq!(ErrorKind::UnknownVariable, err.kind());
+    }
+
+    #[test]
+    fn render_aliased_keys() {
+        let mut map = VarjMap::new();
+        map.insert("testKey", "testValue");
+        map.insert("shadowed", "own");
+        map.alias("oldKey", "testKey");
+        map.alias("shadowed", "testKey");
+        map.alias("chained", "oldKey");
+        map.set_case_insensitive(true);
+
+        assert_eq!(Some("testValue"), map.get("OLDKEY"));
+        assert_eq!(Some("own"), map.get("shadowed"));
+        assert_eq!(None, map.get("chained"));
+
+        let options = RenderOptions::new().deny_unused(true);
+        let actual = map
+            .render_with_options("{{ oldKey }} {{ shadowed }}", &options)
+            .expect("aliased key counts as used");
+        assert_eq!("testValue own", actual);
+
+        assert_eq!(Some("testkey".to_owned()), map.remove_alias("oldKey"));
+        assert_eq!(None, map.get("oldKey"));
+    }
+
+    #[test]
+    fn render_bridged_case() {
+        let mut map = VarjMap::new();
+        map.insert("userName", "ada");
+        map.set_case_bridging(true);
+        map.insert("XMLHttpRequest", "xhr");
+        map.insert("port2Value", "8080");
+
+        assert_eq!(Some("ada"), map.get("user_name"));
+        assert_eq!(Some("xhr"), map.get("xml_http_request"));
+        assert_eq!(Some("8080"), map.get("port2_value"));
+
+        let actual = map
+            .render("{{ user_name }} {{ UserName }} {{ xmlHttpRequest }}")
+            .expect("rendering should succeed");
+        assert_eq!("ada ada xhr", actual);
+    }
+
     #[test]
     fn from_hash_map() {
         let (expected, hash_map) = matching_varj_and_hash_maps();
@@ -472,6 +2553,237 @@ mod tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn render_repeated_keys_memoized() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let mut map = VarjMap::new();
+        map.insert("a", "x\ny\n");
+        map.insert("b", "z");
+        let template = "a: {{ a }}\nl:\n  a: {{ a }}\nb: {{ b }}{{ b }}{{ b | trim }}";
+
+        for (memoize, expected) in [(false, 5), (true, 3)] {
+            let calls = Arc::new(AtomicUsize::new(0));
+            let counter = Arc::clone(&calls);
+            let options = RenderOptions::new()
+                .escape(Escape::Yaml)
+                .memoize(memoize)
+                .on_substitute(move |_, value| {
+                    counter.fetch_add(1, Ordering::Relaxed);
+                    Some(Cow::Borrowed(value))
+                });
+
+            let actual = map
+                .render_with_options(template, &options)
+                .expect("rendering should succeed");
+            assert_eq!("a: |\n  x\n  y\nl:\n  a: |\n    x\n    y\nb: zzz", actual);
+            assert_eq!(expected, calls.load(Ordering::Relaxed));
+        }
+    }
+
+    #[test]
+    fn render_aligned_values() {
+        let mut map = VarjMap::new();
+        map.insert("name", "varj");
+        map.insert("host:port", "a:1");
+        map.insert("a b", "é");
+
+        let template = "[{{ name:>6 }}][{{name:<6}}][{{ name :^7 }}][{{ name:*^8 | trim }}][{{ name:>2 }}][{{ host:port }}][{{ \"a b\":-<3 }}]";
+        let actual = map.render(template).expect("rendering should succeed");
+        assert_eq!(
+            "[  varj][varj  ][ varj  ][**varj**][varj][a:1][é--]",
+            actual
+        );
+
+        let compiled = Template::compile(template);
+        assert_eq!(Ok(actual), compiled.render(&map));
+        assert_ne!(
+            compiled.fingerprint(),
+            Template::compile(template.replace(":>6", "")).fingerprint()
+        );
+
+        // widths past the limit are part of the key instead of allocating
+        for key in ["name:>65536", "name:>18446744073709551615"] {
+            let err = map
+                .render(&format!("{{{{ {} }}}}", key))
+                .expect_err("the key is not set");
+            assert_eq!((ErrorKind::UnknownVariable, key), (err.kind(), err.key()));
+        }
+        assert_eq!(
+            Ok(65535),
+            map.render("{{ name:>65535 }}").map(|output| output.len())
+        );
+    }
+
+    #[test]
+    fn render_trimmed_standalone_lines() {
+        let mut map = VarjMap::new();
+        map.insert("empty", "");
+        map.insert("full", "x");
+        let options = RenderOptions::new().trim_standalone(true);
+
+        let template = "a\n  {{ empty }}  \r\n{{ full }}\n{{ empty }}{{ empty }}\nb {{ empty }}\n\\{{ x }}\n{{ empty }}";
+        let actual = map
+            .render_with_options(template, &options)
+            .expect("rendering should succeed");
+        assert_eq!("a\nx\n\nb \n{{ x }}\n", actual);
+
+        let actual = map.render(template).expect("rendering should succeed");
+        assert_eq!("a\n    \r\nx\n\nb \n{{ x }}\n", actual);
+    }
+
+    #[test]
+    fn render_missing_keys() {
+        let mut map = VarjMap::new();
+        map.insert("a", "x");
+        let template = "{{ a }} {{ b | trim }}\n  {{ b }}\n\\{{ c }}";
+
+        let actual = map.render(template).expect_err("b is missing");
+        assert_eq!(ErrorKind::UnknownVariable, actual.kind());
+
+        let options = RenderOptions::new().missing_key(MissingKey::KeepPlaceholder);
+        let actual = map
+            .render_with_options(template, &options)
+            .expect("rendering should succeed");
+        assert_eq!("x {{ b | trim }}\n  {{ b }}\n{{ c }}", actual);
+        let actual = map
+            .render_bytes_with_options(template.as_bytes(), &options)
+            .expect("rendering should succeed");
+        assert_eq!(b"x {{ b | trim }}\n  {{ b }}\n{{ c }}".as_slice(), actual);
+
+        let options = RenderOptions::new()
+            .missing_key(MissingKey::Empty)
+            .trim_standalone(true);
+        let actual = map
+            .render_with_options(template, &options)
+            .expect("rendering should succeed");
+        assert_eq!("x \n{{ c }}", actual);
+    }
+
+    #[test]
+    fn collect_unknown_variables() {
+        let mut map = VarjMap::new();
+        map.insert("a", "x");
+        let template = "{{ b }} {{ a }}\n{{ c | trim }} {{ b }}";
+
+        let options = RenderOptions::new().missing_key(MissingKey::Collect);
+        let actual = map
+            .render_with_options(template, &options)
+            .expect_err("b and c are missing");
+        let unknown: Vec<(&str, usize, usize)> = actual
+            .unknown_variables()
+            .iter()
+            .map(|err| (err.key(), err.line(), err.col()))
+            .collect();
+        assert_eq!(vec![("b", 1, 1), ("c", 2, 1), ("b", 2, 16)], unknown);
+        assert_eq!(("b", 1, 1), (actual.key(), actual.line(), actual.col()));
+        assert_eq!(
+            "unknown variables: 1:1 'b', 2:1 'c', 2:16 'b'",
+            actual.to_string()
+        );
+
+        let parallel = map
+            .render_with_options(template, &options.clone().threads(2))
+            .expect_err("b and c are missing");
+        assert_eq!(actual.unknown_variables(), parallel.unknown_variables());
+        let bytes = map
+            .render_bytes_with_options(template.as_bytes(), &options)
+            .expect_err("b and c are missing");
+        assert_eq!(actual.unknown_variables(), bytes.unknown_variables());
+
+        let actual = map
+            .render_with_options("{{ b }} {{ a | nope }} {{ c }}", &options)
+            .expect_err("nope is not a filter");
+        assert_eq!(ErrorKind::Filter, actual.kind());
+
+        let actual = map.render(template).expect_err("b is missing");
+        assert!(actual.unknown_variables().is_empty());
+        assert_eq!("1:1 unknown variable 'b'", actual.to_string());
+    }
+
+    #[test]
+    fn parse_defines() {
+        let map = VarjMap::from_defines([
+            "app",
+            "-D",
+            "a=x=y",
+            "-Db:int=+007",
+            "-D",
+            "c:uint=18446744073709551615",
+            "-Dd:float=1e3",
+            "-D",
+            "host:port=1",
+            "-Dname:str=3",
+            "--define=e=1",
+        ])
+        .expect("defines are valid");
+        assert_eq!(Some("x=y"), map.get("a"));
+        assert_eq!(Some("7"), map.get("b"));
+        assert_eq!(Some("18446744073709551615"), map.get("c"));
+        assert_eq!(Some("1000"), map.get("d"));
+        assert_eq!(Some("1"), map.get("host:port"));
+        assert_eq!(Some("3"), map.get("name"));
+        assert_eq!(None, map.get("e"));
+
+        let error = |args: &[&str]| {
+            VarjMap::from_defines(args)
+                .expect_err("defines are invalid")
+                .to_string()
+        };
+        assert_eq!(
+            "invalid define 'n:uint=-1': expected an unsigned integer",
+            error(&["-Dn:uint=-1"])
+        );
+        assert_eq!(
+            "invalid define 'b:bool=yes': expected true or false",
+            error(&["-D", "b:bool=yes"])
+        );
+        assert_eq!("invalid define ':int=1': empty key", error(&["-D:int=1"]));
+        assert_eq!("invalid define 'x': expected key=value", error(&["-Dx"]));
+        assert_eq!(
+            "invalid define '': missing key=value after -D",
+            error(&["-D"])
+        );
+    }
+
+    #[test]
+    fn insert_nested_maps() {
+        let mut inner = VarjMap::new();
+        inner.insert("name", "x");
+        inner.alias("title", "name");
+        inner.set_default("unused");
+
+        let mut map = VarjMap::new();
+        map.set_case_insensitive(true);
+        map.insert("user.name", "replaced");
+        map.insert_nested("User", &inner);
+        let actual = map
+            .render("{{ user.name }} {{ USER.TITLE }}")
+            .expect("rendering should succeed");
+        assert_eq!("x x", actual);
+        map.render("{{ user.age }}").expect_err("age is missing");
+    }
+
+    #[test]
+    fn insert_typed_values() {
+        let mut map = VarjMap::new();
+        map.insert_i64("i", i64::MIN);
+        map.insert_u64("u", u64::MAX);
+        map.insert_f64("f", 1e21);
+        map.insert_f64("small", 1e-7);
+        map.insert_f64("zero", -0.0);
+        map.insert_f64("inf", f64::NEG_INFINITY);
+        map.insert_bool("b", true);
+
+        assert_eq!(Some("-9223372036854775808"), map.get("i"));
+        assert_eq!(Some("18446744073709551615"), map.get("u"));
+        assert_eq!(Some("1000000000000000000000"), map.get("f"));
+        assert_eq!(Some("0.0000001"), map.get("small"));
+        assert_eq!(Some("0"), map.get("zero"));
+        assert_eq!(Some("-inf"), map.get("inf"));
+        assert_eq!(Some("true".to_owned()), map.insert_bool("b", false));
+    }
+
     fn test_render_vars(expected: &str, template: &str, vars: &[(&str, &str)]) {
         let mut map = VarjMap::new();
         for (k, v) in vars {
@@ -484,6 +2796,103 @@ mod tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn parse_escaped_blocks() {
+        test_parsed_blocks(
+            "\\{{a}}\n\\\\{{b}}",
+            vec![
+                Block {
+                    start: 0,
+                    len: 3,
+                    line: 1,
+                    col: 1,
+                    variable_key: "".into(),
+                    align: None,
+                    filters: "",
+                    raw: false,
+                    kind: BlockKind::Escape,
+                },
+                Block {
+                    start: 7,
+                    len: 2,
+                    line: 2,
+                    col: 1,
+                    variable_key: "".into(),
+                    align: None,
+                    filters: "",
+                    raw: false,
+                    kind: BlockKind::Escape,
+                },
+                Block {
+                    start: 9,
+                    len: 5,
+                    line: 2,
+                    col: 3,
+                    variable_key: "b".into(),
+                    align: None,
+                    filters: "",
+                    raw: false,
+                    kind: BlockKind::Variable,
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn parse_quoted_keys() {
+        test_parsed_blocks(
+            "{{ \"a }} | b\" | trim }}\n{{\"c\\\"\\nd\"}}",
+            vec![
+                Block {
+                    start: 0,
+                    len: 23,
+                    line: 1,
+                    col: 1,
+                    variable_key: "a }} | b".into(),
+                    align: None,
+                    filters: "trim",
+                    raw: false,
+                    kind: BlockKind::Variable,
+                },
+                Block {
+                    start: 24,
+                    len: 12,
+                    line: 2,
+                    col: 1,
+                    variable_key: "c\"\nd".into(),
+                    align: None,
+                    filters: "",
+                    raw: false,
+                    kind: BlockKind::Variable,
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn render_quoted_keys() {
+        let mut map = VarjMap::new();
+        map.insert("weird key!", "value");
+        map.insert("{{ braces }}", "braced");
+        map.insert("\"quoted\"", "quotes");
+
+        let template =
+            r#"{{ "weird key!" | pad_left(7, "-") }} {{"{{ braces }}"}} {{ "\"quoted\"" }}"#;
+        let actual = map.render(template).expect("rendering should succeed");
+        assert_eq!("--value braced quotes", actual);
+
+        let compiled = Template::compile(template);
+        assert_eq!(Ok(actual), compiled.render(&map));
+
+        let actual = map
+            .render("{{ \"weird key!\" x }}")
+            .expect_err("text after a quoted key is not a filter");
+        assert_eq!(
+            "1:1 unknown variable '\"weird key!\" x'",
+            actual.to_string()
+        );
+    }
+
     fn test_parsed_blocks(input: &str, expected: Vec<Block>) {
         let actual = parse_blocks(input);
         for (idx, _block) in actual.iter().enumerate() {
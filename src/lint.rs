@@ -0,0 +1,420 @@
+//! Checks for suspicious patterns in templates.
+//!
+//! A [`Linter`] reports [`Finding`]s for mistakes that still render, or
+//! that only fail once a template is rendered with a particular map: braces
+//! that look like a mistyped placeholder block, blocks that are never
+//! closed, unknown filters and keys that break a naming convention.
+//!
+//! # Example
+//!
+//! ```rust
+//! use varj::lint::{KeyConvention, Linter, Rule};
+//!
+//! let linter = Linter::new().key_convention(KeyConvention::SnakeCase);
+//! let findings = linter.check("{{ userName | uper }}\n{ { port }}");
+//!
+//! let rules: Vec<Rule> = findings.iter().map(|finding| finding.rule()).collect();
+//! assert_eq!(vec![Rule::Filter, Rule::KeyConvention, Rule::StrayBrace], rules);
+//! assert_eq!("2:1 '{ {' looks like a split '{{'", findings[2].to_string());
+//! ```
+
+use std::fmt;
+
+use crate::{filter, parse_blocks, BlockKind};
+
+/// Checks templates for suspicious patterns.
+///
+/// All rules are enabled by default except
+/// [`key_convention`](Self::key_convention).
+#[derive(Debug, Clone)]
+pub struct Linter {
+    stray_braces: bool,
+    unclosed_blocks: bool,
+    filters: bool,
+    key_convention: Option<KeyConvention>,
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Self {
+            stray_braces: true,
+            unclosed_blocks: true,
+            filters: true,
+            key_convention: None,
+        }
+    }
+}
+
+impl Linter {
+    /// Create a `Linter` with the default rules.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report a single `{` next to or split from the `{{` of a placeholder
+    /// block, such as `{{{ key }}` or `{ { key }}`. Raw blocks, `{{{ key }}}`,
+    /// are not reported. See [`Rule::StrayBrace`].
+    pub fn stray_braces(mut self, enabled: bool) -> Self {
+        self.stray_braces = enabled;
+        self
+    }
+
+    /// Report `{{` without a matching `}}` before the next `{{` or the end
+    /// of the template. See [`Rule::UnclosedBlock`].
+    pub fn unclosed_blocks(mut self, enabled: bool) -> Self {
+        self.unclosed_blocks = enabled;
+        self
+    }
+
+    /// Report unknown filters and malformed filter chains. See
+    /// [`Rule::Filter`].
+    pub fn filters(mut self, enabled: bool) -> Self {
+        self.filters = enabled;
+        self
+    }
+
+    /// Report keys not following `convention`. See [`Rule::KeyConvention`].
+    pub fn key_convention(mut self, convention: KeyConvention) -> Self {
+        self.key_convention = Some(convention);
+        self
+    }
+
+    /// Check a template, returning its findings ordered by position.
+    pub fn check(&self, template: &str) -> Vec<Finding> {
+        let mut findings = self.check_braces(template);
+
+        let blocks = parse_blocks(template);
+        for block in blocks
+            .iter()
+            .filter(|block| block.kind == BlockKind::Variable)
+        {
+            let finding = |rule, message| Finding {
+                rule,
+                line: block.line,
+                col: block.col,
+                message,
+            };
+            if self.filters {
+                match filter::names(block.filters) {
+                    Ok(names) => {
+                        findings.extend(names.iter().filter(|name| !filter::is_known(name)).map(
+                            |name| finding(Rule::Filter, format!("unknown filter '{}'", name)),
+                        ))
+                    }
+                    Err(msg) => findings.push(finding(Rule::Filter, msg)),
+                }
+            }
+            if let Some(convention) = self.key_convention {
+                if !convention.matches(&block.variable_key) {
+                    findings.push(finding(
+                        Rule::KeyConvention,
+                        format!("key '{}' is not {}", block.variable_key, convention),
+                    ));
+                }
+            }
+        }
+
+        findings.sort_by_key(|finding| (finding.line, finding.col));
+        findings
+    }
+
+    fn check_braces(&self, template: &str) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        let chars: Vec<char> = template.chars().collect();
+        let at = |idx: usize| chars.get(idx).copied();
+        let (mut line, mut col) = (1, 0);
+        // line, column and whether the block opened with `{{{`
+        let mut open: Option<(usize, usize, bool)> = None;
+
+        let mut idx = 0;
+        while idx < chars.len() {
+            let ch = chars[idx];
+            col += 1;
+            if ch == '\n' {
+                line += 1;
+                col = 0;
+                idx += 1;
+                continue;
+            }
+            let finding = |rule, message: &str| Finding {
+                rule,
+                line,
+                col,
+                message: message.to_owned(),
+            };
+
+            match (ch, at(idx + 1)) {
+                ('{', Some('{')) if open.is_none() && escaped(&chars, idx) => {
+                    idx += 2;
+                    col += 1;
+                    continue;
+                }
+                ('{', Some('{')) => {
+                    if let Some((open_line, open_col, _)) = open {
+                        if self.unclosed_blocks {
+                            findings.push(Finding {
+                                rule: Rule::UnclosedBlock,
+                                line: open_line,
+                                col: open_col,
+                                message: "'{{' is not closed before the next '{{'".to_owned(),
+                            });
+                        }
+                    }
+                    let raw = at(idx + 2) == Some('{');
+                    open = Some((line, col, raw));
+                    let braces = 2 + usize::from(raw);
+                    idx += braces;
+                    col += braces - 1;
+                    continue;
+                }
+                ('}', Some('}')) if open.is_some() => {
+                    let (open_line, open_col, raw) = open.take().unwrap_or_default();
+                    let braces = if raw && at(idx + 2) == Some('}') {
+                        3
+                    } else {
+                        2
+                    };
+                    if raw && braces == 2 && self.stray_braces {
+                        findings.push(Finding {
+                            rule: Rule::StrayBrace,
+                            line: open_line,
+                            col: open_col,
+                            message: "'{{{' has a stray '{'".to_owned(),
+                        });
+                    }
+                    idx += braces;
+                    col += braces - 1;
+                    continue;
+                }
+                ('{', Some(' ')) if self.stray_braces && at(idx + 2) == Some('{') => {
+                    findings.push(finding(Rule::StrayBrace, "'{ {' looks like a split '{{'"));
+                }
+                _ => {}
+            }
+            idx += 1;
+        }
+
+        if let Some((line, col, _)) = open.filter(|_| self.unclosed_blocks) {
+            findings.push(Finding {
+                rule: Rule::UnclosedBlock,
+                line,
+                col,
+                message: "'{{' is never closed".to_owned(),
+            });
+        }
+        findings
+    }
+}
+
+/// Whether the `{{` at char `idx` is escaped by an odd number of
+/// backslashes.
+fn escaped(chars: &[char], idx: usize) -> bool {
+    let slashes = chars[..idx].iter().rev().take_while(|&&ch| ch == '\\');
+    slashes.count() % 2 == 1
+}
+
+/// Naming conventions for placeholder keys, see
+/// [`Linter::key_convention`].
+///
+/// Conventions allow ASCII digits after the first character and `.`
+/// between the parts of a key.
+#[derive(Clone, Copy)]
+#[non_exhaustive]
+pub enum KeyConvention {
+    /// Lowercase words joined by `_`, e.g. `user_name`.
+    SnakeCase,
+    /// Words joined with uppercase initials, starting lowercase, e.g.
+    /// `userName`.
+    CamelCase,
+    /// Lowercase words joined by `-`, e.g. `user-name`.
+    KebabCase,
+    /// Uppercase words joined by `_`, e.g. `USER_NAME`.
+    ScreamingSnakeCase,
+    /// Keys accepted by the given function, described by the name.
+    Custom(&'static str, fn(&str) -> bool),
+}
+
+impl KeyConvention {
+    fn matches(&self, key: &str) -> bool {
+        let parts = |valid: fn(&str) -> bool| key.split('.').all(valid);
+        match self {
+            KeyConvention::SnakeCase => parts(|part| words(part, '_', char::is_ascii_lowercase)),
+            KeyConvention::KebabCase => parts(|part| words(part, '-', char::is_ascii_lowercase)),
+            KeyConvention::ScreamingSnakeCase => {
+                parts(|part| words(part, '_', char::is_ascii_uppercase))
+            }
+            KeyConvention::CamelCase => parts(|part| {
+                part.starts_with(|ch: char| ch.is_ascii_lowercase())
+                    && part.chars().all(|ch| ch.is_ascii_alphanumeric())
+            }),
+            KeyConvention::Custom(_, matches) => matches(key),
+        }
+    }
+}
+
+/// Whether `part` is words of `letter`s and digits joined by single `sep`s.
+fn words(part: &str, sep: char, letter: fn(&char) -> bool) -> bool {
+    part.starts_with(|ch: char| letter(&ch))
+        && !part.ends_with(sep)
+        && !part.contains(&format!("{}{}", sep, sep))
+        && part
+            .chars()
+            .all(|ch| ch == sep || ch.is_ascii_digit() || letter(&ch))
+}
+
+impl fmt::Display for KeyConvention {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            KeyConvention::SnakeCase => "snake_case",
+            KeyConvention::CamelCase => "camelCase",
+            KeyConvention::KebabCase => "kebab-case",
+            KeyConvention::ScreamingSnakeCase => "SCREAMING_SNAKE_CASE",
+            KeyConvention::Custom(name, _) => name,
+        })
+    }
+}
+
+impl fmt::Debug for KeyConvention {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyConvention::Custom(name, _) => write!(f, "Custom({:?}, ..)", name),
+            convention => write!(f, "{}", convention),
+        }
+    }
+}
+
+/// The rule that produced a [`Finding`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Rule {
+    /// A single `{` next to or split from the `{{` of a placeholder block.
+    StrayBrace,
+    /// A `{{` without a matching `}}`.
+    UnclosedBlock,
+    /// An unknown filter or malformed filter chain.
+    Filter,
+    /// A key not following the [`KeyConvention`].
+    KeyConvention,
+}
+
+/// A suspicious pattern found by a [`Linter`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    rule: Rule,
+    line: usize,
+    col: usize,
+    message: String,
+}
+
+impl Finding {
+    /// The rule that produced the finding.
+    pub fn rule(&self) -> Rule {
+        self.rule
+    }
+
+    /// The line of the pattern, starting at 1.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// The column of the pattern, starting at 1.
+    pub fn col(&self) -> usize {
+        self.col
+    }
+
+    /// A description of the pattern.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{} {}", self.line, self.col, self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(findings: &[Finding]) -> Vec<(Rule, usize, usize)> {
+        findings
+            .iter()
+            .map(|finding| (finding.rule, finding.line, finding.col))
+            .collect()
+    }
+
+    #[test]
+    fn clean_template() {
+        let linter = Linter::new().key_convention(KeyConvention::SnakeCase);
+        let template = "{\"user\": {{ user.first_name | trim }}, {\"n\": {{ n2 }}}}";
+        assert_eq!(Vec::<Finding>::new(), linter.check(template));
+    }
+
+    #[test]
+    fn stray_and_unclosed_braces() {
+        assert!(Linter::new().check(r"\{{ a \\{{ b }}").is_empty());
+
+        assert!(Linter::new().check("a {{{ x }}}").is_empty());
+        let findings = Linter::new().check("a {{{ x }}\n{{ y } }\n{{ z");
+        assert_eq!(
+            vec![
+                (Rule::StrayBrace, 1, 3),
+                (Rule::UnclosedBlock, 2, 1),
+                (Rule::UnclosedBlock, 3, 1),
+            ],
+            positions(&findings)
+        );
+        assert_eq!("'{{' is never closed", findings[2].message());
+
+        let findings = Linter::new()
+            .stray_braces(false)
+            .unclosed_blocks(false)
+            .check("a {{{ x }}\n{{ y } }\n{{ z");
+        assert!(findings.is_empty());
+    }
+
+    #[test]
+    fn unknown_filters() {
+        let findings = Linter::new().check("{{ a | trim | nope }} {{ b | replace(x }}");
+        assert_eq!(
+            vec![
+                "1:1 unknown filter 'nope'",
+                "1:23 unclosed arguments of filter 'replace'"
+            ],
+            findings.iter().map(Finding::to_string).collect::<Vec<_>>()
+        );
+        assert!(Linter::new()
+            .filters(false)
+            .check("{{ a | nope }}")
+            .is_empty());
+    }
+
+    #[test]
+    fn key_conventions() {
+        let cases = [
+            (KeyConvention::SnakeCase, "user_name2", "user__name"),
+            (KeyConvention::CamelCase, "userName2", "UserName"),
+            (KeyConvention::KebabCase, "user-name", "user_name"),
+            (KeyConvention::ScreamingSnakeCase, "USER_NAME", "USER_"),
+            (
+                KeyConvention::Custom("short", |key| key.len() < 4),
+                "abc",
+                "abcd",
+            ),
+        ];
+        for (convention, valid, invalid) in cases {
+            assert!(convention.matches(valid), "{} {}", convention, valid);
+            assert!(!convention.matches(invalid), "{} {}", convention, invalid);
+        }
+
+        let findings = Linter::new()
+            .key_convention(KeyConvention::KebabCase)
+            .check("{{ a.b-c }} {{ a.b_c }}");
+        assert_eq!(
+            vec!["1:13 key 'a.b_c' is not kebab-case"],
+            findings.iter().map(Finding::to_string).collect::<Vec<_>>()
+        );
+    }
+}
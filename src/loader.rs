@@ -0,0 +1,315 @@
+use std::error::Error;
+#[cfg(feature = "async")]
+use std::future::Future;
+use std::path::{Component, Path, PathBuf};
+
+use crate::Encoding;
+
+/// Fetches template sources by name.
+///
+/// Use with [`TemplateSet::load`](crate::TemplateSet::load) to lazily read
+/// templates from disk with a [`FileLoader`], from an HTTP server with an
+/// `HttpLoader`, or from any other store. Loaded templates are cached in the
+/// set, so each is fetched once.
+///
+/// Closures taking a name and returning its source are loaders too.
+///
+/// # Example
+///
+/// ```rust
+/// # use std::error::Error;
+/// #
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// use varj::{TemplateSet, VarjMap};
+///
+/// let loader = |name: &str| -> Result<String, Box<dyn Error + Send + Sync>> {
+///     match name {
+///         "greeting" => Ok("Hello {{ name }}!".to_owned()),
+///         _ => Err("not found".into()),
+///     }
+/// };
+///
+/// let mut templates = TemplateSet::new();
+/// let mut map = VarjMap::new();
+/// map.insert("name", "Christopher");
+///
+/// let template = templates.load("greeting", &loader)?;
+/// assert_eq!("Hello Christopher!", template.render(&map)?);
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+pub trait TemplateLoader {
+    /// Fetch the source of the template called `name`.
+    fn load(&self, name: &str) -> Result<String, Box<dyn Error + Send + Sync>>;
+}
+
+impl<F> TemplateLoader for F
+where
+    F: Fn(&str) -> Result<String, Box<dyn Error + Send + Sync>>,
+{
+    fn load(&self, name: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+        self(name)
+    }
+}
+
+/// Loads templates from files in a directory, named by their path relative
+/// to it.
+///
+/// Names are paths like `emails/welcome.txt`. Names that are absolute or
+/// contain `..` are rejected, so a loader only reads files below its root.
+/// Files are read as UTF-8 without a byte order mark, unless another
+/// [`Encoding`] is set.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # use std::error::Error;
+/// #
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// let loader = varj::FileLoader::new("templates");
+/// let mut templates = varj::TemplateSet::new();
+///
+/// // reads templates/emails/welcome.txt
+/// let template = templates.load("emails/welcome.txt", &loader)?;
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileLoader {
+    root: PathBuf,
+    encoding: Encoding,
+}
+
+impl FileLoader {
+    /// Create a `FileLoader` reading files below `root`.
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self {
+            root: root.into(),
+            encoding: Encoding::default(),
+        }
+    }
+
+    /// Read files in the given [`Encoding`].
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// The directory templates are read from.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+}
+
+impl TemplateLoader for FileLoader {
+    fn load(&self, name: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+        check_relative(name)?;
+        self.encoding.decode(std::fs::read(self.root.join(name))?)
+    }
+}
+
+/// Loads templates from an HTTP server, named by their path relative to a
+/// base URL.
+///
+/// Names are checked like those of a [`FileLoader`]. Responses other than
+/// `200 OK` are errors.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # use std::error::Error;
+/// #
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// let loader = varj::HttpLoader::new("https://example.com/templates");
+/// let mut templates = varj::TemplateSet::new();
+///
+/// // fetches https://example.com/templates/emails/welcome.txt
+/// let template = templates.load("emails/welcome.txt", &loader)?;
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+#[cfg(feature = "http")]
+#[cfg_attr(docsrs, doc(cfg(feature = "http")))]
+#[derive(Debug, Clone)]
+pub struct HttpLoader {
+    base: String,
+    agent: ureq::Agent,
+}
+
+#[cfg(feature = "http")]
+impl HttpLoader {
+    /// Create an `HttpLoader` fetching templates below `base`.
+    pub fn new<U: Into<String>>(base: U) -> Self {
+        Self::with_agent(base, ureq::Agent::new())
+    }
+
+    /// Create an `HttpLoader` fetching templates below `base` with a
+    /// configured [`ureq::Agent`], e.g. for timeouts or proxies.
+    pub fn with_agent<U: Into<String>>(base: U, agent: ureq::Agent) -> Self {
+        let mut base = base.into();
+        if base.ends_with('/') {
+            base.pop();
+        }
+        Self { base, agent }
+    }
+
+    /// The URL templates are fetched below.
+    pub fn base(&self) -> &str {
+        &self.base
+    }
+}
+
+#[cfg(feature = "http")]
+impl TemplateLoader for HttpLoader {
+    fn load(&self, name: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+        check_relative(name)?;
+        let url = format!("{}/{}", self.base, name.trim_start_matches('/'));
+        let response = self.agent.get(&url).call().map_err(Box::new)?;
+        if response.status() != 200 {
+            return Err(format!("{}: status {}", url, response.status()).into());
+        }
+        Ok(response.into_string()?)
+    }
+}
+
+/// Reject template names that are absolute or leave their root.
+fn check_relative(name: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let escapes = Path::new(name).components().any(|component| {
+        matches!(
+            component,
+            Component::ParentDir | Component::RootDir | Component::Prefix(_)
+        )
+    });
+    if escapes || name.starts_with(['/', '\\']) {
+        return Err(format!("template name '{}' is not a relative path", name).into());
+    }
+    Ok(())
+}
+
+/// Fetches template sources by name asynchronously.
+///
+/// Use with [`TemplateSet::load_async`](crate::TemplateSet::load_async) to
+/// lazily fetch templates from object storage, HTTP or other remote stores.
+/// Loaded templates are cached in the set, so each is fetched once.
+///
+/// # Example
+///
+/// ```rust
+/// use varj::{AsyncTemplateLoader, TemplateSet, VarjMap};
+///
+/// struct Remote;
+///
+/// impl AsyncTemplateLoader for Remote {
+///     async fn load(
+///         &self,
+///         name: &str,
+///     ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+///         // e.g. fetch `templates/{name}` from object storage
+///         match name {
+///             "greeting" => Ok("Hello {{ name }}!".to_owned()),
+///             _ => Err("not found".into()),
+///         }
+///     }
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), varj::Error> {
+/// let mut templates = TemplateSet::new();
+/// let mut map = VarjMap::new();
+/// map.insert("name", "Christopher");
+///
+/// let template = templates.load_async("greeting", &Remote).await?;
+/// assert_eq!("Hello Christopher!", template.render(&map)?);
+///
+/// let err = templates.load_async("farewell", &Remote).await.unwrap_err();
+/// assert_eq!("failed to load template 'farewell': not found", err.to_string());
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+#[cfg(feature = "async")]
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+pub trait AsyncTemplateLoader {
+    /// Fetch the source of the template called `name`.
+    fn load(
+        &self,
+        name: &str,
+    ) -> impl Future<Output = Result<String, Box<dyn Error + Send + Sync>>> + Send;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_files() {
+        let root = std::env::temp_dir().join(format!("varj-loader-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("emails")).expect("creating dirs should succeed");
+        std::fs::write(root.join("emails/welcome.txt"), "\u{FEFF}Hello {{ name }}")
+            .expect("writing should succeed");
+
+        let loader = FileLoader::new(&root);
+        let actual = loader.load("emails/welcome.txt");
+        std::fs::remove_dir_all(&root).expect("removing dirs should succeed");
+
+        assert_eq!("Hello {{ name }}", actual.expect("loading should succeed"));
+        assert!(loader.load("missing.txt").is_err());
+        for name in ["../secret", "emails/../../secret", "/etc/passwd"] {
+            let actual = loader.load(name).expect_err("loading should error");
+            assert_eq!(
+                format!("template name '{}' is not a relative path", name),
+                actual.to_string()
+            );
+        }
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn load_over_http() {
+        use std::io::{BufRead, BufReader, Write};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").expect("binding should succeed");
+        let addr = listener.local_addr().expect("address should be known");
+        let server = std::thread::spawn(move || {
+            let mut paths = Vec::new();
+            for _ in 0..2 {
+                let (stream, _) = listener.accept().expect("accepting should succeed");
+                let mut reader = BufReader::new(stream);
+                let mut request = String::new();
+                reader
+                    .read_line(&mut request)
+                    .expect("reading should succeed");
+                let mut line = String::new();
+                while reader.read_line(&mut line).expect("reading should succeed") > 2 {
+                    line.clear();
+                }
+                let path = request.split(' ').nth(1).unwrap_or_default().to_owned();
+                let response = match path.as_str() {
+                    "/templates/greeting" => {
+                        "HTTP/1.1 200 OK\r\nContent-Length: 16\r\n\r\nHello {{ name }}"
+                    }
+                    _ => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n",
+                };
+                let mut stream = reader.into_inner();
+                stream
+                    .write_all(response.as_bytes())
+                    .expect("writing should succeed");
+                paths.push(path);
+            }
+            paths
+        });
+
+        let loader = HttpLoader::new(format!("http://{}/templates/", addr));
+        let actual = loader.load("greeting").expect("loading should succeed");
+        assert_eq!("Hello {{ name }}", actual);
+        assert!(loader.load("missing").is_err());
+        assert!(loader.load("../greeting").is_err());
+
+        let paths = server.join().expect("server should not panic");
+        assert_eq!(vec!["/templates/greeting", "/templates/missing"], paths);
+    }
+}
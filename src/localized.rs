@@ -0,0 +1,361 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crate::{Error, Locale, RenderOptions, VariableSource, VarjMap};
+
+/// Variable sources for several locales, looked up along a chain of
+/// fallback locales.
+///
+/// The source for a locale looks a key up in the locale itself, then in its
+/// language without a region, then in each fallback locale set with
+/// [`with_fallbacks`](Self::with_fallbacks). For `de-AT` with the fallback
+/// `en`, that is `de-AT`, `de` and `en`. Locales without a source are
+/// skipped.
+///
+/// # Example
+///
+/// ```rust
+/// # use std::error::Error;
+/// #
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// use varj::{Localized, VarjMap};
+///
+/// let mut en = VarjMap::new();
+/// en.insert("greeting", "Hello");
+/// en.insert("cart", "Cart");
+///
+/// let mut de = VarjMap::new();
+/// de.insert("greeting", "Hallo");
+///
+/// let mut de_at = VarjMap::new();
+/// de_at.insert("greeting", "Servus");
+///
+/// let mut translations = Localized::new().with_fallbacks(["en".parse()?]);
+/// translations.insert("en".parse()?, en);
+/// translations.insert("de".parse()?, de);
+/// translations.insert("de-AT".parse()?, de_at);
+///
+/// let template = "{{ greeting }}! {{ cart }}";
+/// let source = translations.source(&"de-AT".parse()?);
+/// assert_eq!("Servus! Cart", varj::render(template, &source)?);
+///
+/// let source = translations.source(&"de-DE".parse()?);
+/// assert_eq!("Hallo! Cart", varj::render(template, &source)?);
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "i18n")))]
+#[derive(Debug, Clone)]
+pub struct Localized<S> {
+    sources: HashMap<Locale, S>,
+    fallbacks: Vec<Locale>,
+}
+
+impl<S> Localized<S> {
+    /// Create an empty `Localized` without fallback locales.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Look keys missing from a locale up in `fallbacks`, in order.
+    pub fn with_fallbacks<I>(mut self, fallbacks: I) -> Self
+    where
+        I: IntoIterator<Item = Locale>,
+    {
+        self.fallbacks = fallbacks.into_iter().collect();
+        self
+    }
+
+    /// The fallback locales, in order.
+    pub fn fallbacks(&self) -> &[Locale] {
+        &self.fallbacks
+    }
+
+    /// Set the source of a locale, returning the previous source if any.
+    pub fn insert(&mut self, locale: Locale, source: S) -> Option<S> {
+        self.sources.insert(locale, source)
+    }
+
+    /// Get the source of exactly `locale`, without fallbacks.
+    pub fn get(&self, locale: &Locale) -> Option<&S> {
+        self.sources.get(locale)
+    }
+
+    /// Get a mutable reference to the source of exactly `locale`.
+    pub fn get_mut(&mut self, locale: &Locale) -> Option<&mut S> {
+        self.sources.get_mut(locale)
+    }
+
+    /// Remove the source of a locale, returning it if it was set.
+    pub fn remove(&mut self, locale: &Locale) -> Option<S> {
+        self.sources.remove(locale)
+    }
+
+    /// The locales looked up for `locale`, in order, including those
+    /// without a source.
+    pub fn chain(&self, locale: &Locale) -> Vec<Locale> {
+        let mut chain: Vec<Locale> = Vec::with_capacity(2 + 2 * self.fallbacks.len());
+        for locale in std::iter::once(locale).chain(&self.fallbacks) {
+            for locale in [Some(locale.clone()), locale.parent()]
+                .into_iter()
+                .flatten()
+            {
+                if !chain.contains(&locale) {
+                    chain.push(locale);
+                }
+            }
+        }
+        chain
+    }
+
+    /// A [`VariableSource`] looking keys up along the chain of `locale`.
+    pub fn source(&self, locale: &Locale) -> LocalizedSource<'_, S> {
+        let sources = self
+            .chain(locale)
+            .iter()
+            .filter_map(|locale| self.sources.get(locale))
+            .collect();
+        LocalizedSource { sources }
+    }
+}
+
+impl<S> Default for Localized<S> {
+    fn default() -> Self {
+        Self {
+            sources: HashMap::new(),
+            fallbacks: Vec::new(),
+        }
+    }
+}
+
+/// The sources of a locale's fallback chain, see [`Localized::source`].
+#[cfg_attr(docsrs, doc(cfg(feature = "i18n")))]
+#[derive(Debug)]
+pub struct LocalizedSource<'a, S> {
+    sources: Vec<&'a S>,
+}
+
+impl<S: VariableSource> VariableSource for LocalizedSource<'_, S> {
+    fn lookup(&self, key: &str) -> Option<Cow<'_, str>> {
+        self.sources.iter().find_map(|source| source.lookup(key))
+    }
+
+    fn list(&self, key: &str) -> Vec<Cow<'_, str>> {
+        self.sources
+            .iter()
+            .map(|source| source.list(key))
+            .find(|list| !list.is_empty())
+            .unwrap_or_default()
+    }
+}
+
+/// Variables for several locales over shared defaults.
+///
+/// Rendering for a locale looks each key up along the locale's fallback
+/// chain, as in [`Localized`], then in the defaults, and sets the locale for
+/// locale-aware filters such as `number` and `date`.
+///
+/// # Example
+///
+/// ```rust
+/// # use std::error::Error;
+/// #
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// use varj::{LocaleSet, VarjMap};
+///
+/// let mut defaults = VarjMap::new();
+/// defaults.insert("total", "1234.5");
+/// defaults.insert("label", "Total");
+///
+/// let mut de = VarjMap::new();
+/// de.insert("label", "Summe");
+///
+/// let mut set = LocaleSet::new(defaults);
+/// set.insert("de".parse()?, de);
+///
+/// let template = "{{ label }}: {{ total | number(2) }}";
+/// assert_eq!("Summe: 1.234,50", set.render(&"de-DE".parse()?, template)?);
+/// assert_eq!("Total: 1,234.50", set.render(&"en-US".parse()?, template)?);
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "i18n")))]
+#[derive(Debug, Clone, Default)]
+pub struct LocaleSet {
+    defaults: VarjMap,
+    locales: Localized<VarjMap>,
+}
+
+impl LocaleSet {
+    /// Create a `LocaleSet` without locales over `defaults`.
+    pub fn new(defaults: VarjMap) -> Self {
+        Self {
+            defaults,
+            locales: Localized::new(),
+        }
+    }
+
+    /// Look keys missing from a locale up in `fallbacks`, in order, before
+    /// the defaults. See [`Localized::with_fallbacks`].
+    pub fn with_fallbacks<I>(mut self, fallbacks: I) -> Self
+    where
+        I: IntoIterator<Item = Locale>,
+    {
+        self.locales = self.locales.with_fallbacks(fallbacks);
+        self
+    }
+
+    /// Get a reference to the shared defaults.
+    pub fn defaults(&self) -> &VarjMap {
+        &self.defaults
+    }
+
+    /// Get a mutable reference to the shared defaults.
+    pub fn defaults_mut(&mut self) -> &mut VarjMap {
+        &mut self.defaults
+    }
+
+    /// Set the variables of a locale, returning the previous ones if any.
+    pub fn insert(&mut self, locale: Locale, map: VarjMap) -> Option<VarjMap> {
+        self.locales.insert(locale, map)
+    }
+
+    /// Get the variables of exactly `locale`.
+    pub fn get(&self, locale: &Locale) -> Option<&VarjMap> {
+        self.locales.get(locale)
+    }
+
+    /// Get a mutable reference to the variables of exactly `locale`.
+    pub fn get_mut(&mut self, locale: &Locale) -> Option<&mut VarjMap> {
+        self.locales.get_mut(locale)
+    }
+
+    /// Remove the variables of a locale, returning them if they were set.
+    pub fn remove(&mut self, locale: &Locale) -> Option<VarjMap> {
+        self.locales.remove(locale)
+    }
+
+    /// Render a template for `locale`.
+    ///
+    /// # Errors
+    ///
+    /// Will return an [`Error`] if the template contains a key that neither
+    /// the locale's chain nor the defaults provide.
+    pub fn render(&self, locale: &Locale, template: &str) -> Result<String, Error> {
+        self.render_with_options(locale, template, &RenderOptions::default())
+    }
+
+    /// Render a template for `locale` using the given [`RenderOptions`],
+    /// with their locale replaced by `locale`.
+    ///
+    /// # Errors
+    ///
+    /// See [`VarjMap::render_with_options`].
+    pub fn render_with_options(
+        &self,
+        locale: &Locale,
+        template: &str,
+        options: &RenderOptions,
+    ) -> Result<String, Error> {
+        let source = WithDefaults {
+            locale: self.locales.source(locale),
+            defaults: &self.defaults,
+        };
+        let options = options.clone().locale(locale.clone());
+        crate::render_with_options(template, &source, &options)
+    }
+}
+
+struct WithDefaults<'a> {
+    locale: LocalizedSource<'a, VarjMap>,
+    defaults: &'a VarjMap,
+}
+
+impl VariableSource for WithDefaults<'_> {
+    fn lookup(&self, key: &str) -> Option<Cow<'_, str>> {
+        self.locale
+            .lookup(key)
+            .or_else(|| self.defaults.lookup(key))
+    }
+
+    fn list(&self, key: &str) -> Vec<Cow<'_, str>> {
+        let list = self.locale.list(key);
+        if list.is_empty() {
+            return self.defaults.list(key);
+        }
+        list
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorKind;
+
+    fn locale(tag: &str) -> Locale {
+        tag.parse().expect("valid language tag")
+    }
+
+    fn map(key: &str, value: &str) -> VarjMap {
+        let mut map = VarjMap::new();
+        map.insert(key, value);
+        map
+    }
+
+    #[test]
+    fn chain_of_locale() {
+        let localized: Localized<VarjMap> =
+            Localized::new().with_fallbacks([locale("en-GB"), locale("de")]);
+        let chain = localized.chain(&locale("de-AT"));
+        assert_eq!(
+            vec![locale("de-AT"), locale("de"), locale("en-GB"), locale("en")],
+            chain
+        );
+    }
+
+    #[test]
+    fn lookup_along_chain() {
+        let mut localized = Localized::new().with_fallbacks([locale("en")]);
+        localized.insert(locale("en"), map("key", "en"));
+        localized.insert(locale("fr-CA"), map("other", "fr-CA"));
+
+        let source = localized.source(&locale("fr-CA"));
+        assert_eq!(Some(Cow::Borrowed("en")), source.lookup("key"));
+        assert_eq!(Some(Cow::Borrowed("fr-CA")), source.lookup("other"));
+        assert_eq!(None, source.lookup("missing"));
+
+        let source = localized.source(&locale("fr"));
+        assert_eq!(None, source.lookup("other"));
+    }
+
+    #[test]
+    fn render_over_defaults() {
+        let mut set = LocaleSet::new(map("key", "default")).with_fallbacks([locale("en")]);
+        set.insert(locale("en"), map("other", "en"));
+        set.insert(locale("fr-CA"), map("key", "fr-CA"));
+        set.defaults_mut().insert("other", "default");
+
+        let template = "{{ key }} {{ other }}";
+        let actual = set
+            .render(&locale("fr-CA"), template)
+            .expect("rendering should succeed");
+        assert_eq!("fr-CA en", actual);
+        let actual = set
+            .render(&locale("fr"), template)
+            .expect("rendering should succeed");
+        assert_eq!("default en", actual);
+
+        set.remove(&locale("en"));
+        let actual = set
+            .render(&locale("pt"), template)
+            .expect("rendering should succeed");
+        assert_eq!("default default", actual);
+
+        let err = set
+            .render(&locale("fr-CA"), "{{ missing }}")
+            .expect_err("key is not set");
+        assert_eq!(ErrorKind::UnknownVariable, err.kind());
+    }
+}
@@ -0,0 +1,960 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::Arc;
+#[cfg(feature = "async")]
+use std::task::Context;
+
+#[cfg(feature = "async")]
+use crate::cancel::Registration;
+#[cfg(feature = "i18n")]
+use crate::Locale;
+use crate::{escape, CancelToken, Encoding, PreProcess, Unmappable};
+
+type CancelCheck = dyn Fn() -> bool + Send + Sync;
+type SubstituteHook = dyn for<'v> Fn(&str, &'v str) -> Option<Cow<'v, str>> + Send + Sync;
+
+/// Options controlling how a template is rendered.
+///
+/// Use with [`VarjMap::render_with_options`](crate::VarjMap::render_with_options).
+///
+/// # Example
+///
+/// ```rust
+/// use varj::{KeyChars, RenderOptions, VarjMap};
+///
+/// let mut map = VarjMap::new();
+/// map.insert("x", "value");
+///
+/// let options = RenderOptions::new().key_chars(KeyChars::Identifier);
+/// let err = map
+///     .render_with_options("{{ if x }}", &options)
+///     .expect_err("key contains a space");
+///
+/// assert_eq!("1:1 invalid key 'if x'", err.to_string());
+/// ```
+#[derive(Default, Clone)]
+pub struct RenderOptions {
+    pub(crate) key_chars: KeyChars,
+    pub(crate) on_substitute: Option<Arc<SubstituteHook>>,
+    pub(crate) deny_unused: bool,
+    pub(crate) cancel_when: Option<Arc<CancelCheck>>,
+    pub(crate) cancel_token: Option<CancelToken>,
+    pub(crate) threads: usize,
+    pub(crate) escape: Escape,
+    pub(crate) bidi_isolate: bool,
+    pub(crate) date_style: DateStyle,
+    #[cfg(feature = "i18n")]
+    pub(crate) locale: Locale,
+    pub(crate) pre_process: Vec<PreProcess>,
+    pub(crate) post_process: Vec<PostProcess>,
+    pub(crate) line_ending: LineEnding,
+    pub(crate) encoding: Encoding,
+    pub(crate) output_encoding: Encoding,
+    pub(crate) unmappable: Unmappable,
+    pub(crate) trim_standalone: bool,
+    pub(crate) missing_key: MissingKey,
+    pub(crate) memoize: bool,
+}
+
+impl RenderOptions {
+    /// Create `RenderOptions` with default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Restrict which characters are valid in a placeholder key.
+    ///
+    /// Keys containing other characters produce an [`ErrorKind::InvalidKey`]
+    /// error instead of being looked up.
+    ///
+    /// [`ErrorKind::InvalidKey`]: crate::ErrorKind::InvalidKey
+    pub fn key_chars(mut self, key_chars: KeyChars) -> Self {
+        self.key_chars = key_chars;
+        self
+    }
+
+    /// Fail rendering if any variable in the map is never referenced by the
+    /// template.
+    ///
+    /// Useful for keeping templates and their variables in sync. The error
+    /// has the kind [`ErrorKind::UnusedVariable`] and names the first unused
+    /// key in sorted order.
+    ///
+    /// [`ErrorKind::UnusedVariable`]: crate::ErrorKind::UnusedVariable
+    pub fn deny_unused(mut self, deny: bool) -> Self {
+        self.deny_unused = deny;
+        self
+    }
+
+    /// Set what to render for placeholders whose key has no value,
+    /// [`MissingKey::Error`] by default.
+    ///
+    /// A [default value](crate::VarjMap::set_default) is used before the
+    /// policy applies.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::error::Error;
+    /// #
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// use varj::{MissingKey, RenderOptions, VarjMap};
+    ///
+    /// let mut map = VarjMap::new();
+    /// map.insert("host", "example.com");
+    ///
+    /// let options = RenderOptions::new().missing_key(MissingKey::KeepPlaceholder);
+    /// let actual = map.render_with_options("{{ host }}:{{ port | trim }}", &options)?;
+    /// assert_eq!("example.com:{{ port | trim }}", actual);
+    ///
+    /// let options = RenderOptions::new().missing_key(MissingKey::Empty);
+    /// assert_eq!("example.com:", map.render_with_options("{{ host }}:{{ port }}", &options)?);
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn missing_key(mut self, missing_key: MissingKey) -> Self {
+        self.missing_key = missing_key;
+        self
+    }
+
+    /// Reuse the value of a placeholder for later placeholders repeating its
+    /// key, alignment and filters within a render.
+    ///
+    /// The key is then looked up, filtered and passed to
+    /// [`on_substitute`](Self::on_substitute) once per render instead of
+    /// once per placeholder, saving work for expensive sources and filters.
+    /// Section tags are still looked up each time. Disabled by default.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::error::Error;
+    /// #
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// use std::borrow::Cow;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use varj::{RenderOptions, VarjMap};
+    ///
+    /// let mut map = VarjMap::new();
+    /// map.insert("name", "Christopher");
+    ///
+    /// let calls = Arc::new(AtomicUsize::new(0));
+    /// let counter = Arc::clone(&calls);
+    /// let options = RenderOptions::new().memoize(true).on_substitute(move |_, value| {
+    ///     counter.fetch_add(1, Ordering::Relaxed);
+    ///     Some(Cow::Borrowed(value))
+    /// });
+    ///
+    /// map.render_with_options("{{ name }}, {{ name }}!", &options)?;
+    /// assert_eq!(1, calls.load(Ordering::Relaxed));
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn memoize(mut self, memoize: bool) -> Self {
+        self.memoize = memoize;
+        self
+    }
+
+    /// Register a hook called with the key and value of every placeholder,
+    /// right before the value is inserted into the output.
+    ///
+    /// The hook returns the value to insert, which may be transformed, or
+    /// `None` to veto the substitution with an [`ErrorKind::Rejected`] error.
+    /// It is called for every placeholder, unless
+    /// [`memoize`](Self::memoize) reuses its result for repeated ones.
+    ///
+    /// [`ErrorKind::Rejected`]: crate::ErrorKind::Rejected
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::error::Error;
+    /// #
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// use std::borrow::Cow;
+    /// use varj::{RenderOptions, VarjMap};
+    ///
+    /// let mut map = VarjMap::new();
+    /// map.insert("user", "admin");
+    /// map.insert("password", "hunter2");
+    ///
+    /// let options = RenderOptions::new().on_substitute(|key, value| {
+    ///     if key == "password" {
+    ///         Some(Cow::Borrowed("********"))
+    ///     } else {
+    ///         Some(Cow::Borrowed(value))
+    ///     }
+    /// });
+    ///
+    /// let actual = map.render_with_options("{{ user }}:{{ password }}", &options)?;
+    /// assert_eq!("admin:********", actual);
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn on_substitute<F>(mut self, hook: F) -> Self
+    where
+        F: for<'v> Fn(&str, &'v str) -> Option<Cow<'v, str>> + Send + Sync + 'static,
+    {
+        self.on_substitute = Some(Arc::new(hook));
+        self
+    }
+
+    /// Register a check polled between placeholder blocks, stopping the
+    /// render with an [`ErrorKind::Cancelled`] error once it returns `true`.
+    ///
+    /// [`ErrorKind::Cancelled`]: crate::ErrorKind::Cancelled
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::sync::Arc;
+    /// use varj::{ErrorKind, RenderOptions, VarjMap};
+    ///
+    /// let mut map = VarjMap::new();
+    /// map.insert("key", "value");
+    ///
+    /// let cancelled = Arc::new(AtomicBool::new(false));
+    /// let flag = Arc::clone(&cancelled);
+    /// let options = RenderOptions::new().cancel_when(move || flag.load(Ordering::Relaxed));
+    ///
+    /// // e.g. set from another thread during shutdown
+    /// cancelled.store(true, Ordering::Relaxed);
+    ///
+    /// let err = map
+    ///     .render_with_options("{{ key }}", &options)
+    ///     .expect_err("render was cancelled");
+    /// assert_eq!(ErrorKind::Cancelled, err.kind());
+    /// ```
+    pub fn cancel_when<F>(mut self, check: F) -> Self
+    where
+        F: Fn() -> bool + Send + Sync + 'static,
+    {
+        self.cancel_when = Some(Arc::new(check));
+        self
+    }
+
+    /// Stop the render with an [`ErrorKind::Cancelled`] error once `token`
+    /// is cancelled.
+    ///
+    /// Unlike [`cancel_when`](Self::cancel_when), async and streaming renders
+    /// waiting on a variable lookup are woken and stop immediately. See
+    /// [`CancelToken`].
+    ///
+    /// [`ErrorKind::Cancelled`]: crate::ErrorKind::Cancelled
+    pub fn cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel_token = Some(token);
+        self
+    }
+
+    /// Render very large templates on up to `threads` threads.
+    ///
+    /// The template is split at placeholder block boundaries into chunks of
+    /// similar size, which are rendered in parallel and joined. Errors report
+    /// the same position as a sequential render. Values `0` and `1` render
+    /// sequentially, the default.
+    ///
+    /// Only worth it for templates of many megabytes; for anything smaller
+    /// the cost of spawning threads outweighs the gain. Applies to
+    /// [`VarjMap`](crate::VarjMap) and [`Template`](crate::Template) renders.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Escape every value for the format of the output.
+    ///
+    /// Values are escaped after any [`on_substitute`](Self::on_substitute)
+    /// hook has run.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::error::Error;
+    /// #
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// use varj::{Escape, RenderOptions, VarjMap};
+    ///
+    /// let mut map = VarjMap::new();
+    /// map.insert("motd", "it's\nalive");
+    ///
+    /// let options = RenderOptions::new().escape(Escape::Toml);
+    /// let actual = map.render_with_options("motd = {{ motd }}", &options)?;
+    /// assert_eq!(r#"motd = "it's\nalive""#, actual);
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn escape(mut self, escape: Escape) -> Self {
+        self.escape = escape;
+        self
+    }
+
+    /// Wrap every value in Unicode bidi isolates, `U+2068` FIRST STRONG
+    /// ISOLATE and `U+2069` POP DIRECTIONAL ISOLATE.
+    ///
+    /// Right-to-left values, such as user names in Arabic or Hebrew, then
+    /// cannot reorder the template text around them when displayed. Values
+    /// are wrapped before they are escaped.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::error::Error;
+    /// #
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// use varj::{RenderOptions, VarjMap};
+    ///
+    /// let mut map = VarjMap::new();
+    /// map.insert("user", "\u{5E9}\u{5E8}\u{5D4}");
+    ///
+    /// let options = RenderOptions::new().bidi_isolate(true);
+    /// let actual = map.render_with_options("{{ user }} liked your post", &options)?;
+    /// assert_eq!("\u{2068}\u{5E9}\u{5E8}\u{5D4}\u{2069} liked your post", actual);
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn bidi_isolate(mut self, isolate: bool) -> Self {
+        self.bidi_isolate = isolate;
+        self
+    }
+
+    /// Set the style of dates written by the `date` filter without a
+    /// format argument, [`DateStyle::Medium`] by default.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::error::Error;
+    /// #
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// use varj::{DateStyle, RenderOptions, VarjMap};
+    ///
+    /// let mut map = VarjMap::new();
+    /// map.insert("due", "2024-03-08");
+    ///
+    /// let options = RenderOptions::new().date_style(DateStyle::Long);
+    /// let actual = map.render_with_options("Due {{ due | date }}", &options)?;
+    /// assert_eq!("Due March 8, 2024", actual);
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn date_style(mut self, style: DateStyle) -> Self {
+        self.date_style = style;
+        self
+    }
+
+    /// Set the locale used by locale-aware filters such as `plural`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::error::Error;
+    /// #
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// use varj::{RenderOptions, VarjMap};
+    ///
+    /// let mut map = VarjMap::new();
+    /// map.insert("count", "3");
+    ///
+    /// let template = "{{ count | plural(one=\"# plik\", few=\"# pliki\", other=\"# plików\") }}";
+    /// let options = RenderOptions::new().locale("pl-PL".parse()?);
+    /// assert_eq!("3 pliki", map.render_with_options(template, &options)?);
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "i18n")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "i18n")))]
+    pub fn locale(mut self, locale: Locale) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    /// Transform the template source before it is parsed.
+    ///
+    /// Pre-processors run in the order they were added, and errors point at
+    /// the line of the original source. They apply to renders parsing the
+    /// template; [`Template`](crate::Template)s are parsed when compiled and
+    /// ignore them, and so do streaming renders.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use varj::{PreProcess, RenderOptions, VarjMap};
+    ///
+    /// let mut map = VarjMap::new();
+    /// map.insert("host", "example.com");
+    ///
+    /// let options = RenderOptions::new().pre_process(PreProcess::StripCommentLines("#"));
+    /// let template = "# generated, do not edit\nhost = {{ host }}\nport = {{ port }}\n";
+    ///
+    /// let err = map
+    ///     .render_with_options(template, &options)
+    ///     .expect_err("port is missing");
+    /// assert_eq!("3:8 unknown variable 'port'", err.to_string());
+    /// ```
+    pub fn pre_process(mut self, pre_process: PreProcess) -> Self {
+        self.pre_process.push(pre_process);
+        self
+    }
+
+    /// Remove the lines of placeholders rendering to nothing that are alone
+    /// on their line but for whitespace, with their line endings, instead of
+    /// leaving blank lines.
+    ///
+    /// Like post-processors, it applies to renders returning a `String`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::error::Error;
+    /// #
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// use varj::{RenderOptions, VarjMap};
+    ///
+    /// let mut map = VarjMap::new();
+    /// map.insert("extra", "");
+    /// map.insert("tls", "ssl on;");
+    ///
+    /// let options = RenderOptions::new().trim_standalone(true);
+    /// let template = "server {\n    {{ extra }}\n    {{ tls }}\n}\n";
+    /// let actual = map.render_with_options(template, &options)?;
+    /// assert_eq!("server {\n    ssl on;\n}\n", actual);
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn trim_standalone(mut self, trim: bool) -> Self {
+        self.trim_standalone = trim;
+        self
+    }
+
+    /// Transform the whole output after every placeholder is substituted.
+    ///
+    /// Post-processors run in the order they were added. They apply to
+    /// renders returning a `String`; streaming renders and `render_json`
+    /// ignore them.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::error::Error;
+    /// #
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// use varj::{PostProcess, RenderOptions, VarjMap};
+    ///
+    /// let mut map = VarjMap::new();
+    /// map.insert("extra", "");
+    ///
+    /// let options = RenderOptions::new()
+    ///     .post_process(PostProcess::TrimTrailingWhitespace)
+    ///     .post_process(PostProcess::CollapseBlankLines)
+    ///     .post_process(PostProcess::FinalNewline);
+    /// let actual = map.render_with_options("a = 1 \n\n{{ extra }}\n\nb = 2", &options)?;
+    /// assert_eq!("a = 1\n\nb = 2\n", actual);
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn post_process(mut self, post_process: PostProcess) -> Self {
+        self.post_process.push(post_process);
+        self
+    }
+
+    /// Normalize the line endings of the output, after any
+    /// [post-processors](Self::post_process) have run.
+    ///
+    /// [`LineEnding::Preserve`] by default. Line endings in values are
+    /// normalized too. Streaming renders and `render_json` ignore it.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::error::Error;
+    /// #
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// use varj::{LineEnding, RenderOptions, VarjMap};
+    ///
+    /// let mut map = VarjMap::new();
+    /// map.insert("motd", "hello\nworld");
+    ///
+    /// let options = RenderOptions::new().line_ending(LineEnding::CrLf);
+    /// let actual = map.render_with_options("[motd]\r\n{{ motd }}\n", &options)?;
+    /// assert_eq!("[motd]\r\nhello\r\nworld\r\n", actual);
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.line_ending = line_ending;
+        self
+    }
+
+    /// Set the encoding of template files read by
+    /// [`render_file_with_options`](crate::render_file_with_options),
+    /// [`Encoding::Utf8`] by default.
+    ///
+    /// A leading byte order mark is dropped in any encoding, so it cannot
+    /// end up in the output.
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Set the encoding of output written by
+    /// [`render_to_with_options`](crate::render_to_with_options) and
+    /// `render_to_async_with_options`, [`Encoding::Utf8`] by default.
+    ///
+    /// No byte order mark is written. See [`unmappable`](Self::unmappable)
+    /// for characters [`Encoding::Latin1`] cannot encode.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::error::Error;
+    /// #
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// use varj::{Encoding, RenderOptions, Unmappable};
+    ///
+    /// let options = RenderOptions::new()
+    ///     .output_encoding(Encoding::Latin1)
+    ///     .unmappable(Unmappable::Replace(b'?'));
+    ///
+    /// let mut output = Vec::new();
+    /// let vars = [("city", "Zürich"), ("country", "Україна")];
+    /// varj::render_to_with_options("{{ city }}, {{ country }}", &vars, &mut output, &options)?;
+    /// assert_eq!(b"Z\xFCrich, ???????", output.as_slice());
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn output_encoding(mut self, encoding: Encoding) -> Self {
+        self.output_encoding = encoding;
+        self
+    }
+
+    /// Set how characters the [`output_encoding`](Self::output_encoding)
+    /// cannot encode are written, [`Unmappable::Error`] by default.
+    pub fn unmappable(mut self, unmappable: Unmappable) -> Self {
+        self.unmappable = unmappable;
+        self
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancel_token
+            .as_ref()
+            .is_some_and(CancelToken::is_cancelled)
+            || self.cancel_when.as_ref().is_some_and(|check| check())
+    }
+
+    /// Check for cancellation, waking the task when the token is cancelled
+    /// for as long as `registration` is kept.
+    #[cfg(feature = "async")]
+    pub(crate) fn poll_cancelled(
+        &self,
+        cx: &mut Context<'_>,
+        registration: &mut Option<Registration>,
+    ) -> bool {
+        if let Some(token) = &self.cancel_token {
+            token.register(cx.waker(), registration);
+        }
+        self.is_cancelled()
+    }
+
+    pub(crate) fn substitute<'v>(&self, key: &str, value: &'v str) -> Option<Cow<'v, str>> {
+        match &self.on_substitute {
+            Some(hook) => hook(key, value),
+            None => Some(Cow::Borrowed(value)),
+        }
+    }
+
+    pub(crate) fn isolate<'v>(&self, value: Cow<'v, str>) -> Cow<'v, str> {
+        if self.bidi_isolate {
+            Cow::Owned(format!("\u{2068}{}\u{2069}", value))
+        } else {
+            value
+        }
+    }
+
+    /// Run the post-processors over a rendered `output`.
+    pub(crate) fn finish(&self, output: String) -> String {
+        let output = self
+            .post_process
+            .iter()
+            .fold(output, |output, post_process| post_process.apply(output));
+        self.line_ending.apply(output)
+    }
+}
+
+impl fmt::Debug for RenderOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut f = f.debug_struct("RenderOptions");
+        f.field("key_chars", &self.key_chars)
+            .field("on_substitute", &self.on_substitute.as_ref().map(|_| ".."))
+            .field("deny_unused", &self.deny_unused)
+            .field("cancel_when", &self.cancel_when.as_ref().map(|_| ".."))
+            .field("cancel_token", &self.cancel_token)
+            .field("threads", &self.threads)
+            .field("escape", &self.escape)
+            .field("bidi_isolate", &self.bidi_isolate)
+            .field("date_style", &self.date_style);
+        #[cfg(feature = "i18n")]
+        f.field("locale", &self.locale);
+        f.field("pre_process", &self.pre_process)
+            .field("post_process", &self.post_process)
+            .field("line_ending", &self.line_ending)
+            .field("encoding", &self.encoding)
+            .field("output_encoding", &self.output_encoding)
+            .field("unmappable", &self.unmappable)
+            .field("trim_standalone", &self.trim_standalone)
+            .field("missing_key", &self.missing_key)
+            .field("memoize", &self.memoize);
+        f.finish()
+    }
+}
+
+/// Characters allowed in placeholder keys.
+#[derive(Default, Clone, Copy)]
+pub enum KeyChars {
+    /// Any key is valid.
+    #[default]
+    Any,
+    /// ASCII letters, digits, `_` and `.`.
+    Identifier,
+    /// Characters accepted by the given function.
+    Custom(fn(char) -> bool),
+}
+
+impl KeyChars {
+    pub(crate) fn is_valid(&self, key: &str) -> bool {
+        match self {
+            KeyChars::Any => true,
+            KeyChars::Identifier => !key.is_empty() && key.chars().all(is_identifier_char),
+            KeyChars::Custom(valid) => !key.is_empty() && key.chars().all(valid),
+        }
+    }
+}
+
+impl fmt::Debug for KeyChars {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyChars::Any => f.write_str("Any"),
+            KeyChars::Identifier => f.write_str("Identifier"),
+            KeyChars::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+/// How values are escaped before they are inserted, see
+/// [`RenderOptions::escape`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Escape {
+    /// Values are inserted as is.
+    #[default]
+    None,
+    /// Values are inserted as TOML strings, quotes included.
+    ///
+    /// Values are written as literal strings (`'value'`) when possible, and
+    /// as basic strings (`"value"`) with escapes when they contain single
+    /// quotes or control characters such as newlines. Place the block where
+    /// a whole TOML value is expected: `key = {{ value }}`.
+    Toml,
+    /// Values are inserted as YAML scalars of type string.
+    ///
+    /// Values that a YAML parser would read as another type or as structure,
+    /// such as `true`, `1.0`, `null` or `a: b`, are double quoted. Multi-line
+    /// values become literal block scalars (`|`) indented below the
+    /// template line. Place the block where a whole YAML value is expected:
+    /// `key: {{ value }}` or `- {{ value }}`.
+    Yaml,
+    /// Values are inserted as HTML text.
+    ///
+    /// `&`, `<`, `>`, `"` and `'` are replaced by `&amp;`, `&lt;`, `&gt;`,
+    /// `&quot;` and `&#39;`, so values are safe in element content and in
+    /// quoted attribute values. Use a raw block, `{{{ value }}}`, to insert
+    /// markup as is.
+    Html,
+}
+
+impl Escape {
+    /// Whether escaping depends on the template text before the value.
+    pub(crate) fn is_positional(&self) -> bool {
+        matches!(self, Escape::Yaml)
+    }
+
+    /// Escape `value`, inserted after the `preceding` template text.
+    pub(crate) fn apply<'v>(&self, value: Cow<'v, str>, preceding: &str) -> Cow<'v, str> {
+        match self {
+            Escape::None => value,
+            Escape::Toml => Cow::Owned(escape::toml(&value)),
+            Escape::Yaml => match escape::yaml(&value, preceding) {
+                Cow::Borrowed(_) => value,
+                Cow::Owned(escaped) => Cow::Owned(escaped),
+            },
+            Escape::Html => match escape::html(&value) {
+                Cow::Borrowed(_) => value,
+                Cow::Owned(escaped) => Cow::Owned(escaped),
+            },
+        }
+    }
+}
+
+/// What to render for a placeholder whose key has no value, see
+/// [`RenderOptions::missing_key`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MissingKey {
+    /// Fail with an [`ErrorKind::UnknownVariable`](crate::ErrorKind::UnknownVariable)
+    /// error.
+    #[default]
+    Error,
+    /// Render nothing, without applying the placeholder's filters.
+    Empty,
+    /// Keep rendering, then fail with an
+    /// [`ErrorKind::UnknownVariable`](crate::ErrorKind::UnknownVariable)
+    /// error listing every unknown variable in
+    /// [`Error::unknown_variables`](crate::Error::unknown_variables), so all
+    /// of them can be fixed at once.
+    ///
+    /// Other errors still stop the render. Renders that output values as
+    /// they are looked up, such as `render_stream` and `render_to_async`,
+    /// fail at the first unknown variable.
+    Collect,
+    /// Keep the placeholder block as written, to fill it in a second pass.
+    ///
+    /// Escaped braces are still rendered as literal `{{`, which a second
+    /// pass would read as a placeholder. Use
+    /// [`Template::apply_partial`](crate::Template::apply_partial) to keep
+    /// them escaped.
+    KeepPlaceholder,
+}
+
+/// A transformation of the whole rendered output, see
+/// [`RenderOptions::post_process`].
+///
+/// Lines end with `\n` or `\r\n`, and keep their line endings.
+#[derive(Clone, Copy)]
+#[non_exhaustive]
+pub enum PostProcess {
+    /// Remove whitespace at the end of each line.
+    TrimTrailingWhitespace,
+    /// Replace runs of blank or whitespace-only lines with a single empty
+    /// line.
+    CollapseBlankLines,
+    /// End non-empty output with a line ending, adding `\n` if missing.
+    FinalNewline,
+    /// Transform the output with the given function.
+    Custom(fn(String) -> String),
+}
+
+impl PostProcess {
+    pub(crate) fn apply(&self, output: String) -> String {
+        match self {
+            PostProcess::TrimTrailingWhitespace => {
+                let mut trimmed = String::with_capacity(output.len());
+                for (line, ending) in lines(&output) {
+                    trimmed.push_str(line.trim_end());
+                    trimmed.push_str(ending);
+                }
+                trimmed
+            }
+            PostProcess::CollapseBlankLines => {
+                let mut collapsed = String::with_capacity(output.len());
+                let mut blank = false;
+                for (line, ending) in lines(&output) {
+                    if line.trim().is_empty() {
+                        if !blank {
+                            collapsed.push_str(ending);
+                        }
+                        blank = true;
+                    } else {
+                        collapsed.push_str(line);
+                        collapsed.push_str(ending);
+                        blank = false;
+                    }
+                }
+                collapsed
+            }
+            PostProcess::FinalNewline => {
+                let mut output = output;
+                if !output.is_empty() && !output.ends_with('\n') {
+                    output.push('\n');
+                }
+                output
+            }
+            PostProcess::Custom(transform) => transform(output),
+        }
+    }
+}
+
+impl fmt::Debug for PostProcess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PostProcess::TrimTrailingWhitespace => f.write_str("TrimTrailingWhitespace"),
+            PostProcess::CollapseBlankLines => f.write_str("CollapseBlankLines"),
+            PostProcess::FinalNewline => f.write_str("FinalNewline"),
+            PostProcess::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+/// Split text into lines and their line endings.
+fn lines(text: &str) -> impl Iterator<Item = (&str, &str)> {
+    text.split_inclusive('\n').map(|line| {
+        let content = line
+            .strip_suffix("\r\n")
+            .or_else(|| line.strip_suffix('\n'))
+            .unwrap_or(line);
+        (content, &line[content.len()..])
+    })
+}
+
+/// Line endings of rendered output, see [`RenderOptions::line_ending`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LineEnding {
+    /// Line endings are left as rendered.
+    #[default]
+    Preserve,
+    /// Every `\r\n` becomes `\n`.
+    Lf,
+    /// Every `\n` not preceded by `\r` becomes `\r\n`.
+    CrLf,
+}
+
+impl LineEnding {
+    pub(crate) fn apply(&self, output: String) -> String {
+        let ending = match self {
+            LineEnding::Preserve => return output,
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        };
+        let mut normalized = String::with_capacity(output.len());
+        for (line, old) in lines(&output) {
+            normalized.push_str(line);
+            if !old.is_empty() {
+                normalized.push_str(ending);
+            }
+        }
+        normalized
+    }
+}
+
+/// Standard date formats of the `date` filter, see
+/// [`RenderOptions::date_style`].
+///
+/// The exact pattern of each style depends on the locale, the examples are
+/// US English.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DateStyle {
+    /// Numeric, e.g. `3/8/24`.
+    Short,
+    /// Abbreviated month name, e.g. `Mar 8, 2024`.
+    #[default]
+    Medium,
+    /// Full month name, e.g. `March 8, 2024`.
+    Long,
+    /// Weekday and full month name, e.g. `Friday, March 8, 2024`.
+    Full,
+}
+
+impl DateStyle {
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "short" => Some(DateStyle::Short),
+            "medium" => Some(DateStyle::Medium),
+            "long" => Some(DateStyle::Long),
+            "full" => Some(DateStyle::Full),
+            _ => None,
+        }
+    }
+}
+
+fn is_identifier_char(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || ch == '_' || ch == '.'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn any_key_chars_accept_everything() {
+        assert!(KeyChars::Any.is_valid("if x"));
+        assert!(KeyChars::Any.is_valid(""));
+    }
+
+    #[test]
+    fn identifier_key_chars() {
+        assert!(KeyChars::Identifier.is_valid("user.first_name2"));
+        assert!(!KeyChars::Identifier.is_valid("if x"));
+        assert!(!KeyChars::Identifier.is_valid("naïve"));
+        assert!(!KeyChars::Identifier.is_valid(""));
+    }
+
+    #[test]
+    fn substitute_without_hook() {
+        let options = RenderOptions::new();
+        assert_eq!(
+            Some(Cow::Borrowed("value")),
+            options.substitute("key", "value")
+        );
+    }
+
+    #[test]
+    fn substitute_with_hook() {
+        let options = RenderOptions::new().on_substitute(|key, value| match key {
+            "secret" => None,
+            _ => Some(Cow::Owned(value.to_uppercase())),
+        });
+        assert_eq!(
+            Some(Cow::Owned("VALUE".to_owned())),
+            options.substitute("key", "value")
+        );
+        assert_eq!(None, options.substitute("secret", "value"));
+    }
+
+    #[test]
+    fn custom_key_chars() {
+        let chars = KeyChars::Custom(|ch| ch.is_ascii_uppercase());
+        assert!(chars.is_valid("HOME"));
+        assert!(!chars.is_valid("home"));
+    }
+
+    #[test]
+    fn normalize_line_endings() {
+        let text = "a\r\nb\nc\rd\n".to_owned();
+        assert_eq!(text, LineEnding::Preserve.apply(text.clone()));
+        assert_eq!("a\nb\nc\rd\n", LineEnding::Lf.apply(text.clone()));
+        assert_eq!("a\r\nb\r\nc\rd\r\n", LineEnding::CrLf.apply(text));
+    }
+
+    #[test]
+    fn post_process_lines() {
+        let text = "a \t\r\n \r\n\n\tb\n\n\nc  ".to_owned();
+        assert_eq!(
+            "a\r\n\r\n\n\tb\n\n\nc",
+            PostProcess::TrimTrailingWhitespace.apply(text.clone())
+        );
+        assert_eq!(
+            "a \t\r\n\r\n\tb\n\nc  ",
+            PostProcess::CollapseBlankLines.apply(text.clone())
+        );
+        assert_eq!("c\n", PostProcess::FinalNewline.apply("c".to_owned()));
+        assert_eq!("c\n", PostProcess::FinalNewline.apply("c\n".to_owned()));
+        assert_eq!("", PostProcess::FinalNewline.apply(String::new()));
+        assert_eq!(
+            "A",
+            PostProcess::Custom(|text| text.to_uppercase()).apply("a".to_owned())
+        );
+    }
+}
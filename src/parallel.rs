@@ -0,0 +1,189 @@
+use std::ops::Range;
+use std::thread;
+
+use crate::{render_span, section, Block, BlockKind, Error, Lookup, RenderOptions};
+
+/// Render parsed blocks split into chunks across up to `threads` threads.
+pub(crate) fn render_blocks<'v, L>(
+    template: &str,
+    blocks: &[Block],
+    options: &RenderOptions,
+    threads: usize,
+    lookup: L,
+) -> Result<String, Error>
+where
+    L: Lookup<'v> + Copy + Send,
+{
+    section::check(blocks)?;
+    let chunks = split(template, blocks, threads);
+
+    let rendered: Vec<Result<String, Error>> = thread::scope(|scope| {
+        let handles: Vec<_> = chunks
+            .into_iter()
+            .map(|(span, blocks)| {
+                scope.spawn(move || render_span(template, span, blocks, options, lookup))
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("render thread panicked"))
+            .collect()
+    });
+
+    // report the first error in template order, or the unknown variables of
+    // all chunks
+    let mut output = String::with_capacity(template.len() + 32);
+    let mut unknown = Vec::new();
+    for chunk in rendered {
+        match chunk {
+            Ok(chunk) => output.push_str(&chunk),
+            Err(err) if !err.unknown.is_empty() => unknown.extend(err.unknown),
+            Err(err) => return Err(err),
+        }
+    }
+    if !unknown.is_empty() {
+        return Err(Error::collected(unknown));
+    }
+
+    Ok(options.finish(output))
+}
+
+/// Split a template into spans of similar length, cut at the start of a
+/// block's line, or at the block if another block ends on its line, so a
+/// standalone block's whole line is in one span. Sections are never cut.
+fn split<'t, 'b>(
+    template: &'t str,
+    blocks: &'b [Block<'t>],
+    threads: usize,
+) -> Vec<(Range<usize>, &'b [Block<'t>])> {
+    // whether each block is outside any section
+    let mut depth = 0;
+    let outside: Vec<bool> = blocks
+        .iter()
+        .map(|block| {
+            let outside = depth == 0;
+            match block.kind {
+                BlockKind::Section { .. } => depth += 1,
+                BlockKind::SectionEnd => depth -= 1,
+                _ => {}
+            }
+            outside
+        })
+        .collect();
+
+    let mut cuts = vec![0];
+    for idx in 1..threads {
+        let target = template.len() / threads * idx;
+        let mut cut = blocks.partition_point(|block| block.start < target);
+        while cut < blocks.len() && !outside[cut] {
+            cut += 1;
+        }
+        if cut > cuts[cuts.len() - 1] && cut < blocks.len() {
+            cuts.push(cut);
+        }
+    }
+
+    let cut_at = |cut: usize| {
+        let block = &blocks[cut];
+        let before = &blocks[cut - 1];
+        let line_start = template[..block.start].rfind('\n').map_or(0, |idx| idx + 1);
+        line_start.max(before.start + before.len)
+    };
+
+    let mut chunks = Vec::with_capacity(cuts.len());
+    for (idx, &cut) in cuts.iter().enumerate() {
+        let start = if idx == 0 { 0 } else { cut_at(cut) };
+        let (end, next_cut) = match cuts.get(idx + 1) {
+            Some(&next_cut) => (cut_at(next_cut), next_cut),
+            None => (template.len(), blocks.len()),
+        };
+        chunks.push((start..end, &blocks[cut..next_cut]));
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ErrorKind, RenderOptions, VarjMap};
+
+    #[test]
+    fn parallel_matches_sequential() {
+        let mut map = VarjMap::new();
+        let mut template = String::new();
+        for idx in 0..1000 {
+            map.insert(format!("testKey{}", idx), format!("testValue{}", idx));
+            template.push_str(&format!("line {}: {{{{ testKey{} }}}}\n", idx, idx));
+        }
+
+        let expected = map.render(&template).expect("rendering should succeed");
+        for threads in [2, 3, 8] {
+            let options = RenderOptions::new().threads(threads);
+            let actual = map
+                .render_with_options(&template, &options)
+                .expect("rendering should succeed");
+            assert_eq!(expected, actual);
+        }
+    }
+
+    #[test]
+    fn parallel_trims_standalone_lines() {
+        let mut map = VarjMap::new();
+        map.insert("empty", "");
+        let template = "  {{ empty }}\n".repeat(100);
+
+        for threads in [1, 4] {
+            let options = RenderOptions::new().threads(threads).trim_standalone(true);
+            let actual = map
+                .render_with_options(&template, &options)
+                .expect("rendering should succeed");
+            assert_eq!("", actual);
+        }
+    }
+
+    #[test]
+    fn parallel_keeps_sections_whole() {
+        let mut map = VarjMap::new();
+        map.insert("testKey", "testValue");
+        let mut template = "{{ testKey }}\n".repeat(100);
+        template.push_str("{{#missing}}\n");
+        template.push_str(&"{{ missing }}\n".repeat(500));
+        template.push_str("{{/missing}}\n");
+
+        let expected = map.render(&template).expect("rendering should succeed");
+        let options = RenderOptions::new().threads(4);
+        let actual = map
+            .render_with_options(&template, &options)
+            .expect("rendering should succeed");
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn parallel_reports_first_error() {
+        let mut map = VarjMap::new();
+        map.insert("testKey", "testValue");
+        let mut template = "{{ testKey }}\n".repeat(500);
+        template.push_str("{{ wrongKey }}\n");
+        template.push_str(&"{{ testKey }}\n".repeat(500));
+        template.push_str("{{ wrongKey2 }}\n");
+
+        let options = RenderOptions::new().threads(4);
+        let actual = map
+            .render_with_options(&template, &options)
+            .expect_err("rendering should error");
+
+        assert_eq!(ErrorKind::UnknownVariable, actual.kind());
+        assert_eq!("501:1 unknown variable 'wrongKey'", actual.to_string());
+    }
+
+    #[test]
+    fn parallel_without_blocks() {
+        let map = VarjMap::new();
+        let options = RenderOptions::new().threads(4);
+
+        let actual = map
+            .render_with_options("testKey: testValue;", &options)
+            .expect("rendering should succeed");
+        assert_eq!("testKey: testValue;", actual);
+    }
+}
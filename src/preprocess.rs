@@ -0,0 +1,141 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::{parse_blocks, Block, RenderOptions};
+
+/// A transformation of the template source before it is parsed, see
+/// [`RenderOptions::pre_process`].
+///
+/// Pre-processors work line by line and record which line of the original
+/// source each line of their output comes from, so errors still point at the
+/// original line. Columns are those of the processed line.
+#[derive(Clone, Copy)]
+#[non_exhaustive]
+pub enum PreProcess {
+    /// Remove lines whose first non-whitespace characters are the given
+    /// prefix, such as `#` or `//`.
+    StripCommentLines(&'static str),
+    /// Transform the source with the given function.
+    ///
+    /// It returns each line of output, including its line ending, with the
+    /// 1-based number of the original line it comes from. Lines inserted by
+    /// expanding a directive would take that directive's line.
+    Custom(fn(&str) -> Vec<(String, usize)>),
+}
+
+impl PreProcess {
+    fn apply(&self, source: &str) -> Vec<(String, usize)> {
+        match self {
+            PreProcess::StripCommentLines(prefix) => source
+                .split_inclusive('\n')
+                .zip(1..)
+                .filter(|(text, _)| !text.trim_start().starts_with(prefix))
+                .map(|(text, line)| (text.to_owned(), line))
+                .collect(),
+            PreProcess::Custom(transform) => transform(source),
+        }
+    }
+}
+
+impl fmt::Debug for PreProcess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PreProcess::StripCommentLines(prefix) => {
+                f.debug_tuple("StripCommentLines").field(prefix).finish()
+            }
+            PreProcess::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+/// A template source after pre-processing, with the original line of each of
+/// its lines.
+pub(crate) struct Processed<'t> {
+    source: Cow<'t, str>,
+    lines: Vec<usize>,
+}
+
+impl Processed<'_> {
+    pub(crate) fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Parse the processed source, with blocks on their original lines.
+    pub(crate) fn blocks(&self) -> Vec<Block<'_>> {
+        let mut blocks = parse_blocks(&self.source);
+        if !self.lines.is_empty() {
+            for block in &mut blocks {
+                if let Some(&line) = self.lines.get(block.line - 1) {
+                    block.line = line;
+                }
+            }
+        }
+        blocks
+    }
+}
+
+/// Run the pre-processors of `options` over `template`.
+pub(crate) fn apply<'t>(template: &'t str, options: &RenderOptions) -> Processed<'t> {
+    let mut processed = Processed {
+        source: Cow::Borrowed(template),
+        lines: Vec::new(),
+    };
+    for pre_process in &options.pre_process {
+        let output = pre_process.apply(&processed.source);
+        processed.lines = output
+            .iter()
+            .map(
+                |&(_, line)| match processed.lines.get(line.wrapping_sub(1)) {
+                    Some(&original) => original,
+                    None => line,
+                },
+            )
+            .collect();
+        processed.source = Cow::Owned(output.into_iter().map(|(text, _)| text).collect());
+    }
+    processed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_comment_lines() {
+        let options = RenderOptions::new().pre_process(PreProcess::StripCommentLines("#"));
+        let processed = apply("# a\r\nb {{ c }}\r\n  # d\n{{ e }}", &options);
+        assert_eq!("b {{ c }}\r\n{{ e }}", processed.source());
+
+        let blocks = processed.blocks();
+        let positions: Vec<(&str, usize, usize)> = blocks
+            .iter()
+            .map(|block| (&*block.variable_key, block.line, block.col))
+            .collect();
+        assert_eq!(vec![("c", 2, 3), ("e", 4, 1)], positions);
+    }
+
+    #[test]
+    fn chain_line_maps() {
+        fn include(source: &str) -> Vec<(String, usize)> {
+            let mut lines = Vec::new();
+            for (text, line) in source.split_inclusive('\n').zip(1..) {
+                if text.trim() == "@header" {
+                    lines.push(("# header\n".to_owned(), line));
+                    lines.push(("title = {{ title }}\n".to_owned(), line));
+                } else {
+                    lines.push((text.to_owned(), line));
+                }
+            }
+            lines
+        }
+
+        let options = RenderOptions::new()
+            .pre_process(PreProcess::Custom(include))
+            .pre_process(PreProcess::StripCommentLines("#"));
+        let processed = apply("# config\n@header\nport = {{ port }}", &options);
+        assert_eq!("title = {{ title }}\nport = {{ port }}", processed.source());
+
+        let lines: Vec<usize> = processed.blocks().iter().map(|block| block.line).collect();
+        assert_eq!(vec![2, 3], lines);
+    }
+}
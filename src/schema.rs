@@ -0,0 +1,255 @@
+use std::fmt;
+
+use crate::VarjMap;
+
+/// Declared variables and the kinds of their values, to validate a
+/// [`VarjMap`] before rendering.
+///
+/// # Example
+///
+/// ```rust
+/// use varj::{Schema, ValueKind, VarjMap};
+///
+/// let schema = Schema::new()
+///     .required("service", ValueKind::String)
+///     .required("port", ValueKind::Integer)
+///     .optional("debug", ValueKind::Boolean)
+///     .required("env", ValueKind::one_of(["dev", "prod"]));
+///
+/// let mut map = VarjMap::new();
+/// map.insert("service", "billing");
+/// map.insert("port", "80a");
+/// map.insert("env", "test");
+///
+/// let violations = schema.validate(&map).expect_err("map is invalid");
+/// let messages: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+/// assert_eq!(
+///     vec![
+///         "variable 'port' must be an integer, got '80a'",
+///         "variable 'env' must be one of 'dev', 'prod', got 'test'",
+///     ],
+///     messages
+/// );
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Schema {
+    variables: Vec<Declaration>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Declaration {
+    key: String,
+    required: bool,
+    kind: ValueKind,
+}
+
+impl Schema {
+    /// Create a `Schema` without variables.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declare a variable that must be set, with a value of `kind`.
+    pub fn required<K: Into<String>>(self, key: K, kind: ValueKind) -> Self {
+        self.declare(key.into(), true, kind)
+    }
+
+    /// Declare a variable that may be set, with a value of `kind` if it is.
+    pub fn optional<K: Into<String>>(self, key: K, kind: ValueKind) -> Self {
+        self.declare(key.into(), false, kind)
+    }
+
+    fn declare(mut self, key: String, required: bool, kind: ValueKind) -> Self {
+        self.variables.push(Declaration {
+            key,
+            required,
+            kind,
+        });
+        self
+    }
+
+    /// Check the variables of `map`.
+    ///
+    /// Keys are looked up as when rendering from the map. Variables the
+    /// schema does not declare are not checked.
+    ///
+    /// # Errors
+    ///
+    /// Will return every [`Violation`], in the order the variables were
+    /// declared.
+    pub fn validate(&self, map: &VarjMap) -> Result<(), Vec<Violation>> {
+        let violations: Vec<Violation> = self
+            .variables
+            .iter()
+            .filter_map(|var| match map.get(&var.key) {
+                None if var.required => Some(Violation::Missing {
+                    key: var.key.clone(),
+                }),
+                Some(value) if !var.kind.matches(value) => Some(Violation::Invalid {
+                    key: var.key.clone(),
+                    expected: var.kind.clone(),
+                    value: value.to_owned(),
+                }),
+                _ => None,
+            })
+            .collect();
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+/// The expected kind of a variable's value in a [`Schema`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ValueKind {
+    /// Any value.
+    String,
+    /// A whole number, such as `-42`.
+    Integer,
+    /// `true` or `false`.
+    Boolean,
+    /// One of the given values.
+    OneOf(Vec<String>),
+}
+
+impl ValueKind {
+    /// One of the given values.
+    pub fn one_of<I, S>(values: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        ValueKind::OneOf(values.into_iter().map(Into::into).collect())
+    }
+
+    fn matches(&self, value: &str) -> bool {
+        match self {
+            ValueKind::String => true,
+            ValueKind::Integer => value.parse::<i64>().is_ok(),
+            ValueKind::Boolean => value == "true" || value == "false",
+            ValueKind::OneOf(values) => values.iter().any(|allowed| allowed == value),
+        }
+    }
+}
+
+impl fmt::Display for ValueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueKind::String => f.write_str("a string"),
+            ValueKind::Integer => f.write_str("an integer"),
+            ValueKind::Boolean => f.write_str("a boolean"),
+            ValueKind::OneOf(values) => {
+                f.write_str("one of ")?;
+                for (idx, value) in values.iter().enumerate() {
+                    if idx > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "'{}'", value)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A variable that does not match its declaration in a [`Schema`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Violation {
+    /// A required variable is not set.
+    Missing {
+        /// The declared key.
+        key: String,
+    },
+    /// A variable's value is not of the declared kind.
+    Invalid {
+        /// The declared key.
+        key: String,
+        /// The declared kind.
+        expected: ValueKind,
+        /// The value that is set.
+        value: String,
+    },
+}
+
+impl Violation {
+    /// The key of the variable.
+    pub fn key(&self) -> &str {
+        match self {
+            Violation::Missing { key } | Violation::Invalid { key, .. } => key,
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::Missing { key } => write!(f, "missing required variable '{}'", key),
+            Violation::Invalid {
+                key,
+                expected,
+                value,
+            } => write!(
+                f,
+                "variable '{}' must be {}, got '{}'",
+                key, expected, value
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Violation {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_kinds() {
+        let schema = Schema::new()
+            .required("name", ValueKind::String)
+            .required("count", ValueKind::Integer)
+            .optional("debug", ValueKind::Boolean)
+            .optional("level", ValueKind::one_of(["low", "high"]));
+
+        let mut map = VarjMap::new();
+        map.insert("name", "");
+        map.insert("count", "-3");
+        assert_eq!(Ok(()), schema.validate(&map));
+
+        map.insert("debug", "yes");
+        map.insert("level", "high");
+        map.insert("undeclared", "x");
+        assert_eq!(
+            Err(vec![Violation::Invalid {
+                key: "debug".to_owned(),
+                expected: ValueKind::Boolean,
+                value: "yes".to_owned(),
+            }]),
+            schema.validate(&map)
+        );
+    }
+
+    #[test]
+    fn validate_missing() {
+        let schema = Schema::new()
+            .required("first", ValueKind::String)
+            .required("second", ValueKind::Integer);
+
+        let mut map = VarjMap::new();
+        map.set_case_insensitive(true);
+        map.insert("FIRST", "1");
+
+        let violations = schema.validate(&map).expect_err("second is missing");
+        assert_eq!(1, violations.len());
+        assert_eq!("second", violations[0].key());
+        assert_eq!(
+            "missing required variable 'second'",
+            violations[0].to_string()
+        );
+    }
+}
@@ -0,0 +1,189 @@
+use std::borrow::Cow;
+use std::ops::Range;
+use std::slice;
+
+#[cfg(feature = "async")]
+use crate::push_block;
+use crate::{filter, standalone_line, Block, BlockKind, Error, ErrorKind, Lookup, RenderOptions};
+
+/// The content of a section, between its tags.
+pub(crate) struct Content<'b, 't> {
+    /// The template text of the content.
+    pub(crate) span: Range<usize>,
+    /// The blocks within the content.
+    pub(crate) blocks: &'b [Block<'t>],
+    /// Where rendering continues after the section.
+    pub(crate) end: usize,
+}
+
+/// The lookup of a section's content, with `.` bound to the current
+/// element when the section repeats over a list.
+pub(crate) struct Scope<'a, 'v> {
+    lookup: &'a dyn Lookup<'v>,
+    element: Option<Cow<'v, str>>,
+}
+
+impl<'v> Lookup<'v> for Scope<'_, 'v> {
+    fn value(&self, key: &str) -> Option<Cow<'v, str>> {
+        match &self.element {
+            Some(element) if key == "." => Some(element.clone()),
+            _ => self.lookup.value(key),
+        }
+    }
+
+    fn list(&self, key: &str) -> Vec<Cow<'v, str>> {
+        self.lookup.list(key)
+    }
+}
+
+/// Check that every section tag of `blocks` is closed by an end tag with the
+/// same key, and every end tag closes the innermost open section.
+pub(crate) fn check(blocks: &[Block]) -> Result<(), Error> {
+    let mut open: Vec<&Block> = Vec::new();
+    for block in blocks {
+        match block.kind {
+            BlockKind::Section { .. } => open.push(block),
+            BlockKind::SectionEnd => match open.pop() {
+                Some(start) if start.variable_key == block.variable_key => {}
+                // report the innermost section left open, if any
+                start => {
+                    let block = start.unwrap_or(block);
+                    return Err(Error::new(ErrorKind::UnmatchedSection, block));
+                }
+            },
+            _ => {}
+        }
+    }
+
+    match open.first() {
+        Some(block) => Err(Error::new(ErrorKind::UnmatchedSection, block)),
+        None => Ok(()),
+    }
+}
+
+/// Whether the content of the section `block` opens is rendered, given the
+/// value of its key after filters: a section when the value is set and not
+/// empty, an inverted section when it is not.
+pub(crate) fn shown(block: &Block, value: Option<&str>) -> bool {
+    let set = value.is_some_and(|value| !value.is_empty());
+    match block.kind {
+        BlockKind::Section { inverted } => set != inverted,
+        _ => true,
+    }
+}
+
+/// Whether the content of the section `block` opens is rendered, given the
+/// value of its key before filters.
+///
+/// Only the tag's filters apply: tags render as nothing, so hooks, alignment
+/// and bidi isolation play no part.
+pub(crate) fn shown_for(
+    block: &Block,
+    value: Option<Cow<str>>,
+    options: &RenderOptions,
+) -> Result<bool, Error> {
+    let value = value
+        .map(|value| filter::apply(value, block.filters, options))
+        .transpose()
+        .map_err(|msg| Error::new(ErrorKind::Filter, block).caused_by(msg))?;
+    Ok(shown(block, value.as_deref()))
+}
+
+/// The index, among the `kinds` of the blocks after a section tag, of the
+/// tag ending that section.
+pub(crate) fn end_index(kinds: impl Iterator<Item = BlockKind>) -> usize {
+    let mut depth = 0;
+    for (idx, kind) in kinds.enumerate() {
+        match kind {
+            BlockKind::Section { .. } => depth += 1,
+            BlockKind::SectionEnd if depth == 0 => return idx,
+            BlockKind::SectionEnd => depth -= 1,
+            _ => {}
+        }
+    }
+    unreachable!("sections are checked before rendering")
+}
+
+/// Take the content of a section whose tag ends at `start` out of
+/// `blocks`, up to its end tag. The end tag's line is left out with
+/// [`RenderOptions::trim_standalone`] if only whitespace surrounds it.
+pub(crate) fn content<'b, 't>(
+    template: &str,
+    start: usize,
+    blocks: &mut slice::Iter<'b, Block<'t>>,
+    options: &RenderOptions,
+) -> Content<'b, 't> {
+    let rest = blocks.as_slice();
+    let len = end_index(rest.iter().map(|block| block.kind));
+    let tag = blocks
+        .nth(len)
+        .expect("sections are checked before rendering");
+    let (content_end, end) = match standalone_line(template, tag) {
+        Some(line) if options.trim_standalone => (line.start.max(start), line.end),
+        _ => (tag.start, tag.start + tag.len),
+    };
+    Content {
+        span: start..content_end,
+        blocks: &rest[..len],
+        end,
+    }
+}
+
+/// The scope of each rendering of the content of the section `block`
+/// opens: once per element if its key holds a list, otherwise once if
+/// [`shown`].
+///
+/// Inverted sections are never rendered for a list with elements.
+pub(crate) fn scopes<'a, 'v>(
+    block: &Block,
+    lookup: &'a dyn Lookup<'v>,
+    options: &RenderOptions,
+) -> Result<Vec<Scope<'a, 'v>>, Error> {
+    let scope = |element| Scope { lookup, element };
+    let elements = lookup.list(&block.variable_key);
+    if !elements.is_empty() {
+        return Ok(match block.kind {
+            BlockKind::Section { inverted: true } => Vec::new(),
+            _ => elements
+                .into_iter()
+                .map(|element| scope(Some(element)))
+                .collect(),
+        });
+    }
+
+    if shown_for(block, lookup.value(&block.variable_key), options)? {
+        Ok(vec![scope(None)])
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+/// Render the section tag `block` of `template` into `output`, returning
+/// where rendering continues: after the tag if the section's content is
+/// shown, otherwise after its end tag, taken from `blocks`.
+///
+/// Tags render as nothing, and lines holding only a tag are removed with
+/// [`RenderOptions::trim_standalone`].
+#[cfg(feature = "async")]
+pub(crate) fn enter<'v>(
+    output: &mut String,
+    template: &str,
+    idx: usize,
+    block: &Block,
+    value: Option<Cow<'v, str>>,
+    blocks: &mut slice::Iter<Block>,
+    options: &RenderOptions,
+) -> Result<usize, Error> {
+    let shown = shown_for(block, value, options)?;
+    let idx = push_block(output, template, idx, block, "", options);
+    if shown {
+        return Ok(idx);
+    }
+
+    let end = end_index(blocks.as_slice().iter().map(|block| block.kind));
+    let end = blocks
+        .nth(end)
+        .expect("sections are checked before rendering");
+    // pushes nothing, skipping the end tag or its standalone line
+    Ok(push_block(output, template, end.start, end, "", options))
+}
@@ -0,0 +1,771 @@
+//! Flattening [serde](https://serde.rs) values into [`VarjMap`] entries.
+//!
+//! Nested structs and maps become dotted keys, and sequences become indexed
+//! keys. Use a [`Flattener`] to change the separator, index style or key
+//! prefix.
+//!
+//! # Example
+//!
+//! ```rust
+//! # use std::error::Error;
+//! #
+//! # fn main() -> Result<(), Box<dyn Error>> {
+//! use serde::Serialize;
+//!
+//! #[derive(Serialize)]
+//! struct Server {
+//!     host: String,
+//!     ports: Vec<u16>,
+//! }
+//!
+//! let server = Server {
+//!     host: "localhost".to_owned(),
+//!     ports: vec![80, 443],
+//! };
+//!
+//! let map = varj::ser::to_map(&server)?;
+//! assert_eq!(
+//!     "localhost:80 localhost:443",
+//!     map.render("{{ host }}:{{ ports.0 }} {{ host }}:{{ ports.1 }}")?
+//! );
+//!
+//! let map = varj::ser::Flattener::new()
+//!     .prefix("server")
+//!     .separator("_")
+//!     .index_style(varj::ser::IndexStyle::Brackets)
+//!     .to_map(&server)?;
+//! assert_eq!("443", map.render("{{ server_ports[1] }}")?);
+//! #
+//! #     Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+
+use serde::ser::{self, Impossible, Serialize};
+
+use crate::VarjMap;
+
+/// Flatten `value` into a new [`VarjMap`] using the default [`Flattener`].
+///
+/// # Errors
+///
+/// See [`Flattener::to_map`].
+pub fn to_map<T: Serialize + ?Sized>(value: &T) -> Result<VarjMap, Error> {
+    Flattener::new().to_map(value)
+}
+
+impl VarjMap {
+    /// Create a `VarjMap` from any serializable value, such as a config
+    /// struct, flattened by the default [`Flattener`].
+    ///
+    /// Fields become keys, nested structs and maps dotted keys such as
+    /// `db.host`, and sequence elements indexed keys such as `ports.0`.
+    /// Numbers and booleans are inserted as text.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::error::Error;
+    /// #
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// #[derive(serde::Serialize)]
+    /// struct Config {
+    ///     name: &'static str,
+    ///     port: u16,
+    ///     db: Db,
+    /// }
+    ///
+    /// #[derive(serde::Serialize)]
+    /// struct Db {
+    ///     host: &'static str,
+    /// }
+    ///
+    /// let config = Config {
+    ///     name: "api",
+    ///     port: 8080,
+    ///     db: Db { host: "db.internal" },
+    /// };
+    ///
+    /// let map = varj::VarjMap::from_serialize(&config)?;
+    /// assert_eq!(
+    ///     "api:8080 -> db.internal",
+    ///     map.render("{{ name }}:{{ port }} -> {{ db.host }}")?
+    /// );
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// See [`Flattener::to_map`].
+    #[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+    pub fn from_serialize<T: Serialize + ?Sized>(value: &T) -> Result<Self, Error> {
+        to_map(value)
+    }
+}
+
+/// Rules for flattening serde values into [`VarjMap`] entries.
+///
+/// By default nested keys are joined with `.`, sequence elements are keyed
+/// by their index (`items.0`) and there is no prefix. `None` and unit values
+/// produce no entry, and unit enum variants become the variant's name.
+#[derive(Debug, Clone)]
+pub struct Flattener {
+    separator: String,
+    index_style: IndexStyle,
+    prefix: String,
+}
+
+/// How sequence elements are keyed by a [`Flattener`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum IndexStyle {
+    /// Index joined like any other key, e.g. `items.0`.
+    #[default]
+    Dotted,
+    /// Index in square brackets, e.g. `items[0]`.
+    Brackets,
+}
+
+impl Default for Flattener {
+    fn default() -> Self {
+        Self {
+            separator: ".".to_owned(),
+            index_style: IndexStyle::default(),
+            prefix: String::new(),
+        }
+    }
+}
+
+impl Flattener {
+    /// Create a `Flattener` with the default rules.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the separator joining nested keys. Defaults to `.`.
+    pub fn separator<S: Into<String>>(mut self, separator: S) -> Self {
+        self.separator = separator.into();
+        self
+    }
+
+    /// Set how sequence elements are keyed. Defaults to
+    /// [`IndexStyle::Dotted`].
+    pub fn index_style(mut self, style: IndexStyle) -> Self {
+        self.index_style = style;
+        self
+    }
+
+    /// Set a prefix for every key, joined with the separator.
+    ///
+    /// With a prefix, scalar values can be flattened into a single entry.
+    pub fn prefix<S: Into<String>>(mut self, prefix: S) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Flatten `value` into a new [`VarjMap`].
+    ///
+    /// # Errors
+    ///
+    /// Will return an [`Error`] if a map key is not a string, integer, `char`
+    /// or `bool`, if a scalar would have an empty key, or if `value` fails to
+    /// serialize.
+    pub fn to_map<T: Serialize + ?Sized>(&self, value: &T) -> Result<VarjMap, Error> {
+        let mut map = VarjMap::new();
+        self.extend_map(&mut map, value)?;
+        Ok(map)
+    }
+
+    /// Flatten `value` into an existing [`VarjMap`], replacing entries with
+    /// the same keys.
+    ///
+    /// # Errors
+    ///
+    /// See [`to_map`](Self::to_map). Entries flattened before an error remain
+    /// in `map`.
+    pub fn extend_map<T: Serialize + ?Sized>(
+        &self,
+        map: &mut VarjMap,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(ValueSerializer {
+            flattener: self,
+            output: map,
+            path: self.prefix.clone(),
+        })
+    }
+
+    fn join(&self, path: &str, segment: &str) -> String {
+        if path.is_empty() {
+            segment.to_owned()
+        } else {
+            format!("{}{}{}", path, self.separator, segment)
+        }
+    }
+
+    fn index(&self, path: &str, index: usize) -> String {
+        match self.index_style {
+            IndexStyle::Dotted => self.join(path, &index.to_string()),
+            IndexStyle::Brackets => format!("{}[{}]", path, index),
+        }
+    }
+}
+
+/// Error flattening a value with a [`Flattener`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    msg: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.msg)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error {
+            msg: msg.to_string(),
+        }
+    }
+}
+
+/// Serializes a value into entries under `path`.
+struct ValueSerializer<'a> {
+    flattener: &'a Flattener,
+    output: &'a mut VarjMap,
+    path: String,
+}
+
+impl<'a> ValueSerializer<'a> {
+    fn insert<V: ToString>(self, value: V) -> Result<(), Error> {
+        if self.path.is_empty() {
+            return Err(ser::Error::custom(
+                "cannot flatten a scalar value without a prefix",
+            ));
+        }
+        self.output.insert(self.path, value.to_string());
+        Ok(())
+    }
+
+    fn compound(self, segment: Option<&str>) -> Compound<'a> {
+        let path = match segment {
+            Some(segment) => self.flattener.join(&self.path, segment),
+            None => self.path,
+        };
+        Compound {
+            flattener: self.flattener,
+            output: self.output,
+            path,
+            index: 0,
+            key: None,
+        }
+    }
+}
+
+macro_rules! serialize_display {
+    ($($method:ident($ty:ty)),*) => {
+        $(
+            fn $method(self, value: $ty) -> Result<(), Error> {
+                self.insert(value)
+            }
+        )*
+    };
+}
+
+impl<'a> ser::Serializer for ValueSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Compound<'a>;
+    type SerializeTuple = Compound<'a>;
+    type SerializeTupleStruct = Compound<'a>;
+    type SerializeTupleVariant = Compound<'a>;
+    type SerializeMap = Compound<'a>;
+    type SerializeStruct = Compound<'a>;
+    type SerializeStructVariant = Compound<'a>;
+
+    serialize_display!(
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_i128(i128),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_u128(u128),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+        serialize_str(&str)
+    );
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<(), Error> {
+        let mut seq = self.compound(None);
+        for byte in value {
+            ser::SerializeSeq::serialize_element(&mut seq, byte)?;
+        }
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.insert(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(ValueSerializer {
+            flattener: self.flattener,
+            path: self.flattener.join(&self.path, variant),
+            output: self.output,
+        })
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Compound<'a>, Error> {
+        Ok(self.compound(None))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Compound<'a>, Error> {
+        Ok(self.compound(None))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>, Error> {
+        Ok(self.compound(None))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>, Error> {
+        Ok(self.compound(Some(variant)))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Compound<'a>, Error> {
+        Ok(self.compound(None))
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Compound<'a>, Error> {
+        Ok(self.compound(None))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>, Error> {
+        Ok(self.compound(Some(variant)))
+    }
+}
+
+/// Serializes the elements, entries or fields of a compound value.
+struct Compound<'a> {
+    flattener: &'a Flattener,
+    output: &'a mut VarjMap,
+    path: String,
+    index: usize,
+    key: Option<String>,
+}
+
+impl Compound<'_> {
+    fn serialize_at<T: Serialize + ?Sized>(
+        &mut self,
+        path: String,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(ValueSerializer {
+            flattener: self.flattener,
+            output: &mut *self.output,
+            path,
+        })
+    }
+
+    fn serialize_indexed<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let path = self.flattener.index(&self.path, self.index);
+        self.index += 1;
+        self.serialize_at(path, value)
+    }
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &str,
+        value: &T,
+    ) -> Result<(), Error> {
+        let path = self.flattener.join(&self.path, key);
+        self.serialize_at(path, value)
+    }
+}
+
+macro_rules! impl_indexed {
+    ($($trait:ident::$method:ident),*) => {
+        $(
+            impl ser::$trait for Compound<'_> {
+                type Ok = ();
+                type Error = Error;
+
+                fn $method<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+                    self.serialize_indexed(value)
+                }
+
+                fn end(self) -> Result<(), Error> {
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+impl_indexed!(
+    SerializeSeq::serialize_element,
+    SerializeTuple::serialize_element,
+    SerializeTupleStruct::serialize_field,
+    SerializeTupleVariant::serialize_field
+);
+
+impl ser::SerializeMap for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| ser::Error::custom("map value serialized before its key"))?;
+        self.serialize_field(&key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStruct for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        Compound::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStructVariant for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        Compound::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Serializes a map key into a key segment.
+struct KeySerializer;
+
+fn key_error() -> Error {
+    ser::Error::custom("map key must be a string, integer, char or bool")
+}
+
+macro_rules! serialize_key {
+    ($($method:ident($ty:ty)),*) => {
+        $(
+            fn $method(self, value: $ty) -> Result<String, Error> {
+                Ok(value.to_string())
+            }
+        )*
+    };
+}
+
+impl ser::Serializer for KeySerializer {
+    type Ok = String;
+    type Error = Error;
+    type SerializeSeq = Impossible<String, Error>;
+    type SerializeTuple = Impossible<String, Error>;
+    type SerializeTupleStruct = Impossible<String, Error>;
+    type SerializeTupleVariant = Impossible<String, Error>;
+    type SerializeMap = Impossible<String, Error>;
+    type SerializeStruct = Impossible<String, Error>;
+    type SerializeStructVariant = Impossible<String, Error>;
+
+    serialize_key!(
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_i128(i128),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_u128(u128),
+        serialize_char(char),
+        serialize_str(&str)
+    );
+
+    fn serialize_f32(self, _value: f32) -> Result<String, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_f64(self, _value: f64) -> Result<String, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_bytes(self, _value: &[u8]) -> Result<String, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_none(self) -> Result<String, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<String, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<String, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<String, Error> {
+        Ok(variant.to_owned())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        Err(key_error())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(key_error())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::Serialize;
+
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Config {
+        name: &'static str,
+        debug: bool,
+        retries: Option<u8>,
+        paths: Vec<&'static str>,
+        env: BTreeMap<u16, &'static str>,
+        mode: Mode,
+        level: Level,
+    }
+
+    #[derive(Serialize)]
+    enum Mode {
+        Serve { root: &'static str },
+    }
+
+    #[derive(Serialize)]
+    enum Level {
+        Info,
+    }
+
+    fn config() -> Config {
+        Config {
+            name: "testName",
+            debug: true,
+            retries: None,
+            paths: vec!["/bin", "/usr/bin"],
+            env: [(8080, "http")].into_iter().collect(),
+            mode: Mode::Serve { root: "/www" },
+            level: Level::Info,
+        }
+    }
+
+    #[test]
+    fn flatten_dotted_keys() {
+        let actual = to_map(&config()).expect("flattening should succeed");
+
+        let mut expected = VarjMap::new();
+        expected.insert("name", "testName");
+        expected.insert("debug", "true");
+        expected.insert("paths.0", "/bin");
+        expected.insert("paths.1", "/usr/bin");
+        expected.insert("env.8080", "http");
+        expected.insert("mode.Serve.root", "/www");
+        expected.insert("level", "Info");
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn flatten_with_rules() {
+        let mut actual = VarjMap::new();
+        actual.insert("config_name", "oldName");
+        actual.insert("other", "testValue");
+
+        Flattener::new()
+            .separator("_")
+            .index_style(IndexStyle::Brackets)
+            .prefix("config")
+            .extend_map(&mut actual, &config())
+            .expect("flattening should succeed");
+
+        assert_eq!(Some("testName"), actual.get("config_name"));
+        assert_eq!(Some("/usr/bin"), actual.get("config_paths[1]"));
+        assert_eq!(Some("/www"), actual.get("config_mode_Serve_root"));
+        assert_eq!(Some("testValue"), actual.get("other"));
+    }
+
+    #[test]
+    fn flatten_invalid_values() {
+        let actual = to_map("testValue").expect_err("flattening should error");
+        assert_eq!(
+            "cannot flatten a scalar value without a prefix",
+            actual.to_string()
+        );
+
+        let actual = Flattener::new().prefix("testKey").to_map("testValue");
+        assert_eq!(Some("testValue"), actual.unwrap().get("testKey"));
+
+        let map: BTreeMap<Vec<u8>, u8> = [(vec![1], 1)].into_iter().collect();
+        let actual = to_map(&map).expect_err("flattening should error");
+        assert_eq!(
+            "map key must be a string, integer, char or bool",
+            actual.to_string()
+        );
+    }
+}
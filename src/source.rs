@@ -0,0 +1,420 @@
+use std::borrow::{Borrow, Cow};
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, Hash};
+
+use crate::{
+    parallel, parse_blocks, preprocess, render_blocks, Error, Lookup, RenderOptions, VarjMap,
+};
+
+/// A source of variables to render templates from.
+///
+/// Implement this to render from data that is not stored in a
+/// [`VarjMap`], such as translation catalogs or values computed on demand.
+///
+/// # Example
+///
+/// ```rust
+/// # use std::error::Error;
+/// #
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// use std::borrow::Cow;
+/// use varj::VariableSource;
+///
+/// struct Upper;
+///
+/// impl VariableSource for Upper {
+///     fn lookup(&self, key: &str) -> Option<Cow<'_, str>> {
+///         Some(Cow::Owned(key.to_uppercase()))
+///     }
+/// }
+///
+/// assert_eq!("HELLO WORLD", varj::render("{{ hello }} {{ world }}", &Upper)?);
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+pub trait VariableSource {
+    /// Look up the value of `key`, returning `None` if it is not set.
+    fn lookup(&self, key: &str) -> Option<Cow<'_, str>>;
+
+    /// The elements of the list under `key`, which a section repeats over.
+    ///
+    /// Returns no elements by default, so sections treat `key` as a single
+    /// value.
+    fn list(&self, _key: &str) -> Vec<Cow<'_, str>> {
+        Vec::new()
+    }
+}
+
+impl VariableSource for VarjMap {
+    fn lookup(&self, key: &str) -> Option<Cow<'_, str>> {
+        self.lookup_value(key).map(Cow::Borrowed)
+    }
+
+    fn list(&self, key: &str) -> Vec<Cow<'_, str>> {
+        Lookup::list(&self, key)
+    }
+}
+
+impl<K, V, S> VariableSource for HashMap<K, V, S>
+where
+    K: Borrow<str> + Hash + Eq,
+    V: AsRef<str>,
+    S: BuildHasher,
+{
+    fn lookup(&self, key: &str) -> Option<Cow<'_, str>> {
+        self.get(key).map(|value| Cow::Borrowed(value.as_ref()))
+    }
+}
+
+impl<K, V> VariableSource for BTreeMap<K, V>
+where
+    K: Borrow<str> + Ord,
+    V: AsRef<str>,
+{
+    fn lookup(&self, key: &str) -> Option<Cow<'_, str>> {
+        self.get(key).map(|value| Cow::Borrowed(value.as_ref()))
+    }
+}
+
+/// Key value pairs, searched in order. A key given more than once has its
+/// last value.
+impl<K, V> VariableSource for [(K, V)]
+where
+    K: AsRef<str>,
+    V: AsRef<str>,
+{
+    fn lookup(&self, key: &str) -> Option<Cow<'_, str>> {
+        self.iter()
+            .rev()
+            .find(|(k, _)| k.as_ref() == key)
+            .map(|(_, value)| Cow::Borrowed(value.as_ref()))
+    }
+}
+
+impl<K, V, const N: usize> VariableSource for [(K, V); N]
+where
+    K: AsRef<str>,
+    V: AsRef<str>,
+{
+    fn lookup(&self, key: &str) -> Option<Cow<'_, str>> {
+        self.as_slice().lookup(key)
+    }
+}
+
+impl<K, V> VariableSource for Vec<(K, V)>
+where
+    K: AsRef<str>,
+    V: AsRef<str>,
+{
+    fn lookup(&self, key: &str) -> Option<Cow<'_, str>> {
+        self.as_slice().lookup(key)
+    }
+}
+
+impl<S: VariableSource + ?Sized> VariableSource for &S {
+    fn lookup(&self, key: &str) -> Option<Cow<'_, str>> {
+        (**self).lookup(key)
+    }
+
+    fn list(&self, key: &str) -> Vec<Cow<'_, str>> {
+        (**self).list(key)
+    }
+}
+
+/// A [`VariableSource`] looked up while rendering.
+struct SourceLookup<'s, S: ?Sized>(&'s S);
+
+impl<S: ?Sized> Clone for SourceLookup<'_, S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<S: ?Sized> Copy for SourceLookup<'_, S> {}
+
+impl<'s, S: VariableSource + ?Sized> Lookup<'s> for SourceLookup<'s, S> {
+    fn value(&self, key: &str) -> Option<Cow<'s, str>> {
+        self.0.lookup(key)
+    }
+
+    fn list(&self, key: &str) -> Vec<Cow<'s, str>> {
+        self.0.list(key)
+    }
+}
+
+/// Render a template, looking up each placeholder's value from a
+/// [`VariableSource`].
+///
+/// Besides [`VarjMap`], sources include std [`HashMap`]s and [`BTreeMap`]s
+/// with string keys and values, which render without converting them, and
+/// slices of key value pairs for one-off substitutions.
+///
+/// # Example
+///
+/// ```rust
+/// # use std::error::Error;
+/// #
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// use std::collections::HashMap;
+///
+/// let mut vars = HashMap::new();
+/// vars.insert("name", "Christopher".to_owned());
+///
+/// assert_eq!("name: Christopher", varj::render("name: {{ name }}", &vars)?);
+///
+/// let actual = varj::render("{{ name }} is {{ age }}", &[("name", "Chris"), ("age", "30")])?;
+/// assert_eq!("Chris is 30", actual);
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Will return an [`Error`] if the template contains a key that the source
+/// does not provide.
+pub fn render<S>(template: &str, source: &S) -> Result<String, Error>
+where
+    S: VariableSource + Sync + ?Sized,
+{
+    render_with_options(template, source, &RenderOptions::default())
+}
+
+/// Render a template from a [`VariableSource`] that cannot be shared between
+/// threads, such as a [`FluentSource`](crate::fluent::FluentSource).
+///
+/// # Errors
+///
+/// Will return an [`Error`] if the template contains a key that the source
+/// does not provide.
+pub fn render_local<S>(template: &str, source: &S) -> Result<String, Error>
+where
+    S: VariableSource + ?Sized,
+{
+    render_local_with_options(template, source, &RenderOptions::default())
+}
+
+/// Render a template with a single variable.
+///
+/// # Errors
+///
+/// Will return an [`Error`] if the template contains any other key.
+///
+/// # Example
+///
+/// ```rust
+/// # use std::error::Error;
+/// #
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// let actual = varj::render_one("listen = \"0.0.0.0:{{ port }}\"", "port", "8080")?;
+/// assert_eq!("listen = \"0.0.0.0:8080\"", actual);
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+pub fn render_one(template: &str, key: &str, value: &str) -> Result<String, Error> {
+    render(template, &[(key, value)])
+}
+
+/// Render a template, looking up each placeholder's value by calling
+/// `lookup` with its key.
+///
+/// Values are resolved lazily, for each placeholder, so they can come from
+/// a database, the environment or be computed on demand. Return `None` for
+/// unknown keys. A closure cannot provide lists, so sections treat their key
+/// as a single value; implement [`VariableSource::list`] to repeat them.
+///
+/// # Example
+///
+/// ```rust
+/// # use std::error::Error;
+/// #
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// use std::borrow::Cow;
+///
+/// let user = "Christopher";
+/// let actual = varj::render_with("{{ user }} has {{ len.user }} letters", |key| match key {
+///     "user" => Some(Cow::Borrowed(user)),
+///     "len.user" => Some(Cow::Owned(user.len().to_string())),
+///     _ => None,
+/// })?;
+/// assert_eq!("Christopher has 11 letters", actual);
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Will return an [`Error`] if `lookup` returns `None` for a key in the
+/// template.
+pub fn render_with<'v, F>(template: &str, lookup: F) -> Result<String, Error>
+where
+    F: Fn(&str) -> Option<Cow<'v, str>>,
+{
+    let options = RenderOptions::default();
+    let blocks = parse_blocks(template);
+    render_blocks(template, &blocks, &options, lookup)
+}
+
+/// Render a template from a [`VariableSource`] using the given
+/// [`RenderOptions`].
+///
+/// [`RenderOptions::deny_unused`] is ignored, as a source cannot list its
+/// variables.
+///
+/// # Errors
+///
+/// See [`VarjMap::render_with_options`].
+pub fn render_with_options<S>(
+    template: &str,
+    source: &S,
+    options: &RenderOptions,
+) -> Result<String, Error>
+where
+    S: VariableSource + Sync + ?Sized,
+{
+    let processed = preprocess::apply(template, options);
+    let template = processed.source();
+    let blocks = processed.blocks();
+    let lookup = SourceLookup(source);
+    match options.threads {
+        threads if threads > 1 => {
+            parallel::render_blocks(template, &blocks, options, threads, lookup)
+        }
+        _ => render_blocks(template, &blocks, options, lookup),
+    }
+}
+
+/// Render a template from a [`VariableSource`] that cannot be shared between
+/// threads using the given [`RenderOptions`].
+///
+/// [`RenderOptions::deny_unused`] is ignored, as a source cannot list its
+/// variables, and so is [`RenderOptions::threads`], as the source cannot be
+/// shared between threads.
+///
+/// # Errors
+///
+/// See [`VarjMap::render_with_options`].
+pub fn render_local_with_options<S>(
+    template: &str,
+    source: &S,
+    options: &RenderOptions,
+) -> Result<String, Error>
+where
+    S: VariableSource + ?Sized,
+{
+    let processed = preprocess::apply(template, options);
+    let template = processed.source();
+    let blocks = processed.blocks();
+    render_blocks(template, &blocks, options, SourceLookup(source))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorKind;
+
+    struct Doubled;
+
+    impl VariableSource for Doubled {
+        fn lookup(&self, key: &str) -> Option<Cow<'_, str>> {
+            key.strip_prefix("double.")
+                .map(|key| Cow::Owned(key.repeat(2)))
+        }
+    }
+
+    #[test]
+    fn render_from_source() {
+        let actual =
+            render("{{ double.ab }}-{{ double.c }}", &Doubled).expect("rendering should succeed");
+        assert_eq!("abab-cc", actual);
+
+        let err = render("{{ single }}", &Doubled).expect_err("key is not provided");
+        assert_eq!(ErrorKind::UnknownVariable, err.kind());
+    }
+
+    #[test]
+    fn render_from_closure() {
+        let calls = std::cell::Cell::new(0);
+        let actual = render_with("{{ a }} {{ b | trim }} {{ a }}", |key| {
+            calls.set(calls.get() + 1);
+            (key != "c").then(|| Cow::Owned(format!(" {} ", key)))
+        })
+        .expect("rendering should succeed");
+        assert_eq!(" a  b  a ", actual);
+        assert_eq!(3, calls.get());
+
+        let err = render_with("{{ c }}", |_| None).expect_err("key is not provided");
+        assert_eq!(ErrorKind::UnknownVariable, err.kind());
+    }
+
+    #[test]
+    fn render_from_std_maps() {
+        let hash_map: HashMap<String, &str> = [("key".to_owned(), "hash")].into_iter().collect();
+        let actual = render("{{ key }}", &hash_map).expect("rendering should succeed");
+        assert_eq!("hash", actual);
+
+        let btree_map: BTreeMap<&str, String> = [("key", "btree".to_owned())].into_iter().collect();
+        let actual = render("{{ key }}", &btree_map).expect("rendering should succeed");
+        assert_eq!("btree", actual);
+    }
+
+    #[test]
+    fn render_from_pairs() {
+        let actual = render("{{ a }}{{ b }}", &[("a", "1"), ("b", "2"), ("a", "3")])
+            .expect("rendering should succeed");
+        assert_eq!("32", actual);
+
+        let pairs = vec![("a".to_owned(), "1".to_owned())];
+        let actual = render("{{ a }}", pairs.as_slice()).expect("rendering should succeed");
+        assert_eq!("1", actual);
+
+        let err = render("{{ c }}", &[("a", "1")]).expect_err("key is not provided");
+        assert_eq!(ErrorKind::UnknownVariable, err.kind());
+    }
+
+    #[test]
+    fn render_single_variable() {
+        let actual = render_one("{{ a }}/{{a}}", "a", "1").expect("rendering should succeed");
+        assert_eq!("1/1", actual);
+
+        let err = render_one("{{ a }}{{ b }}", "a", "1").expect_err("b is not provided");
+        assert_eq!("b", err.key());
+    }
+
+    #[test]
+    fn render_from_local_source() {
+        struct Counted(std::cell::Cell<usize>);
+
+        impl VariableSource for Counted {
+            fn lookup(&self, _key: &str) -> Option<Cow<'_, str>> {
+                self.0.set(self.0.get() + 1);
+                Some(Cow::Owned(self.0.get().to_string()))
+            }
+        }
+
+        let options = RenderOptions::new().threads(2);
+        let actual = render_local_with_options("{{ a }} {{ a }}", &Counted(0.into()), &options)
+            .expect("rendering should succeed");
+        assert_eq!("1 2", actual);
+    }
+
+    #[test]
+    fn render_from_map_source() {
+        let mut map = VarjMap::new();
+        map.insert("key", "value");
+        map.insert_list("items", ["a", "b"]);
+        let source: &(dyn VariableSource + Sync) = &map;
+
+        let options = RenderOptions::new().threads(2);
+        let actual = render_with_options("{{ key }} {{ key }}", source, &options)
+            .expect("rendering should succeed");
+        assert_eq!("value value", actual);
+
+        let actual =
+            render("{{#items}}{{ . }};{{/items}}", source).expect("rendering should succeed");
+        assert_eq!("a;b;", actual);
+    }
+}
@@ -0,0 +1,410 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::vec;
+
+use bytes::Bytes;
+use futures_core::Stream;
+
+use crate::cancel::Registration;
+use crate::filter::Align;
+use crate::{
+    check_block, escape_block, parse_blocks, resolve_block, section, substitute_block,
+    AsyncVariableSource, Block, BlockKind, Error, RenderOptions,
+};
+
+type LookupFuture<'a> = Pin<Box<dyn Future<Output = Option<String>> + Send + 'a>>;
+
+/// Render a template as a [`Stream`] of output chunks.
+///
+/// Each chunk is either a run of literal template text or a substituted
+/// value, so large documents can be sent to a client as they are rendered.
+/// After an error the stream ends.
+///
+/// # Example
+///
+/// ```rust
+/// use std::future::poll_fn;
+/// use std::pin::Pin;
+/// use futures_core::Stream;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), varj::Error> {
+/// let mut map = varj::VarjMap::new();
+/// map.insert("name", "Christopher");
+///
+/// let mut stream = varj::render_stream("name: {{ name }}", &map);
+///
+/// let mut chunks = Vec::new();
+/// while let Some(chunk) = poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await {
+///     chunks.push(chunk?);
+/// }
+///
+/// assert_eq!(vec!["name: ", "Christopher"], chunks);
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+pub fn render_stream<'a, S>(template: &'a str, source: &'a S) -> RenderStream<'a, S>
+where
+    S: AsyncVariableSource + Sync,
+{
+    RenderStream::new(template, source, Cow::Owned(RenderOptions::default()))
+}
+
+/// Render a template as a [`Stream`] of output chunks using the given
+/// [`RenderOptions`].
+///
+/// See [`render_stream`] for details. [`RenderOptions::deny_unused`] is
+/// ignored, as a source cannot list its variables. As chunks are sent while
+/// rendering, so are [`RenderOptions::pre_process`],
+/// [`RenderOptions::post_process`], [`RenderOptions::line_ending`] and
+/// [`RenderOptions::trim_standalone`], and [`MissingKey::Collect`] ends the
+/// stream at the first unknown key, as [`MissingKey::Error`] does.
+///
+/// [`MissingKey::Collect`]: crate::MissingKey::Collect
+/// [`MissingKey::Error`]: crate::MissingKey::Error
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+pub fn render_stream_with_options<'a, S>(
+    template: &'a str,
+    source: &'a S,
+    options: &'a RenderOptions,
+) -> RenderStream<'a, S>
+where
+    S: AsyncVariableSource + Sync,
+{
+    RenderStream::new(template, source, Cow::Borrowed(options))
+}
+
+/// A [`Stream`] of rendered output chunks.
+///
+/// Created by [`render_stream`] and [`render_stream_with_options`].
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+pub struct RenderStream<'a, S> {
+    template: &'a str,
+    source: &'a S,
+    options: Cow<'a, RenderOptions>,
+    blocks: vec::IntoIter<Block<'a>>,
+    lookup: Option<(Block<'a>, LookupFuture<'a>)>,
+    /// The cancel token's waker of the in-flight lookup.
+    registration: Option<Registration>,
+    /// Resolved values with [`RenderOptions::memoize`], by key, alignment
+    /// and filters.
+    memo: HashMap<(Cow<'a, str>, Option<Align>, &'a str), String>,
+    /// A value to send after the literal text before its block.
+    queued: Option<Bytes>,
+    idx: usize,
+    done: bool,
+    /// An error found before rendering, reported as the first item.
+    error: Option<Error>,
+}
+
+impl<'a, S> RenderStream<'a, S>
+where
+    S: AsyncVariableSource + Sync,
+{
+    fn new(template: &'a str, source: &'a S, options: Cow<'a, RenderOptions>) -> Self {
+        let blocks = parse_blocks(template);
+        let error = section::check(&blocks).err();
+        Self {
+            template,
+            source,
+            options,
+            blocks: blocks.into_iter(),
+            lookup: None,
+            registration: None,
+            memo: HashMap::new(),
+            queued: None,
+            idx: 0,
+            done: false,
+            error,
+        }
+    }
+
+    fn fail(&mut self, err: Error) -> Poll<Option<Result<Bytes, Error>>> {
+        self.done = true;
+        self.registration = None;
+        Poll::Ready(Some(Err(err)))
+    }
+}
+
+impl<'a, S> Stream for RenderStream<'a, S>
+where
+    S: AsyncVariableSource + Sync,
+{
+    type Item = Result<Bytes, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        loop {
+            if this.done {
+                return Poll::Ready(None);
+            }
+            if let Some(err) = this.error.take() {
+                return this.fail(err);
+            }
+            if let Some(chunk) = this.queued.take() {
+                return Poll::Ready(Some(Ok(chunk)));
+            }
+
+            // finish an in-flight lookup
+            if let Some((block, future)) = &mut this.lookup {
+                if this.options.is_cancelled() {
+                    return this.fail(Error::cancelled());
+                }
+                let value = match future.as_mut().poll(cx) {
+                    Poll::Ready(value) => value,
+                    Poll::Pending if this.options.poll_cancelled(cx, &mut this.registration) => {
+                        return this.fail(Error::cancelled());
+                    }
+                    Poll::Pending => return Poll::Pending,
+                };
+                let block = block.clone();
+                this.lookup = None;
+                this.registration = None;
+                this.idx = block.start + block.len;
+
+                if let BlockKind::Section { .. } = block.kind {
+                    let value = value.map(Cow::Owned);
+                    match section::shown_for(&block, value, &this.options) {
+                        Ok(true) => {}
+                        Ok(false) => {
+                            let kinds = this.blocks.as_slice().iter().map(|block| block.kind);
+                            let end = section::end_index(kinds);
+                            let end = this.blocks.nth(end).expect("sections are checked");
+                            this.idx = end.start + end.len;
+                        }
+                        Err(err) => return this.fail(err),
+                    }
+                    continue;
+                }
+
+                let value = match value {
+                    Some(value) if this.options.memoize => {
+                        resolve_block(&block, Cow::Owned(value), &this.options).map(|value| {
+                            let key = (block.variable_key.clone(), block.align, block.filters);
+                            let value = this.memo.entry(key).or_insert(value.into_owned());
+                            escape_block(this.template, &block, Cow::Borrowed(value), &this.options)
+                                .into_owned()
+                        })
+                    }
+                    value => {
+                        substitute_block(this.template, &block, value.as_deref(), &this.options)
+                            .map(Cow::into_owned)
+                    }
+                };
+                return match value {
+                    Ok(value) => Poll::Ready(Some(Ok(Bytes::from(value)))),
+                    Err(err) => this.fail(err),
+                };
+            }
+
+            let block = match this.blocks.next() {
+                Some(block) => block,
+                None => {
+                    // copy remaining input
+                    this.done = true;
+                    let literal = &this.template[this.idx..];
+                    if literal.is_empty() {
+                        return Poll::Ready(None);
+                    }
+                    return Poll::Ready(Some(Ok(Bytes::copy_from_slice(literal.as_bytes()))));
+                }
+            };
+
+            if let Some(literal) = block.escaped(this.template) {
+                // copy input until block, then the escaped text
+                let mut chunk = this.template[this.idx..block.start].to_owned();
+                chunk.push_str(&literal);
+                this.idx = block.start + block.len;
+                return Poll::Ready(Some(Ok(Bytes::from(chunk))));
+            }
+
+            if let Err(err) = check_block(&block, &this.options) {
+                return this.fail(err);
+            }
+            if block.kind == BlockKind::SectionEnd {
+                // copy input until the tag, which renders as nothing
+                let literal = &this.template[this.idx..block.start];
+                this.idx = block.start + block.len;
+                if !literal.is_empty() {
+                    return Poll::Ready(Some(Ok(Bytes::copy_from_slice(literal.as_bytes()))));
+                }
+                continue;
+            }
+
+            let literal = &this.template[this.idx..block.start];
+            let key = (block.variable_key.clone(), block.align, block.filters);
+            if let (BlockKind::Variable, Some(value)) = (block.kind, this.memo.get(&key)) {
+                let value =
+                    escape_block(this.template, &block, Cow::Borrowed(value), &this.options);
+                let value = Bytes::from(value.into_owned());
+                this.idx = block.start + block.len;
+                // copy input until block, then the value
+                if literal.is_empty() {
+                    return Poll::Ready(Some(Ok(value)));
+                }
+                this.queued = Some(value);
+                return Poll::Ready(Some(Ok(Bytes::copy_from_slice(literal.as_bytes()))));
+            }
+
+            let future: LookupFuture<'a> = match &block.variable_key {
+                Cow::Borrowed(key) => Box::pin(this.source.lookup(key)),
+                Cow::Owned(key) => {
+                    let (source, key) = (this.source, key.clone());
+                    Box::pin(async move { source.lookup(&key).await })
+                }
+            };
+            this.lookup = Some((block, future));
+
+            // copy input until block
+            if !literal.is_empty() {
+                return Poll::Ready(Some(Ok(Bytes::copy_from_slice(literal.as_bytes()))));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::poll_fn;
+
+    use super::*;
+    use crate::{ErrorKind, VarjMap};
+
+    #[tokio::test]
+    async fn stream_chunks() {
+        let mut map = VarjMap::new();
+        map.insert("testKey", "testValue");
+        map.insert("testKey2", "testValue2");
+
+        let stream = render_stream("{{ testKey }}: {{testKey2}}{{testKey}};", &map);
+        let actual = collect(stream).await.expect("rendering should succeed");
+
+        assert_eq!(
+            vec!["testValue", ": ", "testValue2", "testValue", ";"],
+            actual
+        );
+    }
+
+    #[tokio::test]
+    async fn stream_memoized() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct Counting(AtomicUsize);
+
+        impl AsyncVariableSource for Counting {
+            async fn lookup(&self, key: &str) -> Option<String> {
+                self.0.fetch_add(1, Ordering::Relaxed);
+                Some(key.to_owned())
+            }
+        }
+
+        let template = "{{ a }}{{ a }}, {{ a | hex }}{{#a}} {{ a }}{{/a}}";
+        for (memoize, expected) in [(false, 5), (true, 3)] {
+            let source = Counting(AtomicUsize::new(0));
+            let options = RenderOptions::new().memoize(memoize);
+            let stream = render_stream_with_options(template, &source, &options);
+            let actual = collect(stream).await.expect("rendering should succeed");
+
+            assert_eq!(b"aa, 61 a", actual.concat().as_slice());
+            assert_eq!(expected, source.0.load(Ordering::Relaxed));
+        }
+    }
+
+    #[tokio::test]
+    async fn stream_cancelled_during_lookup() {
+        struct Never;
+
+        impl AsyncVariableSource for Never {
+            async fn lookup(&self, _key: &str) -> Option<String> {
+                std::future::pending().await
+            }
+        }
+
+        let token = crate::CancelToken::new();
+        let options = RenderOptions::new().cancel_token(token.clone());
+        let stream = render_stream_with_options("a {{ testKey }} b", &Never, &options);
+        let cancel = async {
+            tokio::task::yield_now().await;
+            token.cancel();
+        };
+
+        let (actual, ()) = tokio::join!(collect(stream), cancel);
+        let err = actual.expect_err("rendering should be cancelled");
+        assert_eq!(ErrorKind::Cancelled, err.kind());
+    }
+
+    #[tokio::test]
+    async fn wakers_released_after_lookups() {
+        struct Yielding;
+
+        impl AsyncVariableSource for Yielding {
+            async fn lookup(&self, key: &str) -> Option<String> {
+                tokio::task::yield_now().await;
+                Some(key.to_owned())
+            }
+        }
+
+        let token = crate::CancelToken::new();
+        let options = RenderOptions::new().cancel_token(token.clone());
+        for _ in 0..100 {
+            let stream = render_stream_with_options("{{ a }} {{ b }}", &Yielding, &options);
+            let rendered = crate::render_async_with_options("{{ c }}", &Yielding, &options);
+            let (streamed, rendered) = tokio::join!(collect(stream), rendered);
+            streamed.expect("rendering should succeed");
+            rendered.expect("rendering should succeed");
+        }
+        assert_eq!(0, token.waiting());
+    }
+
+    #[tokio::test]
+    async fn stream_ends_after_error() {
+        let mut map = VarjMap::new();
+        map.insert("testKey", "testValue");
+
+        let mut stream = render_stream("a {{ testKey }} {{ wrongKey }} b", &map);
+        let mut items = Vec::new();
+        while let Some(item) = poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await {
+            items.push(item);
+        }
+
+        assert_eq!(4, items.len());
+        let err = items[3].clone().expect_err("rendering should error");
+        assert_eq!(ErrorKind::UnknownVariable, err.kind());
+        assert_eq!("wrongKey", err.key());
+    }
+
+    #[tokio::test]
+    async fn stream_sections() {
+        let mut map = VarjMap::new();
+        map.insert("testKey", "testValue");
+
+        let template =
+            "a{{#testKey}} {{ testKey }}{{/testKey}}{{^testKey}}{{ wrongKey }}{{/testKey}};";
+        let actual = collect(render_stream(template, &map))
+            .await
+            .expect("rendering should succeed");
+        assert_eq!(vec!["a", " ", "testValue", ";"], actual);
+
+        let err = collect(render_stream("a {{#testKey}}", &map))
+            .await
+            .expect_err("section is unmatched");
+        assert_eq!(ErrorKind::UnmatchedSection, err.kind());
+    }
+
+    async fn collect<S>(mut stream: RenderStream<'_, S>) -> Result<Vec<Bytes>, Error>
+    where
+        S: AsyncVariableSource + Sync,
+    {
+        let mut chunks = Vec::new();
+        while let Some(chunk) = poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await {
+            chunks.push(chunk?);
+        }
+        Ok(chunks)
+    }
+}
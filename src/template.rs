@@ -0,0 +1,1019 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroUsize;
+use std::ops::Range;
+use std::sync::OnceLock;
+use std::thread;
+
+use crate::escape::escape_with;
+use crate::filter::Align;
+use crate::{
+    block_kind, parse_blocks_with, split_quoted, substitute_block, Block, BlockKind, Delimiters,
+    Error, ErrorKind, RenderOptions, VarjMap,
+};
+
+/// A template parsed once, to be rendered many times.
+///
+/// Rendering a `Template` skips scanning the source for placeholder blocks.
+///
+/// # Example
+///
+/// ```rust
+/// # use std::error::Error;
+/// #
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// let template = varj::Template::compile("name: {{ name }}");
+///
+/// let mut map = varj::VarjMap::new();
+/// map.insert("name", "Christopher");
+///
+/// assert_eq!("name: Christopher", template.render(&map)?);
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    source: String,
+    spans: Vec<Span>,
+    pub(crate) delimiters: Delimiters,
+}
+
+/// Position of a placeholder block in a compiled template's source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Span {
+    start: usize,
+    len: usize,
+    line: usize,
+    col: usize,
+    key: SpanKey,
+    written_key: Range<usize>,
+    align: Option<Align>,
+    filters: Range<usize>,
+    raw: bool,
+    kind: BlockKind,
+}
+
+/// Where a compiled block's key is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SpanKey {
+    /// A slice of the source.
+    Slice(Range<usize>),
+    /// A quoted key with escapes, unescaped.
+    Unescaped(Box<str>),
+}
+
+impl Template {
+    /// Parse a template's placeholder blocks.
+    pub fn compile<S: Into<String>>(source: S) -> Template {
+        Template::compile_with(source.into(), Delimiters::default())
+    }
+
+    /// Parse a template whose placeholder blocks are delimited by `open`
+    /// and `close` instead of `{{` and `}}`.
+    ///
+    /// Everything else works as with `{{ }}`: a backslash before `open`
+    /// makes it literal, and keys may be quoted and followed by filters.
+    /// Braces are then plain text, which suits sources such as JavaScript.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::error::Error;
+    /// #
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let template = varj::Template::with_delimiters(
+    ///     "const config = { api: \"<% api | trim %>\" }; // \\<% api %>",
+    ///     "<%",
+    ///     "%>",
+    /// );
+    ///
+    /// let mut map = varj::VarjMap::new();
+    /// map.insert("api", " https://example.com ");
+    ///
+    /// assert_eq!(
+    ///     "const config = { api: \"https://example.com\" }; // <% api %>",
+    ///     template.render(&map)?
+    /// );
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if a delimiter is empty or contains whitespace, a backslash or
+    /// a `"`.
+    pub fn with_delimiters<S: Into<String>>(source: S, open: &str, close: &str) -> Template {
+        for delimiter in [open, close] {
+            assert!(
+                !delimiter.is_empty()
+                    && !delimiter
+                        .chars()
+                        .any(|ch| ch.is_whitespace() || ch == '\\' || ch == '"'),
+                "invalid delimiter {:?}",
+                delimiter
+            );
+        }
+        let delimiters = Delimiters {
+            open: Cow::Owned(open.to_owned()),
+            close: Cow::Owned(close.to_owned()),
+        };
+        Template::compile_with(source.into(), delimiters)
+    }
+
+    fn compile_with(source: String, delimiters: Delimiters) -> Template {
+        let spans = parse_blocks_with(&source, &delimiters)
+            .iter()
+            .map(|block| Span::new(&source, block, &delimiters))
+            .collect();
+        Template {
+            source,
+            spans,
+            delimiters,
+        }
+    }
+
+    /// The template's source.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// A stable hash of the template's structure: its literal text and the
+    /// keys and filters of its placeholder blocks.
+    ///
+    /// Whitespace around keys and filters does not change the fingerprint,
+    /// so `{{key}}` and `{{ key }}` hash alike, nor does how a literal `{{`
+    /// is escaped. The hash is the same on every platform and in every
+    /// version of varj, so it can be stored to detect changed templates.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use varj::Template;
+    ///
+    /// let template = Template::compile("port = {{ port }}");
+    /// assert_eq!(template.fingerprint(), Template::compile("port = {{port}}").fingerprint());
+    /// assert_ne!(template.fingerprint(), Template::compile("port = {{ host }}").fingerprint());
+    /// ```
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = Fnv1a::new();
+        for part in self.parts() {
+            match part {
+                Part::Literal(text) => {
+                    hasher.write(&[0]);
+                    hasher.write_str(&text);
+                }
+                Part::Variable {
+                    key,
+                    align,
+                    filters,
+                    raw,
+                    kind,
+                    ..
+                } => {
+                    hasher.write(&[1]);
+                    hasher.write_str(key);
+                    hasher.write_str(filters);
+                    if let Some(align) = align {
+                        hasher.write(&[2]);
+                        hasher.write_str(&align.to_string());
+                    }
+                    if raw {
+                        hasher.write(&[3]);
+                    }
+                    match kind {
+                        BlockKind::Section { inverted } => hasher.write(&[4, u8::from(inverted)]),
+                        BlockKind::SectionEnd => hasher.write(&[5]),
+                        _ => {}
+                    }
+                }
+            }
+        }
+        hasher.finish()
+    }
+
+    /// The template's literal text and placeholder blocks, in order.
+    ///
+    /// Escaped braces are part of the literal text around them.
+    pub(crate) fn parts(&self) -> Vec<Part<'_>> {
+        let mut parts = Vec::with_capacity(self.spans.len() * 2 + 1);
+        let mut literal = String::new();
+        let mut idx = 0;
+        for (span, block) in self.spans.iter().zip(self.blocks()) {
+            literal.push_str(&self.source[idx..block.start]);
+            idx = block.start + block.len;
+            match block.escaped(&self.source) {
+                Some(escaped) => literal.push_str(&escaped),
+                None => {
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Variable {
+                        key: self.key(span),
+                        align: block.align,
+                        filters: block.filters,
+                        raw: block.raw,
+                        kind: block.kind,
+                        line: block.line,
+                        col: block.col,
+                    });
+                }
+            }
+        }
+        literal.push_str(&self.source[idx..]);
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        parts
+    }
+
+    /// Render the template with its placeholder blocks replaced by values from
+    /// a [`VarjMap`].
+    ///
+    /// # Errors
+    ///
+    /// See [`VarjMap::render`].
+    pub fn render(&self, map: &VarjMap) -> Result<String, Error> {
+        self.render_with_options(map, &RenderOptions::default())
+    }
+
+    /// Render the template using the given [`RenderOptions`].
+    ///
+    /// # Errors
+    ///
+    /// See [`VarjMap::render_with_options`].
+    pub fn render_with_options(
+        &self,
+        map: &VarjMap,
+        options: &RenderOptions,
+    ) -> Result<String, Error> {
+        map.render_blocks(&self.source, &self.blocks(), options)
+    }
+
+    /// The distinct keys of the template's placeholder blocks, in order of
+    /// first use.
+    ///
+    /// Keys are as written, quoted keys unquoted. Escaped blocks and `.`,
+    /// bound to the element of a [list](VarjMap::insert_list), are not
+    /// keys. Use [`positions_of`](Self::positions_of) to find where each key
+    /// is used.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let template = varj::Template::compile("{{ user }}@{{ host }}: {{ user | trim }} \\{{ x }}");
+    /// assert_eq!(vec!["user", "host"], template.keys());
+    ///
+    /// let position = &template.positions_of("host")[0];
+    /// assert_eq!((1, 12), (position.line(), position.col()));
+    /// ```
+    pub fn keys(&self) -> Vec<&str> {
+        let mut seen = HashSet::new();
+        self.spans
+            .iter()
+            .filter(|span| span.kind.has_key())
+            .map(|span| self.key(span))
+            .filter(|key| *key != "." && seen.insert(*key))
+            .collect()
+    }
+
+    /// Every placeholder block with `key`, in template order.
+    ///
+    /// Keys are matched exactly as written, quoted keys once unquoted. Byte
+    /// ranges index into [`source`](Self::source), so tooling can highlight
+    /// uses of a key or edit them all, working from the last one back.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let template = varj::Template::compile("{{ host }}:{{ port }} ({{host|trim}})");
+    ///
+    /// let mut source = template.source().to_owned();
+    /// for position in template.positions_of("host").iter().rev() {
+    ///     source.replace_range(position.key_span(), "hostname");
+    /// }
+    /// assert_eq!("{{ hostname }}:{{ port }} ({{hostname|trim}})", source);
+    /// ```
+    pub fn positions_of(&self, key: &str) -> Vec<KeyPosition> {
+        self.spans
+            .iter()
+            .filter(|span| span.kind.has_key() && self.key(span) == key)
+            .map(|span| KeyPosition {
+                span: span.start..span.start + span.len,
+                key: span.written_key.clone(),
+                line: span.line,
+                col: span.col,
+            })
+            .collect()
+    }
+
+    /// Substitute the placeholders whose keys are set in `map`, keeping all
+    /// others as written to render them later.
+    ///
+    /// Values are filtered as when rendering and become literal text, so a
+    /// `{{`, or the template's opening delimiter, in a value is escaped. The map's [default
+    /// value](VarjMap::set_default) is not used, and a placeholder whose
+    /// filters fail is kept, to fail when the rest is rendered.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::error::Error;
+    /// #
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let template = varj::Template::compile("{{ image }}:{{ version }} on {{ host }}");
+    ///
+    /// let mut build = varj::VarjMap::new();
+    /// build.insert("image", "api");
+    /// build.insert("version", "1.4.2");
+    /// let partial = template.apply_partial(&build);
+    /// assert_eq!("api:1.4.2 on {{ host }}", partial.source());
+    ///
+    /// let mut deploy = varj::VarjMap::new();
+    /// deploy.insert("host", "eu-1");
+    /// assert_eq!("api:1.4.2 on eu-1", partial.render(&deploy)?);
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn apply_partial(&self, map: &VarjMap) -> Template {
+        let options = RenderOptions::default();
+        let mut source = String::with_capacity(self.source.len());
+        // text the partial template renders as, up to the next kept block
+        let mut literal = String::new();
+        let mut idx = 0;
+        for block in self.blocks() {
+            literal.push_str(&self.source[idx..block.start]);
+            idx = block.start + block.len;
+            if let Some(escaped) = block.escaped(&self.source) {
+                literal.push_str(&escaped);
+                continue;
+            }
+            let value = match block.kind {
+                BlockKind::Variable => map.get(&*block.variable_key).and_then(|value| {
+                    substitute_block(&self.source, &block, Some(value), &options).ok()
+                }),
+                // section tags are kept, to be shown or hidden when the rest
+                // is rendered
+                _ => None,
+            };
+            match value {
+                Some(value) => literal.push_str(&value),
+                None => {
+                    push_literal(&mut source, &literal, &self.delimiters, true);
+                    literal.clear();
+                    source.push_str(&self.source[block.start..idx]);
+                }
+            }
+        }
+        literal.push_str(&self.source[idx..]);
+        push_literal(&mut source, &literal, &self.delimiters, false);
+        Template::compile_with(source, self.delimiters.clone())
+    }
+
+    /// The key of a span's block, unquoted.
+    fn key<'a>(&'a self, span: &'a Span) -> &'a str {
+        match &span.key {
+            SpanKey::Slice(range) => &self.source[range.clone()],
+            SpanKey::Unescaped(key) => key,
+        }
+    }
+
+    pub(crate) fn blocks(&self) -> Vec<Block<'_>> {
+        self.spans
+            .iter()
+            .map(|span| Block {
+                start: span.start,
+                len: span.len,
+                line: span.line,
+                col: span.col,
+                variable_key: Cow::Borrowed(self.key(span)),
+                align: span.align,
+                filters: &self.source[span.filters.clone()],
+                raw: span.raw,
+                kind: span.kind,
+            })
+            .collect()
+    }
+}
+
+impl Span {
+    fn new(source: &str, block: &Block, delimiters: &Delimiters) -> Span {
+        Span {
+            start: block.start,
+            len: block.len,
+            line: block.line,
+            col: block.col,
+            key: match &block.variable_key {
+                Cow::Borrowed(key) => SpanKey::Slice(range_of(source, key)),
+                Cow::Owned(key) => SpanKey::Unescaped(key.as_str().into()),
+            },
+            written_key: range_of(source, written_key(source, block, delimiters)),
+            align: block.align,
+            filters: range_of(source, block.filters),
+            raw: block.raw,
+            kind: block.kind,
+        }
+    }
+}
+
+/// Where a placeholder block with a key is in a template, see
+/// [`Template::positions_of`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyPosition {
+    span: Range<usize>,
+    key: Range<usize>,
+    line: usize,
+    col: usize,
+}
+
+impl KeyPosition {
+    /// Byte range of the whole block, delimiters included.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    /// Byte range of the key inside the block, without surrounding
+    /// whitespace. The range of a quoted key includes its quotes.
+    pub fn key_span(&self) -> Range<usize> {
+        self.key.clone()
+    }
+
+    /// The line of the block, starting at 1.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// The column of the block, starting at 1.
+    pub fn col(&self) -> usize {
+        self.col
+    }
+}
+
+/// A piece of a template, see [`Template::parts`].
+#[derive(Debug, Clone)]
+pub(crate) enum Part<'a> {
+    Literal(String),
+    Variable {
+        key: &'a str,
+        align: Option<Align>,
+        filters: &'a str,
+        raw: bool,
+        kind: BlockKind,
+        line: usize,
+        col: usize,
+    },
+}
+
+impl Part<'_> {
+    /// Whether two parts have the same text, wherever they are.
+    pub(crate) fn same(&self, other: &Part) -> bool {
+        match (self, other) {
+            (Part::Literal(a), Part::Literal(b)) => a == b,
+            (
+                Part::Variable { key, filters, .. },
+                Part::Variable {
+                    key: other_key,
+                    filters: other_filters,
+                    ..
+                },
+            ) => key == other_key && filters == other_filters,
+            _ => false,
+        }
+    }
+}
+
+/// 64-bit FNV-1a, a hash that never changes between platforms or releases,
+/// unlike std's `DefaultHasher`.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    /// Write a length-prefixed string, so adjacent strings stay apart.
+    fn write_str(&mut self, text: &str) {
+        self.write(&(text.len() as u64).to_le_bytes());
+        self.write(text.as_bytes());
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// The key of `block` as written in `source`, with the quotes of a quoted
+/// key.
+fn written_key<'t>(source: &'t str, block: &Block, delimiters: &Delimiters) -> &'t str {
+    if block.kind.has_key() {
+        let braces = usize::from(block.raw);
+        let inner = &source[block.start + delimiters.open.len() + braces
+            ..block.start + block.len - delimiters.close.len() - braces];
+        let inner = match block.kind {
+            BlockKind::Variable => inner,
+            _ => block_kind(inner).1,
+        };
+        let inner = inner.trim_start();
+        if let Some((key, rest)) = split_quoted(inner) {
+            if key == block.variable_key {
+                return &inner[..inner.len() - rest.len()];
+            }
+        }
+    }
+    &source[range_of(source, &block.variable_key)]
+}
+
+/// Append template source rendering as `text`, doubling backslashes at its
+/// end if a placeholder block follows, so they do not escape it.
+fn push_literal(source: &mut String, text: &str, delimiters: &Delimiters, before_block: bool) {
+    source.push_str(&escape_with(text, &delimiters.open));
+    if before_block {
+        let slashes = text.len() - text.trim_end_matches('\\').len();
+        source.extend(std::iter::repeat('\\').take(slashes));
+    }
+}
+
+/// Byte range of `part`, a slice of `source`.
+fn range_of(source: &str, part: &str) -> Range<usize> {
+    let start = part.as_ptr() as usize - source.as_ptr() as usize;
+    start..start + part.len()
+}
+
+/// A set of named templates, compiled on first use.
+///
+/// Call [`compile_all`](Self::compile_all) at startup to compile every
+/// template up front, so no render pays the parsing cost.
+///
+/// # Example
+///
+/// ```rust
+/// # use std::error::Error;
+/// #
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// let mut templates = varj::TemplateSet::new();
+/// templates.insert("greeting", "Hello {{ name }}!");
+/// templates.insert("farewell", "Goodbye {{ name }}!");
+/// templates.compile_all();
+///
+/// let mut map = varj::VarjMap::new();
+/// map.insert("name", "Christopher");
+///
+/// assert_eq!("Hello Christopher!", templates.render("greeting", &map)?);
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct TemplateSet {
+    templates: HashMap<String, Entry>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    source: String,
+    compiled: OnceLock<Template>,
+}
+
+impl TemplateSet {
+    /// Create an empty `TemplateSet`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a template source under a name, replacing any template
+    /// already registered with that name.
+    ///
+    /// The source is compiled on first use.
+    pub fn insert<N, S>(&mut self, name: N, source: S)
+    where
+        N: Into<String>,
+        S: Into<String>,
+    {
+        let entry = Entry {
+            source: source.into(),
+            compiled: OnceLock::new(),
+        };
+        self.templates.insert(name.into(), entry);
+    }
+
+    /// Unregister the template with a name, returning whether there was
+    /// one.
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.templates.remove(name).is_some()
+    }
+
+    /// The names of the registered templates, in arbitrary order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.templates.keys().map(String::as_str)
+    }
+
+    /// Get a compiled template by name, compiling it if needed.
+    pub fn get(&self, name: &str) -> Option<&Template> {
+        self.templates.get(name).map(Entry::template)
+    }
+
+    /// Render a named template with values from a [`VarjMap`].
+    ///
+    /// # Errors
+    ///
+    /// Will return an [`Error`] of kind [`ErrorKind::UnknownTemplate`] if no
+    /// template is registered under `name`, otherwise see
+    /// [`VarjMap::render`].
+    pub fn render(&self, name: &str, map: &VarjMap) -> Result<String, Error> {
+        self.render_with_options(name, map, &RenderOptions::default())
+    }
+
+    /// Render a named template using the given [`RenderOptions`].
+    ///
+    /// # Errors
+    ///
+    /// See [`render`](Self::render).
+    pub fn render_with_options(
+        &self,
+        name: &str,
+        map: &VarjMap,
+        options: &RenderOptions,
+    ) -> Result<String, Error> {
+        match self.get(name) {
+            Some(template) => template.render_with_options(map, options),
+            None => Err(Error::without_block(ErrorKind::UnknownTemplate, name)),
+        }
+    }
+
+    /// Get a compiled template by name, fetching it with a
+    /// [`TemplateLoader`] if it is not registered yet.
+    ///
+    /// Fetched templates are registered under `name`, so later calls do not
+    /// fetch them again.
+    ///
+    /// # Errors
+    ///
+    /// Will return an [`Error`] of kind [`ErrorKind::Load`] naming the
+    /// template, with the loader's error as its source, if fetching fails.
+    ///
+    /// [`TemplateLoader`]: crate::TemplateLoader
+    pub fn load<L>(&mut self, name: &str, loader: &L) -> Result<&Template, Error>
+    where
+        L: crate::TemplateLoader + ?Sized,
+    {
+        if !self.templates.contains_key(name) {
+            let source = loader
+                .load(name)
+                .map_err(|err| Error::with_source(ErrorKind::Load, name, err))?;
+            self.insert(name, source);
+        }
+        Ok(self.templates[name].template())
+    }
+
+    /// Get a compiled template by name, fetching it with an
+    /// [`AsyncTemplateLoader`] if it is not registered yet.
+    ///
+    /// Fetched templates are registered under `name`, so later calls do not
+    /// fetch them again.
+    ///
+    /// # Errors
+    ///
+    /// Will return an [`Error`] of kind [`ErrorKind::Load`] naming the
+    /// template, with the loader's error as its source, if fetching fails.
+    ///
+    /// [`AsyncTemplateLoader`]: crate::AsyncTemplateLoader
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub async fn load_async<L>(&mut self, name: &str, loader: &L) -> Result<&Template, Error>
+    where
+        L: crate::AsyncTemplateLoader + Sync,
+    {
+        if !self.templates.contains_key(name) {
+            let source = loader
+                .load(name)
+                .await
+                .map_err(|err| Error::with_source(ErrorKind::Load, name, err))?;
+            self.insert(name, source);
+        }
+        Ok(self.templates[name].template())
+    }
+
+    /// Compile every registered template that has not been compiled yet,
+    /// spread across the available CPU cores.
+    ///
+    /// Blocks until all templates are compiled. In async contexts, run it
+    /// on a blocking task, e.g. tokio's `spawn_blocking`.
+    pub fn compile_all(&self) {
+        let pending: Vec<&Entry> = self
+            .templates
+            .values()
+            .filter(|entry| entry.compiled.get().is_none())
+            .collect();
+
+        let threads = thread::available_parallelism()
+            .map_or(1, NonZeroUsize::get)
+            .min(pending.len());
+        if threads <= 1 {
+            pending.iter().for_each(|entry| {
+                entry.template();
+            });
+            return;
+        }
+
+        let chunk_size = pending.len().div_ceil(threads);
+        thread::scope(|scope| {
+            for chunk in pending.chunks(chunk_size) {
+                scope.spawn(move || {
+                    chunk.iter().for_each(|entry| {
+                        entry.template();
+                    })
+                });
+            }
+        });
+    }
+}
+
+impl Entry {
+    fn template(&self) -> &Template {
+        self.compiled
+            .get_or_init(|| Template::compile(self.source.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compiled_template_renders() {
+        let template = Template::compile("testKey: {{ testKey }};\n{{testKey2}}");
+        let mut map = VarjMap::new();
+        map.insert("testKey", "testValue");
+        map.insert("testKey2", "testValue2");
+
+        let actual = template.render(&map).expect("rendering should succeed");
+        assert_eq!("testKey: testValue;\ntestValue2", actual);
+
+        let actual = template
+            .render(&VarjMap::new())
+            .expect_err("rendering should error");
+        assert_eq!("1:10 unknown variable 'testKey'", actual.to_string());
+    }
+
+    #[test]
+    fn stable_fingerprint() {
+        let fingerprint = |source: &str| Template::compile(source).fingerprint();
+
+        assert_eq!(
+            0xccee_9327_c15c_66e0,
+            fingerprint("a {{ b | trim }} \\{{ c")
+        );
+        assert_eq!(
+            fingerprint("a {{ b | trim }} \\{{ c"),
+            fingerprint("a {{b|trim}} \\{{ c")
+        );
+        assert_ne!(fingerprint("a {{ b }}"), fingerprint("a {{ b | trim }}"));
+        assert_ne!(fingerprint("a {{ b }}"), fingerprint("a  {{ b }}"));
+        assert_ne!(fingerprint("{{ ab }}"), fingerprint("{{ a }}b"));
+        assert_ne!(fingerprint("{{ a }}"), fingerprint("{{{ a }}}"));
+        assert_ne!(fingerprint("{{#a}}{{/a}}"), fingerprint("{{^a}}{{/a}}"));
+        assert_ne!(fingerprint(""), fingerprint("{{ }}"));
+    }
+
+    #[test]
+    fn apply_partial_values() {
+        let mut map = VarjMap::new();
+        map.insert("a", "{{ b }}\\");
+        map.insert("b", "x");
+        map.insert("slash", "\\");
+        map.insert("n", " 3 ");
+        map.set_default("default");
+
+        let template = Template::compile(
+            "{{ a }}{{ c }} \\{{ a }} \\\\{{ a }} {{ n | trim }} {{ n | nope }} {{ slash }}{{ c | trim }} {{ \"d e\" }}",
+        );
+        let partial = template.apply_partial(&map);
+        assert_eq!(
+            "\\{{ b }}\\\\{{ c }} \\{{ a }} \\\\\\{{ b }}\\ 3 {{ n | nope }} \\\\{{ c | trim }} {{ \"d e\" }}",
+            partial.source()
+        );
+
+        let mut rest = VarjMap::new();
+        rest.insert("c", " c ");
+        rest.insert("n", "4");
+        rest.insert("d e", "f");
+        let err = partial.render(&rest).expect_err("nope is not a filter");
+        assert_eq!(ErrorKind::Filter, err.kind());
+
+        let template = Template::compile(template.source().replace(" {{ n | nope }}", ""));
+        let mut all = map.clone();
+        all.insert("c", " c ");
+        all.insert("d e", "f");
+        assert_eq!(
+            template.render(&all),
+            template.apply_partial(&map).render(&rest)
+        );
+        assert_eq!(template, template.apply_partial(&VarjMap::new()));
+    }
+
+    #[test]
+    fn custom_delimiters() {
+        let mut map = VarjMap::new();
+        map.insert("a", "x");
+        map.insert("%>", "y");
+
+        let template = Template::with_delimiters(
+            "{{ a }} <% a %> \\<% a %> <%\"%>\"%>\n<%a:>3|trim%> <% b %>",
+            "<%",
+            "%>",
+        );
+        let actual = template.render(&map).expect_err("b is missing");
+        assert_eq!("2:15 unknown variable 'b'", actual.to_string());
+        map.insert("b", "z");
+        let actual = template.render(&map).expect("rendering should succeed");
+        assert_eq!("{{ a }} x <% a %> y\n  x z", actual);
+
+        let positions: Vec<Range<usize>> = template
+            .positions_of("a")
+            .iter()
+            .map(KeyPosition::key_span)
+            .collect();
+        assert_eq!(vec![11..12, 36..37], positions);
+
+        let mut partial = VarjMap::new();
+        partial.insert("open", "«");
+        let template = Template::with_delimiters("«open» «b» \\«b»", "«", "»");
+        let template = template.apply_partial(&partial);
+        assert_eq!("\\« «b» \\«b»", template.source());
+        let actual = template.render(&map).expect("rendering should succeed");
+        assert_eq!("« z «b»", actual);
+
+        let template = Template::with_delimiters("${ a }", "${", "}");
+        let actual = template.render(&map).expect("rendering should succeed");
+        assert_eq!("x", actual);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid delimiter")]
+    fn reject_empty_delimiters() {
+        Template::with_delimiters("", "", "}}");
+    }
+
+    #[test]
+    fn list_distinct_keys() {
+        let template = Template::compile(
+            "{{ b }} {{ a | trim }} \\{{ c }} {{ \"b\" }} {{ \"d e\":>3 }} \\\\{{ a }}",
+        );
+        assert_eq!(vec!["b", "a", "d e"], template.keys());
+        assert!(Template::compile("no {{ keys").keys().is_empty());
+    }
+
+    #[test]
+    fn find_key_positions() {
+        let template = Template::compile("{{ a }}\n\\{{ a }} {{a|trim}} {{ ab }} \\\\{{ a }}");
+
+        let positions: Vec<(Range<usize>, Range<usize>, usize, usize)> = template
+            .positions_of("a")
+            .iter()
+            .map(|position| {
+                (
+                    position.span(),
+                    position.key_span(),
+                    position.line(),
+                    position.col(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (0..7, 3..4, 1, 1),
+                (17..27, 19..20, 2, 10),
+                (39..46, 42..43, 2, 32),
+            ],
+            positions
+        );
+        assert!(template.positions_of("c").is_empty());
+
+        let template = Template::compile("{{ a }} {{ \"a\" }} {{ \"\\a\" | trim }} {{ \"a b\" }}");
+        let key_spans: Vec<Range<usize>> = template
+            .positions_of("a")
+            .iter()
+            .map(KeyPosition::key_span)
+            .collect();
+        assert_eq!(vec![3..4, 11..14, 21..25], key_spans);
+        assert_eq!(1, template.positions_of("a b").len());
+    }
+
+    #[test]
+    fn compile_all_templates() {
+        let mut templates = TemplateSet::new();
+        for idx in 0..64 {
+            templates.insert(format!("test{}", idx), format!("{{{{ testKey{} }}}}", idx));
+        }
+
+        templates.compile_all();
+
+        assert!(templates
+            .templates
+            .values()
+            .all(|entry| entry.compiled.get().is_some()));
+        let mut map = VarjMap::new();
+        map.insert("testKey42", "testValue");
+        let actual = templates
+            .render("test42", &map)
+            .expect("rendering should succeed");
+        assert_eq!("testValue", actual);
+    }
+
+    #[test]
+    fn load_templates() {
+        use std::cell::Cell;
+
+        let calls = Cell::new(0);
+        let loader = |name: &str| -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+            calls.set(calls.get() + 1);
+            match name {
+                "test" => Ok("testKey: {{ testKey }};".to_owned()),
+                _ => Err("not found".into()),
+            }
+        };
+        let mut templates = TemplateSet::new();
+        let mut map = VarjMap::new();
+        map.insert("testKey", "testValue");
+
+        for _ in 0..2 {
+            let template = templates
+                .load("test", &loader)
+                .expect("loading should succeed");
+            let actual = template.render(&map).expect("rendering should succeed");
+            assert_eq!("testKey: testValue;", actual);
+        }
+        assert_eq!(1, calls.get());
+
+        let actual = templates
+            .load("wrong", &loader)
+            .expect_err("loading should error");
+        assert_eq!(ErrorKind::Load, actual.kind());
+        assert_eq!("wrong", actual.key());
+        assert!(std::error::Error::source(&actual).is_some());
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn load_templates_once() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct CountingLoader(AtomicUsize);
+
+        impl crate::AsyncTemplateLoader for CountingLoader {
+            async fn load(
+                &self,
+                name: &str,
+            ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                match name {
+                    "test" => Ok("testKey: {{ testKey }};".to_owned()),
+                    _ => Err("not found".into()),
+                }
+            }
+        }
+
+        let loader = CountingLoader(AtomicUsize::new(0));
+        let mut templates = TemplateSet::new();
+        let mut map = VarjMap::new();
+        map.insert("testKey", "testValue");
+
+        for _ in 0..2 {
+            let template = templates
+                .load_async("test", &loader)
+                .await
+                .expect("loading should succeed");
+            let actual = template.render(&map).expect("rendering should succeed");
+            assert_eq!("testKey: testValue;", actual);
+        }
+        assert_eq!(1, loader.0.load(Ordering::SeqCst));
+
+        let actual = templates
+            .load_async("wrong", &loader)
+            .await
+            .expect_err("loading should error");
+        assert_eq!(ErrorKind::Load, actual.kind());
+        assert_eq!("wrong", actual.key());
+        assert!(std::error::Error::source(&actual).is_some());
+    }
+
+    #[test]
+    fn render_unknown_template() {
+        let templates = TemplateSet::new();
+
+        let actual = templates
+            .render("test", &VarjMap::new())
+            .expect_err("rendering should error");
+        assert_eq!(ErrorKind::UnknownTemplate, actual.kind());
+        assert_eq!("unknown template 'test'", actual.to_string());
+    }
+}
@@ -0,0 +1,619 @@
+//! Helpers for testing templates.
+//!
+//! [`assert_render_eq!`] checks a single render, [`assert_render_cases`] a
+//! table of templates and their expected output. A [`Contract`] checks one
+//! template against example maps, for templates stored as files. Failures
+//! show a line diff of the expected and actual output, so a changed line in a
+//! long template is easy to find.
+//!
+//! A [`RecordingSource`] records the keys a template looks up, to test which
+//! variables it consumes.
+//!
+//! # Example
+//!
+//! ```rust
+//! use varj::assert_render_eq;
+//! use varj::testing::assert_render_cases;
+//!
+//! let mut map = varj::VarjMap::new();
+//! map.insert("name", "billing");
+//! map.insert("port", "8080");
+//!
+//! assert_render_eq!(map, "{{ name }}:{{ port }}", "billing:8080");
+//! assert_render_cases(
+//!     &map,
+//!     &[
+//!         ("service = {{ name }}", "service = billing"),
+//!         ("port = {{ port | pad_left(6) }}", "port =   8080"),
+//!     ],
+//! );
+//! ```
+
+use std::borrow::Cow;
+use std::fmt::{self, Write};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::{render_local, Template, VariableSource, VarjMap};
+
+pub use crate::assert_render_eq;
+
+/// Assert that rendering a template from a [`VariableSource`] succeeds with
+/// the expected output.
+///
+/// On failure, panics with a line diff of the expected and actual output, or
+/// with the render error.
+///
+/// # Example
+///
+/// ```rust
+/// let mut map = varj::VarjMap::new();
+/// map.insert("key", "value");
+///
+/// varj::assert_render_eq!(map, "{{ key }}", "value");
+/// ```
+///
+/// ```rust,should_panic
+/// let mut map = varj::VarjMap::new();
+/// map.insert("key", "value");
+///
+/// // panics with:
+/// // -other
+/// // +value
+/// varj::assert_render_eq!(map, "{{ key }}", "other");
+/// ```
+#[macro_export]
+macro_rules! assert_render_eq {
+    ($source:expr, $template:expr, $expected:expr $(,)?) => {
+        $crate::testing::assert_render(&$source, $template, $expected)
+    };
+}
+
+/// Assert that rendering a template from a [`VariableSource`] succeeds with
+/// the expected output.
+///
+/// This is the function behind [`assert_render_eq!`].
+///
+/// # Panics
+///
+/// Will panic with a line diff of the expected and actual output, or with
+/// the render error.
+#[track_caller]
+pub fn assert_render<S: VariableSource + ?Sized>(source: &S, template: &str, expected: &str) {
+    if let Some(failure) = check(source, template, expected) {
+        panic!("{}", failure);
+    }
+}
+
+/// Assert that each template in a table of `(template, expected)` cases
+/// renders from a [`VariableSource`] with its expected output.
+///
+/// Every case is rendered before failing, so one run reports all of them.
+///
+/// # Panics
+///
+/// Will panic listing each failed case by its index, with a line diff of
+/// the expected and actual output, or with the render error.
+#[track_caller]
+pub fn assert_render_cases<S: VariableSource + ?Sized>(source: &S, cases: &[(&str, &str)]) {
+    let failures: Vec<String> = cases
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, (template, expected))| {
+            check(source, template, expected).map(|failure| format!("case {}: {}", idx, failure))
+        })
+        .collect();
+
+    if !failures.is_empty() {
+        panic!(
+            "{} of {} cases failed\n\n{}",
+            failures.len(),
+            cases.len(),
+            failures.join("\n")
+        );
+    }
+}
+
+fn check<S: VariableSource + ?Sized>(source: &S, template: &str, expected: &str) -> Option<String> {
+    match render_local(template, source) {
+        Ok(actual) if actual == expected => None,
+        Ok(actual) => Some(format!(
+            "rendered output of {:?} does not match\n{}",
+            template,
+            diff(expected, &actual)
+        )),
+        Err(err) => Some(format!("rendering {:?} failed: {}\n", template, err)),
+    }
+}
+
+/// Example maps a [`Template`] must render, each with its expected output or
+/// a check of the output.
+///
+/// [`check`](Self::check) renders every example and collects the results
+/// into a [`ContractReport`].
+///
+/// # Example
+///
+/// ```rust
+/// use varj::testing::Contract;
+/// use varj::{Template, VarjMap};
+///
+/// let template = Template::compile("[server]\nhost = {{ host }}\nport = {{ port }}\n");
+///
+/// let mut prod = VarjMap::new();
+/// prod.insert("host", "example.com");
+/// prod.insert("port", "443");
+///
+/// let mut dev = VarjMap::new();
+/// dev.insert("host", "localhost");
+/// dev.insert("port", "8080");
+///
+/// Contract::new(&template)
+///     .expect_output("prod", prod, "[server]\nhost = example.com\nport = 443\n")
+///     .expect_that("dev", dev, |output| {
+///         if output.contains("localhost") {
+///             Ok(())
+///         } else {
+///             Err("dev must run on localhost".to_owned())
+///         }
+///     })
+///     .check()
+///     .assert();
+/// ```
+pub struct Contract<'t> {
+    template: &'t Template,
+    examples: Vec<Example>,
+}
+
+struct Example {
+    name: String,
+    map: VarjMap,
+    expectation: Expectation,
+}
+
+type Invariant = dyn Fn(&str) -> Result<(), String>;
+
+enum Expectation {
+    Output(String),
+    Invariant(Box<Invariant>),
+}
+
+impl<'t> Contract<'t> {
+    /// Create a `Contract` for a template, without examples.
+    pub fn new(template: &'t Template) -> Self {
+        Self {
+            template,
+            examples: Vec::new(),
+        }
+    }
+
+    /// Add an example that must render to `expected`.
+    pub fn expect_output<N, E>(mut self, name: N, map: VarjMap, expected: E) -> Self
+    where
+        N: Into<String>,
+        E: Into<String>,
+    {
+        self.examples.push(Example {
+            name: name.into(),
+            map,
+            expectation: Expectation::Output(expected.into()),
+        });
+        self
+    }
+
+    /// Add an example whose output must pass `invariant`, which returns a
+    /// description of the problem otherwise.
+    pub fn expect_that<N, F>(mut self, name: N, map: VarjMap, invariant: F) -> Self
+    where
+        N: Into<String>,
+        F: Fn(&str) -> Result<(), String> + 'static,
+    {
+        self.examples.push(Example {
+            name: name.into(),
+            map,
+            expectation: Expectation::Invariant(Box::new(invariant)),
+        });
+        self
+    }
+
+    /// Render every example, reporting those that fail to render or do not
+    /// meet their expectation.
+    pub fn check(&self) -> ContractReport {
+        let failures = self
+            .examples
+            .iter()
+            .filter_map(|example| {
+                let message = match (self.template.render(&example.map), &example.expectation) {
+                    (Err(err), _) => format!("rendering failed: {}\n", err),
+                    (Ok(actual), Expectation::Output(expected)) if actual != *expected => {
+                        format!("output does not match\n{}", diff(expected, &actual))
+                    }
+                    (Ok(actual), Expectation::Invariant(invariant)) => {
+                        format!("{}\n", invariant(&actual).err()?)
+                    }
+                    (Ok(_), Expectation::Output(_)) => return None,
+                };
+                Some(ContractFailure {
+                    example: example.name.clone(),
+                    message,
+                })
+            })
+            .collect();
+
+        ContractReport {
+            examples: self.examples.len(),
+            failures,
+        }
+    }
+}
+
+impl fmt::Debug for Contract<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let examples: Vec<&str> = self.examples.iter().map(|ex| ex.name.as_str()).collect();
+        f.debug_struct("Contract")
+            .field("template", &self.template)
+            .field("examples", &examples)
+            .finish()
+    }
+}
+
+/// The results of checking a [`Contract`].
+///
+/// Displays as a summary followed by each failure.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContractReport {
+    examples: usize,
+    failures: Vec<ContractFailure>,
+}
+
+impl ContractReport {
+    /// Whether every example passed.
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// The number of examples that passed.
+    pub fn passed(&self) -> usize {
+        self.examples - self.failures.len()
+    }
+
+    /// The examples that failed, in the order they were added.
+    pub fn failures(&self) -> &[ContractFailure] {
+        &self.failures
+    }
+
+    /// Assert that every example passed.
+    ///
+    /// # Panics
+    ///
+    /// Will panic with the report if any example failed.
+    #[track_caller]
+    pub fn assert(&self) {
+        if !self.is_ok() {
+            panic!("{}", self);
+        }
+    }
+}
+
+impl fmt::Display for ContractReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} of {} examples passed", self.passed(), self.examples)?;
+        for failure in &self.failures {
+            write!(f, "\n\n{}", failure)?;
+        }
+        Ok(())
+    }
+}
+
+/// An example of a [`Contract`] that failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContractFailure {
+    example: String,
+    message: String,
+}
+
+impl ContractFailure {
+    /// The name of the example.
+    pub fn example(&self) -> &str {
+        &self.example
+    }
+
+    /// Why the example failed: the render error, a line diff of the
+    /// expected and actual output, or the problem found by the invariant.
+    pub fn message(&self) -> &str {
+        self.message.trim_end()
+    }
+}
+
+impl fmt::Display for ContractFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "example '{}': {}", self.example, self.message())
+    }
+}
+
+/// A [`VariableSource`] wrapper recording every key looked up in it.
+///
+/// Lookups are recorded whether or not the key is set, so a test can assert
+/// exactly which variables a template consumes, including those it fails on.
+///
+/// # Example
+///
+/// ```rust
+/// use varj::testing::RecordingSource;
+///
+/// let mut map = varj::VarjMap::new();
+/// map.insert("name", "billing");
+/// map.insert("unused", "");
+///
+/// let source = RecordingSource::new(map);
+/// let _ = varj::render("{{ name }}{{ name }}{{ port }}", &source);
+///
+/// assert_eq!(vec!["name", "port"], source.keys());
+/// assert_eq!(vec!["port"], source.missing());
+/// assert_eq!(3, source.lookups().len());
+/// ```
+#[derive(Debug, Default)]
+pub struct RecordingSource<S> {
+    inner: S,
+    lookups: Mutex<Vec<Lookup>>,
+}
+
+impl<S> RecordingSource<S> {
+    /// Wrap a source, with nothing recorded.
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            lookups: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Every lookup, in the order they were made.
+    pub fn lookups(&self) -> Vec<Lookup> {
+        self.recorded().clone()
+    }
+
+    /// Each key looked up, in the order of their first lookup.
+    pub fn keys(&self) -> Vec<String> {
+        self.unique_keys(|_| true)
+    }
+
+    /// Each key looked up that is not set, in the order of their first
+    /// lookup.
+    pub fn missing(&self) -> Vec<String> {
+        self.unique_keys(|lookup| !lookup.found)
+    }
+
+    fn unique_keys(&self, filter: impl Fn(&Lookup) -> bool) -> Vec<String> {
+        let mut keys: Vec<String> = Vec::new();
+        for lookup in self.recorded().iter().filter(|lookup| filter(lookup)) {
+            if !keys.contains(&lookup.key) {
+                keys.push(lookup.key.clone());
+            }
+        }
+        keys
+    }
+
+    /// Forget all recorded lookups.
+    pub fn clear(&self) {
+        self.recorded().clear();
+    }
+
+    /// The wrapped source.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Unwrap the wrapped source.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    fn recorded(&self) -> MutexGuard<'_, Vec<Lookup>> {
+        self.lookups
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<S: VariableSource> VariableSource for RecordingSource<S> {
+    fn lookup(&self, key: &str) -> Option<Cow<'_, str>> {
+        let value = self.inner.lookup(key);
+        self.recorded().push(Lookup {
+            key: key.to_owned(),
+            found: value.is_some(),
+        });
+        value
+    }
+
+    fn list(&self, key: &str) -> Vec<Cow<'_, str>> {
+        self.inner.list(key)
+    }
+}
+
+/// A key looked up in a [`RecordingSource`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lookup {
+    key: String,
+    found: bool,
+}
+
+impl Lookup {
+    /// The key, as written in the template.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Whether the source had a value for the key.
+    pub fn found(&self) -> bool {
+        self.found
+    }
+}
+
+/// A line diff from `expected` to `actual`.
+///
+/// Starts with `--- expected` and `+++ actual` headers, followed by every
+/// line prefixed with `-` if it is only expected, `+` if it is only in the
+/// actual text, or a space if it is in both.
+///
+/// # Example
+///
+/// ```rust
+/// let diff = varj::testing::diff("a\nb\nc", "a\nB\nc");
+/// assert_eq!("--- expected\n+++ actual\n a\n-b\n+B\n c\n", diff);
+/// ```
+pub fn diff(expected: &str, actual: &str) -> String {
+    let old: Vec<&str> = expected.split('\n').collect();
+    let new: Vec<&str> = actual.split('\n').collect();
+
+    // lengths of the longest common subsequences of each pair of suffixes
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = String::from("--- expected\n+++ actual\n");
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            let _ = writeln!(out, " {}", old[i]);
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            let _ = writeln!(out, "-{}", old[i]);
+            i += 1;
+        } else {
+            let _ = writeln!(out, "+{}", new[j]);
+            j += 1;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    use super::*;
+    use crate::{render, VarjMap};
+
+    fn panic_message(f: impl FnOnce()) -> String {
+        let payload = catch_unwind(AssertUnwindSafe(f)).expect_err("should panic");
+        payload
+            .downcast_ref::<String>()
+            .cloned()
+            .expect("panic message is a String")
+    }
+
+    #[test]
+    fn report_contract() {
+        let template = Template::compile("a = {{ a }}\nb = {{ b }}");
+        let map = |a: &str, b: &str| {
+            let mut map = VarjMap::new();
+            map.insert("a", a);
+            map.insert("b", b);
+            map
+        };
+        let non_empty = |output: &str| {
+            if output.lines().any(|line| line.ends_with("= ")) {
+                Err("empty value".to_owned())
+            } else {
+                Ok(())
+            }
+        };
+
+        let report = Contract::new(&template)
+            .expect_output("first", map("1", "2"), "a = 1\nb = 2")
+            .expect_output("second", map("1", "3"), "a = 1\nb = 2")
+            .expect_that("third", map("1", ""), non_empty)
+            .expect_that("fourth", map("1", "2"), non_empty)
+            .expect_that("fifth", VarjMap::new(), non_empty)
+            .check();
+
+        assert!(!report.is_ok());
+        assert_eq!(2, report.passed());
+        assert_eq!(
+            vec!["second", "third", "fifth"],
+            report
+                .failures()
+                .iter()
+                .map(ContractFailure::example)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            "2 of 5 examples passed\n\n\
+             example 'second': output does not match\n\
+             --- expected\n+++ actual\n a = 1\n-b = 2\n+b = 3\n\n\
+             example 'third': empty value\n\n\
+             example 'fifth': rendering failed: 1:5 unknown variable 'a'",
+            report.to_string()
+        );
+        assert_eq!(report.to_string(), panic_message(|| report.assert()));
+    }
+
+    #[test]
+    fn record_lookups() {
+        let source = RecordingSource::new([("a", "1"), ("b", "2")]);
+        let actual =
+            render("{{ b }}{{ a }}{{ b | trim }}", &source).expect("rendering should succeed");
+        assert_eq!("212", actual);
+        assert_eq!(vec!["b", "a"], source.keys());
+        assert!(source.missing().is_empty());
+        assert_eq!(3, source.lookups().len());
+
+        source.clear();
+        render("{{ a }}{{ c }}{{ b }}", &source).expect_err("c is missing");
+        assert_eq!(
+            vec![
+                Lookup {
+                    key: "a".to_owned(),
+                    found: true
+                },
+                Lookup {
+                    key: "c".to_owned(),
+                    found: false
+                },
+            ],
+            source.lookups()
+        );
+        assert_eq!(vec!["c"], source.missing());
+        assert_eq!(Some(Cow::Borrowed("1")), source.into_inner().lookup("a"));
+    }
+
+    #[test]
+    fn diff_lines() {
+        assert_eq!("--- expected\n+++ actual\n same\n", diff("same", "same"));
+        assert_eq!(
+            "--- expected\n+++ actual\n a\n-b\n c\n+d\n",
+            diff("a\nb\nc", "a\nc\nd")
+        );
+        assert_eq!("--- expected\n+++ actual\n-\n+x\n", diff("", "x"));
+    }
+
+    #[test]
+    fn report_failed_cases() {
+        let mut map = VarjMap::new();
+        map.insert("a", "1");
+        map.insert("b", "2");
+
+        assert_render_eq!(map, "{{ a }}{{ b }}", "12");
+
+        let message = panic_message(|| {
+            assert_render_cases(
+                &map,
+                &[("{{ a }}", "1"), ("x\n{{ b }}", "x\n3"), ("{{ c }}", "")],
+            )
+        });
+        assert_eq!(
+            "2 of 3 cases failed\n\n\
+             case 1: rendered output of \"x\\n{{ b }}\" does not match\n\
+             --- expected\n+++ actual\n x\n-3\n+2\n\n\
+             case 2: rendering \"{{ c }}\" failed: 1:1 unknown variable 'c'\n",
+            message
+        );
+    }
+}
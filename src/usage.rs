@@ -0,0 +1,236 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::{parse_blocks, render_local, BlockKind, Error, TemplateSet, VariableSource, VarjMap};
+
+/// Placeholder usage aggregated across many renders.
+///
+/// Record each render by template name, then ask which keys were used how
+/// often and by which templates, which entries of a [`VarjMap`] no template
+/// uses, or which templates of a [`TemplateSet`] are never rendered. Shared
+/// between threads, it records renders made anywhere in an application.
+///
+/// # Example
+///
+/// ```rust
+/// # use std::error::Error;
+/// #
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// let mut map = varj::VarjMap::new();
+/// map.insert("host", "example.com");
+/// map.insert("port", "443");
+/// map.insert("legacy_url", "http://old.example.com");
+///
+/// let mut templates = varj::TemplateSet::new();
+/// templates.insert("nginx.conf", "server_name {{ host }};\nlisten {{ port }};");
+/// templates.insert("motd", "Welcome to {{ host }}");
+/// templates.insert("old.conf", "proxy_pass {{ legacy_url }};");
+///
+/// let tracker = varj::UsageTracker::new();
+/// for name in ["nginx.conf", "motd", "motd"] {
+///     let template = templates.get(name).expect("registered template");
+///     tracker.render(name, template.source(), &map)?;
+/// }
+///
+/// let host = &tracker.keys()[0];
+/// assert_eq!(("host", 3), (host.key(), host.count()));
+/// assert_eq!(vec!["motd", "nginx.conf"], host.templates());
+/// assert_eq!(vec!["legacy_url"], tracker.unused_keys(&map));
+/// assert_eq!(vec!["old.conf"], tracker.unused_templates(&templates));
+/// #
+/// #     Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct UsageTracker {
+    usage: Mutex<Usage>,
+}
+
+#[derive(Debug, Default)]
+struct Usage {
+    keys: BTreeMap<String, KeyUsage>,
+    renders: BTreeMap<String, usize>,
+}
+
+impl UsageTracker {
+    /// Create a `UsageTracker` with nothing recorded.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a render of `template` under `name`, without rendering it.
+    ///
+    /// Use it for templates rendered elsewhere, such as by a [`TemplateSet`].
+    pub fn record(&self, name: &str, template: &str) {
+        let mut usage = self.usage();
+        *usage.renders.entry(name.to_owned()).or_default() += 1;
+
+        let blocks = parse_blocks(template);
+        for block in blocks
+            .iter()
+            .filter(|block| block.kind == BlockKind::Variable)
+        {
+            let key = &*block.variable_key;
+            let stats = usage
+                .keys
+                .entry(key.to_owned())
+                .or_insert_with(|| KeyUsage {
+                    key: key.to_owned(),
+                    count: 0,
+                    templates: BTreeSet::new(),
+                });
+            stats.count += 1;
+            if !stats.templates.contains(name) {
+                stats.templates.insert(name.to_owned());
+            }
+        }
+    }
+
+    /// Render a template from a [`VariableSource`] and record it under
+    /// `name`.
+    ///
+    /// # Errors
+    ///
+    /// See [`render`](crate::render). The render is recorded even if it
+    /// fails.
+    pub fn render<S>(&self, name: &str, template: &str, source: &S) -> Result<String, Error>
+    where
+        S: VariableSource + ?Sized,
+    {
+        self.record(name, template);
+        render_local(template, source)
+    }
+
+    /// The usage of each key, sorted by key.
+    ///
+    /// Keys are as written in the templates, so keys a case-insensitive
+    /// [`VarjMap`] matches alike are counted apart.
+    pub fn keys(&self) -> Vec<KeyUsage> {
+        self.usage().keys.values().cloned().collect()
+    }
+
+    /// The number of recorded renders of the template `name`.
+    pub fn renders(&self, name: &str) -> usize {
+        self.usage().renders.get(name).copied().unwrap_or(0)
+    }
+
+    /// The entries of `map` no recorded render used, sorted.
+    ///
+    /// Keys are matched, and aliases followed, as when rendering from the
+    /// map.
+    pub fn unused_keys(&self, map: &VarjMap) -> Vec<String> {
+        let usage = self.usage();
+        let used = map.used_keys(usage.keys.keys().map(String::as_str));
+        let mut unused: Vec<String> = map
+            .map
+            .keys()
+            .filter(|key| !used.contains(key.as_str()))
+            .cloned()
+            .collect();
+        unused.sort_unstable();
+        unused
+    }
+
+    /// The templates of `templates` without recorded renders, sorted by
+    /// name.
+    pub fn unused_templates(&self, templates: &TemplateSet) -> Vec<String> {
+        let usage = self.usage();
+        let mut unused: Vec<String> = templates
+            .names()
+            .filter(|name| !usage.renders.contains_key(*name))
+            .map(str::to_owned)
+            .collect();
+        unused.sort_unstable();
+        unused
+    }
+
+    /// Forget everything recorded.
+    pub fn clear(&self) {
+        let mut usage = self.usage();
+        usage.keys.clear();
+        usage.renders.clear();
+    }
+
+    fn usage(&self) -> MutexGuard<'_, Usage> {
+        self.usage
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// How often a key was used, and by which templates, in the renders recorded
+/// by a [`UsageTracker`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyUsage {
+    key: String,
+    count: usize,
+    templates: BTreeSet<String>,
+}
+
+impl KeyUsage {
+    /// The key, as written in the templates.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// The number of placeholders with the key across all recorded renders.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// The names of the templates using the key, sorted.
+    pub fn templates(&self) -> Vec<&str> {
+        self.templates.iter().map(String::as_str).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count_usage_across_renders() {
+        let tracker = UsageTracker::new();
+        tracker.record("a", "{{ x }}{{ x | trim }} \\{{ y }}");
+        tracker.record("b", "{{ y }}");
+        tracker.record("a", "{{ x }}");
+        tracker
+            .render("c", "{{ z }}", &[("x", "1")])
+            .expect_err("z is missing");
+
+        let keys = tracker.keys();
+        let usage: Vec<(&str, usize, Vec<&str>)> = keys
+            .iter()
+            .map(|usage| (usage.key(), usage.count(), usage.templates()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("x", 3, vec!["a"]),
+                ("y", 1, vec!["b"]),
+                ("z", 1, vec!["c"]),
+            ],
+            usage
+        );
+        assert_eq!(2, tracker.renders("a"));
+        assert_eq!(0, tracker.renders("d"));
+
+        tracker.clear();
+        assert!(tracker.keys().is_empty());
+        assert_eq!(0, tracker.renders("a"));
+    }
+
+    #[test]
+    fn find_unused_keys() {
+        let mut map = VarjMap::new();
+        map.set_case_insensitive(true);
+        map.insert("used", "");
+        map.insert("target", "");
+        map.insert("unused", "");
+        map.insert("also_unused", "");
+        map.alias("alias", "target");
+
+        let tracker = UsageTracker::new();
+        tracker.record("a", "{{ USED }}{{ alias }}");
+        assert_eq!(vec!["also_unused", "unused"], tracker.unused_keys(&map));
+    }
+}
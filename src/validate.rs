@@ -0,0 +1,307 @@
+use crate::{
+    check_block, filter, parse_blocks, scan_blocks, section, Block, BlockKind, Delimiters, Error,
+    ErrorKind, MissingKey, RenderOptions, Template, VarjMap,
+};
+
+/// Check that every placeholder block of a template is closed, and every
+/// section closed by a matching end tag.
+///
+/// Rendering copies a `{{` without a matching `}}` to the output as is, so a
+/// mistyped block such as `{{ name }` goes unnoticed. Check templates from
+/// users or files before rendering them to reject these instead.
+///
+/// # Example
+///
+/// ```rust
+/// assert!(varj::check_syntax("Hello {{ name }}! \\{{ literal").is_ok());
+///
+/// let err = varj::check_syntax("Hello {{ name }!\nBye {{ name }}").unwrap_err();
+/// assert_eq!(varj::ErrorKind::UnclosedBlock, err.kind());
+/// assert_eq!("1:7 unclosed placeholder block", err.to_string());
+/// ```
+///
+/// # Errors
+///
+/// Will return an [`Error`] of kind [`ErrorKind::UnclosedBlock`] at the
+/// first opening `{{` that is not closed before the next `{{` or the end of
+/// the template, otherwise of kind [`ErrorKind::UnmatchedSection`] at the
+/// first section tag without a match.
+pub fn check_syntax(template: &str) -> Result<(), Error> {
+    syntax(template, &Delimiters::default())
+}
+
+impl Template {
+    /// Check that every placeholder block of the template is closed, using
+    /// its delimiters.
+    ///
+    /// # Errors
+    ///
+    /// See [`check_syntax`].
+    pub fn check_syntax(&self) -> Result<(), Error> {
+        syntax(self.source(), &self.delimiters)
+    }
+
+    /// Check that the template would render from `map`, without rendering
+    /// it.
+    ///
+    /// # Errors
+    ///
+    /// See [`VarjMap::validate`].
+    pub fn check(&self, map: &VarjMap) -> Result<(), Error> {
+        self.check_syntax()?;
+        map.validate_blocks(&self.blocks(), &RenderOptions::default())
+    }
+}
+
+impl VarjMap {
+    /// Check that a template would render from this map, without building
+    /// its output.
+    ///
+    /// Every placeholder block must be closed, have a value, possibly the
+    /// [default value](Self::set_default), and name known filters in a
+    /// well-formed chain. Filters failing on a particular value, such as
+    /// `b64decode` on text that is not base64, are not run and so pass.
+    /// Placeholders in a section hidden by the value of its key, before
+    /// filters, are not checked, and `{{ . }}` has a value in a section
+    /// repeating over a [list](Self::insert_list).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let mut map = varj::VarjMap::new();
+    /// map.insert("name", "Christopher");
+    ///
+    /// assert!(map.validate("Hello {{ name | trim }}!").is_ok());
+    ///
+    /// let err = map.validate("Hello {{ name }} {{ age }}!").unwrap_err();
+    /// assert_eq!("1:18 unknown variable 'age'", err.to_string());
+    ///
+    /// let err = map.validate("Hello {{ name | shout }}!").unwrap_err();
+    /// assert_eq!(varj::ErrorKind::Filter, err.kind());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Will return an [`Error`] of kind [`ErrorKind::UnclosedBlock`],
+    /// [`ErrorKind::UnmatchedSection`], [`ErrorKind::UnknownVariable`] or
+    /// [`ErrorKind::Filter`] for the first problem found.
+    pub fn validate(&self, template: &str) -> Result<(), Error> {
+        self.validate_with_options(template, &RenderOptions::default())
+    }
+
+    /// Check that a template would render from this map using the given
+    /// [`RenderOptions`], without building its output.
+    ///
+    /// Keys must also pass [`RenderOptions::key_chars`], and unused entries
+    /// are rejected with [`RenderOptions::deny_unused`]. Placeholders need
+    /// not have a value when [`RenderOptions::missing_key`] renders them
+    /// anyway, as with [`MissingKey::Empty`]. Hooks such as
+    /// [`RenderOptions::on_substitute`] are not called.
+    ///
+    /// # Errors
+    ///
+    /// See [`validate`](Self::validate), and an [`Error`] of kind
+    /// [`ErrorKind::InvalidKey`] or [`ErrorKind::UnusedVariable`] as when
+    /// rendering.
+    pub fn validate_with_options(
+        &self,
+        template: &str,
+        options: &RenderOptions,
+    ) -> Result<(), Error> {
+        check_syntax(template)?;
+        self.validate_blocks(&parse_blocks(template), options)
+    }
+
+    fn validate_blocks(&self, blocks: &[Block], options: &RenderOptions) -> Result<(), Error> {
+        // whether each open section repeats over a list, binding `.`
+        let mut lists = Vec::new();
+        let mut remaining = blocks.iter();
+        while let Some(block) = remaining.next() {
+            match block.kind {
+                BlockKind::Escape => continue,
+                BlockKind::SectionEnd => {
+                    lists.pop();
+                    continue;
+                }
+                _ => {}
+            }
+            check_block(block, options)?;
+            let value = self.lookup_value(&block.variable_key);
+            match block.kind {
+                BlockKind::Section { inverted } => {
+                    let listed = self.list_entries(&block.variable_key).next().is_some();
+                    let shown = if listed {
+                        !inverted
+                    } else {
+                        section::shown(block, value)
+                    };
+                    if shown {
+                        lists.push(listed);
+                    } else {
+                        let kinds = remaining.as_slice().iter().map(|block| block.kind);
+                        remaining.nth(section::end_index(kinds));
+                    }
+                }
+                BlockKind::Variable if value.is_none() => {
+                    let element = block.variable_key == "." && lists.contains(&true);
+                    let required =
+                        matches!(options.missing_key, MissingKey::Error | MissingKey::Collect);
+                    if required && !element {
+                        return Err(Error::new(ErrorKind::UnknownVariable, block));
+                    }
+                }
+                _ => {}
+            }
+            let unknown = filter::names(block.filters).and_then(|names| {
+                match names.iter().find(|name| !filter::is_known(name)) {
+                    Some(name) => Err(format!("unknown filter '{}'", name)),
+                    None => Ok(()),
+                }
+            });
+            unknown.map_err(|msg| Error::new(ErrorKind::Filter, block).caused_by(msg))?;
+        }
+        if options.deny_unused {
+            self.check_used(blocks)?;
+        }
+        Ok(())
+    }
+}
+
+fn syntax(template: &str, delimiters: &Delimiters) -> Result<(), Error> {
+    let mut unclosed = None;
+    let blocks = scan_blocks(template, delimiters, |line, col| {
+        unclosed.get_or_insert((line, col));
+    });
+    match unclosed {
+        Some((line, col)) => Err(Error {
+            line,
+            col,
+            ..Error::without_block(ErrorKind::UnclosedBlock, "")
+        }),
+        None => section::check(&blocks),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_unclosed_blocks() {
+        let unclosed = |template: &str| {
+            check_syntax(template)
+                .err()
+                .map(|err| (err.kind(), err.line(), err.col()))
+        };
+        assert_eq!(None, unclosed("{{ a }} \\{{ b {{ \"}}{{\" }} }}"));
+        assert_eq!(
+            Some((ErrorKind::UnclosedBlock, 1, 1)),
+            unclosed("{{ a {{ b }}")
+        );
+        assert_eq!(
+            Some((ErrorKind::UnclosedBlock, 2, 3)),
+            unclosed("{{ a }}\n\\\\{{ b }")
+        );
+        assert_eq!(
+            Some((ErrorKind::UnclosedBlock, 1, 1)),
+            unclosed("{{ \"a }}")
+        );
+
+        assert_eq!(
+            Some((ErrorKind::UnmatchedSection, 1, 1)),
+            unclosed("{{#a}}{{ b }}{{/b}}")
+        );
+
+        let template = Template::with_delimiters("{{ a <% b %> <% c", "<%", "%>");
+        let err = template.check_syntax().expect_err("c is not closed");
+        assert_eq!((1, 14), (err.line(), err.col()));
+    }
+
+    #[test]
+    fn validate_templates() {
+        let mut map = VarjMap::new();
+        map.insert("a", "x");
+        map.insert("b", "");
+        map.insert_list("l", ["y"]);
+        let problem = |template: &str, options: &RenderOptions| {
+            map.validate_with_options(template, options)
+                .err()
+                .map(|err| (err.kind(), err.key().to_owned(), err.to_string()))
+        };
+        let options = RenderOptions::default();
+
+        assert_eq!(
+            None,
+            problem("{{ a | trim }} \\{{ c }} {{ b:>3 }}", &options)
+        );
+        assert_eq!(
+            Some((
+                ErrorKind::UnknownVariable,
+                "c".to_owned(),
+                "1:9 unknown variable 'c'".to_owned()
+            )),
+            problem("{{ a }} {{ c }}", &options)
+        );
+        assert_eq!(
+            ErrorKind::UnclosedBlock,
+            problem("{{ c }} {{ d", &options).expect("d is unclosed").0
+        );
+        assert_eq!(
+            Some((
+                ErrorKind::Filter,
+                "a".to_owned(),
+                "1:1 filter failed for 'a': unknown filter 'nope'".to_owned()
+            )),
+            problem("{{ a | trim | nope }}", &options)
+        );
+        assert_eq!(
+            ErrorKind::Filter,
+            problem("{{ a | trim( }}", &options)
+                .expect("chain is malformed")
+                .0
+        );
+
+        assert_eq!(
+            None,
+            problem("{{^a}}{{ c }}{{/a}}{{#b}}{{ c }}{{/b}}", &options)
+        );
+        assert_eq!(
+            ErrorKind::UnknownVariable,
+            problem("{{#a}}{{ c }}{{/a}}", &options)
+                .expect("c is missing")
+                .0
+        );
+        assert_eq!(
+            None,
+            problem("{{#l}}{{ . }}{{/l}}{{^l}}{{ c }}{{/l}}", &options)
+        );
+        assert_eq!(
+            Some(".".to_owned()),
+            problem("{{#a}}{{ . }}{{/a}}", &options).map(|(_, key, _)| key)
+        );
+
+        for missing_key in [MissingKey::Empty, MissingKey::KeepPlaceholder] {
+            let options = RenderOptions::new().missing_key(missing_key);
+            assert_eq!(None, problem("{{ a }} {{ c }}", &options));
+        }
+        let options = RenderOptions::new().missing_key(MissingKey::Collect);
+        assert_eq!(
+            ErrorKind::UnknownVariable,
+            problem("{{ a }} {{ c }}", &options)
+                .expect("c is missing")
+                .0
+        );
+
+        let options = RenderOptions::new().deny_unused(true);
+        assert_eq!(
+            ErrorKind::UnusedVariable,
+            problem("{{ a }}", &options).expect("b is unused").0
+        );
+
+        let template = Template::compile("{{ a }} {{ c }}");
+        let err = template.check(&map).expect_err("c is missing");
+        assert_eq!(ErrorKind::UnknownVariable, err.kind());
+        map.set_default("");
+        assert!(template.check(&map).is_ok());
+    }
+}
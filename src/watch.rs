@@ -0,0 +1,251 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::{Encoding, Error, ErrorKind, RenderOptions, TemplateSet, VarjMap};
+
+impl TemplateSet {
+    /// Load every file below a directory as a template and keep them up to
+    /// date as files change.
+    ///
+    /// Templates are named by their path relative to `dir`, with `/`
+    /// separators, like the names of a [`FileLoader`](crate::FileLoader).
+    /// When a file is created or modified, its template is replaced and
+    /// compiled on the watcher's thread; when it is removed, so is its
+    /// template. Files that cannot be read as UTF-8 text keep their previous
+    /// version, and a leading byte order mark is dropped.
+    ///
+    /// Watching stops when the returned [`WatchedTemplateSet`] is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Will return an [`Error`] of kind [`ErrorKind::Load`] naming `dir`,
+    /// with the I/O or watcher error as its source, if the directory cannot
+    /// be read or watched.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use std::error::Error;
+    /// #
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let templates = varj::TemplateSet::watch("templates")?;
+    ///
+    /// let mut map = varj::VarjMap::new();
+    /// map.insert("name", "Christopher");
+    ///
+    /// // always renders the latest templates/greeting.txt
+    /// let greeting = templates.render("greeting.txt", &map)?;
+    /// #
+    /// #     Ok(())
+    /// # }
+    /// ```
+    #[cfg_attr(docsrs, doc(cfg(feature = "notify")))]
+    pub fn watch<P: AsRef<Path>>(dir: P) -> Result<WatchedTemplateSet, Error> {
+        let dir = dir.as_ref();
+        let error = |err: Box<dyn std::error::Error + Send + Sync>| {
+            Error::with_source(ErrorKind::Load, &dir.to_string_lossy(), err)
+        };
+
+        let root = dir.canonicalize().map_err(|err| error(err.into()))?;
+        let mut templates = TemplateSet::new();
+        load_dir(&root, &root, &mut templates).map_err(|err| error(err.into()))?;
+        templates.compile_all();
+        let templates = Arc::new(RwLock::new(templates));
+
+        let shared = Arc::clone(&templates);
+        let watch_root = root.clone();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            if let Ok(event) = event {
+                let mut templates = shared.write().unwrap_or_else(|p| p.into_inner());
+                for path in &event.paths {
+                    reload(&watch_root, path, &mut templates);
+                }
+            }
+        })
+        .map_err(|err| error(err.into()))?;
+        watcher
+            .watch(&root, RecursiveMode::Recursive)
+            .map_err(|err| error(err.into()))?;
+
+        Ok(WatchedTemplateSet {
+            templates,
+            root,
+            _watcher: watcher,
+        })
+    }
+}
+
+/// A [`TemplateSet`] kept up to date with a directory, see
+/// [`TemplateSet::watch`].
+///
+/// Renders see each template as it was when they started; changes made by
+/// the watcher wait for running renders to finish.
+#[cfg_attr(docsrs, doc(cfg(feature = "notify")))]
+pub struct WatchedTemplateSet {
+    templates: Arc<RwLock<TemplateSet>>,
+    root: PathBuf,
+    _watcher: RecommendedWatcher,
+}
+
+impl WatchedTemplateSet {
+    /// The watched directory.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Render a named template with values from a [`VarjMap`].
+    ///
+    /// # Errors
+    ///
+    /// See [`TemplateSet::render`].
+    pub fn render(&self, name: &str, map: &VarjMap) -> Result<String, Error> {
+        self.read().render(name, map)
+    }
+
+    /// Render a named template using the given [`RenderOptions`].
+    ///
+    /// # Errors
+    ///
+    /// See [`TemplateSet::render`].
+    pub fn render_with_options(
+        &self,
+        name: &str,
+        map: &VarjMap,
+        options: &RenderOptions,
+    ) -> Result<String, Error> {
+        self.read().render_with_options(name, map, options)
+    }
+
+    /// Lock the current templates for reading.
+    ///
+    /// The watcher cannot update templates while the guard is held, so keep
+    /// it briefly.
+    pub fn read(&self) -> RwLockReadGuard<'_, TemplateSet> {
+        self.templates
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl std::fmt::Debug for WatchedTemplateSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WatchedTemplateSet")
+            .field("templates", &self.templates)
+            .field("root", &self.root)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Register every file below `dir` under its path relative to `root`.
+fn load_dir(root: &Path, dir: &Path, templates: &mut TemplateSet) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            load_dir(root, &path, templates)?;
+        } else if let (Some(name), Ok(source)) = (name(root, &path), read(&path)) {
+            templates.insert(name, source);
+        }
+    }
+    Ok(())
+}
+
+/// Bring the templates for `path` in line with the file system.
+fn reload(root: &Path, path: &Path, templates: &mut TemplateSet) {
+    if path.is_dir() {
+        // a directory moved in may hold templates; unreadable ones are skipped
+        let _ = load_dir(root, path, templates);
+    } else if let Some(name) = name(root, path) {
+        if path.is_file() {
+            if let Ok(source) = read(path) {
+                templates.insert(name.as_str(), source);
+                templates.get(&name);
+            }
+        } else if !templates.remove(&name) {
+            // a removed directory: drop the templates it held
+            let prefix = format!("{}/", name);
+            let nested: Vec<String> = templates
+                .names()
+                .filter(|other| other.starts_with(&prefix))
+                .map(str::to_owned)
+                .collect();
+            for other in nested {
+                templates.remove(&other);
+            }
+        }
+    }
+}
+
+/// Read a template file as UTF-8.
+fn read(path: &Path) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    Encoding::Utf8.decode(fs::read(path)?)
+}
+
+/// The template name of a path below `root`.
+fn name(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    let parts: Option<Vec<&str>> = relative.iter().map(|part| part.to_str()).collect();
+    let parts = parts?;
+    if parts.is_empty() {
+        return None;
+    }
+    Some(parts.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    /// Poll until `check` passes or a few seconds have passed.
+    fn eventually(check: impl Fn() -> bool) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            if check() {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        false
+    }
+
+    #[test]
+    fn reload_changed_templates() {
+        let dir = std::env::temp_dir().join(format!("varj-watch-{}", std::process::id()));
+        fs::create_dir_all(dir.join("emails")).expect("creating dirs should succeed");
+        fs::write(dir.join("greeting"), "Hello {{ name }}").expect("writing should succeed");
+        fs::write(dir.join("emails/welcome"), "Welcome").expect("writing should succeed");
+
+        let templates = TemplateSet::watch(&dir).expect("watching should succeed");
+        let mut map = VarjMap::new();
+        map.insert("name", "Christopher");
+        let render = |name: &str| templates.render(name, &map).ok();
+
+        assert_eq!(Some("Hello Christopher".to_owned()), render("greeting"));
+        assert_eq!(Some("Welcome".to_owned()), render("emails/welcome"));
+
+        fs::write(dir.join("greeting"), "Hi {{ name }}").expect("writing should succeed");
+        fs::write(dir.join("farewell"), "Bye").expect("writing should succeed");
+        fs::remove_dir_all(dir.join("emails")).expect("removing dirs should succeed");
+        let updated = eventually(|| {
+            render("greeting").as_deref() == Some("Hi Christopher")
+                && render("farewell").as_deref() == Some("Bye")
+                && render("emails/welcome").is_none()
+        });
+        drop(templates);
+        fs::remove_dir_all(&dir).expect("removing dirs should succeed");
+
+        assert!(updated, "templates should follow the directory");
+    }
+
+    #[test]
+    fn watch_missing_directory() {
+        let actual = TemplateSet::watch("does/not/exist").expect_err("watching should error");
+        assert_eq!(ErrorKind::Load, actual.kind());
+        assert_eq!("does/not/exist", actual.key());
+    }
+}
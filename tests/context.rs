@@ -0,0 +1,29 @@
+#![cfg(feature = "macros")]
+
+varj::varj_context!("tests/templates/greeting.tpl");
+varj::varj_context!(Welcome, "tests/templates/greeting.tpl");
+
+#[test]
+fn render_generated_context() {
+    let greeting = Greeting {
+        name: "Christopher".to_owned(),
+        site_title: "varj".to_owned(),
+        user_name: " sonro ".to_owned(),
+        r#type: "admin".to_owned(),
+    };
+
+    let actual = greeting.render().expect("rendering should succeed");
+    assert_eq!(
+        "Hello Christopher, welcome to varj!\nsonro {{ literal }} admin\n",
+        actual
+    );
+    assert_eq!(include_str!("templates/greeting.tpl"), Welcome::TEMPLATE);
+}
+
+#[test]
+fn render_default_context() {
+    let actual = Welcome::default()
+        .render()
+        .expect("rendering should succeed");
+    assert_eq!("Hello , welcome to !\n {{ literal }} \n", actual);
+}
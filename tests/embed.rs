@@ -0,0 +1,24 @@
+#![cfg(feature = "macros")]
+
+#[test]
+fn render_embedded_templates() {
+    let templates = varj::embed_templates!("tests/templates");
+
+    let mut names: Vec<&str> = templates.names().collect();
+    names.sort_unstable();
+    assert_eq!(vec!["emails/welcome.tpl", "greeting.tpl"], names);
+
+    let mut map = varj::VarjMap::new();
+    map.insert("name", "Christopher");
+    let actual = templates
+        .render("emails/welcome.tpl", &map)
+        .expect("rendering should succeed");
+    assert_eq!("Welcome Christopher!\n", actual);
+    assert_eq!(
+        include_str!("templates/greeting.tpl"),
+        templates
+            .get("greeting.tpl")
+            .expect("template should be embedded")
+            .source()
+    );
+}
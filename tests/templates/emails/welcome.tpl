@@ -0,0 +1 @@
+Welcome {{ name }}!
@@ -0,0 +1,2 @@
+Hello {{ name }}, welcome to {{ site.title }}!
+{{ userName | trim }} \{{ literal }} {{ type }}
@@ -0,0 +1,439 @@
+//! Procedural macros for [varj](https://docs.rs/varj).
+//!
+//! Use them through varj's `macros` feature rather than depending on this
+//! crate directly.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::{parse_macro_input, Ident, LitStr, Token};
+
+/// Generate a struct with a `String` field for each placeholder key of a
+/// template file, and a `render` method filling the template from them.
+///
+/// The path is relative to the directory of the crate's `Cargo.toml`. The
+/// struct is named after the file name before its first `.` in
+/// `UpperCamelCase`, or by an identifier given before the path.
+///
+/// Field names are the keys in `snake_case`, with other characters than
+/// letters, digits and `_` replaced by `_`: `userName` and `user.name` both
+/// become `user_name`. Keys mapping to the same field are a compile error.
+///
+/// The template is embedded with `include_str!`, so editing it rebuilds the
+/// crate, and a key added to the template without setting the new field is
+/// a compile error.
+///
+/// # Example
+///
+/// With a `templates/greeting.tpl` of `Hello {{ name }}, welcome to {{
+/// site.title }}!`:
+///
+/// ```rust,ignore
+/// varj::varj_context!("templates/greeting.tpl");
+/// varj::varj_context!(Farewell, "templates/farewell.tpl");
+///
+/// let greeting = Greeting {
+///     name: "Christopher".to_owned(),
+///     site_title: "varj".to_owned(),
+/// };
+/// assert_eq!("Hello Christopher, welcome to varj!", greeting.render()?);
+/// ```
+#[proc_macro]
+pub fn varj_context(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as ContextInput);
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Build a `varj::TemplateSet` of every file below a directory, embedded in
+/// the binary with `include_str!`.
+///
+/// The path is relative to the directory of the crate's `Cargo.toml`.
+/// Templates are named by their path relative to it, with `/` separators,
+/// like the names of a `varj::FileLoader`. Files that are not UTF-8 text are
+/// skipped.
+///
+/// Editing an embedded file rebuilds the crate, but adding or removing files
+/// does not: touch a source file of the crate to pick them up.
+///
+/// # Example
+///
+/// With `templates/greeting.txt` and `templates/emails/welcome.txt`:
+///
+/// ```rust,ignore
+/// let templates = varj::embed_templates!("templates");
+///
+/// let mut map = varj::VarjMap::new();
+/// map.insert("name", "Christopher");
+/// let welcome = templates.render("emails/welcome.txt", &map)?;
+/// ```
+#[proc_macro]
+pub fn embed_templates(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as EmbedInput);
+    match expand_embed(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+struct EmbedInput {
+    path: LitStr,
+}
+
+impl Parse for EmbedInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let path = input.parse()?;
+        if !input.is_empty() {
+            input.parse::<Token![,]>()?;
+        }
+        Ok(EmbedInput { path })
+    }
+}
+
+fn expand_embed(input: EmbedInput) -> syn::Result<proc_macro2::TokenStream> {
+    let relative = input.path.value();
+    let error = |msg: String| syn::Error::new(input.path.span(), msg);
+
+    let dir = std::env::var("CARGO_MANIFEST_DIR")
+        .map_err(|_| error("CARGO_MANIFEST_DIR is not set".to_owned()))?;
+    let root = PathBuf::from(dir).join(&relative);
+    let mut files = BTreeMap::new();
+    template_files(&root, &root, &mut files)
+        .map_err(|err| error(format!("cannot read '{}': {}", root.display(), err)))?;
+
+    let names = files.keys();
+    let paths = files.values().map(|path| path.to_string_lossy());
+    Ok(quote! {
+        {
+            let mut templates = ::varj::TemplateSet::new();
+            #(
+                templates.insert(#names, include_str!(#paths));
+            )*
+            templates
+        }
+    })
+}
+
+/// Collect the UTF-8 files below `dir` by their name relative to `root`.
+fn template_files(
+    root: &Path,
+    dir: &Path,
+    files: &mut BTreeMap<String, PathBuf>,
+) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            template_files(root, &path, files)?;
+            continue;
+        }
+        let name: Option<Vec<&str>> = path
+            .strip_prefix(root)
+            .ok()
+            .and_then(|relative| relative.iter().map(|part| part.to_str()).collect());
+        if let (Some(name), Ok(_)) = (name, std::fs::read_to_string(&path)) {
+            files.insert(name.join("/"), path);
+        }
+    }
+    Ok(())
+}
+
+struct ContextInput {
+    name: Option<Ident>,
+    path: LitStr,
+}
+
+impl Parse for ContextInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = if input.peek(Ident) {
+            let name = input.parse()?;
+            input.parse::<Token![,]>()?;
+            Some(name)
+        } else {
+            None
+        };
+        let path = input.parse()?;
+        if !input.is_empty() {
+            input.parse::<Token![,]>()?;
+        }
+        Ok(ContextInput { name, path })
+    }
+}
+
+fn expand(input: ContextInput) -> syn::Result<proc_macro2::TokenStream> {
+    let relative = input.path.value();
+    let error = |msg: String| syn::Error::new(input.path.span(), msg);
+
+    let dir = std::env::var("CARGO_MANIFEST_DIR")
+        .map_err(|_| error("CARGO_MANIFEST_DIR is not set".to_owned()))?;
+    let path = PathBuf::from(dir).join(&relative);
+    let template = std::fs::read_to_string(&path)
+        .map_err(|err| error(format!("cannot read '{}': {}", path.display(), err)))?;
+
+    let name = match input.name {
+        Some(name) => name,
+        None => struct_name(&relative)
+            .ok_or_else(|| error(format!("cannot name a struct after '{}'", relative)))?,
+    };
+
+    // field name to key, sorted for stable output
+    let mut fields: BTreeMap<String, String> = BTreeMap::new();
+    // `.` is bound to the element of a list section, not set by a field
+    for key in keys(&template).into_iter().filter(|key| key != ".") {
+        let field = field_name(&key);
+        match fields.insert(field.clone(), key.clone()) {
+            Some(other) if other != key => {
+                return Err(error(format!(
+                    "keys '{}' and '{}' both map to field '{}'",
+                    other, key, field
+                )));
+            }
+            _ => {}
+        }
+    }
+
+    let idents: Vec<Ident> = fields.keys().map(|field| field_ident(field)).collect();
+    let keys: Vec<&str> = fields.values().map(String::as_str).collect();
+    let path = path.to_string_lossy();
+    let doc = format!("Variables of the template `{}`.", relative);
+    let field_docs = keys
+        .iter()
+        .map(|key| format!("The value of `{{{{ {} }}}}`.", key));
+
+    Ok(quote! {
+        #[doc = #doc]
+        #[derive(Debug, Clone, Default, PartialEq, Eq)]
+        pub struct #name {
+            #(
+                #[doc = #field_docs]
+                pub #idents: ::std::string::String,
+            )*
+        }
+
+        impl #name {
+            /// The template's source.
+            pub const TEMPLATE: &'static str = include_str!(#path);
+
+            /// Render the template with the values of the fields.
+            ///
+            /// # Errors
+            ///
+            /// See `varj::render`.
+            pub fn render(&self) -> ::std::result::Result<::std::string::String, ::varj::Error> {
+                ::varj::render(Self::TEMPLATE, &[#((#keys, self.#idents.as_str())),*])
+            }
+        }
+    })
+}
+
+/// The keys of a template's placeholder blocks, parsed as varj does.
+fn keys(template: &str) -> Vec<String> {
+    let bytes = template.as_bytes();
+    let mut keys = Vec::new();
+    let mut open = None;
+
+    let mut idx = 0;
+    while idx + 1 < bytes.len() {
+        match (bytes[idx], bytes[idx + 1], open) {
+            (b'"', _, Some(start)) if opens_key(&template[start + 2..idx]) => {
+                // skip a quoted key, which may contain braces
+                idx += 1;
+                while idx < bytes.len() {
+                    match bytes[idx] {
+                        b'\\' => idx += 2,
+                        b'"' => break,
+                        _ => idx += 1,
+                    }
+                }
+                idx += 1;
+            }
+            (b'}', b'}', Some(start)) => {
+                // a raw block, `{{{ key }}}`, has braces inside its delimiters
+                let raw = bytes[start + 2] == b'{' && bytes.get(idx + 2) == Some(&b'}');
+                let inner: &str = &template[start + 2 + usize::from(raw)..idx];
+                // the key of a section tag, `{{#key}}`, `{{^key}}` or
+                // `{{/key}}`, follows its sigil
+                let inner = match inner.trim_start().strip_prefix(['#', '^', '/']) {
+                    Some(key) if !raw => key,
+                    _ => inner,
+                };
+                let quoted = unquote(inner.trim_start()).filter(|(_, rest)| {
+                    let spec = rest.split('|').next().unwrap_or("").trim();
+                    spec.is_empty() || spec.strip_prefix(':').is_some_and(is_align)
+                });
+                let key = match quoted {
+                    Some((key, _)) => key,
+                    None => {
+                        let key = inner.find('|').map_or(inner, |end| &inner[..end]).trim();
+                        match key.rsplit_once(':') {
+                            Some((key, spec)) if is_align(spec) => key.trim_end().to_owned(),
+                            _ => key.to_owned(),
+                        }
+                    }
+                };
+                keys.push(key);
+                open = None;
+                idx += 2 + usize::from(raw);
+            }
+            (b'{', b'{', _) => {
+                let slashes = bytes[..idx].iter().rev().take_while(|&&b| b == b'\\');
+                // braces escaped by an odd number of backslashes are literal
+                if open.is_some() || slashes.count() % 2 == 0 {
+                    open = Some(idx);
+                }
+                idx += 2;
+            }
+            _ => idx += 1,
+        }
+    }
+
+    keys
+}
+
+/// Whether a quote after `inside`, a block's text so far, starts a quoted
+/// key.
+fn opens_key(inside: &str) -> bool {
+    let inside = inside.strip_prefix('{').unwrap_or(inside).trim_start();
+    let inside = inside.strip_prefix(['#', '^', '/']).unwrap_or(inside);
+    inside.trim().is_empty()
+}
+
+/// Whether `spec` is an alignment such as `>12` or `*^10`, as in
+/// `{{ key:>12 }}`.
+fn is_align(spec: &str) -> bool {
+    let aligned = |text: &str| {
+        // varj takes widths up to 65535 as an alignment
+        text.strip_prefix(['<', '>', '^']).is_some_and(|width| {
+            width.bytes().all(|b| b.is_ascii_digit()) && width.parse::<u16>().is_ok()
+        })
+    };
+    aligned(spec)
+        || spec
+            .chars()
+            .next()
+            .is_some_and(|fill| aligned(&spec[fill.len_utf8()..]))
+}
+
+/// The key of a quoted key at the start of `text`, and the text after it.
+fn unquote(text: &str) -> Option<(String, &str)> {
+    let body = text.strip_prefix('"')?;
+    let mut key = String::new();
+    let mut chars = body.char_indices();
+    while let Some((idx, ch)) = chars.next() {
+        match ch {
+            '"' => return Some((key, &body[idx + 1..])),
+            '\\' => match chars.next()?.1 {
+                'n' => key.push('\n'),
+                't' => key.push('\t'),
+                ch => key.push(ch),
+            },
+            ch => key.push(ch),
+        }
+    }
+    None
+}
+
+/// A key in `snake_case` with other characters than letters, digits and `_`
+/// replaced.
+fn field_name(key: &str) -> String {
+    let mut field = String::with_capacity(key.len() + 4);
+    let mut prev_lower = false;
+    for ch in key.chars() {
+        if ch.is_uppercase() && prev_lower {
+            field.push('_');
+        }
+        prev_lower = ch.is_lowercase() || ch.is_ascii_digit();
+        if ch.is_alphanumeric() || ch == '_' {
+            field.extend(ch.to_lowercase());
+        } else {
+            field.push('_');
+        }
+    }
+    if field.is_empty() || field.starts_with(|ch: char| ch.is_ascii_digit()) {
+        field.insert(0, '_');
+    }
+    field
+}
+
+/// An identifier for a field name, raw if the name is a keyword.
+fn field_ident(field: &str) -> Ident {
+    match syn::parse_str::<Ident>(field) {
+        Ok(ident) => ident,
+        Err(_) if matches!(field, "_" | "self" | "crate" | "super") => format_ident!("{}_", field),
+        Err(_) => Ident::new_raw(field, Span::call_site()),
+    }
+}
+
+/// An `UpperCamelCase` name from the file name of `path` before its first
+/// `.`.
+fn struct_name(path: &str) -> Option<Ident> {
+    let file = path.rsplit(['/', '\\']).next()?;
+    let stem = file.split('.').next()?;
+    let name: String = stem
+        .split(|ch: char| !ch.is_alphanumeric())
+        .flat_map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .into_iter()
+                .flat_map(char::to_uppercase)
+                .chain(chars)
+        })
+        .collect();
+    syn::parse_str(&name).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_keys() {
+        assert_eq!(
+            vec!["a", "b", "{c", "d"],
+            keys("{{ a }} {{b|trim}} \\{{ x }} \\\\{{ {c }} {{ y {{ d }} {{ z")
+        );
+        assert_eq!(
+            vec!["}} |", "a\"b", "\"c\" d"],
+            keys("{{ \"}} |\" | trim }} {{\"a\\\"b\"}} {{ \"c\" d }}")
+        );
+        assert_eq!(
+            vec!["a", "b", "c:d", "e f", "g:>65536"],
+            keys("{{ a:>5 }} {{ b :*^3 | trim }} {{ c:d }} {{ \"e f\":<2 }} {{ g:>65536 }}")
+        );
+        assert_eq!(
+            vec!["a", "b c", "{ d"],
+            keys("{{{ a | trim }}} {{{ \"b c\" }}} {{{ d }}")
+        );
+        assert_eq!(
+            vec!["a", "b", "a", "c d", "c d"],
+            keys("{{#a}}{{ b }}{{/a}} {{^ \"c d\" }}{{/\"c d\"}}")
+        );
+    }
+
+    #[test]
+    fn name_fields() {
+        assert_eq!("user_name", field_name("userName"));
+        assert_eq!("user_name", field_name("user.name"));
+        assert_eq!("_2fa", field_name("2fa"));
+        assert_eq!("r#type", field_ident(&field_name("type")).to_string());
+        assert_eq!("self_", field_ident(&field_name("self")).to_string());
+        assert_eq!("__", field_ident(&field_name("")).to_string());
+    }
+
+    #[test]
+    fn name_structs() {
+        let name = |path| struct_name(path).map(|ident| ident.to_string());
+        assert_eq!(Some("Greeting".to_owned()), name("templates/greeting.tpl"));
+        assert_eq!(
+            Some("NginxSite".to_owned()),
+            name("conf\\nginx-site.conf.tpl")
+        );
+        assert_eq!(None, name("templates/.tpl"));
+    }
+}